@@ -0,0 +1,53 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ctftimebot::CtfEvent;
+use serde_json::Value;
+
+/// Number of events in the synthetic dataset
+const NUM_EVENTS: usize = 5000;
+
+/// Create a JSON array with [`NUM_EVENTS`] events, based on the events in the test data
+fn synthetic_dataset() -> String {
+    let json = std::fs::read_to_string("./tests/ctfs.json").unwrap();
+    let templates: Vec<Value> = serde_json::from_str(&json).unwrap();
+    let events: Vec<Value> = templates
+        .iter()
+        .cycle()
+        .take(NUM_EVENTS)
+        .enumerate()
+        .map(|(idx, template)| {
+            let mut event = template.clone();
+            event["id"] = idx.into();
+            event["title"] = format!("{} #{}", template["title"].as_str().unwrap(), idx).into();
+            event
+        })
+        .collect();
+    serde_json::to_string(&events).unwrap()
+}
+
+fn bench_events(c: &mut Criterion) {
+    let json = synthetic_dataset();
+    let events: Vec<CtfEvent> = serde_json::from_str(&json).unwrap();
+
+    c.bench_function("deserialize 5000 events", |b| {
+        b.iter(|| serde_json::from_str::<Vec<CtfEvent>>(black_box(&json)).unwrap())
+    });
+    c.bench_function("filter 5000 events", |b| {
+        b.iter(|| {
+            black_box(&events)
+                .iter()
+                .filter(|event| event.should_print_event())
+                .count()
+        })
+    });
+    c.bench_function("render 5000 events", |b| {
+        b.iter(|| {
+            black_box(&events)
+                .iter()
+                .map(CtfEvent::to_slack)
+                .collect::<Vec<_>>()
+        })
+    });
+}
+
+criterion_group!(benches, bench_events);
+criterion_main!(benches);
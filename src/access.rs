@@ -0,0 +1,252 @@
+//! Access control of the server endpoints with scoped tokens
+//!
+//! Tokens are managed with the `token` subcommand and stored as SHA-256 hashes in the [`State`], the secret is only shown once.
+//! Each endpoint requires a [`Scope`]: the JSON API and GraphQL `read-events`, the actions and the slash command `manage-subscriptions`,
+//! and the admin commands `admin`, which also grants all other scopes.
+//!
+//! As long as no token exists, the server behaves as before: the slash command checks `COMMAND_TOKEN`, the API `API_TOKEN`, and the actions are open.
+//! The admin commands are the exception, they are refused unless `COMMAND_TOKEN` is configured.
+//! Once tokens exist, every request needs a token with the required scope. `COMMAND_TOKEN` and `API_TOKEN` stay valid for their endpoints.
+
+use crate::state::State;
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+/// Permission granted by a token
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scope {
+    /// Read the events, RSVPs, and history via the JSON API and GraphQL
+    ReadEvents,
+    /// Click the buttons and use the slash command, except for the admin commands
+    ManageSubscriptions,
+    /// Use the admin commands, grants all other scopes
+    Admin,
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read-events" => Ok(Scope::ReadEvents),
+            "manage-subscriptions" => Ok(Scope::ManageSubscriptions),
+            "admin" => Ok(Scope::Admin),
+            _ => Err(format!("Unknown scope `{}`", s)),
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Scope::ReadEvents => "read-events",
+            Scope::ManageSubscriptions => "manage-subscriptions",
+            Scope::Admin => "admin",
+        })
+    }
+}
+
+/// A token as stored in the [`State`], keyed by its name
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AccessToken {
+    /// SHA-256 of the secret, see [`hash_secret`]
+    pub hash: String,
+    pub scopes: BTreeSet<Scope>,
+    pub created: DateTime<Utc>,
+}
+
+impl AccessToken {
+    /// Whether the token grants `scope`
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.contains(&Scope::Admin) || self.scopes.contains(&scope)
+    }
+}
+
+/// Hex encoded SHA-256 of the secret
+pub fn hash_secret(secret: &str) -> String {
+    Sha256::digest(secret.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// New random secret with 256 bits
+pub fn generate_secret() -> String {
+    let mut bytes = [0; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+/// Secret of an `Authorization` header, either `Bearer <secret>` or `Token <secret>` as sent by Mattermost slash commands
+pub fn bearer_token(authorization: &str) -> Option<&str> {
+    authorization
+        .strip_prefix("Bearer ")
+        .or_else(|| authorization.strip_prefix("Token "))
+        .map(str::trim)
+}
+
+/// Whether the `presented` secret grants `scope`
+///
+/// `legacy` is the token configured for the endpoint, e.g., `COMMAND_TOKEN`.
+/// Without any tokens in the state, only the legacy token is checked, and endpoints without one are open, except for [`Scope::Admin`].
+/// Secrets are compared in constant time.
+pub fn is_allowed(
+    state: &State,
+    presented: Option<&str>,
+    scope: Scope,
+    legacy: Option<&str>,
+) -> bool {
+    let matches_legacy = |presented: &str| {
+        legacy.map_or(false, |legacy| {
+            constant_time_eq(presented.as_bytes(), legacy.as_bytes())
+        })
+    };
+    if state.tokens.is_empty() {
+        return match presented {
+            _ if legacy.is_none() => scope != Scope::Admin,
+            Some(presented) => matches_legacy(presented),
+            None => false,
+        };
+    }
+    let presented = match presented {
+        Some(presented) => presented,
+        None => return false,
+    };
+    if matches_legacy(presented) {
+        return true;
+    }
+    let hash = hash_secret(presented);
+    state.tokens.values().any(|token| {
+        constant_time_eq(token.hash.as_bytes(), hash.as_bytes()) && token.allows(scope)
+    })
+}
+
+/// Compare two secrets without leaking the position of the first difference through the timing
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Store a new token and return its secret
+///
+/// A given `secret` is used instead of a random one, e.g., the token Mattermost generated for the slash command.
+pub fn create_token(
+    state: &mut State,
+    name: &str,
+    scopes: &[Scope],
+    secret: Option<String>,
+    now: DateTime<Utc>,
+) -> Result<String, String> {
+    if state.tokens.contains_key(name) {
+        return Err(format!("A token named `{}` already exists", name));
+    }
+    if scopes.is_empty() {
+        return Err("A token needs at least one scope".to_string());
+    }
+    let secret = secret.unwrap_or_else(generate_secret);
+    state.tokens.insert(
+        name.to_string(),
+        AccessToken {
+            hash: hash_secret(&secret),
+            scopes: scopes.iter().copied().collect(),
+            created: now,
+        },
+    );
+    Ok(secret)
+}
+
+/// One line per token with its name, scopes, and creation date
+pub fn token_list(state: &State) -> Vec<String> {
+    state
+        .tokens
+        .iter()
+        .map(|(name, token)| {
+            let scopes: Vec<String> = token.scopes.iter().map(Scope::to_string).collect();
+            format!(
+                "{}: {} (created {})",
+                name,
+                scopes.join(", "),
+                token.created.format("%F")
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_access() {
+    let now = "2021-10-01T12:00:00Z".parse().unwrap();
+    let mut state = State::default();
+    assert_eq!(
+        "manage-subscriptions".parse(),
+        Ok(Scope::ManageSubscriptions)
+    );
+    assert!("write".parse::<Scope>().is_err());
+    assert_eq!(bearer_token("Bearer abc"), Some("abc"));
+    assert_eq!(bearer_token("Token abc"), Some("abc"));
+    assert_eq!(bearer_token("Basic abc"), None);
+
+    // Without tokens, only the legacy tokens are checked
+    assert!(is_allowed(&state, None, Scope::ManageSubscriptions, None));
+    assert!(!is_allowed(&state, None, Scope::Admin, None));
+    assert!(!is_allowed(&state, Some("anything"), Scope::Admin, None));
+    assert!(!is_allowed(&state, None, Scope::Admin, Some("cmd")));
+    assert!(is_allowed(&state, Some("cmd"), Scope::Admin, Some("cmd")));
+    assert!(!is_allowed(
+        &state,
+        Some("other"),
+        Scope::Admin,
+        Some("cmd")
+    ));
+
+    let reader = create_token(&mut state, "dashboard", &[Scope::ReadEvents], None, now).unwrap();
+    assert_eq!(reader.len(), 43);
+    assert!(create_token(&mut state, "dashboard", &[Scope::Admin], None, now).is_err());
+    assert!(create_token(&mut state, "empty", &[], None, now).is_err());
+    create_token(
+        &mut state,
+        "mattermost",
+        &[Scope::Admin],
+        Some("cmd".to_string()),
+        now,
+    )
+    .unwrap();
+    assert_eq!(state.tokens["mattermost"].hash, hash_secret("cmd"));
+
+    assert!(is_allowed(&state, Some(&reader), Scope::ReadEvents, None));
+    assert!(!is_allowed(
+        &state,
+        Some(&reader),
+        Scope::ManageSubscriptions,
+        None
+    ));
+    assert!(is_allowed(&state, Some("cmd"), Scope::ReadEvents, None));
+    assert!(!is_allowed(&state, None, Scope::ManageSubscriptions, None));
+    assert!(is_allowed(
+        &state,
+        Some("api"),
+        Scope::ReadEvents,
+        Some("api")
+    ));
+
+    assert!(!is_allowed(
+        &state,
+        Some("cm"),
+        Scope::ReadEvents,
+        Some("cmd")
+    ));
+
+    assert!(constant_time_eq(b"secret", b"secret"));
+    assert!(!constant_time_eq(b"secret", b"secreT"));
+    assert!(!constant_time_eq(b"secret", b"secrets"));
+
+    assert_eq!(
+        token_list(&state),
+        vec![
+            "dashboard: read-events (created 2021-10-01)",
+            "mattermost: admin (created 2021-10-01)",
+        ]
+    );
+}
@@ -0,0 +1,156 @@
+//! Handling of the interactive message buttons
+//!
+//! Each button carries an [`ActionContext`] describing what should happen when it is clicked.
+//! Mattermost retries the request if the server answers too slowly, and users double-click.
+//! The same click of a user on a post within [`REPLAY_WINDOW_SECONDS`] is therefore only processed once.
+//! The window is short, such that a deliberate second click, e.g., after changing one's mind, still counts.
+
+use crate::{
+    mattermost_hook_api::{ActionEvent, ActionResponse},
+    rsvp::{rsvp, RsvpContext},
+    state::{State, StateStore},
+    vote::{FeedbackContext, FeedbackKind},
+};
+use chrono::{DateTime, Duration, Utc};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+/// Repeated clicks within this many seconds are treated as retries of the first one
+const REPLAY_WINDOW_SECONDS: i64 = 5;
+
+/// Context of an [`Integration`][crate::mattermost_hook_api::Integration], which is sent back by Mattermost once the button is clicked
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum ActionContext {
+    /// Feedback about a played event, used for the weight vote
+    Feedback(FeedbackContext),
+    /// Sign up for an announced event
+    Rsvp(RsvpContext),
+}
+
+/// Process a button click and create the response for the user
+pub fn handle_action(store: &StateStore, event: ActionEvent) -> ActionResponse {
+    let key = click_key(&event);
+    let context: ActionContext = match serde_json::from_value(event.context) {
+        Ok(context) => context,
+        Err(err) => {
+            warn!("Received action with unknown context: {}", err);
+            return ephemeral("Sorry, I do not know this action.");
+        }
+    };
+    match store.update(|state| record_click(state, key, Utc::now())) {
+        Ok(true) => {}
+        Ok(false) => {
+            info!(
+                "Ignoring repeated action of {} on {}",
+                event.user_id, event.post_id
+            );
+            return ephemeral("Your click was already processed.");
+        }
+        Err(err) => {
+            error!("Couldn't write state file: {}", err);
+            return ephemeral("Sorry, your click could not be processed.");
+        }
+    }
+
+    match context {
+        ActionContext::Feedback(feedback) => {
+            let user_id = event.user_id;
+            let res = store.update(|state| {
+                let entry = state
+                    .events
+                    .entry(feedback.event_id)
+                    .or_default()
+                    .feedback
+                    .entry(user_id)
+                    .or_default();
+                match feedback.kind {
+                    FeedbackKind::Infra => entry.infra = Some(feedback.score),
+                    FeedbackKind::Quality => entry.quality = Some(feedback.score),
+                }
+            });
+            match res {
+                Ok(()) => ephemeral("Thanks for your feedback!"),
+                Err(err) => {
+                    error!("Couldn't write state file: {}", err);
+                    ephemeral("Sorry, your feedback could not be saved.")
+                }
+            }
+        }
+        ActionContext::Rsvp(context) => {
+            let user_id = event.user_id;
+            let post_id = event.post_id;
+            match store.update(|state| {
+                state
+                    .events
+                    .entry(context.event_id)
+                    .or_default()
+                    .announcement_post = Some(post_id);
+                rsvp(state, context.event_id, user_id)
+            }) {
+                Ok(text) => ephemeral(&text),
+                Err(err) => {
+                    error!("Couldn't write state file: {}", err);
+                    ephemeral("Sorry, your RSVP could not be saved.")
+                }
+            }
+        }
+    }
+}
+
+/// Key identifying a click of the user on a button of a post
+fn click_key(event: &ActionEvent) -> String {
+    format!("{}/{}/{}", event.post_id, event.user_id, event.context)
+}
+
+/// Remember the click `key` at `now`, returns `false` if the same click was seen within the replay window
+///
+/// Clicks older than the window are forgotten.
+fn record_click(state: &mut State, key: String, now: DateTime<Utc>) -> bool {
+    let window = Duration::seconds(REPLAY_WINDOW_SECONDS);
+    state
+        .recent_clicks
+        .retain(|_, &mut clicked| now - clicked < window);
+    if state.recent_clicks.contains_key(&key) {
+        return false;
+    }
+    state.recent_clicks.insert(key, now);
+    true
+}
+
+fn ephemeral(text: &str) -> ActionResponse {
+    ActionResponse {
+        ephemeral_text: Some(text.to_string()),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_record_click() {
+    let now: DateTime<Utc> = "2021-10-01T12:00:00Z".parse().unwrap();
+    let mut state = State::default();
+    let event = ActionEvent {
+        user_id: "alice".to_string(),
+        post_id: "post".to_string(),
+        channel_id: "channel".to_string(),
+        team_id: "team".to_string(),
+        context: serde_json::to_value(ActionContext::Rsvp(RsvpContext { event_id: 724 })).unwrap(),
+    };
+    let key = click_key(&event);
+    assert_eq!(key, r#"post/alice/{"action":"rsvp","event_id":724}"#);
+
+    assert!(record_click(&mut state, key.clone(), now));
+    assert!(!record_click(
+        &mut state,
+        key.clone(),
+        now + Duration::seconds(2)
+    ));
+    assert!(record_click(
+        &mut state,
+        "post/bob/rsvp".to_string(),
+        now + Duration::seconds(2)
+    ));
+    // Clicks outside of the window are processed again
+    assert!(record_click(&mut state, key, now + Duration::seconds(10)));
+    assert_eq!(state.recent_clicks.len(), 1);
+}
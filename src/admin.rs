@@ -0,0 +1,150 @@
+//! Administrative subcommands of the `/ctftime` slash command
+//!
+//! Only users listed in [`Config::admins`][crate::Config::admins] may use them.
+//! The user name is sent by the chat server, so the server only accepts the subcommands with `COMMAND_TOKEN` or a token with the admin scope, see [`crate::access`].
+//! Changes are stored in the [`State`], since the configuration is only read on startup.
+
+use crate::{duration::parse_duration, state::State, Config, CONFIG};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Settings changed with admin commands
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct AdminState {
+    /// No posts to the channels until this time
+    #[serde(default)]
+    pub muted_until: Option<DateTime<Utc>>,
+    /// Additional CTF ids which are always shown, see [`Config::always_show_ctfs`]
+    #[serde(default)]
+    pub always_show: BTreeSet<usize>,
+}
+
+impl AdminState {
+    pub fn is_muted(&self, now: DateTime<Utc>) -> bool {
+        self.muted_until.map_or(false, |until| now < until)
+    }
+}
+
+/// Reply to an admin command
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdminReply {
+    pub text: String,
+    /// The bot should restart to load the new configuration, after the reply was sent
+    pub reload: bool,
+}
+
+impl From<String> for AdminReply {
+    fn from(text: String) -> Self {
+        Self {
+            text,
+            reload: false,
+        }
+    }
+}
+
+const HELP: &str = "Admin commands:
+- `/ctftime admin reload-config` restarts the bot with the current configuration
+- `/ctftime admin mute <duration>|off` stops all posts to the channels, e.g., for `7d` or `12h`
+- `/ctftime admin add-always <ctf id>` always shows the CTF in the digest
+- `/ctftime admin remove-always <ctf id>` undoes `add-always`";
+
+/// Process the arguments following `admin` in a slash command
+pub fn handle_admin(
+    state: &mut State,
+    user_name: &str,
+    args: &[&str],
+    now: DateTime<Utc>,
+) -> AdminReply {
+    if !CONFIG.admins.iter().any(|admin| admin == user_name) {
+        return format!("Sorry, {} is not an admin.", user_name).into();
+    }
+    let admin = &mut state.admin;
+    match args {
+        ["reload-config"] => {
+            if !cfg!(unix) {
+                return "Reloading is only supported on Unix.".to_string().into();
+            }
+            match Config::load() {
+                Ok(_) => AdminReply {
+                    text: "Restarting with the new configuration…".to_string(),
+                    reload: true,
+                },
+                Err(err) => format!("The configuration is invalid: {}", err).into(),
+            }
+        }
+        ["mute", "off"] => {
+            admin.muted_until = None;
+            "Unmuted.".to_string().into()
+        }
+        ["mute", duration] => match parse_duration(duration) {
+            Ok(duration) if duration > Duration::zero() => {
+                let until = now + duration;
+                admin.muted_until = Some(until);
+                format!("Muted until {}.", until.format("%F %R UTC")).into()
+            }
+            _ => format!(
+                "Invalid duration `{}`, expected e.g. `7d` or `12h`",
+                duration
+            )
+            .into(),
+        },
+        ["add-always", id] => match id.parse::<usize>() {
+            Ok(id) => {
+                admin.always_show.insert(id);
+                format!("CTF {} is always shown.", id).into()
+            }
+            Err(_) => format!("Invalid CTF id `{}`", id).into(),
+        },
+        ["remove-always", id] => match id.parse::<usize>() {
+            Ok(id) if admin.always_show.remove(&id) => {
+                format!("CTF {} is no longer always shown.", id).into()
+            }
+            Ok(id) => format!("CTF {} was not added with `add-always`.", id).into(),
+            Err(_) => format!("Invalid CTF id `{}`", id).into(),
+        },
+        _ => HELP.to_string().into(),
+    }
+}
+
+/// Restart the bot in place, such that the configuration is read again
+///
+/// The state file is written after every change, so nothing is lost.
+/// This function only returns if the restart failed.
+#[cfg(unix)]
+pub fn restart() -> std::io::Error {
+    use std::os::unix::process::CommandExt;
+    match std::env::current_exe() {
+        Ok(exe) => std::process::Command::new(exe)
+            .args(std::env::args_os().skip(1))
+            .exec(),
+        Err(err) => err,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn restart() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Other,
+        "restarting is only supported on Unix",
+    )
+}
+
+#[test]
+fn test_handle_admin_unauthorized() {
+    let mut state = State::default();
+    let reply = handle_admin(&mut state, "mallory", &["mute", "7d"], Utc::now());
+    assert_eq!(reply.text, "Sorry, mallory is not an admin.");
+    assert!(!reply.reload);
+    assert_eq!(state.admin.muted_until, None);
+}
+
+#[test]
+fn test_admin_state() {
+    let now = Utc::now();
+    let mut admin = AdminState::default();
+    assert!(!admin.is_muted(now));
+    admin.muted_until = Some(now + Duration::days(7));
+    assert!(admin.is_muted(now));
+    assert!(!admin.is_muted(now + Duration::days(8)));
+}
@@ -0,0 +1,117 @@
+//! Notifications about changes of events between two runs
+
+use crate::{state::State, CtfEvent};
+
+/// Announce events whose weight increased above `threshold` since the last run
+///
+/// Only events which are now part of the digest, i.e., pass [`CtfEvent::should_print_event`], are considered.
+/// Events which were not seen before do not produce an alert, since they are announced in the digest anyway.
+pub fn weight_alerts(state: &State, events: &[CtfEvent], threshold: u32) -> Vec<String> {
+    events
+        .iter()
+        .filter(|event| event.should_print_event())
+        .filter_map(|event| {
+            let old = state.events.get(&event.id())?.weight.floor() as u32;
+            let new = event.rating_weight()?;
+            if old < threshold && new >= threshold {
+                Some(format!(
+                    "[{}]({}) was rated: weight jumped from {} → {}, now above your threshold — added to this week's digest",
+                    event.title(),
+                    event.ctftime_url(),
+                    old,
+                    new
+                ))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Announce events whose number of participants increased above `threshold` since the last run
+///
+/// Only events which are part of the digest, i.e., pass [`CtfEvent::should_print_event`], are considered.
+pub fn participants_alerts(state: &State, events: &[CtfEvent], threshold: usize) -> Vec<String> {
+    events
+        .iter()
+        .filter(|event| event.should_print_event())
+        .filter_map(|event| {
+            let old = state.events.get(&event.id())?.participants()?;
+            let new = event.participants();
+            if old < threshold && new >= threshold {
+                Some(format!(
+                    "[{}]({}) now has {} teams signed up, crossing your threshold of {}",
+                    event.title(),
+                    event.ctftime_url(),
+                    new,
+                    threshold
+                ))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_weight_alerts() {
+    use crate::state::EventRecord;
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    // Unknown events are part of the normal digest
+    let mut state = State::default();
+    assert!(weight_alerts(&state, &events, 20).is_empty());
+
+    state.events.insert(
+        724,
+        EventRecord {
+            weight: 0.,
+            ..Default::default()
+        },
+    );
+    let alerts = weight_alerts(&state, &events, 20);
+    assert_eq!(alerts.len(), 1);
+    assert!(
+        alerts[0].contains("weight jumped from 0 → 24"),
+        "{}",
+        alerts[0]
+    );
+    assert!(weight_alerts(&state, &events, 30).is_empty());
+
+    state.record_events(&events);
+    assert!(weight_alerts(&state, &events, 20).is_empty());
+}
+
+#[test]
+fn test_participants_alerts() {
+    use crate::state::{EventRecord, ParticipantsSample};
+    use chrono::Utc;
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    let mut state = State::default();
+    assert!(participants_alerts(&state, &events, 100).is_empty());
+
+    state.events.insert(
+        724,
+        EventRecord {
+            weight: 24.07,
+            participants: vec![ParticipantsSample {
+                time: Utc::now(),
+                participants: 90,
+            }],
+            ..Default::default()
+        },
+    );
+    let alerts = participants_alerts(&state, &events, 100);
+    assert_eq!(alerts.len(), 1);
+    assert!(alerts[0].contains("now has 146 teams"), "{}", alerts[0]);
+    assert!(participants_alerts(&state, &events, 200).is_empty());
+
+    state.record_events(&events);
+    assert_eq!(state.events[&724].participants.len(), 2);
+    assert!(participants_alerts(&state, &events, 100).is_empty());
+}
@@ -0,0 +1,148 @@
+//! Read-only JSON API of the server for other tools of the team, e.g., a dashboard or another chat bot
+//!
+//! All data comes from the [`State`], such that the tools don't need to query CTFtime themselves.
+//! Requests must send the configured token or one with the `read-events` scope as `Authorization: Bearer <token>`, see [`access`][crate::access].
+//!
+//! * `/api/events`: announced events which did not finish yet
+//! * `/api/rsvps/<event_id>`: players who RSVP'd to the event
+//! * `/api/history`: past events the team played, the most recent first
+
+use crate::{state::State, BASE_URL, CONFIG};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+/// Prefix of the paths of the API
+pub const API_PATH: &str = "api/";
+
+/// An event as returned by the API
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ApiEvent {
+    pub id: usize,
+    pub title: String,
+    pub start: Option<DateTime<Utc>>,
+    pub finish: Option<DateTime<Utc>>,
+    /// Rounded to two digits
+    pub weight: f64,
+    pub ctftime_url: String,
+    /// The event is configured as playing or has RSVPs
+    pub playing: bool,
+    pub rsvps: usize,
+}
+
+/// The players of an event, as returned by `/api/rsvps/<event_id>`
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ApiRsvps {
+    pub event_id: usize,
+    pub title: String,
+    pub players: BTreeSet<String>,
+    /// Too few players RSVP'd, see [`escalation`][crate::escalation]
+    pub needs_players: bool,
+}
+
+fn api_event(state: &State, id: usize) -> Option<ApiEvent> {
+    let record = state.events.get(&id)?;
+    Some(ApiEvent {
+        id,
+        title: record.title.clone(),
+        start: record.start,
+        finish: record.finish,
+        weight: (f64::from(record.weight) * 100.).round() / 100.,
+        ctftime_url: format!("{}/event/{}/", BASE_URL, id),
+        playing: CONFIG.is_playing(id) || !record.rsvps.is_empty(),
+        rsvps: record.rsvps.len(),
+    })
+}
+
+/// Announced events which did not finish at `now`, the earliest first
+pub fn upcoming_events(state: &State, now: DateTime<Utc>) -> Vec<ApiEvent> {
+    let mut events: Vec<ApiEvent> = state
+        .events
+        .iter()
+        .filter(|(_, record)| record.announced)
+        .filter(|(_, record)| record.finish.map_or(false, |finish| finish > now))
+        .filter_map(|(&id, _)| api_event(state, id))
+        .collect();
+    events.sort_by_key(|event| (event.start, event.id));
+    events
+}
+
+/// Players of the event, `None` if the event is unknown
+pub fn event_rsvps(state: &State, event_id: usize) -> Option<ApiRsvps> {
+    let record = state.events.get(&event_id)?;
+    Some(ApiRsvps {
+        event_id,
+        title: record.title.clone(),
+        players: record.rsvps.clone(),
+        needs_players: record.needs_players,
+    })
+}
+
+/// Events the team played which finished before `now`, the most recent first
+pub fn history(state: &State, now: DateTime<Utc>) -> Vec<ApiEvent> {
+    let mut events: Vec<ApiEvent> = state
+        .events
+        .keys()
+        .filter_map(|&id| api_event(state, id))
+        .filter(|event| event.playing)
+        .filter(|event| event.finish.map_or(false, |finish| finish <= now))
+        .collect();
+    events.sort_by_key(|event| std::cmp::Reverse((event.finish, event.id)));
+    events
+}
+
+/// Response to the API request for `path`, without the [`API_PATH`] prefix, or `None` if the path is unknown
+pub fn api_response(path: &str, state: &State, now: DateTime<Utc>) -> Option<serde_json::Value> {
+    let value = match path.trim_end_matches('/').split('/').collect::<Vec<_>>()[..] {
+        ["events"] => serde_json::to_value(upcoming_events(state, now)),
+        ["history"] => serde_json::to_value(history(state, now)),
+        ["rsvps", id] => serde_json::to_value(event_rsvps(state, id.parse().ok()?)?),
+        _ => return None,
+    };
+    Some(value.expect("Serializing the response cannot fail"))
+}
+
+#[test]
+fn test_api() {
+    use crate::CtfEvent;
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let mut state = State::default();
+    state.record_events(&events);
+    let during = events[0].start_date().with_timezone(&Utc);
+    let after = events[0].finish_date().with_timezone(&Utc);
+
+    assert!(upcoming_events(&state, during).is_empty());
+    state.mark_announced(&[724]);
+    let upcoming = upcoming_events(&state, during);
+    assert_eq!(upcoming.len(), 1);
+    assert_eq!(upcoming[0].title, "X-MAS CTF 2018");
+    assert_eq!(upcoming[0].weight, 24.07);
+    assert!(!upcoming[0].playing);
+    assert!(upcoming_events(&state, after).is_empty());
+
+    assert!(history(&state, after).is_empty());
+    state
+        .events
+        .get_mut(&724)
+        .unwrap()
+        .rsvps
+        .insert("alice".to_string());
+    assert!(history(&state, during).is_empty());
+    assert_eq!(history(&state, after)[0].id, 724);
+
+    assert_eq!(
+        api_response("rsvps/724", &state, during).unwrap(),
+        serde_json::json!({
+            "event_id": 724,
+            "title": "X-MAS CTF 2018",
+            "players": ["alice"],
+            "needs_players": false,
+        })
+    );
+    assert!(api_response("events/", &state, during).is_some());
+    assert_eq!(api_response("rsvps/1", &state, during), None);
+    assert_eq!(api_response("rsvps/x", &state, during), None);
+    assert_eq!(api_response("teams", &state, during), None);
+}
@@ -0,0 +1,72 @@
+//! Fan out notifications through an [Apprise API] instance
+//!
+//! Apprise supports many notification services, e.g., Telegram, Pushover, or email.
+//! The bot sends each notification once to the Apprise API, which forwards it to all configured services.
+//!
+//! [Apprise API]: https://github.com/caronc/apprise-api
+
+use crate::mattermost_hook_api::Url;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Configuration of the Apprise backend, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct AppriseConfig {
+    /// Notify endpoint of the Apprise API
+    ///
+    /// Use `http://apprise:8000/notify/<KEY>` for a persistent configuration stored under `KEY`,
+    /// or `http://apprise:8000/notify/` together with [`urls`][AppriseConfig::urls].
+    pub url: Url,
+    /// Apprise URLs of the services, only required for the stateless endpoint
+    #[serde(default)]
+    pub urls: Vec<String>,
+    /// Only notify services with this tag
+    pub tag: Option<String>,
+    /// Format of the body sent to Apprise
+    #[serde(default)]
+    pub format: AppriseFormat,
+}
+
+/// Body formats understood by Apprise
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AppriseFormat {
+    #[default]
+    Markdown,
+    Text,
+}
+
+impl AppriseConfig {
+    /// Send a notification, `markdown` or `plain_text` is used depending on the configured format
+    pub fn send(
+        &self,
+        client: &Client,
+        title: &str,
+        markdown: &str,
+        plain_text: &str,
+    ) -> Result<(), reqwest::Error> {
+        let body = match self.format {
+            AppriseFormat::Markdown => markdown,
+            AppriseFormat::Text => plain_text,
+        };
+        let mut payload = json!({
+            "title": title,
+            "body": body,
+            "type": "info",
+            "format": self.format,
+        });
+        if !self.urls.is_empty() {
+            payload["urls"] = json!(self.urls.join(","));
+        }
+        if let Some(ref tag) = self.tag {
+            payload["tag"] = json!(tag);
+        }
+        client
+            .post(self.url.clone())
+            .json(&payload)
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,307 @@
+//! Cards on a Trello or Nextcloud Deck board for the announced events
+//!
+//! Each announced event gets one card, due at the start of the event.
+//! On Trello, the logo of the event becomes the cover image of the card.
+//! The cards are moved between the columns once the event starts and finishes.
+
+use crate::{mattermost_hook_api::Url, state::State, timed, CtfEvent};
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+/// Columns of the board, in the order an event moves through them
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Column {
+    Upcoming,
+    Playing,
+    Done,
+}
+
+impl Column {
+    /// Column of the event at time `now`
+    pub fn of_event(event: &CtfEvent, now: DateTime<Utc>) -> Self {
+        if now < event.start_date() {
+            Column::Upcoming
+        } else if now < event.finish_date() {
+            Column::Playing
+        } else {
+            Column::Done
+        }
+    }
+}
+
+/// The card of an event, as stored in the [`State`]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Card {
+    pub id: String,
+    pub column: Column,
+}
+
+/// Configuration of the board, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BoardConfig {
+    /// Trello board, the columns are identified by their list ids
+    Trello {
+        key: String,
+        token: String,
+        upcoming_list: String,
+        playing_list: String,
+        done_list: String,
+    },
+    /// Nextcloud Deck board, the columns are identified by their stack ids
+    Deck {
+        /// URL of the Nextcloud instance
+        url: Url,
+        username: String,
+        /// Password or app password of the user
+        password: String,
+        board_id: u64,
+        upcoming_stack: u64,
+        playing_stack: u64,
+        done_stack: u64,
+    },
+}
+
+impl BoardConfig {
+    fn column_id(&self, column: Column) -> String {
+        match (self, column) {
+            (BoardConfig::Trello { upcoming_list, .. }, Column::Upcoming) => upcoming_list.clone(),
+            (BoardConfig::Trello { playing_list, .. }, Column::Playing) => playing_list.clone(),
+            (BoardConfig::Trello { done_list, .. }, Column::Done) => done_list.clone(),
+            (BoardConfig::Deck { upcoming_stack, .. }, Column::Upcoming) => {
+                upcoming_stack.to_string()
+            }
+            (BoardConfig::Deck { playing_stack, .. }, Column::Playing) => playing_stack.to_string(),
+            (BoardConfig::Deck { done_stack, .. }, Column::Done) => done_stack.to_string(),
+        }
+    }
+
+    /// Create a new card for the event and return its id
+    pub fn create_card(
+        &self,
+        client: &Client,
+        event: &CtfEvent,
+        column: Column,
+    ) -> Result<String, reqwest::Error> {
+        let title = event.title();
+        let description = card_description(event);
+        let due = event.start_date().to_rfc3339();
+        let created: Value = match self {
+            BoardConfig::Trello { key, token, .. } => {
+                let created: Value = client
+                    .post("https://api.trello.com/1/cards")
+                    .query(&[("key", key), ("token", token)])
+                    .json(&json!({
+                        "idList": self.column_id(column),
+                        "name": title,
+                        "desc": description,
+                        "due": due,
+                    }))
+                    .send()?
+                    .error_for_status()?
+                    .json()?;
+                if let (Some(logo), Some(id)) = (cover_url(event), created["id"].as_str()) {
+                    // The card exists already, so a missing cover must not fail the creation
+                    if let Err(err) = client
+                        .post(&format!(
+                            "https://api.trello.com/1/cards/{}/attachments",
+                            id
+                        ))
+                        .query(&[("key", key), ("token", token)])
+                        .json(&json!({
+                            "url": logo,
+                            "name": "Logo",
+                            "setCover": true,
+                        }))
+                        .send()
+                        .and_then(|response| response.error_for_status())
+                    {
+                        error!("Couldn't set the cover of event {}: {}", event.id(), err);
+                    }
+                }
+                created
+            }
+            BoardConfig::Deck {
+                url,
+                username,
+                password,
+                board_id,
+                ..
+            } => client
+                .post(deck_url(
+                    url,
+                    &format!(
+                        "boards/{}/stacks/{}/cards",
+                        board_id,
+                        self.column_id(column)
+                    ),
+                ))
+                .basic_auth(username, Some(password))
+                .header("OCS-APIRequest", "true")
+                .json(&json!({
+                    "title": title,
+                    "type": "plain",
+                    "order": 999,
+                    "description": description,
+                    "duedate": due,
+                }))
+                .send()?
+                .error_for_status()?
+                .json()?,
+        };
+        Ok(match &created["id"] {
+            Value::String(id) => id.clone(),
+            id => id.to_string(),
+        })
+    }
+
+    /// Move the card to the column `to`
+    pub fn move_card(
+        &self,
+        client: &Client,
+        card: &Card,
+        to: Column,
+    ) -> Result<(), reqwest::Error> {
+        match self {
+            BoardConfig::Trello { key, token, .. } => {
+                client
+                    .put(&format!("https://api.trello.com/1/cards/{}", card.id))
+                    .query(&[
+                        ("key", key),
+                        ("token", token),
+                        ("idList", &self.column_id(to)),
+                    ])
+                    .send()?
+                    .error_for_status()?;
+            }
+            BoardConfig::Deck {
+                url,
+                username,
+                password,
+                board_id,
+                ..
+            } => {
+                client
+                    .put(deck_url(
+                        url,
+                        &format!(
+                            "boards/{}/stacks/{}/cards/{}/reorder",
+                            board_id,
+                            self.column_id(card.column),
+                            card.id
+                        ),
+                    ))
+                    .basic_auth(username, Some(password))
+                    .header("OCS-APIRequest", "true")
+                    .json(&json!({
+                        "order": 0,
+                        "stackId": self.column_id(to).parse::<u64>().unwrap_or_default(),
+                    }))
+                    .send()?
+                    .error_for_status()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn deck_url(base: &Url, path: &str) -> String {
+    format!(
+        "{}/index.php/apps/deck/api/v1.0/{}",
+        base.as_str().trim_end_matches('/'),
+        path
+    )
+}
+
+/// Logo of the event as cover image, if it has a valid one
+fn cover_url(event: &CtfEvent) -> Option<Url> {
+    event.logo_url()?.parse().ok()
+}
+
+fn card_description(event: &CtfEvent) -> String {
+    let mut description = format!(
+        "{} — {}\n\nCTFtime: {}\n",
+        event.title(),
+        event.format(),
+        event.ctftime_url()
+    );
+    if let Some(url) = event.url() {
+        description += &format!("Website: {}\n", url);
+    }
+    if let Some(weight) = event.rating_weight() {
+        description += &format!("Rating weight: {}\n", weight);
+    }
+    description
+}
+
+/// Create cards for the `new` events and move the existing cards to their current column
+///
+/// `events` should contain all known events, such that cards of past events are still moved.
+/// Errors are logged and retried during the next synchronization.
+pub fn sync_board(
+    board: &BoardConfig,
+    client: &Client,
+    state: &mut State,
+    events: &[CtfEvent],
+    new: &[usize],
+    now: DateTime<Utc>,
+) {
+    for event in events {
+        let column = Column::of_event(event, now);
+        let record = state.events.entry(event.id()).or_default();
+        match record.card {
+            Some(ref mut card) if card.column != column => {
+                match timed("Moving a card", || board.move_card(client, card, column)) {
+                    Ok(()) => {
+                        info!("Moved card of event {} to {:?}", event.id(), column);
+                        card.column = column;
+                    }
+                    Err(err) => error!("Couldn't move card of event {}: {}", event.id(), err),
+                }
+            }
+            Some(_) => {}
+            None if new.contains(&event.id()) => match timed("Creating a card", || {
+                board.create_card(client, event, column)
+            }) {
+                Ok(id) => {
+                    info!("Created card for event {}", event.id());
+                    record.card = Some(Card { id, column });
+                }
+                Err(err) => error!("Couldn't create card for event {}: {}", event.id(), err),
+            },
+            None => {}
+        }
+    }
+}
+
+#[test]
+fn test_card_column() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let event = &events[0];
+
+    let start = event.start_date().with_timezone(&Utc);
+    let finish = event.finish_date().with_timezone(&Utc);
+    assert_eq!(
+        Column::of_event(event, start - chrono::Duration::hours(1)),
+        Column::Upcoming
+    );
+    assert_eq!(Column::of_event(event, start), Column::Playing);
+    assert_eq!(Column::of_event(event, finish), Column::Done);
+}
+
+#[test]
+fn test_cover_url() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    for event in &events {
+        // Events without a logo have an empty string
+        let has_logo = event.logo_url().map_or(false, |logo| !logo.is_empty());
+        assert_eq!(cover_url(event).is_some(), has_logo);
+    }
+}
@@ -0,0 +1,82 @@
+//! Announcements of major CTFs for a general audience, e.g., a company-wide channel
+//!
+//! Broadcasts use a simplified template without CTF jargon like formats or weights.
+
+use crate::{local_date, mattermost_hook_api::Message, CtfEvent};
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+/// Settings of a broadcast [`Target`][crate::config::Target]
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub struct Broadcast {
+    /// Only events with at least this rating weight are announced
+    pub min_weight: f32,
+    /// Name of the team used in the announcement
+    #[serde(default = "default_team_name")]
+    pub team_name: String,
+}
+
+fn default_team_name() -> String {
+    "Our security team".to_string()
+}
+
+impl Broadcast {
+    /// Announcement of the major events of `events`, or `None` if there are none
+    pub fn message(&self, events: &[&CtfEvent], timezone: Option<Tz>) -> Option<Message> {
+        let lines: Vec<String> = events
+            .iter()
+            .filter(|event| event.weight() >= self.min_weight)
+            .map(|event| {
+                let start = local_date(&event.start_date(), timezone);
+                let finish = local_date(&event.finish_date(), timezone);
+                let dates = if start == finish {
+                    format!("on {}", start.format("%A, %B %-d"))
+                } else {
+                    format!(
+                        "from {} to {}",
+                        start.format("%A, %B %-d"),
+                        finish.format("%A, %B %-d")
+                    )
+                };
+                format!(
+                    "{} is competing in [{}]({}) {}.",
+                    self.team_name,
+                    event.title(),
+                    event.url().unwrap_or_else(|| event.ctftime_url()),
+                    dates,
+                )
+            })
+            .collect();
+        if lines.is_empty() {
+            return None;
+        }
+        Some(Message {
+            text: Some(lines.join("\n")),
+            ..Default::default()
+        })
+    }
+}
+
+#[test]
+fn test_broadcast_message() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let events: Vec<&CtfEvent> = events.iter().collect();
+
+    let broadcast = Broadcast {
+        min_weight: 20.,
+        team_name: default_team_name(),
+    };
+    let message = broadcast.message(&events, Some(Tz::UTC)).unwrap();
+    assert_eq!(
+        message.text.as_deref(),
+        Some("Our security team is competing in [X-MAS CTF 2018](https://www.xmas-ctf.cf/) from Friday, December 14 to Friday, December 21.")
+    );
+
+    let broadcast = Broadcast {
+        min_weight: 50.,
+        ..broadcast
+    };
+    assert!(broadcast.message(&events, Some(Tz::UTC)).is_none());
+}
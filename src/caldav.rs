@@ -0,0 +1,155 @@
+//! Synchronize the announced events into a CalDAV calendar, e.g., of Nextcloud or Radicale
+//!
+//! Each event is stored as its own resource, named after the CTFtime id, such that it can be updated and deleted.
+//! An event is uploaded again once its [`fingerprint`][CtfEvent::fingerprint] changes, e.g., because of new dates.
+//! Future events which disappear from CTFtime were cancelled, and their resources are deleted.
+
+use crate::{ical::to_ics, mattermost_hook_api::Url, state::State, timed, CtfEvent};
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use reqwest::{blocking::Client, StatusCode};
+use serde::Deserialize;
+
+/// Configuration of the CalDAV calendar, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct CalDavConfig {
+    /// URL of the calendar collection, e.g., `https://cloud.example.com/remote.php/dav/calendars/bot/ctfs/`
+    pub url: Url,
+    pub username: String,
+    /// Password or app password of the user
+    pub password: String,
+}
+
+impl CalDavConfig {
+    fn resource_url(&self, event_id: usize) -> String {
+        format!(
+            "{}/ctftimebot-{}.ics",
+            self.url.as_str().trim_end_matches('/'),
+            event_id
+        )
+    }
+
+    /// Create or replace the calendar entry of the event
+    pub fn put(
+        &self,
+        client: &Client,
+        event: &CtfEvent,
+        now: DateTime<Utc>,
+    ) -> Result<(), reqwest::Error> {
+        client
+            .put(&self.resource_url(event.id()))
+            .basic_auth(&self.username, Some(&self.password))
+            .header("Content-Type", "text/calendar; charset=utf-8")
+            .body(to_ics(&[event], now))
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Delete the calendar entry of the event, entries which don't exist are ignored
+    pub fn delete(&self, client: &Client, event_id: usize) -> Result<(), reqwest::Error> {
+        let response = client
+            .delete(&self.resource_url(event_id))
+            .basic_auth(&self.username, Some(&self.password))
+            .send()?;
+        if response.status() != StatusCode::NOT_FOUND {
+            response.error_for_status()?;
+        }
+        Ok(())
+    }
+}
+
+/// Ids of the synchronized events which were cancelled
+///
+/// An event was cancelled if it starts in the future, but is missing from the `events` fetched from CTFtime.
+pub fn cancelled_events(state: &State, events: &[CtfEvent], now: DateTime<Utc>) -> Vec<usize> {
+    state
+        .events
+        .iter()
+        .filter(|(_, record)| record.caldav_entry.is_some())
+        .filter(|(_, record)| record.start.map_or(false, |start| start > now))
+        .filter(|(&id, _)| events.iter().all(|event| event.id() != id))
+        .map(|(&id, _)| id)
+        .collect()
+}
+
+/// Upload the `announced` events which changed and delete the cancelled events
+///
+/// `events` must contain all events fetched from CTFtime, to detect the cancelled events.
+/// Errors are logged and retried during the next synchronization.
+pub fn sync_caldav(
+    caldav: &CalDavConfig,
+    client: &Client,
+    state: &mut State,
+    events: &[CtfEvent],
+    announced: &[usize],
+    now: DateTime<Utc>,
+) {
+    for event in events
+        .iter()
+        .filter(|event| announced.contains(&event.id()))
+    {
+        let fingerprint = event.fingerprint();
+        let record = state.events.entry(event.id()).or_default();
+        if record.caldav_entry.as_ref() == Some(&fingerprint) {
+            continue;
+        }
+        match timed("Uploading a calendar entry", || {
+            caldav.put(client, event, now)
+        }) {
+            Ok(()) => {
+                info!("Uploaded calendar entry of event {}", event.id());
+                record.caldav_entry = Some(fingerprint);
+            }
+            Err(err) => error!(
+                "Couldn't upload calendar entry of event {}: {}",
+                event.id(),
+                err
+            ),
+        }
+    }
+    for id in cancelled_events(state, events, now) {
+        match timed("Deleting a calendar entry", || caldav.delete(client, id)) {
+            Ok(()) => {
+                info!("Deleted calendar entry of cancelled event {}", id);
+                if let Some(record) = state.events.get_mut(&id) {
+                    record.caldav_entry = None;
+                }
+            }
+            Err(err) => error!("Couldn't delete calendar entry of event {}: {}", id, err),
+        }
+    }
+}
+
+#[test]
+fn test_caldav() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let mut events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let caldav = CalDavConfig {
+        url: "https://cloud.example.com/dav/calendars/bot/ctfs/"
+            .parse()
+            .unwrap(),
+        username: "bot".to_string(),
+        password: "secret".to_string(),
+    };
+    assert_eq!(
+        caldav.resource_url(724),
+        "https://cloud.example.com/dav/calendars/bot/ctfs/ctftimebot-724.ics"
+    );
+
+    let mut state = State::default();
+    state.record_events(&events);
+    let before = events[0].start_date().with_timezone(&Utc) - chrono::Duration::days(1);
+    assert!(cancelled_events(&state, &[], before).is_empty());
+    state.events.get_mut(&724).unwrap().caldav_entry = Some(events[0].fingerprint());
+    assert!(cancelled_events(&state, &events, before).is_empty());
+    assert_eq!(cancelled_events(&state, &[], before), vec![724]);
+    // Past events only drop out of the fetched time range
+    let after = events[0].start_date().with_timezone(&Utc) + chrono::Duration::days(1);
+    assert!(cancelled_events(&state, &[], after).is_empty());
+
+    let old = events[0].fingerprint();
+    events[0].title = "X-MAS CTF 2018 (rescheduled)".to_string();
+    assert_ne!(events[0].fingerprint(), old);
+}
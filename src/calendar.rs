@@ -0,0 +1,229 @@
+//! Internal events of the team from an ICS calendar, e.g., practice sessions and meetings
+//!
+//! CTFs which overlap with an internal event are flagged in the digest.
+//! Only the start, end, and summary of the events are read, recurring events are not expanded.
+
+use crate::{format_date, mattermost_hook_api::Url, CtfEvent};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use reqwest::blocking::Client;
+use std::collections::BTreeMap;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// An event of the team calendar
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InternalEvent {
+    pub summary: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+/// Load the calendar from a `http(s)` or `file` URL
+pub fn load_calendar(
+    client: &Client,
+    url: &Url,
+    timezone: Option<Tz>,
+) -> Result<Vec<InternalEvent>, BoxError> {
+    let ics = if url.scheme() == "file" {
+        let path = url
+            .to_file_path()
+            .map_err(|()| format!("Invalid file URL {}", url))?;
+        std::fs::read_to_string(path)?
+    } else {
+        client.get(url.clone()).send()?.error_for_status()?.text()?
+    };
+    Ok(parse_ics(&ics, timezone))
+}
+
+/// Parse the events of an ICS file
+///
+/// Times without timezone are interpreted in `timezone`, or UTC if unset.
+/// Events without a start are skipped, events without an end last one hour or one day, for all-day events.
+pub fn parse_ics(ics: &str, timezone: Option<Tz>) -> Vec<InternalEvent> {
+    // Continuation lines start with a space or tab
+    let unfolded = ics
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+    let mut events = Vec::new();
+    let mut current: Option<BTreeMap<String, (Option<String>, String)>> = None;
+    for line in unfolded.lines() {
+        match line {
+            "BEGIN:VEVENT" => current = Some(BTreeMap::new()),
+            "END:VEVENT" => {
+                if let Some(event) = current.take().and_then(|props| to_event(&props, timezone)) {
+                    events.push(event);
+                }
+            }
+            _ => {
+                if let (Some(props), Some((name, value))) = (&mut current, line.split_once(':')) {
+                    // Parameters like `DTSTART;TZID=Europe/Berlin`, only the TZID is used
+                    let mut params = name.split(';');
+                    let name = params.next().unwrap_or_default().to_ascii_uppercase();
+                    let tzid = params
+                        .filter_map(|param| param.strip_prefix("TZID="))
+                        .map(|tzid| tzid.trim_matches('"').to_string())
+                        .next();
+                    props.insert(name, (tzid, value.to_string()));
+                }
+            }
+        }
+    }
+    events
+}
+
+fn to_event(
+    props: &BTreeMap<String, (Option<String>, String)>,
+    timezone: Option<Tz>,
+) -> Option<InternalEvent> {
+    let (start, all_day) = props
+        .get("DTSTART")
+        .and_then(|(tzid, value)| parse_time(value, tzid.as_deref(), timezone))?;
+    let end = props
+        .get("DTEND")
+        .and_then(|(tzid, value)| parse_time(value, tzid.as_deref(), timezone))
+        .map(|(end, _)| end)
+        .unwrap_or_else(|| {
+            start
+                + if all_day {
+                    chrono::Duration::days(1)
+                } else {
+                    chrono::Duration::hours(1)
+                }
+        });
+    let summary = props
+        .get("SUMMARY")
+        .map(|(_, summary)| {
+            summary
+                .replace("\\,", ",")
+                .replace("\\;", ";")
+                .replace("\\n", " ")
+        })
+        .unwrap_or_default();
+    Some(InternalEvent {
+        summary,
+        start,
+        end,
+    })
+}
+
+/// Parse a `DATE` or `DATE-TIME` value, the second value is true for dates
+fn parse_time(
+    value: &str,
+    tzid: Option<&str>,
+    timezone: Option<Tz>,
+) -> Option<(DateTime<Utc>, bool)> {
+    let tz = tzid
+        .and_then(|tzid| tzid.parse::<Tz>().ok())
+        .or(timezone)
+        .unwrap_or(Tz::UTC);
+    if let Some(utc) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some((DateTime::from_utc(time, Utc), false));
+    }
+    if let Ok(time) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        let time = tz.from_local_datetime(&time).earliest()?;
+        return Some((time.with_timezone(&Utc), false));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+    let time = tz.from_local_datetime(&date.and_hms(0, 0, 0)).earliest()?;
+    Some((time.with_timezone(&Utc), true))
+}
+
+/// Internal events which overlap with `event`
+pub fn clashes<'a>(event: &CtfEvent, calendar: &'a [InternalEvent]) -> Vec<&'a InternalEvent> {
+    calendar
+        .iter()
+        .filter(|internal| {
+            internal.start < event.finish_date() && event.start_date() < internal.end
+        })
+        .collect()
+}
+
+/// Warnings for all `events` which clash with the team calendar, keyed by the event id
+pub fn clash_notes(
+    events: &[CtfEvent],
+    calendar: &[InternalEvent],
+    timezone: Option<Tz>,
+) -> BTreeMap<usize, String> {
+    events
+        .iter()
+        .filter_map(|event| {
+            let clashes = clashes(event, calendar);
+            if clashes.is_empty() {
+                return None;
+            }
+            let clashes: Vec<String> = clashes
+                .iter()
+                .map(|internal| {
+                    format!(
+                        "{} ({})",
+                        internal.summary,
+                        format_date(
+                            &internal.start.with_timezone(&FixedOffset::east(0)),
+                            timezone
+                        )
+                    )
+                })
+                .collect();
+            Some((
+                event.id(),
+                format!("⚠ Clashes with team calendar: {}", clashes.join(", ")),
+            ))
+        })
+        .collect()
+}
+
+#[test]
+fn test_parse_ics() {
+    let ics = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+DTSTART:20181215T140000Z\r
+DTEND:20181215T160000Z\r
+SUMMARY:Practice session\\, web\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART;TZID=Europe/Berlin:20181210T190000\r
+SUMMARY:Team meet\r
+ ing\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART;VALUE=DATE:20181224\r
+DTEND;VALUE=DATE:20181227\r
+SUMMARY:Holidays\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+    let calendar = parse_ics(ics, None);
+    assert_eq!(
+        calendar,
+        vec![
+            InternalEvent {
+                summary: "Practice session, web".to_string(),
+                start: "2018-12-15T14:00:00Z".parse().unwrap(),
+                end: "2018-12-15T16:00:00Z".parse().unwrap(),
+            },
+            InternalEvent {
+                summary: "Team meeting".to_string(),
+                start: "2018-12-10T18:00:00Z".parse().unwrap(),
+                end: "2018-12-10T19:00:00Z".parse().unwrap(),
+            },
+            InternalEvent {
+                summary: "Holidays".to_string(),
+                start: "2018-12-24T00:00:00Z".parse().unwrap(),
+                end: "2018-12-27T00:00:00Z".parse().unwrap(),
+            },
+        ]
+    );
+
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let notes = clash_notes(&events, &calendar, Some(Tz::UTC));
+    assert_eq!(
+        notes[&724],
+        "⚠ Clashes with team calendar: Practice session, web (Sat 2018-12-15 14:00 UTC)"
+    );
+}
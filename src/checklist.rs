@@ -0,0 +1,87 @@
+//! Checklist posted before the start of an event the team plays
+//!
+//! The checklist is a [Handlebars] template with the same values as the template webhooks, see [`event_context`].
+//! Mattermost incoming webhooks cannot reply in a thread, so the checklist links to the announcement the players RSVP'd on instead.
+//!
+//! [Handlebars]: https://handlebarsjs.com/guide/
+
+use crate::{
+    config::Target,
+    format_date,
+    mattermost_hook_api::Url,
+    webhook::{event_context, render_text},
+    CtfEvent, CONFIG,
+};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Checklist used if no template is configured
+pub const DEFAULT_CHECKLIST: &str = "- [ ] Infrastructure prepared
+- [ ] VPN configs distributed
+- [ ] Roles assigned
+- [ ] Scoreboard bookmarked: {{url}}";
+
+/// Link to a Mattermost post, using the server of the first Mattermost target
+///
+/// The `_redirect` permalink works without knowing the name of the team.
+pub fn permalink(targets: &[Target], post_id: &str) -> Option<Url> {
+    let target = targets
+        .iter()
+        .find(|target| target.backend == "mattermost" && target.broadcast.is_none())?;
+    let mut url = target.webhook_url.clone();
+    url.set_query(None);
+    url.path_segments_mut()
+        .ok()?
+        .clear()
+        .extend(&["_redirect", "pl", post_id]);
+    Some(url)
+}
+
+/// Text of the checklist post for `event`, linking to the `announcement` if known
+pub fn checklist_message(
+    event: &CtfEvent,
+    template: &str,
+    announcement: Option<&Url>,
+) -> Result<String, BoxError> {
+    let mut text = format!(
+        "📋 Checklist for [{}]({}), starting {}",
+        event.display_title(),
+        event.ctftime_url(),
+        format_date(&event.start_date(), CONFIG.timezone),
+    );
+    if let Some(announcement) = announcement {
+        text += &format!(" — see the [announcement]({})", announcement);
+    }
+    text += "\n\n";
+    text += &render_text(template, &event_context(event))?;
+    Ok(text)
+}
+
+#[test]
+fn test_checklist() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let target = |backend: &str| Target {
+        webhook_url: "https://chat.example.com/hooks/abc?x=1".parse().unwrap(),
+        channel: None,
+        username: None,
+        icon_url: None,
+        icon_emoji: None,
+        backend: backend.to_string(),
+        broadcast: None,
+        signing_secret: None,
+    };
+
+    assert_eq!(permalink(&[target("discord")], "p1"), None);
+    let link = permalink(&[target("discord"), target("mattermost")], "p1").unwrap();
+    assert_eq!(link.as_str(), "https://chat.example.com/_redirect/pl/p1");
+
+    let text = checklist_message(&events[0], DEFAULT_CHECKLIST, Some(&link)).unwrap();
+    assert!(text.starts_with(
+        "📋 Checklist for [X-MAS CTF 2018](https://ctftime.org/event/724/), starting "
+    ));
+    assert!(text.contains(" — see the [announcement](https://chat.example.com/_redirect/pl/p1)\n\n- [ ] Infrastructure prepared\n"));
+    assert!(text.ends_with("- [ ] Scoreboard bookmarked: https://www.xmas-ctf.cf/"));
+    assert!(checklist_message(&events[0], "{{unknown}}", None).is_err());
+}
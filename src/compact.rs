@@ -0,0 +1,59 @@
+//! Short texts of events for microblogging backends with a character limit, e.g., Mastodon and X
+
+use crate::{format_date, CtfEvent, CONFIG};
+
+/// Hashtag of the event title, e.g., `#XMASCTF2018`
+pub fn event_hashtag(event: &CtfEvent) -> Option<String> {
+    let tag: String = event
+        .title()
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect();
+    // Hashtags consisting only of digits are not recognized
+    if tag.chars().all(|c| c.is_ascii_digit()) {
+        None
+    } else {
+        Some(format!("#{}", tag))
+    }
+}
+
+/// Title, format, date, hashtags, and link of `event`, shortened to `max_length` characters
+///
+/// The link is always kept complete, the hashtags are cut first.
+pub fn compact_text(event: &CtfEvent, hashtags: &[String], max_length: usize) -> String {
+    let mut tags: Vec<String> = event_hashtag(event).into_iter().collect();
+    tags.extend(hashtags.iter().map(|tag| format!("#{}", tag)));
+    let tags = tags.join(" ");
+    let head = format!(
+        "{} — {}\n{}\n",
+        event.title(),
+        event.format(),
+        format_date(&event.start_date(), CONFIG.timezone),
+    );
+    let link = event.url().unwrap_or_else(|| event.ctftime_url());
+    // Both Mastodon and X count links as 23 characters, but a full link is always short enough
+    let budget = max_length.saturating_sub(link.chars().count() + 1);
+    let mut text: String = format!("{}\n{}", head, tags).chars().take(budget).collect();
+    text += "\n";
+    text += link;
+    text
+}
+
+#[test]
+fn test_compact_text() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    assert_eq!(event_hashtag(&events[0]).as_deref(), Some("#XMASCTF2018"));
+    let text = compact_text(&events[0], &["CTF".to_string()], 500);
+    assert!(text.starts_with("X-MAS CTF 2018 — Jeopardy\n"));
+    assert!(text.ends_with("\n\n#XMASCTF2018 #CTF\nhttps://www.xmas-ctf.cf/"));
+
+    let many: Vec<String> = (0..100).map(|idx| format!("tag{}", idx)).collect();
+    for &max_length in &[500, 280] {
+        let text = compact_text(&events[0], &many, max_length);
+        assert!(text.chars().count() <= max_length);
+        assert!(text.ends_with("\nhttps://www.xmas-ctf.cf/"));
+    }
+}
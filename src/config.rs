@@ -0,0 +1,1025 @@
+use crate::{
+    apprise::AppriseConfig,
+    board::BoardConfig,
+    broadcast::Broadcast,
+    caldav::CalDavConfig,
+    digest::PostLimits,
+    duration::Humantime,
+    email::EmailConfig,
+    escalation::EscalationConfig,
+    github_issues::GithubIssuesConfig,
+    gitlab_issues::GitlabIssuesConfig,
+    holidays::{Blackout, Holiday},
+    i18n::Locale,
+    mastodon::MastodonConfig,
+    matrix::MatrixConfig,
+    mattermost_hook_api::{Color, Message, Url},
+    notion::NotionConfig,
+    push::PushConfig,
+    qualifiers::{PhaseOverride, QualifierLink},
+    recap::RecapDate,
+    reformat::MattermostApiConfig,
+    server::ACTIONS_PATH,
+    signal::SignalConfig,
+    spreadsheet::SpreadsheetConfig,
+    twilio::TwilioConfig,
+    twitter::TwitterConfig,
+    warmup::WarmupConfig,
+    webhook::TemplateWebhook,
+    xmpp::XmppConfig,
+    zulip::ZulipConfig,
+};
+use chrono::{Duration, Weekday};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr, NoneAsEmptyString};
+use std::{collections::BTreeMap, fmt, net::IpAddr, path::PathBuf};
+
+/// Name of the environment variable pointing to a TOML configuration file
+pub const CONFIG_FILE_ENV: &str = "CONFIG_FILE";
+
+#[serde_as]
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct Config {
+    /// Webhook used if no [`targets`][Config::targets] are configured
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub webhook_url: Option<Url>,
+    /// Secret to sign the requests to [`webhook_url`][Config::webhook_url], see [`Target::signing_secret`]
+    #[serde(default)]
+    pub webhook_secret: Option<String>,
+    /// Webhook for operational messages, e.g., failed fetches or deliveries, see [`ops`][crate::ops]
+    #[serde_as(as = "NoneAsEmptyString")]
+    #[serde(default)]
+    pub admin_webhook_url: Option<Url>,
+    /// Only events starting within this time are announced, e.g., `21d` or `36h`
+    #[serde_as(as = "Humantime")]
+    pub lookahead: Duration,
+    pub color_jeopardy: Color,
+    pub color_attack_defense: Color,
+    pub bot_icon: Option<Url>,
+    /// Emoji used as profile picture, overrides [`bot_icon`][Config::bot_icon]
+    #[serde(default)]
+    pub bot_icon_emoji: Option<String>,
+    /// Username the bot posts as
+    #[serde(default)]
+    pub bot_username: Option<String>,
+    pub always_show_ctfs: Vec<usize>,
+    pub mattermost_channel: Option<String>,
+    /// Only show events with at least this rating weight
+    ///
+    /// Events crossing this threshold between two runs are announced separately.
+    #[serde(default)]
+    pub min_weight: Option<u32>,
+    /// Announce events once this many teams signed up for them
+    #[serde(default)]
+    pub participants_threshold: Option<usize>,
+    /// File storing the state between two runs
+    #[serde(default)]
+    pub state_file: Option<PathBuf>,
+    /// Events the team plays, identified by their CTFtime event id
+    #[serde(default)]
+    pub playing_events: Vec<usize>,
+    /// Time before the end of a played event to send a reminder, e.g., `2h`
+    #[serde_as(as = "Humantime")]
+    #[serde(default = "default_ends_soon_lead_time")]
+    pub ends_soon_lead_time: Duration,
+    /// Post a checklist before the start of a played event
+    #[serde(default)]
+    pub pre_event_checklist: bool,
+    /// Time before the start of a played event to post the checklist
+    #[serde_as(as = "Humantime")]
+    #[serde(default = "default_checklist_lead_time")]
+    pub checklist_lead_time: Duration,
+    /// Handlebars template of the checklist, with the values of the event like the template webhooks
+    ///
+    /// Defaults to [`DEFAULT_CHECKLIST`][crate::checklist::DEFAULT_CHECKLIST].
+    #[serde(default)]
+    pub checklist_template: Option<String>,
+    /// Time after the end of a played event to ask for the writeups
+    #[serde_as(as = "Humantime")]
+    #[serde(default = "default_writeup_ping_delay")]
+    pub writeup_ping_delay: Duration,
+    /// Challenge categories listed in the writeup post
+    #[serde(default)]
+    pub writeup_categories: Vec<String>,
+    /// Time after the end of a played event to ask for the weight vote
+    #[serde_as(as = "Humantime")]
+    #[serde(default = "default_vote_prompt_delay")]
+    pub vote_prompt_delay: Duration,
+    /// Time after the end of a played event to post the results, once CTFtime has them
+    #[serde_as(as = "Humantime")]
+    #[serde(default = "default_results_delay")]
+    pub results_delay: Duration,
+    /// Weight suggested for events without a previous weight
+    #[serde(default = "default_vote_default_weight")]
+    pub vote_default_weight: f64,
+    /// Maximal reduction of the suggested weight for unstable infrastructure, between `0` and `1`
+    #[serde(default = "default_vote_factor")]
+    pub vote_infra_factor: f64,
+    /// Maximal change of the suggested weight based on the challenge quality, between `0` and `1`
+    #[serde(default = "default_vote_factor")]
+    pub vote_quality_factor: f64,
+    /// Address the server listens on in daemon mode, e.g., `127.0.0.1:8080`
+    #[serde(default)]
+    pub server_address: Option<String>,
+    /// Public URL of the server, used for the interactive buttons
+    #[serde(default)]
+    pub server_url: Option<Url>,
+    /// Token of the `/ctftime` slash command, requests with a different token are rejected
+    #[serde(default)]
+    pub command_token: Option<String>,
+    /// Token of the read-only JSON API of the server, the API is disabled if unset and no access tokens exist
+    ///
+    /// Clients send the token as `Authorization: Bearer <token>`.
+    /// The token also protects the GraphQL endpoint of the `graphql` feature.
+    #[serde(default)]
+    pub api_token: Option<String>,
+    /// Token added to the URLs of the interactive buttons
+    ///
+    /// Required once access tokens exist, it needs the `manage-subscriptions` scope, see [`access`][crate::access].
+    #[serde(default)]
+    pub actions_token: Option<String>,
+    /// PEM certificate chain, the server uses HTTPS if this and [`server_tls_key`][Config::server_tls_key] are set
+    ///
+    /// Requires the `server-tls` feature.
+    #[serde(default)]
+    pub server_tls_cert: Option<PathBuf>,
+    /// PEM private key of [`server_tls_cert`][Config::server_tls_cert]
+    #[serde(default)]
+    pub server_tls_key: Option<PathBuf>,
+    /// Reverse proxies whose `X-Forwarded-For` header is trusted
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+    /// User names allowed to use the admin commands, e.g., `/ctftime admin mute 7d`
+    #[serde(default)]
+    pub admins: Vec<String>,
+    /// Maximal number of button clicks and commands per user and minute
+    #[serde(default = "default_rate_limit_per_user")]
+    pub rate_limit_per_user: u32,
+    /// Maximal number of button clicks and commands per channel and minute
+    #[serde(default = "default_rate_limit_per_channel")]
+    pub rate_limit_per_channel: u32,
+    /// Trello or Nextcloud Deck board with a card for each announced event
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub board: Option<BoardConfig>,
+    /// CalDAV calendar with an entry for each announced event
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub caldav: Option<CalDavConfig>,
+    /// GitHub repository with an issue for each announced event
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub github_issues: Option<GithubIssuesConfig>,
+    /// GitLab project with an issue for each announced event
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub gitlab_issues: Option<GitlabIssuesConfig>,
+    /// REST API of Mattermost, used by the `reformat` subcommand to edit the recent digests
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub mattermost_api: Option<MattermostApiConfig>,
+    /// Pin the latest digest and keep its weights and numbers of participants current on every run
+    ///
+    /// Requires [`mattermost_api`][Config::mattermost_api] and a state file.
+    /// Editing the post doesn't notify anyone.
+    #[serde(default)]
+    pub update_in_place: bool,
+    /// Notion database with a row for each announced event
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub notion: Option<NotionConfig>,
+    /// Webhook or command to warm up the infrastructure before played Attack-Defense events
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub warmup: Option<WarmupConfig>,
+    /// Escalate high-weight events with too few RSVPs, with an `@channel` post and direct messages to the captains
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub escalation: Option<EscalationConfig>,
+    /// Google Sheet or CSV file in a GitHub repository with a row for each announced event
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub spreadsheet: Option<SpreadsheetConfig>,
+    /// Signal group or recipients, which receive the digest and reminders as plain text
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub signal: Option<SignalConfig>,
+    /// Mastodon account, which posts the digest as a public thread
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub mastodon: Option<MastodonConfig>,
+    /// X account, which posts the digest as a public thread
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub twitter: Option<TwitterConfig>,
+    /// Zulip stream, which receives the digest as one message per event
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub zulip: Option<ZulipConfig>,
+    /// Recipients of the digest as email
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub email: Option<EmailConfig>,
+    /// ntfy topic or Gotify server, which receives a push notification per newly announced event
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub push: Option<PushConfig>,
+    /// Webhooks receiving JSON rendered from a template, for services without a dedicated backend
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub webhooks: Vec<TemplateWebhook>,
+    /// Post the digest to an XMPP multi-user chat, requires the `xmpp` feature
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub xmpp: Option<XmppConfig>,
+    /// Handlebars templates replacing the digest text of single backends, e.g., a terse text for `push`
+    ///
+    /// The keys are the backends of the targets, e.g., `mattermost`, or `signal`, `matrix`, `apprise`, `mastodon`, `x`, `xmpp`, `zulip`, and `push`.
+    /// The values of [`digest_context`](crate::webhook::digest_context) are available, for `push` those of [`event_context`](crate::webhook::event_context).
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub templates: BTreeMap<String, String>,
+    /// Matrix room, which receives the digest and reminders as formatted notices
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub matrix: Option<MatrixConfig>,
+    /// SMS or WhatsApp recipients for high-priority reminders
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub twilio: Option<TwilioConfig>,
+    /// Apprise API instance, which forwards the digest and reminders to further services
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub apprise: Option<AppriseConfig>,
+    /// Settings for individual events
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub events: Vec<EventSettings>,
+    /// Time between two refreshes of the event data in daemon mode and of the iCalendar feed
+    #[serde_as(as = "Humantime")]
+    #[serde(default = "default_refresh_interval")]
+    pub refresh_interval: Duration,
+    /// Fail on fields of the CTFtime API which the bot doesn't know, instead of only logging them
+    #[serde(default)]
+    pub strict_api: bool,
+    /// Maximal number of events per request to the CTFtime API, larger time ranges are split into multiple requests
+    #[serde(default = "default_api_limit")]
+    pub api_limit: usize,
+    /// Base URL of the CTFtime API, e.g., for a mirror or a local server in tests
+    #[serde(default = "default_ctftime_url")]
+    pub ctftime_url: Url,
+    /// Look up the organizing teams of the events and cache them in the state file
+    #[serde(default)]
+    pub enrich_teams: bool,
+    /// Time until cached team information is fetched again, e.g., `7d`
+    #[serde_as(as = "Humantime")]
+    #[serde(default = "default_team_cache_ttl")]
+    pub team_cache_ttl: Duration,
+    /// Name of the filter configuration, included in the post metadata
+    #[serde(default)]
+    pub filter_profile: Option<String>,
+    /// Prometheus pushgateway receiving the metrics of each run in cron mode
+    #[serde(default)]
+    pub pushgateway_url: Option<Url>,
+    /// DSN of the Sentry project receiving errors and panics, requires the `sentry` feature
+    #[serde(default)]
+    pub sentry_dsn: Option<String>,
+    /// Keep previously announced events in the digest, even if they no longer match the filters
+    #[serde(default)]
+    pub sticky_announcements: bool,
+    /// List the events published on CTFtime since the last run in the digest, requires the state file
+    #[serde(default)]
+    pub whats_new: bool,
+    /// Add a footer with the time the data was fetched to the digest
+    #[serde(default)]
+    pub digest_footer: bool,
+    /// Free text describing the next digest, e.g., `Monday`, shown in the footer
+    #[serde(default)]
+    pub digest_next_update: Option<String>,
+    /// Icon shown next to the footer of the digest
+    #[serde(default)]
+    pub digest_footer_icon: Option<Url>,
+    /// Leave out fields without a value, e.g., unknown organizers, instead of showing them as `unknown`
+    #[serde(default)]
+    pub hide_empty_fields: bool,
+    /// Larger digests are split into multiple posts, see [`PostLimits`]
+    #[serde(default = "default_max_events_per_post")]
+    pub max_events_per_post: usize,
+    /// Digests whose events are longer than this many characters are split into multiple posts
+    #[serde(default = "default_max_post_length")]
+    pub max_post_length: usize,
+    /// Add a fun fact to the end of the digest
+    ///
+    /// Facts about the results of [`team_id`][Config::team_id] last year are preferred over the [`trivia`][Config::trivia] pool.
+    #[serde(default)]
+    pub trivia_footer: bool,
+    /// Pool of fun facts for the digest
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub trivia: Vec<String>,
+    /// CTFtime id of the team, used to look up past results
+    #[serde(default)]
+    pub team_id: Option<usize>,
+    /// CTFtime ids of rival teams, whose placements are shown next to ours in the results
+    #[serde(default)]
+    pub rivals: Vec<usize>,
+    /// Post a recap of the season on [`recap_date`][Config::recap_date], requires [`team_id`][Config::team_id]
+    #[serde(default)]
+    pub season_recap: bool,
+    /// Day of the season recap as `MM-DD`
+    #[serde(default)]
+    pub recap_date: RecapDate,
+    /// Country code of the team, e.g., `de`, to post changes of our rank in the national leaderboard
+    ///
+    /// Requires [`team_id`][Config::team_id].
+    #[serde(default)]
+    pub country: Option<String>,
+    /// Post the top 10 teams of CTFtime every week on this day, e.g., `Mon`
+    #[serde(default)]
+    pub leaderboard_weekday: Option<Weekday>,
+    /// Country code, e.g., `de`, to post the national instead of the global top 10 teams
+    #[serde(default)]
+    pub leaderboard_country: Option<String>,
+    /// Timezone of the dates, e.g., `Europe/Berlin`, the local timezone is used if unset
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub timezone: Option<Tz>,
+    /// Language of the durations, e.g., `de` for `3 Tage`, one of `en`, `de`, `fr`, `pl`, and `ru`
+    #[serde(default)]
+    pub locale: Locale,
+    /// Public holidays, which are noted on overlapping events
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub holidays: Vec<Holiday>,
+    /// Periods during which the team does not play
+    ///
+    /// Events during these periods are still listed, but tagged and excluded from reminders.
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub blackouts: Vec<Blackout>,
+    /// ICS calendar with the internal events of the team, e.g., practice sessions
+    ///
+    /// CTFs clashing with these events are flagged in the digest. Supports `http(s)` and `file` URLs.
+    #[serde(default)]
+    pub team_calendar: Option<Url>,
+    /// Qualifiers and their finals, which cannot be linked by their titles
+    ///
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub qualifiers: Vec<QualifierLink>,
+    /// Phases of events whose titles don't mention them, e.g., `{ event = 1234, phase = "teaser" }`
+    ///
+    /// The phase is added to the title in the digest and the reminders.
+    /// Only available in the configuration file.
+    #[serde(default)]
+    pub phases: Vec<PhaseOverride>,
+    /// Destinations which receive the posts
+    ///
+    /// Only available in the configuration file.
+    /// If empty, a single target is created from [`webhook_url`][Config::webhook_url] and the `bot_*` options.
+    #[serde(default)]
+    pub targets: Vec<Target>,
+}
+
+fn default_api_limit() -> usize {
+    30
+}
+
+fn default_max_events_per_post() -> usize {
+    20
+}
+
+/// Mattermost accepts up to 16383 characters by default
+fn default_max_post_length() -> usize {
+    16000
+}
+
+fn default_ctftime_url() -> Url {
+    "https://ctftime.org"
+        .parse()
+        .expect("The default URL is valid")
+}
+
+fn default_refresh_interval() -> Duration {
+    Duration::minutes(15)
+}
+
+fn default_rate_limit_per_user() -> u32 {
+    10
+}
+
+fn default_rate_limit_per_channel() -> u32 {
+    60
+}
+
+fn default_team_cache_ttl() -> Duration {
+    Duration::days(7)
+}
+
+fn default_ends_soon_lead_time() -> Duration {
+    Duration::hours(2)
+}
+
+fn default_checklist_lead_time() -> Duration {
+    Duration::hours(48)
+}
+
+fn default_writeup_ping_delay() -> Duration {
+    Duration::hours(72)
+}
+
+fn default_vote_prompt_delay() -> Duration {
+    Duration::hours(24)
+}
+
+fn default_results_delay() -> Duration {
+    Duration::hours(24)
+}
+
+fn default_vote_default_weight() -> f64 {
+    25.
+}
+
+fn default_vote_factor() -> f64 {
+    0.5
+}
+
+/// Former names of the durations with the unit of their plain numbers, and their current names
+const LEGACY_DURATIONS: &[(&str, &str, &str)] = &[
+    ("days_into_future", "lookahead", "d"),
+    ("ends_soon_hours", "ends_soon_lead_time", "h"),
+    ("checklist_hours", "checklist_lead_time", "h"),
+    ("writeup_ping_delay_hours", "writeup_ping_delay", "h"),
+    ("vote_prompt_delay_hours", "vote_prompt_delay", "h"),
+    ("results_delay_hours", "results_delay", "h"),
+    ("refresh_interval_minutes", "refresh_interval", "m"),
+    ("team_cache_ttl_hours", "team_cache_ttl", "h"),
+];
+
+/// Former names of the durations in [`EventSettings`], see [`LEGACY_DURATIONS`]
+const LEGACY_EVENT_DURATIONS: &[(&str, &str, &str)] = &[
+    ("ends_soon_hours", "ends_soon_lead_time", "h"),
+    ("warmup_hours", "warmup_lead_time", "h"),
+];
+
+/// Value of a duration under its former name, plain numbers get the former `unit`
+fn legacy_duration(value: &str, unit: &str) -> String {
+    match value.trim().parse::<i64>() {
+        Ok(number) => format!("{}{}", number, unit),
+        Err(_) => value.to_string(),
+    }
+}
+
+/// Rename the durations of `table` with a former name, see [`LEGACY_DURATIONS`]
+///
+/// The current name wins if both are set.
+fn upgrade_legacy_durations(table: &mut toml::value::Table, legacy: &[(&str, &str, &str)]) {
+    for (old, new, unit) in legacy {
+        let value = match table.get(*old) {
+            Some(toml::Value::Integer(number)) => format!("{}{}", number, unit),
+            Some(toml::Value::String(value)) => legacy_duration(value, unit),
+            _ => continue,
+        };
+        table.remove(*old);
+        table
+            .entry(new.to_string())
+            .or_insert(toml::Value::String(value));
+    }
+}
+
+/// Error while loading the [`Config`]
+#[derive(Debug)]
+pub enum ConfigError {
+    Env(envy::Error),
+    Io(PathBuf, std::io::Error),
+    Toml(PathBuf, toml::de::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Env(err) => write!(f, "Invalid environment configuration: {}", err),
+            ConfigError::Io(path, err) => write!(f, "Cannot read {}: {}", path.display(), err),
+            ConfigError::Toml(path, err) => {
+                write!(f, "Invalid config file {}: {}", path.display(), err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Load the configuration
+    ///
+    /// The environment is populated from the `.env` file first.
+    /// If the [`CONFIG_FILE`][CONFIG_FILE_ENV] environment variable is set, the configuration is read from this TOML file.
+    /// Otherwise the configuration is read from the environment variables.
+    pub fn load() -> Result<Self, ConfigError> {
+        dotenv::dotenv().expect("Failed to read .env file");
+        match std::env::var_os(CONFIG_FILE_ENV) {
+            Some(path) => Self::from_file(path.into()),
+            None => Self::from_env_vars(std::env::vars()),
+        }
+    }
+
+    /// Read the configuration from environment variables, accepting the former names of the durations
+    pub fn from_env_vars(
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, ConfigError> {
+        let vars = vars.into_iter().map(|(name, value)| {
+            let legacy = LEGACY_DURATIONS
+                .iter()
+                .find(|(old, _, _)| old.eq_ignore_ascii_case(&name));
+            match legacy {
+                Some((_, new, unit)) => (new.to_uppercase(), legacy_duration(&value, unit)),
+                None => (name, value),
+            }
+        });
+        envy::from_iter(vars).map_err(ConfigError::Env)
+    }
+
+    /// Read the configuration from a TOML file
+    pub fn from_file(path: PathBuf) -> Result<Self, ConfigError> {
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) => return Err(ConfigError::Io(path, err)),
+        };
+        Self::from_toml(&content).map_err(|err| ConfigError::Toml(path, err))
+    }
+
+    /// Parse the configuration from TOML, accepting the former names of the durations
+    pub fn from_toml(content: &str) -> Result<Self, toml::de::Error> {
+        let mut value: toml::Value = toml::from_str(content)?;
+        if let Some(table) = value.as_table_mut() {
+            upgrade_legacy_durations(table, LEGACY_DURATIONS);
+            for settings in table
+                .get_mut("events")
+                .and_then(toml::Value::as_array_mut)
+                .into_iter()
+                .flatten()
+                .filter_map(toml::Value::as_table_mut)
+            {
+                upgrade_legacy_durations(settings, LEGACY_EVENT_DURATIONS);
+            }
+            for section in &["warmup", "escalation"] {
+                if let Some(section) = table.get_mut(*section).and_then(toml::Value::as_table_mut) {
+                    upgrade_legacy_durations(section, &[("hours", "lead_time", "h")]);
+                }
+            }
+        }
+        value.try_into()
+    }
+
+    /// URL receiving the clicks of the interactive buttons, if the server is configured
+    pub fn actions_url(&self) -> Option<Url> {
+        let mut url = self.server_url.as_ref()?.join(ACTIONS_PATH).ok()?;
+        if let Some(ref token) = self.actions_token {
+            url.query_pairs_mut().append_pair("token", token);
+        }
+        Some(url)
+    }
+
+    /// Limits of a single post of the digest
+    pub fn post_limits(&self) -> PostLimits {
+        PostLimits {
+            max_events: self.max_events_per_post,
+            max_length: self.max_post_length,
+        }
+    }
+
+    /// Settings for the event with id `event_id`, if any
+    pub fn event_settings(&self, event_id: usize) -> Option<&EventSettings> {
+        self.events.iter().find(|settings| settings.id == event_id)
+    }
+
+    /// Whether the team plays the event
+    pub fn is_playing(&self, event_id: usize) -> bool {
+        self.playing_events.contains(&event_id)
+            || self
+                .event_settings(event_id)
+                .map_or(false, |settings| settings.playing)
+    }
+
+    /// Time before the end of the event to send the [`EndsSoon`][crate::scheduler::Reminder::EndsSoon] reminder
+    pub fn ends_soon(&self, event_id: usize) -> Duration {
+        self.event_settings(event_id)
+            .and_then(|settings| settings.ends_soon_lead_time)
+            .unwrap_or(self.ends_soon_lead_time)
+    }
+
+    /// Challenge categories for the writeup post of the event
+    pub fn writeup_categories(&self, event_id: usize) -> &[String] {
+        self.event_settings(event_id)
+            .and_then(|settings| settings.writeup_categories.as_deref())
+            .unwrap_or(&self.writeup_categories)
+    }
+
+    /// All destinations the posts should be send to
+    ///
+    /// The list is empty if neither [`targets`][Config::targets] nor [`webhook_url`][Config::webhook_url] are configured.
+    pub fn targets(&self) -> Vec<Target> {
+        if !self.targets.is_empty() {
+            return self.targets.clone();
+        }
+        self.webhook_url
+            .iter()
+            .map(|webhook_url| Target {
+                webhook_url: webhook_url.clone(),
+                channel: self.mattermost_channel.clone(),
+                username: self.bot_username.clone(),
+                icon_url: self.bot_icon.clone(),
+                icon_emoji: self.bot_icon_emoji.clone(),
+                backend: default_backend(),
+                broadcast: None,
+                signing_secret: self.webhook_secret.clone(),
+            })
+            .collect()
+    }
+
+    /// Destination of the operational messages, if configured
+    pub fn admin_target(&self) -> Option<Target> {
+        self.admin_webhook_url.as_ref().map(|webhook_url| Target {
+            webhook_url: webhook_url.clone(),
+            channel: None,
+            username: None,
+            icon_url: None,
+            icon_emoji: None,
+            backend: default_backend(),
+            broadcast: None,
+            signing_secret: self.webhook_secret.clone(),
+        })
+    }
+}
+
+/// Settings for a single event
+#[serde_as]
+#[derive(Clone, Deserialize, Debug, Eq, PartialEq)]
+pub struct EventSettings {
+    /// CTFtime event id
+    pub id: usize,
+    /// The team plays this event
+    #[serde(default)]
+    pub playing: bool,
+    /// Overrides [`Config::ends_soon_lead_time`]
+    #[serde_as(as = "Option<Humantime>")]
+    #[serde(default)]
+    pub ends_soon_lead_time: Option<Duration>,
+    /// Overrides [`Config::writeup_categories`], e.g., with the categories solved during the event
+    pub writeup_categories: Option<Vec<String>>,
+    /// Overrides whether the event needs a warm-up, by default only Attack-Defense events do
+    #[serde(default)]
+    pub warmup: Option<bool>,
+    /// Overrides [`WarmupConfig::lead_time`]
+    #[serde_as(as = "Option<Humantime>")]
+    #[serde(default)]
+    pub warmup_lead_time: Option<Duration>,
+    /// RSS, Atom, or JSON feed with the announcements of the organizers, instead of the live feed on CTFtime
+    #[serde(default)]
+    pub news_url: Option<Url>,
+}
+
+/// A destination for the posts of the bot
+///
+/// Each target can override how the bot appears in the channel.
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub struct Target {
+    /// Incoming webhook to post to
+    pub webhook_url: Url,
+    /// Overrides the channel of the webhook, see [`Message::channel`]
+    pub channel: Option<String>,
+    /// Overrides the username, see [`Message::username`]
+    pub username: Option<String>,
+    /// Overrides the profile picture, see [`Message::icon_url`]
+    pub icon_url: Option<Url>,
+    /// Overrides the profile picture with an emoji, see [`Message::icon_emoji`]
+    pub icon_emoji: Option<String>,
+    /// Name of the [`Renderer`][crate::render::Renderer] for the webhook, e.g., `mattermost`, `discord`, or `plain` for IRC bridges
+    #[serde(default = "default_backend")]
+    pub backend: String,
+    /// Turns the target into a broadcast target for a general audience
+    ///
+    /// Broadcast targets only receive a short, simplified announcement of the major events in the digest, but no other posts.
+    #[serde(default)]
+    pub broadcast: Option<Broadcast>,
+    /// Sign the requests to the webhook with this secret, see [`signature`][crate::signature]
+    ///
+    /// Allows receivers, e.g., automation platforms, to verify that the posts come from the bot.
+    #[serde(default)]
+    pub signing_secret: Option<String>,
+}
+
+fn default_backend() -> String {
+    "mattermost".to_string()
+}
+
+impl Target {
+    /// Apply the overrides of this target to the message
+    ///
+    /// Values already set on the message are only replaced if the target overrides them.
+    pub fn apply(&self, message: &mut Message) {
+        if let Some(ref channel) = self.channel {
+            message.channel = Some(channel.clone());
+        }
+        if let Some(ref username) = self.username {
+            message.username = Some(username.clone());
+        }
+        if let Some(ref icon_url) = self.icon_url {
+            message.icon_url = Some(icon_url.clone());
+        }
+        if let Some(ref icon_emoji) = self.icon_emoji {
+            message.icon_emoji = Some(icon_emoji.clone());
+        }
+    }
+}
+
+#[test]
+fn test_load_config() {
+    dotenv::dotenv().expect("Failed to read .env file");
+    let config = envy::from_env::<Config>().expect("Couldn't read config");
+    let expected = Config {
+        webhook_url: None,
+        webhook_secret: None,
+        admin_webhook_url: None,
+        lookahead: Duration::days(21),
+        color_jeopardy: "#0099e1".parse().unwrap(),
+        color_attack_defense: "#da5422".parse().unwrap(),
+        bot_icon: Some(
+            "https://ctftime.org/static/images/ctftime-logo-avatar.png"
+                .parse()
+                .unwrap(),
+        ),
+        bot_icon_emoji: None,
+        bot_username: None,
+        always_show_ctfs: vec![6, 7, 24, 117, 412],
+        mattermost_channel: None,
+        min_weight: None,
+        participants_threshold: None,
+        state_file: None,
+        playing_events: vec![],
+        ends_soon_lead_time: Duration::hours(2),
+        pre_event_checklist: false,
+        checklist_lead_time: Duration::hours(48),
+        checklist_template: None,
+        writeup_ping_delay: Duration::hours(72),
+        writeup_categories: vec![],
+        vote_prompt_delay: Duration::hours(24),
+        results_delay: Duration::hours(24),
+        vote_default_weight: 25.,
+        vote_infra_factor: 0.5,
+        vote_quality_factor: 0.5,
+        server_address: None,
+        server_url: None,
+        command_token: None,
+        api_token: None,
+        actions_token: None,
+        server_tls_cert: None,
+        server_tls_key: None,
+        trusted_proxies: vec![],
+        admins: vec![],
+        rate_limit_per_user: 10,
+        rate_limit_per_channel: 60,
+        board: None,
+        caldav: None,
+        github_issues: None,
+        gitlab_issues: None,
+        mattermost_api: None,
+        update_in_place: false,
+        notion: None,
+        warmup: None,
+        escalation: None,
+        spreadsheet: None,
+        signal: None,
+        mastodon: None,
+        twitter: None,
+        zulip: None,
+        email: None,
+        push: None,
+        webhooks: vec![],
+        xmpp: None,
+        templates: BTreeMap::new(),
+        matrix: None,
+        twilio: None,
+        apprise: None,
+        events: vec![],
+        refresh_interval: Duration::minutes(15),
+        strict_api: false,
+        api_limit: 30,
+        ctftime_url: "https://ctftime.org".parse().unwrap(),
+        enrich_teams: false,
+        team_cache_ttl: Duration::days(7),
+        filter_profile: None,
+        pushgateway_url: None,
+        sentry_dsn: None,
+        sticky_announcements: false,
+        whats_new: false,
+        digest_footer: false,
+        digest_next_update: None,
+        digest_footer_icon: None,
+        hide_empty_fields: false,
+        max_events_per_post: 20,
+        max_post_length: 16000,
+        trivia_footer: false,
+        trivia: vec![],
+        team_id: None,
+        rivals: vec![],
+        country: None,
+        leaderboard_weekday: None,
+        leaderboard_country: None,
+        season_recap: false,
+        recap_date: RecapDate::default(),
+        timezone: None,
+        locale: Locale::En,
+        holidays: vec![],
+        blackouts: vec![],
+        team_calendar: None,
+        qualifiers: vec![],
+        phases: vec![],
+        targets: vec![],
+    };
+    assert_eq!(config, expected)
+}
+
+#[test]
+fn test_load_config_targets() {
+    let config: Config = toml::from_str(
+        r##"
+lookahead = "14d"
+color_jeopardy = "#0099e1"
+color_attack_defense = "danger"
+always_show_ctfs = []
+
+[[events]]
+id = 724
+playing = true
+ends_soon_lead_time = "4h"
+
+[[targets]]
+webhook_url = "https://chat.example.com/hooks/abc"
+channel = "ctf"
+username = "CTF Reminders"
+icon_emoji = "triangular_flag_on_post"
+
+[[targets]]
+webhook_url = "https://discord.com/api/webhooks/123/def"
+backend = "discord"
+username = "Weekly Digest"
+signing_secret = "hunter2"
+
+[[targets]]
+webhook_url = "https://chat.example.com/hooks/ghi"
+channel = "town-square"
+broadcast = { min_weight = 50.0 }
+"##,
+    )
+    .unwrap();
+    assert_eq!(config.color_attack_defense, Color::Danger);
+    assert!(config.is_playing(724));
+    assert_eq!(config.ends_soon(724), Duration::hours(4));
+    assert_eq!(config.ends_soon(725), Duration::hours(2));
+    assert_eq!(config.lookahead, Duration::days(14));
+    let targets = config.targets();
+    assert_eq!(targets.len(), 3);
+    assert_eq!(targets[0].broadcast, None);
+    assert_eq!(targets[0].backend, "mattermost");
+    assert_eq!(targets[1].backend, "discord");
+    assert_eq!(targets[0].signing_secret, None);
+    assert_eq!(targets[1].signing_secret.as_deref(), Some("hunter2"));
+    assert_eq!(
+        targets[2].broadcast,
+        Some(Broadcast {
+            min_weight: 50.,
+            team_name: "Our security team".to_string(),
+        })
+    );
+
+    let mut message = Message {
+        username: Some("Upcoming CTFs".to_string()),
+        ..Default::default()
+    };
+    targets[0].apply(&mut message);
+    assert_eq!(message.channel.as_deref(), Some("ctf"));
+    assert_eq!(message.username.as_deref(), Some("CTF Reminders"));
+    assert_eq!(
+        message.icon_emoji.as_deref(),
+        Some("triangular_flag_on_post")
+    );
+    assert_eq!(message.icon_url, None);
+}
+
+#[test]
+fn test_load_config_legacy_durations() {
+    let config = Config::from_toml(
+        r##"
+days_into_future = 14
+refresh_interval_minutes = "30"
+team_cache_ttl_hours = "2d"
+checklist_hours = 24
+checklist_lead_time = "12h"
+color_jeopardy = "#0099e1"
+color_attack_defense = "danger"
+always_show_ctfs = []
+
+[[events]]
+id = 724
+ends_soon_hours = 4
+warmup_hours = 6
+
+[warmup]
+hours = 3
+kind = "command"
+program = "./warmup.sh"
+"##,
+    )
+    .unwrap();
+    assert_eq!(config.lookahead, Duration::days(14));
+    assert_eq!(config.refresh_interval, Duration::minutes(30));
+    assert_eq!(config.team_cache_ttl, Duration::days(2));
+    // The current name wins
+    assert_eq!(config.checklist_lead_time, Duration::hours(12));
+    assert_eq!(config.ends_soon(724), Duration::hours(4));
+    assert_eq!(config.events[0].warmup_lead_time, Some(Duration::hours(6)));
+    assert_eq!(config.warmup.unwrap().lead_time, Duration::hours(3));
+
+    let config = Config::from_env_vars(vec![
+        ("DAYS_INTO_FUTURE".to_string(), "21".to_string()),
+        ("ENDS_SOON_HOURS".to_string(), "2".to_string()),
+        ("REFRESH_INTERVAL".to_string(), "1h".to_string()),
+        ("COLOR_JEOPARDY".to_string(), "#0099e1".to_string()),
+        ("COLOR_ATTACK_DEFENSE".to_string(), "danger".to_string()),
+        ("ALWAYS_SHOW_CTFS".to_string(), "".to_string()),
+    ])
+    .unwrap();
+    assert_eq!(config.lookahead, Duration::days(21));
+    assert_eq!(config.ends_soon_lead_time, Duration::hours(2));
+    assert_eq!(config.refresh_interval, Duration::hours(1));
+    // Plain numbers are only accepted under the former names
+    assert!(Config::from_toml(
+        r##"
+lookahead = "14"
+color_jeopardy = "#0099e1"
+color_attack_defense = "danger"
+always_show_ctfs = []
+"##
+    )
+    .is_err());
+}
+
+#[test]
+fn test_load_config_invalid_values() {
+    let err = toml::from_str::<Config>(
+        r##"
+lookahead = "14d"
+color_jeopardy = "0099e1"
+color_attack_defense = "#da5422"
+always_show_ctfs = []
+"##,
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string().contains("invalid color `0099e1`"),
+        "{}",
+        err
+    );
+
+    let err = toml::from_str::<Config>(
+        r##"
+lookahead = "14d"
+color_jeopardy = "#0099e1"
+color_attack_defense = "#da5422"
+always_show_ctfs = []
+bot_icon = "ctftime-logo-avatar.png"
+"##,
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string().contains("relative URL without a base"),
+        "{}",
+        err
+    );
+}
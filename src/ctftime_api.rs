@@ -0,0 +1,141 @@
+//! Client of the CTFtime API
+//!
+//! The base URL is configurable with `CTFTIME_URL`, e.g., for a mirror or a local server in tests.
+
+use crate::{
+    mattermost_hook_api::Url, parse_event, parse_events, teams::TeamInfo, timed, CtfEvent,
+};
+use std::fmt;
+
+/// Error while fetching data from the CTFtime API
+#[derive(Debug)]
+pub enum ApiError {
+    /// The request failed or CTFtime answered with an error status
+    Request(reqwest::Error),
+    /// The response is no valid JSON or misses fields, see [`parse_events`]
+    Parse(serde_json::Error),
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Request(err) => write!(f, "Request to CTFtime failed: {}", err),
+            ApiError::Parse(err) => write!(f, "Invalid response from CTFtime: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ApiError::Request(err) => Some(err),
+            ApiError::Parse(err) => Some(err),
+        }
+    }
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(err: reqwest::Error) -> Self {
+        ApiError::Request(err)
+    }
+}
+
+impl From<serde_json::Error> for ApiError {
+    fn from(err: serde_json::Error) -> Self {
+        ApiError::Parse(err)
+    }
+}
+
+/// Client of the CTFtime API, sharing the connection pool of the HTTP client
+#[derive(Clone, Debug)]
+pub struct Client {
+    http: reqwest::blocking::Client,
+    base_url: Url,
+    strict: bool,
+}
+
+impl Client {
+    /// Client for the API at `base_url`, e.g., `https://ctftime.org`
+    ///
+    /// In `strict` mode, unknown fields in the responses are errors, see [`parse_events`].
+    pub fn new(http: reqwest::blocking::Client, base_url: Url, strict: bool) -> Self {
+        Self {
+            http,
+            base_url,
+            strict,
+        }
+    }
+
+    /// URL of the API endpoint `path`, e.g., `events/`
+    pub fn url(&self, path: &str) -> String {
+        format!(
+            "{}/api/v1/{}",
+            self.base_url.as_str().trim_end_matches('/'),
+            path
+        )
+    }
+
+    /// Up to `limit` events which start between the UNIX timestamps `start` and `finish`
+    pub fn events(&self, start: i64, finish: i64, limit: usize) -> Result<Vec<CtfEvent>, ApiError> {
+        let data = timed("Fetching the events", || {
+            self.http
+                .get(&self.url("events/"))
+                .query(&[
+                    ("limit", limit as i64),
+                    ("start", start),
+                    ("finish", finish),
+                ])
+                .send()?
+                .error_for_status()?
+                .text()
+        })?;
+        Ok(parse_events(&data, self.strict)?)
+    }
+
+    /// The event with the CTFtime id `id`
+    pub fn event(&self, id: usize) -> Result<CtfEvent, ApiError> {
+        let data = timed("Fetching the event", || {
+            self.http
+                .get(&self.url(&format!("events/{}/", id)))
+                .send()?
+                .error_for_status()?
+                .text()
+        })?;
+        Ok(parse_event(&data, self.strict)?)
+    }
+
+    /// The team with the CTFtime id `id`, including its rating history
+    ///
+    /// Only the fields of [`TeamInfo`] are checked, also in `strict` mode.
+    pub fn team(&self, id: usize) -> Result<TeamInfo, ApiError> {
+        let data = timed("Fetching the team", || {
+            self.http
+                .get(&self.url(&format!("teams/{}/", id)))
+                .send()?
+                .error_for_status()?
+                .text()
+        })?;
+        Ok(serde_json::from_str(&data)?)
+    }
+}
+
+#[test]
+fn test_ctftime_client() {
+    let http = reqwest::blocking::Client::new();
+    let client = Client::new(http.clone(), "https://ctftime.org".parse().unwrap(), false);
+    assert_eq!(client.url("events/"), "https://ctftime.org/api/v1/events/");
+    let mirror = Client::new(
+        http,
+        "http://127.0.0.1:8080/ctftime/".parse().unwrap(),
+        false,
+    );
+    assert_eq!(
+        mirror.url("events/"),
+        "http://127.0.0.1:8080/ctftime/api/v1/events/"
+    );
+
+    let err = ApiError::from(serde_json::from_str::<u8>("x").unwrap_err());
+    assert!(err
+        .to_string()
+        .starts_with("Invalid response from CTFtime: expected value"));
+}
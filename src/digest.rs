@@ -0,0 +1,650 @@
+//! The digest of upcoming events, independent of the output format
+//!
+//! Each backend renders the same [`Digest`], such that all of them show the same events.
+
+use crate::{
+    discord_hook_api, format_date, local_date,
+    mattermost_hook_api::{Attachment, Message, Url},
+    post_metadata,
+    rsvp::rsvp_button,
+    state::State,
+    CtfEvent, CtfTeam, CONFIG,
+};
+use chrono::{DateTime, Datelike, Utc};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Note on events which are only part of the digest because they were announced before
+const STICKY_NOTE: &str = "No longer matches your filters, kept because previously announced";
+/// Heading of the section listing the events published since the last run
+pub(crate) const NEW_EVENTS_TITLE: &str = "New on CTFtime since the last update";
+
+lazy_static! {
+    static ref RE_MARKDOWN_LINK: Regex =
+        Regex::new(r"\[(?P<text>[^\]]*)\]\((?P<url>[^)]*)\)").unwrap();
+}
+
+/// How the organizers of an event link to their CTFtime team pages
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TeamLinks {
+    /// Markdown links, e.g., `[HTsP](https://ctftime.org/team/59758)`
+    Inline,
+    /// Numbered references, e.g., `HTsP [1]`, with the links collected in [`Footnotes`]
+    Footnote,
+    /// Only the names, for formats without clickable links
+    Omit,
+}
+
+/// Links referenced with [`TeamLinks::Footnote`], numbered from 1
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Footnotes(Vec<String>);
+
+impl Footnotes {
+    /// Add the link and return its number, links are only added once
+    pub fn add(&mut self, url: String) -> usize {
+        match self.0.iter().position(|known| *known == url) {
+            Some(idx) => idx + 1,
+            None => {
+                self.0.push(url);
+                self.0.len()
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// One line per link, e.g., `[1] https://ctftime.org/team/59758`
+    pub fn to_plain_text(&self) -> String {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(idx, url)| format!("[{}] {}\n", idx + 1, url))
+            .collect()
+    }
+}
+
+/// Comma separated names of the teams, linked as set by `links`
+pub fn organizers_text<'t>(
+    teams: impl IntoIterator<Item = &'t CtfTeam>,
+    links: TeamLinks,
+    footnotes: &mut Footnotes,
+) -> String {
+    teams
+        .into_iter()
+        .map(|team| match links {
+            TeamLinks::Inline => team.to_markdown_link(),
+            TeamLinks::Footnote => format!("{} [{}]", team.name(), footnotes.add(team.url())),
+            TeamLinks::Omit => team.name().to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Limits of a single post, larger digests are split, see [`Digest::split`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PostLimits {
+    /// Maximal number of events per post
+    pub max_events: usize,
+    /// Maximal length of the events of a post, as Markdown
+    pub max_length: usize,
+}
+
+/// List of upcoming events which should be announced
+#[derive(Clone, Debug)]
+pub struct Digest<'a> {
+    /// Title of the digest, also used as username of the bot
+    pub title: String,
+    /// Number of this part and of all parts, if the digest was split, see [`Digest::split`]
+    pub part: Option<(usize, usize)>,
+    /// Link to the full list of events
+    pub link: String,
+    pub events: Vec<&'a CtfEvent>,
+    /// Events published on CTFtime since the last run, independent of their start date
+    pub new_events: Vec<&'a CtfEvent>,
+    /// Ids of the events which are only shown because they were announced before
+    pub sticky: BTreeSet<usize>,
+    /// Additional lines below an event, e.g., links between qualifiers and finals or clashes with the team calendar
+    pub notes: BTreeMap<usize, Vec<String>>,
+    /// Fun fact shown before the footer, see [`pick_trivia`][crate::trivia::pick_trivia]
+    pub trivia: Option<String>,
+    /// Line at the end of the digest, e.g., from [`freshness_footer`]
+    pub footer: Option<String>,
+    /// Icon shown next to the footer in Mattermost
+    pub footer_icon: Option<Url>,
+    /// Adds RSVP buttons to the events in Mattermost, if set
+    pub actions_url: Option<Url>,
+}
+
+impl<'a> Digest<'a> {
+    pub fn new(events: Vec<&'a CtfEvent>) -> Self {
+        Self {
+            title: "Upcoming CTFs".to_string(),
+            part: None,
+            link: "https://ctftime.org/event/list/upcoming".to_string(),
+            events,
+            new_events: Vec::new(),
+            sticky: BTreeSet::new(),
+            notes: BTreeMap::new(),
+            trivia: None,
+            footer: None,
+            footer_icon: None,
+            actions_url: None,
+        }
+    }
+
+    /// Add the events of `events` which were announced before but are missing in the digest
+    ///
+    /// This keeps events in the digest, which no longer match the filters, e.g., because the weight decreased.
+    pub fn keep_announced(&mut self, events: &'a [CtfEvent], state: &State) {
+        let shown: BTreeSet<usize> = self.event_ids().into_iter().collect();
+        for event in events {
+            let announced = state
+                .events
+                .get(&event.id())
+                .map_or(false, |record| record.announced);
+            if announced && !shown.contains(&event.id()) {
+                self.sticky.insert(event.id());
+                self.events.push(event);
+            }
+        }
+        self.events
+            .sort_by_key(|event| (event.start_date(), event.id()));
+    }
+
+    /// Collect the events of `events` which are not yet known in `state`
+    ///
+    /// On the first run, all events are unknown, so none of them are listed.
+    pub fn add_new_events(&mut self, events: &'a [CtfEvent], state: &State) {
+        if state.events.is_empty() {
+            return;
+        }
+        self.new_events = events
+            .iter()
+            .filter(|event| !state.events.contains_key(&event.id()))
+            .collect();
+    }
+
+    /// Whether there is anything to post
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty() && self.new_events.is_empty()
+    }
+
+    /// One line per new event with a Markdown link and the start date
+    pub(crate) fn new_events_lines(&self) -> Vec<String> {
+        self.new_events
+            .iter()
+            .map(|event| {
+                format!(
+                    "- [{}]({}) — starts {}",
+                    event.title(),
+                    event.ctftime_url(),
+                    format_date(&event.start_date(), CONFIG.timezone)
+                )
+            })
+            .collect()
+    }
+
+    /// Add the `notes` for the events, see [`notes`][Digest::notes]
+    pub fn add_notes(&mut self, notes: impl IntoIterator<Item = (usize, String)>) {
+        for (event_id, note) in notes {
+            self.notes.entry(event_id).or_default().push(note);
+        }
+    }
+
+    /// Render a single event, including the notes and the RSVP button
+    fn attachment(&self, event: &CtfEvent) -> Attachment {
+        let mut attachment = event.to_slack();
+        for note in self.notes.get(&event.id()).into_iter().flatten() {
+            attachment.text = Some(format!("{}\n{}", attachment.text.unwrap_or_default(), note));
+        }
+        if let Some(ref actions_url) = self.actions_url {
+            attachment
+                .actions
+                .push(rsvp_button(event.id(), actions_url));
+        }
+        if self.sticky.contains(&event.id()) {
+            attachment.text = Some(format!(
+                "_{}_\n{}",
+                STICKY_NOTE,
+                attachment.text.unwrap_or_default()
+            ));
+        }
+        attachment
+    }
+
+    /// Split the digest into multiple digests within the `limits`, with headings like `Upcoming CTFs (1/3)`
+    ///
+    /// Events starting in the same week stay together, unless they exceed the limits on their own.
+    /// The new events, the trivia, and the footer are only part of the last digest.
+    pub fn split(&self, limits: PostLimits) -> Vec<Digest<'a>> {
+        let mut weeks: Vec<Vec<&'a CtfEvent>> = Vec::new();
+        let mut last_week = None;
+        for &event in &self.events {
+            let week = local_date(&event.start_date(), CONFIG.timezone).iso_week();
+            match weeks.last_mut() {
+                Some(events) if last_week == Some(week) => events.push(event),
+                _ => weeks.push(vec![event]),
+            }
+            last_week = Some(week);
+        }
+
+        let length = |event: &CtfEvent| self.event_markdown(event).len();
+        let mut parts: Vec<Vec<&'a CtfEvent>> = vec![Vec::new()];
+        let mut part_length = 0;
+        for week in weeks {
+            let week_length: usize = week.iter().map(|event| length(event)).sum();
+            let part = parts.last().expect("There is always a part");
+            if !part.is_empty()
+                && (part.len() + week.len() > limits.max_events
+                    || part_length + week_length > limits.max_length)
+            {
+                parts.push(Vec::new());
+                part_length = 0;
+            }
+            for event in week {
+                let part = parts.last().expect("There is always a part");
+                if !part.is_empty()
+                    && (part.len() >= limits.max_events
+                        || part_length + length(event) > limits.max_length)
+                {
+                    parts.push(Vec::new());
+                    part_length = 0;
+                }
+                part_length += length(event);
+                parts
+                    .last_mut()
+                    .expect("There is always a part")
+                    .push(event);
+            }
+        }
+
+        let count = parts.len();
+        if count == 1 {
+            return vec![self.clone()];
+        }
+        parts
+            .into_iter()
+            .enumerate()
+            .map(|(idx, events)| {
+                let last = idx + 1 == count;
+                Digest {
+                    title: self.title.clone(),
+                    part: Some((idx + 1, count)),
+                    link: self.link.clone(),
+                    events,
+                    new_events: if last {
+                        self.new_events.clone()
+                    } else {
+                        Vec::new()
+                    },
+                    sticky: self.sticky.clone(),
+                    notes: self.notes.clone(),
+                    trivia: self.trivia.clone().filter(|_| last),
+                    footer: self.footer.clone().filter(|_| last),
+                    footer_icon: self.footer_icon.clone(),
+                    actions_url: self.actions_url.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Title of the digest with the number of the part, e.g., `Upcoming CTFs (1/3)`
+    pub fn heading(&self) -> String {
+        match self.part {
+            Some((part, count)) => format!("{} ({}/{})", self.title, part, count),
+            None => self.title.clone(),
+        }
+    }
+
+    /// CTFtime ids of all events in the digest
+    pub fn event_ids(&self) -> Vec<usize> {
+        self.events.iter().map(|event| event.id()).collect()
+    }
+
+    /// Render the digest as a Mattermost message with one attachment per event
+    pub fn to_mattermost(&self) -> Message {
+        let mut attachments: Vec<Attachment> = self
+            .events
+            .iter()
+            .map(|event| self.attachment(event))
+            .collect();
+        if !self.new_events.is_empty() {
+            let text = self.new_events_lines().join("\n");
+            attachments.push(Attachment {
+                fallback: format!("{}: {} events", NEW_EVENTS_TITLE, self.new_events.len()),
+                title: Some(NEW_EVENTS_TITLE.to_string()),
+                text: Some(text),
+                ..Default::default()
+            });
+        }
+        if let Some(ref trivia) = self.trivia {
+            attachments.push(Attachment {
+                fallback: trivia.clone(),
+                text: Some(format!("💡 {}", trivia)),
+                ..Default::default()
+            });
+        }
+        if let Some(ref footer) = self.footer {
+            attachments.push(Attachment {
+                fallback: footer.clone(),
+                footer: Some(footer.clone()),
+                footer_icon: self.footer_icon.clone(),
+                ..Default::default()
+            });
+        }
+        Message {
+            username: Some(self.title.clone()),
+            text: Some(format!("[{}]({})", self.heading(), self.link)),
+            attachments,
+            props: Some(post_metadata(&self.event_ids())),
+            ..Default::default()
+        }
+    }
+
+    /// Render the digest as a Discord message with one embed per event
+    ///
+    /// Use [`split`][discord_hook_api::Message::split] for digests with more than ten events.
+    pub fn to_discord(&self) -> discord_hook_api::Message {
+        discord_hook_api::Message::from(&self.to_mattermost())
+    }
+
+    /// Render the digest as Markdown with a section per event
+    pub fn to_markdown(&self) -> String {
+        let mut text = format!("[{}]({})\n", self.heading(), self.link);
+        for event in &self.events {
+            text += "\n";
+            text += &self.event_markdown(event);
+        }
+        if !self.new_events.is_empty() {
+            text += &format!("\n### {}\n", NEW_EVENTS_TITLE);
+            for line in self.new_events_lines() {
+                text += &line;
+                text += "\n";
+            }
+        }
+        if let Some(ref trivia) = self.trivia {
+            text += &format!("\n💡 {}\n", trivia);
+        }
+        if let Some(ref footer) = self.footer {
+            text += &format!("\n_{}_\n", footer);
+        }
+        text
+    }
+
+    /// Render a single event of the digest as Markdown, including its notes
+    pub fn event_markdown(&self, event: &CtfEvent) -> String {
+        let attachment = self.attachment(event);
+        format!(
+            "### [{}]({})\n{}\n",
+            attachment.title.unwrap_or_default(),
+            event.ctftime_url(),
+            attachment.text.unwrap_or_default()
+        )
+    }
+
+    /// Render the digest as plain text without any markup
+    ///
+    /// The links to the organizers are listed as footnotes after the events.
+    pub fn to_plain_text(&self) -> String {
+        let mut text = format!("{}\n{}\n", self.heading(), self.link);
+        let mut footnotes = Footnotes::default();
+        for event in &self.events {
+            text += "\n";
+            if self.sticky.contains(&event.id()) {
+                text += STICKY_NOTE;
+                text += "\n";
+            }
+            text += &event.to_plain_text_with(TeamLinks::Footnote, &mut footnotes);
+            text += "\n";
+            for note in self.notes.get(&event.id()).into_iter().flatten() {
+                text += &markdown_to_plain_text(note);
+                text += "\n";
+            }
+        }
+        if !self.new_events.is_empty() {
+            text += "\n";
+            text += NEW_EVENTS_TITLE;
+            text += "\n";
+            for line in self.new_events_lines() {
+                text += &markdown_to_plain_text(&line);
+                text += "\n";
+            }
+        }
+        if !footnotes.is_empty() {
+            text += "\n";
+            text += &footnotes.to_plain_text();
+        }
+        if let Some(ref trivia) = self.trivia {
+            text += "\n💡 ";
+            text += trivia;
+            text += "\n";
+        }
+        if let Some(ref footer) = self.footer {
+            text += "\n";
+            text += footer;
+            text += "\n";
+        }
+        text
+    }
+}
+
+/// Footer stating when the data was fetched from CTFtime and the bot version
+///
+/// `next_update` is a free text, e.g., `Monday`, since the schedule of the bot is not known.
+pub fn freshness_footer(fetched: DateTime<Utc>, next_update: Option<&str>) -> String {
+    let mut footer = format!(
+        "Data from ctftime.org, fetched {}",
+        fetched.format("%Y-%m-%d %H:%M UTC")
+    );
+    if let Some(next_update) = next_update {
+        footer += ", next update ";
+        footer += next_update;
+    }
+    footer += concat!(" · ctftimebot ", env!("CARGO_PKG_VERSION"));
+    footer
+}
+
+/// Convert the Markdown used in alerts and reminders into plain text
+///
+/// Links are written as `text (url)` and emphasis is removed.
+pub fn markdown_to_plain_text(text: &str) -> String {
+    RE_MARKDOWN_LINK
+        .replace_all(text, "$text ($url)")
+        .replace("**", "")
+}
+
+#[test]
+fn test_markdown_to_plain_text() {
+    assert_eq!(
+        markdown_to_plain_text(
+            "🏁 [X-MAS CTF 2018](https://ctftime.org/event/724/) is live. Suggested weight: **27**"
+        ),
+        "🏁 X-MAS CTF 2018 (https://ctftime.org/event/724/) is live. Suggested weight: 27"
+    );
+}
+
+#[test]
+fn test_digest_plain_text() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    let digest = Digest::new(events.iter().collect());
+    assert_eq!(digest.event_ids(), vec![724]);
+    let text = digest.to_plain_text();
+    assert!(text.starts_with(
+        "Upcoming CTFs\nhttps://ctftime.org/event/list/upcoming\n\nX-MAS CTF 2018 — Jeopardy\n"
+    ));
+    assert!(text.contains("Rating: 24\n"));
+    assert!(!text.contains("**"));
+    assert!(text.contains("Organizers: Hecării, Țuica și Păunii [1]\n"));
+    assert!(text.ends_with("\n\n[1] https://ctftime.org/team/58218\n"));
+    assert!(events[0]
+        .to_plain_text()
+        .contains("Organizers: Hecării, Țuica și Păunii\n"));
+
+    let mut footnotes = Footnotes::default();
+    let organizers = events[0]
+        .known_organizers()
+        .chain(events[0].known_organizers());
+    assert_eq!(
+        organizers_text(organizers, TeamLinks::Footnote, &mut footnotes),
+        "Hecării, Țuica și Păunii [1], Hecării, Țuica și Păunii [1]"
+    );
+    assert_eq!(
+        organizers_text(
+            events[0].known_organizers(),
+            TeamLinks::Inline,
+            &mut footnotes
+        ),
+        "[Hecării, Țuica și Păunii](https://ctftime.org/team/58218)"
+    );
+}
+
+#[test]
+fn test_digest_footer() {
+    let fetched = "2024-03-12T09:00:00Z".parse().unwrap();
+    let footer = freshness_footer(fetched, Some("Monday"));
+    assert!(footer.starts_with(
+        "Data from ctftime.org, fetched 2024-03-12 09:00 UTC, next update Monday · ctftimebot "
+    ));
+    assert!(freshness_footer(fetched, None)
+        .starts_with("Data from ctftime.org, fetched 2024-03-12 09:00 UTC · ctftimebot "));
+
+    let mut digest = Digest::new(vec![]);
+    digest.footer = Some(footer.clone());
+    let message = digest.to_mattermost();
+    assert_eq!(message.attachments.len(), 1);
+    assert_eq!(message.attachments[0].footer.as_ref(), Some(&footer));
+    assert!(digest.to_plain_text().ends_with(&format!("\n{}\n", footer)));
+    assert!(digest.to_markdown().ends_with(&format!("\n_{}_\n", footer)));
+}
+
+#[test]
+fn test_digest_split() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let mut digest = Digest::new(events.iter().take(30).collect());
+    digest.footer = Some("Data from ctftime.org".to_string());
+    let week = |event: &CtfEvent| local_date(&event.start_date(), CONFIG.timezone).iso_week();
+
+    let unlimited = PostLimits {
+        max_events: 100,
+        max_length: 1_000_000,
+    };
+    let parts = digest.split(unlimited);
+    assert_eq!(parts.len(), 1);
+    assert_eq!(parts[0].heading(), "Upcoming CTFs");
+    assert_eq!(parts[0].event_ids(), digest.event_ids());
+
+    let limits = PostLimits {
+        max_events: 5,
+        max_length: 1_000_000,
+    };
+    let parts = digest.split(limits);
+    assert!(parts.len() >= 6);
+    assert_eq!(
+        parts[0].heading(),
+        format!("Upcoming CTFs (1/{})", parts.len())
+    );
+    let message = parts[0].to_mattermost();
+    assert_eq!(message.username.as_deref(), Some("Upcoming CTFs"));
+    assert!(message
+        .text
+        .unwrap()
+        .starts_with(&format!("[Upcoming CTFs (1/{})]", parts.len())));
+    assert_eq!(
+        parts.iter().flat_map(Digest::event_ids).collect::<Vec<_>>(),
+        digest.event_ids()
+    );
+    for (idx, part) in parts.iter().enumerate() {
+        assert!(!part.events.is_empty() && part.events.len() <= 5);
+        assert_eq!(part.footer.is_some(), idx + 1 == parts.len());
+    }
+    // A week is only split if it has too many events on its own
+    for pair in parts.windows(2) {
+        let (last, first) = (*pair[0].events.last().unwrap(), pair[1].events[0]);
+        if week(last) == week(first) {
+            let same_week = digest
+                .events
+                .iter()
+                .filter(|&&event| week(event) == week(first))
+                .count();
+            assert!(same_week > 5);
+        }
+    }
+
+    // Events longer than the limit are posted on their own
+    let parts = digest.split(PostLimits {
+        max_events: 100,
+        max_length: 1,
+    });
+    assert_eq!(parts.len(), 30);
+}
+
+#[test]
+fn test_digest_keep_announced() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    let mut state = State::default();
+    let mut digest = Digest::new(vec![]);
+    digest.keep_announced(&events, &state);
+    assert!(digest.events.is_empty());
+
+    state.mark_announced(&[724]);
+    digest.keep_announced(&events, &state);
+    assert_eq!(digest.event_ids(), vec![724]);
+    assert!(digest.to_plain_text().contains(STICKY_NOTE));
+    assert!(digest.to_mattermost().attachments[0]
+        .text
+        .as_ref()
+        .unwrap()
+        .starts_with(&format!("_{}_\n", STICKY_NOTE)));
+
+    // Events already in the digest are not duplicated or marked
+    let mut digest = Digest::new(events.iter().collect());
+    digest.keep_announced(&events, &state);
+    assert_eq!(digest.event_ids(), vec![724]);
+    assert!(digest.sticky.is_empty());
+}
+
+#[test]
+fn test_digest_new_events() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    // Nothing is new on the first run
+    let mut state = State::default();
+    let mut digest = Digest::new(vec![]);
+    digest.add_new_events(&events, &state);
+    assert!(digest.is_empty());
+
+    state.record_events(&events[..440]);
+    digest.add_new_events(&events, &state);
+    assert!(!digest.is_empty());
+    let new_ids: Vec<usize> = digest.new_events.iter().map(|event| event.id()).collect();
+    assert_eq!(new_ids, vec![501, 514]);
+
+    let markdown = digest.to_markdown();
+    assert!(markdown.contains(&format!(
+        "\n### {}\n- [RHme3 - Qualifiers](",
+        NEW_EVENTS_TITLE
+    )));
+    let plain_text = digest.to_plain_text();
+    assert!(
+        plain_text.contains("\n- RHme3 - Qualifiers (https://ctftime.org/event/501/) — starts ")
+    );
+    let message = digest.to_mattermost();
+    assert_eq!(message.attachments.len(), 1);
+    assert_eq!(
+        message.attachments[0].title.as_deref(),
+        Some(NEW_EVENTS_TITLE)
+    );
+}
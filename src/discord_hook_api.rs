@@ -0,0 +1,191 @@
+//! Types of the Discord webhook API
+//!
+//! The messages are converted from the Mattermost [`Message`][mattermost::Message]s, such that both show the same content.
+//! Buttons and post metadata are not supported by Discord webhooks and dropped.
+
+use crate::mattermost_hook_api::{self as mattermost, Color, Url};
+use serde::{Deserialize, Serialize};
+
+/// Maximal number of embeds per message
+pub const MAX_EMBEDS: usize = 10;
+
+/// Discord webhooks let you POST a message with up to [`MAX_EMBEDS`] embeds to a channel.
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Message {
+    /// [Markdown-formatted][] message, up to 2000 characters.
+    ///
+    /// The value is **mandatory**, if [`embeds`][Message::embeds] is empty.
+    ///
+    /// [Markdown-formatted]: https://support.discord.com/hc/en-us/articles/210298617
+    pub content: Option<String>,
+    /// Overrides the username of the webhook
+    pub username: Option<String>,
+    /// Overrides the avatar of the webhook
+    pub avatar_url: Option<Url>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub embeds: Vec<Embed>,
+}
+
+/// Rich content of a [`Message`], the equivalent of a Mattermost [`Attachment`][mattermost::Attachment]
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Embed {
+    /// Title of the embed, up to 256 characters
+    pub title: Option<String>,
+    /// Link of the title
+    pub url: Option<Url>,
+    /// Markdown-formatted text, up to 4096 characters
+    pub description: Option<String>,
+    /// Color of the left border as an RGB integer
+    pub color: Option<u32>,
+    pub thumbnail: Option<EmbedImage>,
+    pub image: Option<EmbedImage>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<EmbedField>,
+    pub footer: Option<EmbedFooter>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EmbedImage {
+    pub url: Url,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EmbedField {
+    pub name: String,
+    pub value: String,
+    pub inline: Option<bool>,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EmbedFooter {
+    pub text: String,
+    pub icon_url: Option<Url>,
+}
+
+/// The RGB value of `color`, named colors use the values of Slack
+///
+/// [`Color::Theme`] has no equivalent, such that Discord uses its default color.
+pub fn rgb(color: &Color) -> Option<u32> {
+    match color {
+        Color::Good => Some(0x2eb886),
+        Color::Warning => Some(0xdaa038),
+        Color::Danger => Some(0xa30200),
+        Color::Theme => None,
+        Color::Hex(hex) => {
+            let digits = hex.as_str().trim_start_matches('#');
+            let digits = if digits.len() == 3 {
+                digits.chars().flat_map(|c| vec![c, c]).collect()
+            } else {
+                digits.to_string()
+            };
+            u32::from_str_radix(&digits, 16).ok()
+        }
+    }
+}
+
+impl From<&mattermost::Attachment> for Embed {
+    fn from(attachment: &mattermost::Attachment) -> Self {
+        Embed {
+            title: attachment.title.clone(),
+            url: attachment.title_link.clone(),
+            description: attachment
+                .pretext
+                .iter()
+                .chain(&attachment.text)
+                .cloned()
+                .reduce(|pretext, text| format!("{}\n{}", pretext, text)),
+            color: attachment.color.as_ref().and_then(rgb),
+            thumbnail: attachment.thumb_url.clone().map(|url| EmbedImage { url }),
+            image: attachment.image_url.clone().map(|url| EmbedImage { url }),
+            fields: attachment
+                .fields
+                .iter()
+                .map(|field| EmbedField {
+                    name: field.title.clone().unwrap_or_default(),
+                    value: field.value.clone().unwrap_or_default(),
+                    inline: field.short,
+                })
+                .collect(),
+            footer: attachment.footer.clone().map(|text| EmbedFooter {
+                text,
+                icon_url: attachment.footer_icon.clone(),
+            }),
+        }
+    }
+}
+
+impl From<&mattermost::Message> for Message {
+    fn from(message: &mattermost::Message) -> Self {
+        Message {
+            content: message.text.clone(),
+            username: message.username.clone(),
+            avatar_url: message.icon_url.clone(),
+            embeds: message.attachments.iter().map(Embed::from).collect(),
+        }
+    }
+}
+
+impl Message {
+    /// Split the message into messages with at most [`MAX_EMBEDS`] embeds
+    ///
+    /// Only the first message has the [`content`][Message::content].
+    pub fn split(self) -> Vec<Message> {
+        if self.embeds.len() <= MAX_EMBEDS {
+            return vec![self];
+        }
+        self.embeds
+            .chunks(MAX_EMBEDS)
+            .enumerate()
+            .map(|(idx, embeds)| Message {
+                content: if idx == 0 { self.content.clone() } else { None },
+                username: self.username.clone(),
+                avatar_url: self.avatar_url.clone(),
+                embeds: embeds.to_vec(),
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_discord_message() {
+    assert_eq!(rgb(&"#0099e1".parse().unwrap()), Some(0x0099e1));
+    assert_eq!(rgb(&"#FFF".parse().unwrap()), Some(0xffffff));
+    assert_eq!(rgb(&Color::Theme), None);
+
+    let attachment = mattermost::Attachment {
+        fallback: "X-MAS CTF 2018".to_string(),
+        title: Some("X-MAS CTF 2018 — Jeopardy".to_string()),
+        title_link: "https://ctftime.org/event/724/".parse().ok(),
+        text: Some("**Rating**: 24".to_string()),
+        color: Some(Color::Danger),
+        footer: Some("CTFtime #724".to_string()),
+        ..Default::default()
+    };
+    let message = mattermost::Message {
+        text: Some("[Upcoming CTFs](https://ctftime.org/event/list/upcoming)".to_string()),
+        attachments: vec![attachment; 12],
+        ..Default::default()
+    };
+    let json = serde_json::to_value(Message::from(&message).split()[0].clone()).unwrap();
+    assert_eq!(
+        json["embeds"][0],
+        serde_json::json!({
+            "title": "X-MAS CTF 2018 — Jeopardy",
+            "url": "https://ctftime.org/event/724/",
+            "description": "**Rating**: 24",
+            "color": 0xa30200,
+            "footer": {"text": "CTFtime #724"},
+        })
+    );
+
+    let messages = Message::from(&message).split();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].embeds.len(), 10);
+    assert!(messages[0].content.is_some());
+    assert_eq!(messages[1].embeds.len(), 2);
+    assert!(messages[1].content.is_none());
+}
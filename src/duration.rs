@@ -0,0 +1,68 @@
+//! Durations in the configuration and on the command line, e.g., `14d`, `36h`, or `1h 30m`
+//!
+//! All durations are parsed with [`humantime`], so the options, `reformat --since`, and `/ctftime admin mute` accept the same syntax.
+//! Use [`Humantime`] with `serde_as`, e.g., `#[serde_as(as = "Humantime")]` on a [`Duration`] field.
+
+use chrono::Duration;
+use serde::{de, Deserializer};
+use serde_with::DeserializeAs;
+
+/// Parse a duration with units, see [`humantime::parse_duration`] for the supported units
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let duration = humantime::parse_duration(s).map_err(|err| format!("`{}`: {}", s, err))?;
+    Duration::from_std(duration).map_err(|_| format!("`{}` is too long", s))
+}
+
+/// Reads a [`Duration`] via [`humantime_serde`]
+#[derive(Clone, Copy, Debug)]
+pub struct Humantime;
+
+impl<'de> DeserializeAs<'de, Duration> for Humantime {
+    fn deserialize_as<D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let duration: std::time::Duration = humantime_serde::deserialize(deserializer)?;
+        Duration::from_std(duration).map_err(de::Error::custom)
+    }
+}
+
+#[test]
+fn test_config_durations() {
+    use serde::Deserialize;
+    use serde_with::serde_as;
+
+    #[serde_as]
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Durations {
+        #[serde_as(as = "Humantime")]
+        lookahead: Duration,
+        #[serde_as(as = "Option<Humantime>")]
+        #[serde(default)]
+        lead_time: Option<Duration>,
+    }
+
+    let parsed: Durations = toml::from_str("lookahead = \"36h\"\nlead_time = \"1h 30m\"").unwrap();
+    assert_eq!(
+        parsed,
+        Durations {
+            lookahead: Duration::hours(36),
+            lead_time: Some(Duration::minutes(90)),
+        }
+    );
+    let parsed: Durations =
+        envy::from_iter(vec![("LOOKAHEAD".to_string(), "14d".to_string())]).unwrap();
+    assert_eq!(
+        parsed,
+        Durations {
+            lookahead: Duration::days(14),
+            lead_time: None,
+        }
+    );
+    assert!(toml::from_str::<Durations>("lookahead = \"soon\"").is_err());
+    // Plain numbers have no unit
+    assert!(toml::from_str::<Durations>("lookahead = \"14\"").is_err());
+
+    assert_eq!(parse_duration("7d"), Ok(Duration::days(7)));
+    assert_eq!(parse_duration("12h"), Ok(Duration::hours(12)));
+    assert_eq!(parse_duration("30m"), Ok(Duration::minutes(30)));
+    assert!(parse_duration("14").is_err());
+    assert!(parse_duration("").is_err());
+}
@@ -0,0 +1,140 @@
+//! Send the digest as email via SMTP
+//!
+//! The email contains an HTML and a plain text version of the digest, for members who don't follow the chat daily.
+//! The connection uses STARTTLS or TLS, depending on the port, with the TLS backend selected by the cargo features.
+
+use crate::{digest::Digest, local_date, matrix::markdown_to_html, timed};
+use chrono::{DateTime, Datelike, Utc, Weekday};
+use chrono_tz::Tz;
+use lettre::{
+    message::{Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+    Message, SmtpTransport, Transport,
+};
+use serde::Deserialize;
+
+/// Configuration of the email backend, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct EmailConfig {
+    /// Host name of the SMTP server
+    pub smtp_host: String,
+    /// Port of the SMTP server, 465 uses TLS, all other ports STARTTLS
+    #[serde(default = "default_smtp_port")]
+    pub smtp_port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sender of the emails, e.g., `CTFtime Bot <ctf@example.com>`
+    pub from: String,
+    pub recipients: Vec<String>,
+    /// Subject of the emails, `{title}`, `{count}`, and `{date}` are replaced with the values of the digest
+    #[serde(default = "default_subject")]
+    pub subject: String,
+    /// Only send the email on these days, e.g., `["Mon"]`, or on every run if empty
+    #[serde(default)]
+    pub weekdays: Vec<Weekday>,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+fn default_subject() -> String {
+    "{title}: {count} events".to_string()
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+impl EmailConfig {
+    /// Whether the email is sent in a run at `now`
+    pub fn is_due(&self, now: DateTime<Utc>, timezone: Option<Tz>) -> bool {
+        self.weekdays.is_empty()
+            || self
+                .weekdays
+                .contains(&local_date(&now.into(), timezone).weekday())
+    }
+
+    /// Subject of the email with the placeholders replaced
+    pub fn subject(&self, digest: &Digest, now: DateTime<Utc>, timezone: Option<Tz>) -> String {
+        self.subject
+            .replace("{title}", &digest.heading())
+            .replace("{count}", &digest.events.len().to_string())
+            .replace(
+                "{date}",
+                &local_date(&now.into(), timezone).format("%F").to_string(),
+            )
+    }
+
+    /// Send the digest to all recipients
+    pub fn send(
+        &self,
+        digest: &Digest,
+        now: DateTime<Utc>,
+        timezone: Option<Tz>,
+    ) -> Result<(), BoxError> {
+        let mut builder = Message::builder()
+            .from(self.from.parse::<Mailbox>()?)
+            .subject(self.subject(digest, now, timezone));
+        for recipient in &self.recipients {
+            builder = builder.to(recipient.parse::<Mailbox>()?);
+        }
+        let email = builder.multipart(MultiPart::alternative_plain_html(
+            digest.to_plain_text(),
+            digest_html(digest),
+        ))?;
+
+        let transport = if self.smtp_port == 465 {
+            SmtpTransport::relay(&self.smtp_host)?
+        } else {
+            SmtpTransport::starttls_relay(&self.smtp_host)?
+        };
+        let mut transport = transport.port(self.smtp_port);
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        timed("Sending the email", || transport.build().send(&email))?;
+        Ok(())
+    }
+}
+
+/// HTML document of the digest
+pub fn digest_html(digest: &Digest) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{}</title></head><body>{}</body></html>\n",
+        digest.heading(),
+        markdown_to_html(&digest.to_markdown())
+    )
+}
+
+#[test]
+fn test_email() {
+    use crate::CtfEvent;
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let digest = Digest::new(events.iter().collect());
+
+    let config: EmailConfig = toml::from_str(
+        r#"
+smtp_host = "smtp.example.com"
+from = "CTFtime Bot <ctf@example.com>"
+recipients = ["team@example.com"]
+subject = "{title} ({date}): {count} events"
+weekdays = ["Mon"]
+"#,
+    )
+    .unwrap();
+    assert_eq!(config.smtp_port, 587);
+    let monday = "2018-12-10T12:00:00Z".parse().unwrap();
+    let tuesday = "2018-12-11T12:00:00Z".parse().unwrap();
+    assert!(config.is_due(monday, Some(Tz::UTC)));
+    assert!(!config.is_due(tuesday, Some(Tz::UTC)));
+    assert_eq!(
+        config.subject(&digest, monday, Some(Tz::UTC)),
+        "Upcoming CTFs (2018-12-10): 1 events"
+    );
+
+    let html = digest_html(&digest);
+    assert!(html.contains(
+        r#"<h3><a href="https://ctftime.org/event/724/">X-MAS CTF 2018 — Jeopardy</a></h3>"#
+    ));
+}
@@ -0,0 +1,159 @@
+//! Escalation for important events which too few players RSVP'd to
+//!
+//! A few days before a high-weight event starts, the bot counts the RSVPs.
+//! If fewer than the configured number of players signed up, it posts again with `@channel`, sends a direct message to the captains,
+//! and marks the event as needing players, which adds a note to the following digests.
+
+use crate::{duration::Humantime, format_duration, state::State, CtfEvent};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use serde_with::serde_as;
+use std::collections::BTreeMap;
+
+/// Configuration of the escalation, only available in the configuration file
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct EscalationConfig {
+    /// Only events with at least this rating weight are escalated
+    #[serde(default = "default_escalation_min_weight")]
+    pub min_weight: f32,
+    /// Events with fewer RSVPs are escalated
+    #[serde(default = "default_escalation_min_rsvps")]
+    pub min_rsvps: usize,
+    /// Time before the start of the event to count the RSVPs, e.g., `3d`
+    #[serde_as(as = "Humantime")]
+    #[serde(default = "default_escalation_lead_time")]
+    pub lead_time: Duration,
+    /// Mattermost user names of the captains, who receive a direct message
+    #[serde(default)]
+    pub captains: Vec<String>,
+}
+
+fn default_escalation_min_weight() -> f32 {
+    50.
+}
+
+fn default_escalation_min_rsvps() -> usize {
+    3
+}
+
+fn default_escalation_lead_time() -> Duration {
+    Duration::hours(72)
+}
+
+impl EscalationConfig {
+    /// Whether the event is important enough to be escalated
+    pub fn is_watched(&self, event: &CtfEvent) -> bool {
+        event.weight() >= self.min_weight
+    }
+
+    /// Whether too few players RSVP'd to the event
+    pub fn needs_players(&self, state: &State, event_id: usize) -> bool {
+        rsvp_count(state, event_id) < self.min_rsvps
+    }
+
+    /// Text of the escalation post in the channel
+    pub fn channel_message(&self, event: &CtfEvent, state: &State, now: DateTime<Utc>) -> String {
+        format!(
+            "@channel 🙋 [{}]({}) starts in {}, but only {} of {} players signed up — click “I'm in” if you can play",
+            event.display_title(),
+            event.ctftime_url(),
+            format_duration(&event.start_date().signed_duration_since(now)),
+            rsvp_count(state, event.id()),
+            self.min_rsvps,
+        )
+    }
+
+    /// Text of the direct message to the captains
+    pub fn captain_message(&self, event: &CtfEvent, state: &State) -> String {
+        let mut text = format!(
+            "[{}]({}) needs players: {} of {} signed up",
+            event.display_title(),
+            event.ctftime_url(),
+            rsvp_count(state, event.id()),
+            self.min_rsvps,
+        );
+        if let Some(record) = state.events.get(&event.id()) {
+            if !record.rsvps.is_empty() {
+                text += " —";
+                for user in &record.rsvps {
+                    text += &format!(" @{}", user);
+                }
+            }
+        }
+        text
+    }
+
+    /// Notes for the digest on the events marked as needing players, which still lack RSVPs
+    pub fn notes(&self, state: &State) -> BTreeMap<usize, String> {
+        state
+            .events
+            .iter()
+            .filter(|(_, record)| record.needs_players)
+            .filter(|(&id, _)| self.needs_players(state, id))
+            .map(|(&id, record)| {
+                let note = format!(
+                    "🙋 Needs players: {} of {} signed up",
+                    record.rsvps.len(),
+                    self.min_rsvps
+                );
+                (id, note)
+            })
+            .collect()
+    }
+}
+
+fn rsvp_count(state: &State, event_id: usize) -> usize {
+    state
+        .events
+        .get(&event_id)
+        .map_or(0, |record| record.rsvps.len())
+}
+
+#[test]
+fn test_escalation() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let config: EscalationConfig =
+        serde_json::from_str(r#"{"min_weight": 20, "captains": ["alice"]}"#).unwrap();
+    assert_eq!(config.min_rsvps, 3);
+    assert_eq!(config.lead_time, Duration::hours(72));
+    assert!(config.is_watched(&events[0]));
+
+    let mut state = State::default();
+    state.record_events(&events);
+    state
+        .events
+        .get_mut(&724)
+        .unwrap()
+        .rsvps
+        .insert("bob".to_string());
+    assert!(config.needs_players(&state, 724));
+    let now = events[0].start_date().with_timezone(&Utc) - chrono::Duration::hours(72);
+    assert_eq!(
+        config.channel_message(&events[0], &state, now),
+        "@channel 🙋 [X-MAS CTF 2018](https://ctftime.org/event/724/) starts in 3 days, but only 1 of 3 players signed up — click “I'm in” if you can play"
+    );
+    assert_eq!(
+        config.captain_message(&events[0], &state),
+        "[X-MAS CTF 2018](https://ctftime.org/event/724/) needs players: 1 of 3 signed up — @bob"
+    );
+
+    assert!(config.notes(&state).is_empty());
+    state.events.get_mut(&724).unwrap().needs_players = true;
+    assert_eq!(
+        config.notes(&state)[&724],
+        "🙋 Needs players: 1 of 3 signed up"
+    );
+    for user in &["carol", "dave"] {
+        state
+            .events
+            .get_mut(&724)
+            .unwrap()
+            .rsvps
+            .insert(user.to_string());
+    }
+    assert!(!config.needs_players(&state, 724));
+    assert!(config.notes(&state).is_empty());
+}
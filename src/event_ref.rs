@@ -0,0 +1,115 @@
+//! Borrowing variant of [`CtfEvent`] for high-throughput consumers
+//!
+//! [`CtfEventRef`] borrows the strings from the response buffer where possible, instead of allocating each of them.
+//! Strings containing JSON escape sequences cannot be borrowed and are allocated.
+
+use crate::{CtfEvent, CtfFormat, CtfRestrictions, CtfTeam};
+use chrono::{DateTime, FixedOffset};
+use serde::Deserialize;
+use std::borrow::Cow;
+
+/// A CTFtime event borrowing from the input, see [`CtfEvent`] for the meaning of the fields
+///
+/// Optional strings are empty if they are missing.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CtfEventRef<'a> {
+    #[serde(borrow)]
+    pub title: Cow<'a, str>,
+    #[serde(borrow)]
+    pub ctftime_url: Cow<'a, str>,
+    #[serde(borrow, default)]
+    pub description: Cow<'a, str>,
+    pub id: usize,
+    #[serde(rename = "start")]
+    pub start_date: DateTime<FixedOffset>,
+    #[serde(rename = "finish")]
+    pub finish_date: DateTime<FixedOffset>,
+    #[serde(borrow, rename = "logo")]
+    pub logo_url: Cow<'a, str>,
+    #[serde(borrow)]
+    pub url: Cow<'a, str>,
+    pub format: CtfFormat,
+    pub public_votable: bool,
+    pub weight: f32,
+    #[serde(borrow)]
+    pub live_feed: Cow<'a, str>,
+    pub restrictions: CtfRestrictions,
+    /// Some of the locations are `null` and not `""`.
+    #[serde(borrow, default)]
+    pub location: Option<Cow<'a, str>>,
+    pub onsite: bool,
+    #[serde(borrow)]
+    pub organizers: Vec<CtfTeamRef<'a>>,
+    pub ctf_id: usize,
+    pub participants: usize,
+}
+
+/// A team borrowing from the input, see [`CtfTeam`]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct CtfTeamRef<'a> {
+    pub id: usize,
+    #[serde(borrow)]
+    pub name: Cow<'a, str>,
+}
+
+fn non_empty(s: Cow<'_, str>) -> Option<String> {
+    if s.is_empty() {
+        None
+    } else {
+        Some(s.into_owned())
+    }
+}
+
+impl<'a> From<CtfEventRef<'a>> for CtfEvent {
+    fn from(event: CtfEventRef<'a>) -> Self {
+        CtfEvent {
+            title: event.title.into_owned(),
+            ctftime_url: event.ctftime_url.into_owned(),
+            description: event.description.into_owned(),
+            id: event.id,
+            start_date: event.start_date,
+            finish_date: event.finish_date,
+            logo_url: non_empty(event.logo_url),
+            url: non_empty(event.url),
+            format: event.format,
+            public_votable: event.public_votable,
+            weight: event.weight,
+            live_feed: non_empty(event.live_feed),
+            restrictions: event.restrictions,
+            location: event.location.and_then(non_empty),
+            onsite: event.onsite,
+            organizers: event
+                .organizers
+                .into_iter()
+                .map(|team| CtfTeam {
+                    id: team.id,
+                    name: team.name.into_owned(),
+                })
+                .collect(),
+            ctf_id: event.ctf_id,
+            participants: event.participants,
+        }
+    }
+}
+
+#[test]
+fn test_deserialize_ctf_event_ref() {
+    let json = std::fs::read_to_string("./tests/ctfs.json").unwrap();
+
+    let res: Vec<CtfEventRef<'_>> = serde_json::from_str(&json).unwrap();
+    assert_eq!(res.len(), 442);
+
+    let event = &res[440];
+    assert_eq!(event.title, "RHme3 - Qualifiers");
+    assert!(matches!(event.title, Cow::Borrowed(_)));
+    assert!(matches!(event.ctftime_url, Cow::Borrowed(_)));
+
+    let event: CtfEvent = res[441].clone().into();
+    assert_eq!(event.id, 514);
+    assert_eq!(
+        event.location,
+        Some("NH Hotel, The Hague, Netherlands".to_string())
+    );
+    assert_eq!(event.live_feed, None);
+    assert_eq!(event.format, CtfFormat::AttackDefense);
+}
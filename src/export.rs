@@ -0,0 +1,111 @@
+//! Export of the fetched events for planning and analysis in spreadsheets
+//!
+//! Unlike the digest, the export is not filtered and contains all fields of the events.
+
+use crate::{spreadsheet::csv_line, CtfEvent};
+use chrono::{SecondsFormat, Utc};
+use std::str::FromStr;
+
+/// Columns of the export, see [`export_row`]
+const EXPORT_HEADER: [&str; 16] = [
+    "id",
+    "ctf_id",
+    "title",
+    "start",
+    "finish",
+    "weight",
+    "format",
+    "restrictions",
+    "onsite",
+    "location",
+    "organizers",
+    "participants",
+    "public_votable",
+    "url",
+    "ctftime_url",
+    "description",
+];
+
+/// File format of the export
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            _ => Err(format!("Unknown export format `{}`", s)),
+        }
+    }
+}
+
+/// Values of all fields of `event`, matching [`EXPORT_HEADER`]
+///
+/// Multiple organizers are separated by `; `.
+pub fn export_row(event: &CtfEvent) -> Vec<String> {
+    vec![
+        event.id().to_string(),
+        event.ctf_id().to_string(),
+        event.title().to_string(),
+        event
+            .start_date()
+            .with_timezone(&Utc)
+            .to_rfc3339_opts(SecondsFormat::Secs, true),
+        event
+            .finish_date()
+            .with_timezone(&Utc)
+            .to_rfc3339_opts(SecondsFormat::Secs, true),
+        event.weight().to_string(),
+        event.format().to_string(),
+        event.restrictions.to_string(),
+        event.onsite().to_string(),
+        event.location().unwrap_or_default().to_string(),
+        event
+            .known_organizers()
+            .map(|team| team.name())
+            .collect::<Vec<_>>()
+            .join("; "),
+        event.participants().to_string(),
+        event.public_votable().to_string(),
+        event.url().unwrap_or_default().to_string(),
+        event.ctftime_url().to_string(),
+        event.description().to_string(),
+    ]
+}
+
+/// Export `events` in `format`
+pub fn export(events: &[CtfEvent], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Csv => {
+            let mut csv = csv_line(&EXPORT_HEADER) + "\n";
+            for event in events {
+                csv += &csv_line(&export_row(event));
+                csv += "\n";
+            }
+            csv
+        }
+    }
+}
+
+#[test]
+fn test_export_csv() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    assert_eq!("csv".parse(), Ok(ExportFormat::Csv));
+    assert!("xlsx".parse::<ExportFormat>().is_err());
+
+    let csv = export(&events, ExportFormat::Csv);
+    let mut lines = csv.lines();
+    assert_eq!(lines.next().unwrap(), EXPORT_HEADER.join(","));
+    assert!(lines.next().unwrap().starts_with(
+        "724,277,X-MAS CTF 2018,2018-12-14T18:00:00Z,2018-12-21T18:00:00Z,24.07,Jeopardy,Open,false,,\"Hecării, Țuica și Păunii\",146,true,https://www.xmas-ctf.cf/,"
+    ));
+    let row = export_row(&events[0]);
+    assert_eq!(row.len(), EXPORT_HEADER.len());
+    assert_eq!(row[14], "https://ctftime.org/event/724/");
+}
@@ -0,0 +1,109 @@
+//! Comparison of two filter configurations
+//!
+//! Shows which events a changed configuration would add to or remove from the digest,
+//! before the configuration is deployed.
+
+use crate::{Config, CtfEvent};
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+/// Events whose digest membership differs between two configurations
+#[derive(Debug)]
+pub struct FilterDiff<'a> {
+    /// Events only shown with the new configuration
+    pub added: Vec<&'a CtfEvent>,
+    /// Events only shown with the old configuration
+    pub removed: Vec<&'a CtfEvent>,
+}
+
+/// Compare the events shown in the digest with the `old` and `new` configuration
+pub fn diff_filters<'a>(
+    events: &'a [CtfEvent],
+    old: &Config,
+    new: &Config,
+    now: DateTime<Utc>,
+) -> FilterDiff<'a> {
+    let mut diff = FilterDiff {
+        added: Vec::new(),
+        removed: Vec::new(),
+    };
+    for event in events {
+        match (
+            event.matches_filters(old, now),
+            event.matches_filters(new, now),
+        ) {
+            (false, true) => diff.added.push(event),
+            (true, false) => diff.removed.push(event),
+            _ => {}
+        }
+    }
+    diff
+}
+
+impl<'a> FilterDiff<'a> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+
+    /// List the added and removed events, one per line
+    pub fn to_markdown(&self) -> String {
+        if self.is_empty() {
+            return "Both configurations show the same events.\n".to_string();
+        }
+        let mut res = String::new();
+        for (sign, events) in &[("+", &self.added), ("-", &self.removed)] {
+            for event in events.iter() {
+                let _ = writeln!(
+                    res,
+                    "{} [{}]({}) (weight {:.2}, starts {})",
+                    sign,
+                    event.title(),
+                    event.ctftime_url(),
+                    event.weight(),
+                    event.start_date().format("%Y-%m-%d"),
+                );
+            }
+        }
+        let _ = writeln!(
+            res,
+            "\n{} added, {} removed",
+            self.added.len(),
+            self.removed.len()
+        );
+        res
+    }
+}
+
+#[test]
+fn test_diff_filters() {
+    let json = std::fs::read_to_string("./tests/ctfs.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_str(&json).unwrap();
+    let config = |extra: &str| -> Config {
+        toml::from_str(&format!(
+            r##"
+lookahead = "100000d"
+color_jeopardy = "#0099e1"
+color_attack_defense = "danger"
+{}
+"##,
+            extra
+        ))
+        .unwrap()
+    };
+    let old = config("always_show_ctfs = []");
+    let new = config("always_show_ctfs = [216]\nmin_weight = 25");
+    let now = "2018-01-01T00:00:00Z".parse().unwrap();
+
+    let diff = diff_filters(&events, &old, &new, now);
+    assert_eq!(diff.added.len(), 1);
+    assert_eq!(diff.added[0].id(), 514);
+    assert_eq!(diff.removed.len(), 148);
+    assert!(diff.removed.iter().all(|event| event.weight() < 25.0));
+
+    let diff = diff_filters(&events, &old, &old, now);
+    assert!(diff.is_empty());
+    assert_eq!(
+        diff.to_markdown(),
+        "Both configurations show the same events.\n"
+    );
+}
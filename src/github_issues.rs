@@ -0,0 +1,167 @@
+//! GitHub issue for each announced event, e.g., for tracking the sign-ups and the task assignments
+//!
+//! Each announced event gets one issue, labeled with the format and the weight of the event.
+//! The number of the issue is stored in the state, such that no event gets a second issue.
+
+use crate::{format_date, mattermost_hook_api::Url, state::State, timed, CtfEvent, CONFIG};
+use log::{error, info};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Configuration of the issues, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct GithubIssuesConfig {
+    /// Repository in the form `owner/name`
+    pub repository: String,
+    /// Personal access token with write access to the issues of the repository
+    pub token: String,
+    /// Labels added to every issue, in addition to the format and weight labels
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Only events with at least this rating weight get an issue
+    #[serde(default)]
+    pub min_weight: f32,
+    /// URL of the GitHub API, e.g., for GitHub Enterprise Server
+    #[serde(default = "default_github_api")]
+    pub api_url: Url,
+}
+
+fn default_github_api() -> Url {
+    "https://api.github.com"
+        .parse()
+        .expect("The default URL is valid")
+}
+
+/// Label of the weight range of the event, e.g., `weight: 25-50`
+fn weight_label(event: &CtfEvent) -> String {
+    let weight = event.weight();
+    if weight <= 0. {
+        "weight: unrated".to_string()
+    } else if weight < 25. {
+        "weight: <25".to_string()
+    } else if weight < 50. {
+        "weight: 25-50".to_string()
+    } else if weight < 75. {
+        "weight: 50-75".to_string()
+    } else {
+        "weight: 75+".to_string()
+    }
+}
+
+/// Labels of the issue of `event`
+pub fn issue_labels(event: &CtfEvent, labels: &[String]) -> Vec<String> {
+    let mut labels = labels.to_vec();
+    labels.push(format!("format: {}", event.format()));
+    labels.push(weight_label(event));
+    labels
+}
+
+/// Markdown body of the issue of `event`
+pub fn issue_body(event: &CtfEvent) -> String {
+    let mut body = format!(
+        "**Start:** {}\n**End:** {}\n**Format:** {}\n",
+        format_date(&event.start_date(), CONFIG.timezone),
+        format_date(&event.finish_date(), CONFIG.timezone),
+        event.format(),
+    );
+    if let Some(weight) = event.rating_weight() {
+        body += &format!("**Weight:** {}\n", weight);
+    }
+    let organizers: Vec<String> = event
+        .known_organizers()
+        .map(|team| format!("[{}]({})", team.name(), team.url()))
+        .collect();
+    if !organizers.is_empty() {
+        body += &format!("**Organizers:** {}\n", organizers.join(", "));
+    }
+    body += &format!("\nCTFtime: {}\n", event.ctftime_url());
+    if let Some(url) = event.url() {
+        body += &format!("Website: {}\n", url);
+    }
+    body += "\n### Players\n\n- [ ] \n\n### Tasks\n\n- [ ] ";
+    body
+}
+
+impl GithubIssuesConfig {
+    /// Open the issue for the event and return its number
+    pub fn create_issue(&self, client: &Client, event: &CtfEvent) -> Result<u64, reqwest::Error> {
+        let created: Value = client
+            .post(&format!(
+                "{}/repos/{}/issues",
+                self.api_url.as_str().trim_end_matches('/'),
+                self.repository
+            ))
+            .bearer_auth(&self.token)
+            .header("Accept", "application/vnd.github.v3+json")
+            .header("User-Agent", "ctftimebot")
+            .json(&json!({
+                "title": event.display_title(),
+                "body": issue_body(event),
+                "labels": issue_labels(event, &self.labels),
+            }))
+            .send()?
+            .error_for_status()?
+            .json()?;
+        Ok(created["number"].as_u64().unwrap_or_default())
+    }
+}
+
+/// Open issues for the `announced` events which have none yet
+///
+/// Errors are logged, the event gets its issue once it is announced again.
+pub fn sync_issues(
+    issues: &GithubIssuesConfig,
+    client: &Client,
+    state: &mut State,
+    events: &[CtfEvent],
+    announced: &[usize],
+) {
+    for event in events
+        .iter()
+        .filter(|event| announced.contains(&event.id()))
+        .filter(|event| event.weight() >= issues.min_weight)
+    {
+        let record = state.events.entry(event.id()).or_default();
+        if record.github_issue.is_some() {
+            continue;
+        }
+        match timed("Creating a GitHub issue", || {
+            issues.create_issue(client, event)
+        }) {
+            Ok(number) => {
+                info!("Opened issue #{} for event {}", number, event.id());
+                record.github_issue = Some(number);
+            }
+            Err(err) => error!("Couldn't open issue for event {}: {}", event.id(), err),
+        }
+    }
+}
+
+#[test]
+fn test_github_issues() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let mut events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let config: GithubIssuesConfig =
+        serde_json::from_str(r#"{"repository": "team/ctfs", "token": "t", "labels": ["ctf"]}"#)
+            .unwrap();
+    assert_eq!(config.api_url.as_str(), "https://api.github.com/");
+
+    assert_eq!(
+        issue_labels(&events[0], &config.labels),
+        vec!["ctf", "format: Jeopardy", "weight: <25"]
+    );
+    let body = issue_body(&events[0]);
+    assert!(body.contains("**Weight:** 24\n"));
+    assert!(body
+        .contains("**Organizers:** [Hecării, Țuica și Păunii](https://ctftime.org/team/58218)\n"));
+    assert!(body.contains(
+        "\nCTFtime: https://ctftime.org/event/724/\nWebsite: https://www.xmas-ctf.cf/\n"
+    ));
+
+    events[0].weight = 0.;
+    assert_eq!(weight_label(&events[0]), "weight: unrated");
+    events[0].weight = 50.;
+    assert_eq!(weight_label(&events[0]), "weight: 50-75");
+}
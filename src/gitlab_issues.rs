@@ -0,0 +1,211 @@
+//! GitLab issue for each announced event, the counterpart of [`github_issues`][crate::github_issues]
+//!
+//! The issues contain the CTFtime id in a hidden marker in their description.
+//! The marker is used to find the issue of an event, such that the state is not needed and the issues are updated once the event changes.
+
+use crate::{
+    github_issues::{issue_body, issue_labels},
+    mattermost_hook_api::Url,
+    timed, CtfEvent,
+};
+use log::{error, info};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+/// Configuration of the issues, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct GitlabIssuesConfig {
+    /// URL of the GitLab instance
+    #[serde(default = "default_gitlab_url")]
+    pub url: Url,
+    /// Id or path of the project, e.g., `team/ctfs`
+    pub project: String,
+    /// Personal or project access token with the `api` scope
+    pub token: String,
+    /// Labels added to every issue, in addition to the format and weight labels
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// Only events with at least this rating weight get an issue
+    #[serde(default)]
+    pub min_weight: f32,
+}
+
+fn default_gitlab_url() -> Url {
+    "https://gitlab.com"
+        .parse()
+        .expect("The default URL is valid")
+}
+
+/// An issue as returned by the GitLab API
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+struct Issue {
+    iid: u64,
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    labels: Vec<String>,
+}
+
+/// Hidden marker identifying the issue of the event
+fn marker(event_id: usize) -> String {
+    format!("<!-- ctftime-event: {} -->", event_id)
+}
+
+/// Description of the issue of `event`, ending with the [`marker`]
+pub fn issue_description(event: &CtfEvent) -> String {
+    format!("{}\n\n{}", issue_body(event), marker(event.id()))
+}
+
+/// Description with the current details of `event`, keeping the players and tasks edited by the team
+fn updated_description(existing: &str, event: &CtfEvent) -> String {
+    const EDITED: &str = "\n### Players";
+    let fresh = issue_description(event);
+    match (existing.find(EDITED), fresh.find(EDITED)) {
+        (Some(old), Some(new)) => format!("{}{}", &fresh[..new], &existing[old..]),
+        _ => fresh,
+    }
+}
+
+impl GitlabIssuesConfig {
+    fn issues_url(&self) -> String {
+        format!(
+            "{}/api/v4/projects/{}/issues",
+            self.url.as_str().trim_end_matches('/'),
+            self.project.replace('/', "%2F")
+        )
+    }
+
+    /// Issue with the marker of the event, including closed issues
+    fn find_issue(
+        &self,
+        client: &Client,
+        event_id: usize,
+    ) -> Result<Option<Issue>, reqwest::Error> {
+        let marker = marker(event_id);
+        let issues: Vec<Issue> = client
+            .get(&self.issues_url())
+            .header("PRIVATE-TOKEN", &self.token)
+            .query(&[
+                ("search", marker.as_str()),
+                ("in", "description"),
+                ("scope", "all"),
+            ])
+            .send()?
+            .error_for_status()?
+            .json()?;
+        // The search also matches similar descriptions, e.g., of events with a longer id
+        Ok(issues.into_iter().find(|issue| {
+            issue
+                .description
+                .as_deref()
+                .map_or(false, |description| description.contains(&marker))
+        }))
+    }
+
+    /// Create the issue of the event, or update it if it changed
+    ///
+    /// Returns the iid of the issue and whether it was created or updated.
+    pub fn upsert_issue(
+        &self,
+        client: &Client,
+        event: &CtfEvent,
+    ) -> Result<(u64, bool), reqwest::Error> {
+        let title = event.display_title();
+        let labels = issue_labels(event, &self.labels);
+        match self.find_issue(client, event.id())? {
+            Some(issue) => {
+                let existing = issue.description.as_deref().unwrap_or_default();
+                let description = updated_description(existing, event);
+                let unchanged = issue.title == title
+                    && existing == description
+                    && labels.iter().all(|label| issue.labels.contains(label));
+                if !unchanged {
+                    client
+                        .put(&format!("{}/{}", self.issues_url(), issue.iid))
+                        .header("PRIVATE-TOKEN", &self.token)
+                        .json(&json!({
+                            "title": title,
+                            "description": description,
+                            "add_labels": labels.join(","),
+                        }))
+                        .send()?
+                        .error_for_status()?;
+                }
+                Ok((issue.iid, !unchanged))
+            }
+            None => {
+                let created: Issue = client
+                    .post(&self.issues_url())
+                    .header("PRIVATE-TOKEN", &self.token)
+                    .json(&json!({
+                        "title": title,
+                        "description": issue_description(event),
+                        "labels": labels.join(","),
+                    }))
+                    .send()?
+                    .error_for_status()?
+                    .json()?;
+                Ok((created.iid, true))
+            }
+        }
+    }
+}
+
+/// Create or update the issues of the `announced` events, errors are logged and retried during the next run
+pub fn sync_gitlab_issues(
+    issues: &GitlabIssuesConfig,
+    client: &Client,
+    events: &[CtfEvent],
+    announced: &[usize],
+) {
+    for event in events
+        .iter()
+        .filter(|event| announced.contains(&event.id()))
+        .filter(|event| event.weight() >= issues.min_weight)
+    {
+        match timed("Updating a GitLab issue", || {
+            issues.upsert_issue(client, event)
+        }) {
+            Ok((iid, true)) => info!("Updated issue #{} of event {}", iid, event.id()),
+            Ok((_, false)) => {}
+            Err(err) => error!(
+                "Couldn't update the GitLab issue of event {}: {}",
+                event.id(),
+                err
+            ),
+        }
+    }
+}
+
+#[test]
+fn test_gitlab_issues() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let config: GitlabIssuesConfig =
+        serde_json::from_str(r#"{"project": "team/ctfs", "token": "t"}"#).unwrap();
+    assert_eq!(
+        config.issues_url(),
+        "https://gitlab.com/api/v4/projects/team%2Fctfs/issues"
+    );
+
+    let description = issue_description(&events[0]);
+    assert!(description.starts_with("**Start:** "));
+    assert!(description.ends_with("\n\n<!-- ctftime-event: 724 -->"));
+    assert_eq!(updated_description(&description, &events[0]), description);
+    let edited = description.replace("### Tasks\n\n- [ ] ", "### Tasks\n\n- [x] pwn1");
+    let outdated = edited.replace("**Format:** Jeopardy", "**Format:** Attack-Defense");
+    assert_eq!(updated_description(&outdated, &events[0]), edited);
+    assert_eq!(
+        updated_description("Moved to the wiki", &events[0]),
+        description
+    );
+    let issue: Issue = serde_json::from_str(
+        r#"{"iid": 3, "title": "X-MAS CTF 2018", "description": null, "labels": ["ctf"], "state": "opened"}"#,
+    )
+    .unwrap();
+    assert_eq!(issue.iid, 3);
+    assert_eq!(issue.description, None);
+}
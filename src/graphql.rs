@@ -0,0 +1,219 @@
+//! GraphQL endpoint over the data of the bot, for internal tools needing more than the JSON [`api`][crate::api]
+//!
+//! Requires the `graphql` feature.
+//! The server accepts POST requests at [`GRAPHQL_PATH`], authenticated with the same token as the JSON API.
+//! Events, announcements, and RSVPs come from the [`State`], the results and ratings of the team are fetched from CTFtime on demand.
+
+use crate::{recap::fetch_ratings, state::State, trivia::fetch_results, BASE_URL, CONFIG};
+use async_graphql::{EmptyMutation, EmptySubscription, Object, Schema, SimpleObject};
+use chrono::{DateTime, Utc};
+use reqwest::blocking::Client;
+use serde_json::Value;
+
+/// Path of the GraphQL endpoint
+pub const GRAPHQL_PATH: &str = "graphql";
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// An event known to the bot
+#[derive(Clone, Debug, PartialEq, SimpleObject)]
+pub struct Event {
+    pub id: usize,
+    pub title: String,
+    pub start: Option<DateTime<Utc>>,
+    pub finish: Option<DateTime<Utc>>,
+    pub weight: f64,
+    pub ctftime_url: String,
+    /// The event was part of a digest
+    pub announced: bool,
+    /// The event is configured as playing or has RSVPs
+    pub playing: bool,
+    /// Users who RSVP'd to the event
+    pub rsvps: Vec<String>,
+    /// Too few players RSVP'd, see [`escalation`][crate::escalation]
+    pub needs_players: bool,
+    /// Most recently seen number of participating teams
+    pub participants: Option<usize>,
+}
+
+/// Placement of the team at a past event
+#[derive(Clone, Debug, PartialEq, SimpleObject)]
+pub struct EventResult {
+    pub title: String,
+    pub place: usize,
+    pub points: f64,
+}
+
+/// Rating of the team in one year
+#[derive(Clone, Debug, PartialEq, SimpleObject)]
+pub struct YearStats {
+    pub year: String,
+    pub rating_place: Option<usize>,
+    pub rating_points: Option<f64>,
+    pub country_place: Option<usize>,
+}
+
+/// Statistics of the configured team
+#[derive(Clone, Debug, PartialEq, SimpleObject)]
+pub struct TeamStats {
+    pub team_id: usize,
+    /// Place in the national top 10, see [`leaderboard`][crate::leaderboard]
+    pub national_place: Option<usize>,
+    pub ratings: Vec<YearStats>,
+}
+
+/// Root of the queries, answered from a snapshot of the state
+pub struct QueryRoot {
+    state: State,
+    now: DateTime<Utc>,
+    client: Client,
+}
+
+impl QueryRoot {
+    fn to_event(&self, id: usize) -> Option<Event> {
+        let record = self.state.events.get(&id)?;
+        Some(Event {
+            id,
+            title: record.title.clone(),
+            start: record.start,
+            finish: record.finish,
+            weight: (f64::from(record.weight) * 100.).round() / 100.,
+            ctftime_url: format!("{}/event/{}/", BASE_URL, id),
+            announced: record.announced,
+            playing: CONFIG.is_playing(id) || !record.rsvps.is_empty(),
+            rsvps: record.rsvps.iter().cloned().collect(),
+            needs_players: record.needs_players,
+            participants: record.participants(),
+        })
+    }
+}
+
+#[Object]
+impl QueryRoot {
+    /// Events sorted by their start, all filters are optional
+    async fn events(
+        &self,
+        announced: Option<bool>,
+        playing: Option<bool>,
+        #[graphql(desc = "Only events which did not finish yet")] upcoming: Option<bool>,
+        min_weight: Option<f64>,
+        #[graphql(desc = "Only events this user RSVP'd to")] rsvp: Option<String>,
+    ) -> Vec<Event> {
+        let mut events: Vec<Event> = self
+            .state
+            .events
+            .keys()
+            .filter_map(|&id| self.to_event(id))
+            .filter(|event| announced.map_or(true, |announced| event.announced == announced))
+            .filter(|event| playing.map_or(true, |playing| event.playing == playing))
+            .filter(|event| {
+                upcoming.map_or(true, |upcoming| {
+                    event.finish.map_or(false, |finish| finish > self.now) == upcoming
+                })
+            })
+            .filter(|event| min_weight.map_or(true, |min_weight| event.weight >= min_weight))
+            .filter(|event| {
+                rsvp.as_ref()
+                    .map_or(true, |user| event.rsvps.contains(user))
+            })
+            .collect();
+        events.sort_by_key(|event| (event.start, event.id));
+        events
+    }
+
+    /// Event with the CTFtime id `id`
+    async fn event(&self, id: usize) -> Option<Event> {
+        self.to_event(id)
+    }
+
+    /// Placements of the team in `year`, requires the team id
+    async fn results(&self, year: i32) -> async_graphql::Result<Vec<EventResult>> {
+        let team_id = CONFIG.team_id.ok_or("No team id configured")?;
+        Ok(fetch_results(&self.client, year, team_id)?
+            .into_iter()
+            .map(|result| EventResult {
+                title: result.title,
+                place: result.place,
+                points: result.points,
+            })
+            .collect())
+    }
+
+    /// Ratings and national rank of the team, requires the team id
+    async fn team_stats(&self) -> async_graphql::Result<TeamStats> {
+        let team_id = CONFIG.team_id.ok_or("No team id configured")?;
+        let ratings = fetch_ratings(&self.client, team_id)?
+            .into_iter()
+            .map(|(year, rating)| YearStats {
+                year,
+                rating_place: rating.rating_place,
+                rating_points: rating.rating_points,
+                country_place: rating.country_place,
+            })
+            .collect();
+        Ok(TeamStats {
+            team_id,
+            national_place: self
+                .state
+                .national_rank
+                .as_ref()
+                .and_then(|rank| rank.place),
+            ratings,
+        })
+    }
+}
+
+/// Execute the GraphQL request `body` against the `state` and return the JSON response
+pub fn execute(body: &[u8], state: State, client: Client) -> Result<Value, BoxError> {
+    let request: async_graphql::Request = serde_json::from_slice(body)?;
+    let root = QueryRoot {
+        state,
+        now: Utc::now(),
+        client,
+    };
+    let schema = Schema::new(root, EmptyMutation, EmptySubscription);
+    let response = futures_executor::block_on(schema.execute(request));
+    Ok(serde_json::to_value(&response)?)
+}
+
+#[test]
+fn test_graphql() {
+    use crate::CtfEvent;
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let mut state = State::default();
+    state.record_events(&events);
+    state.mark_announced(&[724]);
+    state
+        .events
+        .get_mut(&724)
+        .unwrap()
+        .rsvps
+        .insert("alice".to_string());
+
+    let query = |query: &str| {
+        let body = serde_json::to_vec(&serde_json::json!({ "query": query })).unwrap();
+        execute(&body, state.clone(), Client::new()).unwrap()
+    };
+    assert_eq!(
+        query(r#"{ events(rsvp: "alice") { id title playing rsvps participants } }"#)["data"],
+        serde_json::json!({"events": [{
+            "id": 724,
+            "title": "X-MAS CTF 2018",
+            "playing": true,
+            "rsvps": ["alice"],
+            "participants": 146,
+        }]})
+    );
+    assert_eq!(
+        query(r#"{ events(rsvp: "bob") { id } }"#)["data"],
+        serde_json::json!({"events": []})
+    );
+    assert_eq!(
+        query("{ event(id: 724) { weight announced } }")["data"],
+        serde_json::json!({"event": {"weight": 24.07, "announced": true}})
+    );
+    assert!(query("{ unknown }")["errors"].is_array());
+    assert!(execute(b"not json", state.clone(), Client::new()).is_err());
+}
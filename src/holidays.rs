@@ -0,0 +1,144 @@
+//! Public holidays and blackout periods of the team, which are noted on overlapping events
+//!
+//! Holidays next to a weekend make long weekends, which are a good time for long events.
+//! Other holidays often clash with plans of the players.
+//! During blackout periods, e.g., exam weeks, the team does not play at all.
+
+use crate::{local_date, CtfEvent};
+use chrono::{Datelike, NaiveDate, Weekday};
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+/// A single public holiday
+#[derive(Clone, Deserialize, Debug, Eq, PartialEq)]
+pub struct Holiday {
+    pub date: NaiveDate,
+    pub name: String,
+}
+
+/// A date range during which the team does not play, e.g., exams or a company freeze
+#[derive(Clone, Deserialize, Debug, Eq, PartialEq)]
+pub struct Blackout {
+    /// First day of the period
+    pub start: NaiveDate,
+    /// Last day of the period, inclusive
+    pub end: NaiveDate,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+impl Blackout {
+    /// Tag shown on events during the blackout period
+    pub fn tag(&self) -> String {
+        match self.name {
+            Some(ref name) => format!("⛔ blackout period ({})", name),
+            None => "⛔ blackout period".to_string(),
+        }
+    }
+}
+
+/// The first blackout period overlapping `event`
+///
+/// The dates of the event are taken in `timezone`, or the local timezone if unset.
+pub fn find_blackout<'a>(
+    event: &CtfEvent,
+    blackouts: &'a [Blackout],
+    timezone: Option<Tz>,
+) -> Option<&'a Blackout> {
+    let start = local_date(&event.start_date(), timezone);
+    let finish = local_date(&event.finish_date(), timezone);
+    blackouts
+        .iter()
+        .find(|blackout| blackout.start <= finish && start <= blackout.end)
+}
+
+/// Describe the holidays during `event`, if any
+///
+/// The dates of the event are taken in `timezone`, or the local timezone if unset.
+pub fn holiday_note(
+    event: &CtfEvent,
+    holidays: &[Holiday],
+    timezone: Option<Tz>,
+) -> Option<String> {
+    let start = local_date(&event.start_date(), timezone);
+    let finish = local_date(&event.finish_date(), timezone);
+    let overlapping: Vec<&Holiday> = holidays
+        .iter()
+        .filter(|holiday| start <= holiday.date && holiday.date <= finish)
+        .collect();
+    if overlapping.is_empty() {
+        return None;
+    }
+    let names = overlapping
+        .iter()
+        .map(|holiday| holiday.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let has_weekend = start
+        .iter_days()
+        .take_while(|date| *date <= finish)
+        .any(|date| matches!(date.weekday(), Weekday::Sat | Weekday::Sun));
+    let next_to_weekend = overlapping
+        .iter()
+        .any(|holiday| matches!(holiday.date.weekday(), Weekday::Fri | Weekday::Mon));
+    if has_weekend && next_to_weekend {
+        Some(format!(
+            "🏖 Long weekend ({}) — good for longer events",
+            names
+        ))
+    } else {
+        Some(format!("⚠ Clashes with {}", names))
+    }
+}
+
+#[test]
+fn test_holiday_note() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    // X-MAS CTF 2018 runs from Friday, 2018-12-14 18:00 UTC for 7 days
+    let event = &events[0];
+    let utc = Some(Tz::UTC);
+    let holiday = |date: &str, name: &str| Holiday {
+        date: date.parse().unwrap(),
+        name: name.to_string(),
+    };
+
+    assert_eq!(holiday_note(event, &[], utc), None);
+    assert_eq!(
+        holiday_note(event, &[holiday("2018-12-24", "Christmas Eve")], utc),
+        None
+    );
+    assert_eq!(
+        holiday_note(event, &[holiday("2018-12-17", "Some Monday")], utc).unwrap(),
+        "🏖 Long weekend (Some Monday) — good for longer events"
+    );
+    assert_eq!(
+        holiday_note(event, &[holiday("2018-12-19", "Some Wednesday")], utc).unwrap(),
+        "⚠ Clashes with Some Wednesday"
+    );
+}
+
+#[test]
+fn test_find_blackout() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let event = &events[0];
+    let utc = Some(Tz::UTC);
+    let blackout = |start: &str, end: &str| Blackout {
+        start: start.parse().unwrap(),
+        end: end.parse().unwrap(),
+        name: Some("Exams".to_string()),
+    };
+
+    assert_eq!(find_blackout(event, &[], utc), None);
+    let blackouts = [
+        blackout("2018-12-01", "2018-12-13"),
+        blackout("2018-12-21", "2019-01-07"),
+    ];
+    assert_eq!(find_blackout(event, &blackouts, utc), Some(&blackouts[1]));
+    assert_eq!(blackouts[1].tag(), "⛔ blackout period (Exams)");
+    assert_eq!(find_blackout(event, &blackouts[..1], utc), None);
+}
@@ -0,0 +1,134 @@
+//! Static HTML page with the upcoming events, e.g., for publishing the schedule on the website of the team
+//!
+//! The page is rendered from a [Handlebars] template with the values of [`html_context`].
+//! The default template is self-contained: the styles and the script for the countdowns and the filters are inlined.
+//!
+//! [Handlebars]: https://handlebarsjs.com/guide/
+
+use crate::{digest::Digest, webhook::digest_events};
+use chrono::{DateTime, Utc};
+use handlebars::Handlebars;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Template used if no template is given
+pub const DEFAULT_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{{title}}</title>
+<style>
+body { font-family: sans-serif; max-width: 50em; margin: 2em auto; padding: 0 1em; color: #222; }
+.filters { display: flex; gap: 1em; margin-bottom: 1em; }
+.event { display: flex; gap: 1em; align-items: center; padding: 0.75em 0; border-bottom: 1px solid #ddd; }
+.event img { width: 64px; height: 64px; object-fit: contain; }
+.event h2 { font-size: 1.1em; margin: 0 0 0.25em; }
+.meta, .notes, footer { color: #666; font-size: 0.9em; }
+.countdown { font-weight: bold; }
+</style>
+</head>
+<body>
+<h1>{{title}}</h1>
+<div class="filters">
+<label>Format <select id="format"><option value="">All</option>{{#each formats}}<option>{{this}}</option>{{/each}}</select></label>
+<label>Minimal weight <input id="weight" type="number" min="0" step="1" value="0"></label>
+</div>
+{{#each events}}
+<div class="event" data-format="{{format}}" data-weight="{{weight}}">
+{{#if logo_url}}<img src="{{logo_url}}" alt="">{{/if}}
+<div>
+<h2><a href="{{url}}">{{title}}</a></h2>
+<div class="meta">{{format}} · weight {{weight}} · {{duration}} · <a href="{{ctftime_url}}">CTFtime</a></div>
+<div class="meta"><time datetime="{{start}}">{{start}}</time> — <span class="countdown" data-start="{{start}}" data-finish="{{finish}}"></span></div>
+{{#each notes}}<div class="notes">{{this}}</div>{{/each}}
+</div>
+</div>
+{{else}}
+<p>No upcoming events.</p>
+{{/each}}
+<footer>Generated {{generated}} from <a href="{{link}}">CTFtime</a></footer>
+<script>
+function filter() {
+  var format = document.getElementById("format").value;
+  var weight = parseFloat(document.getElementById("weight").value) || 0;
+  document.querySelectorAll(".event").forEach(function (event) {
+    var shown = (!format || event.dataset.format === format) && parseFloat(event.dataset.weight) >= weight;
+    event.style.display = shown ? "" : "none";
+  });
+}
+function countdown() {
+  var now = Date.now();
+  document.querySelectorAll(".countdown").forEach(function (element) {
+    var start = Date.parse(element.dataset.start), finish = Date.parse(element.dataset.finish);
+    if (now >= finish) { element.textContent = "finished"; return; }
+    if (now >= start) { element.textContent = "running"; return; }
+    var minutes = Math.floor((start - now) / 60000);
+    var days = Math.floor(minutes / 1440), hours = Math.floor(minutes % 1440 / 60);
+    element.textContent = "starts in " + (days ? days + "d " : "") + hours + "h " + minutes % 60 + "m";
+  });
+  document.querySelectorAll("time").forEach(function (element) {
+    element.textContent = new Date(element.dateTime).toLocaleString();
+  });
+}
+document.getElementById("format").addEventListener("change", filter);
+document.getElementById("weight").addEventListener("input", filter);
+countdown();
+setInterval(countdown, 60000);
+</script>
+</body>
+</html>
+"#;
+
+/// Values of the digest available in the template
+///
+/// `title`, `link`, `generated`, the distinct `formats` for the filter, and `events`, see [`digest_events`].
+pub fn html_context(digest: &Digest, now: DateTime<Utc>) -> Value {
+    let formats: BTreeSet<String> = digest
+        .events
+        .iter()
+        .map(|event| event.format().to_string())
+        .collect();
+    json!({
+        "title": digest.title,
+        "link": digest.link,
+        "generated": now.to_rfc3339(),
+        "formats": formats,
+        "events": digest_events(digest),
+    })
+}
+
+/// Render the template with HTML escaping
+pub fn render_html(template: &str, context: &Value) -> Result<String, BoxError> {
+    let mut handlebars = Handlebars::new();
+    handlebars.set_strict_mode(true);
+    Ok(handlebars.render_template(template, context)?)
+}
+
+#[test]
+fn test_render_html() {
+    use crate::CtfEvent;
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let mut events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    events[0].title = "<X-MAS> CTF 2018".to_string();
+    let mut digest = Digest::new(events.iter().collect());
+    digest.add_notes(vec![(724, "Qualifier for: X-MAS Finals".to_string())]);
+    let now = "2018-12-01T12:00:00Z".parse().unwrap();
+
+    let context = html_context(&digest, now);
+    assert_eq!(context["formats"], json!(["Jeopardy"]));
+    let html = render_html(DEFAULT_TEMPLATE, &context).unwrap();
+    assert!(
+        html.contains(r#"<h2><a href="https://www.xmas-ctf.cf/">&lt;X-MAS&gt; CTF 2018</a></h2>"#)
+    );
+    assert!(html.contains(r#"data-start="2018-12-14T18:00:00+00:00""#));
+    assert!(html.contains(r#"<div class="notes">Qualifier for: X-MAS Finals</div>"#));
+    assert!(html.contains("Generated 2018-12-01T12:00:00+00:00"));
+
+    let html = render_html(DEFAULT_TEMPLATE, &html_context(&Digest::new(vec![]), now)).unwrap();
+    assert!(html.contains("<p>No upcoming events.</p>"));
+    assert!(render_html("{{unknown}}", &context).is_err());
+}
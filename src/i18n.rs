@@ -0,0 +1,246 @@
+//! Localization of the durations, e.g., `starts in 3 days`, with the plural rules of the Unicode CLDR
+//!
+//! Only the units are translated, the rest of the messages stays English.
+//! The plural rules only cover integers, which is all durations need.
+
+use chrono::Duration;
+use serde_with::DeserializeFromStr;
+use std::{fmt, str::FromStr};
+
+/// Language of the durations, configured with `LOCALE`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, DeserializeFromStr)]
+pub enum Locale {
+    #[default]
+    En,
+    De,
+    Fr,
+    Pl,
+    Ru,
+}
+
+/// Error while parsing a [`Locale`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnknownLocale(String);
+
+impl fmt::Display for UnknownLocale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown locale `{}`, expected one of en, de, fr, pl, ru",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownLocale {}
+
+impl FromStr for Locale {
+    type Err = UnknownLocale;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Accept full tags like `de-AT` or `pl_PL`, only the language matters
+        let language = s.split(|c| c == '-' || c == '_').next().unwrap_or_default();
+        match &*language.to_ascii_lowercase() {
+            "en" => Ok(Locale::En),
+            "de" => Ok(Locale::De),
+            "fr" => Ok(Locale::Fr),
+            "pl" => Ok(Locale::Pl),
+            "ru" => Ok(Locale::Ru),
+            _ => Err(UnknownLocale(s.to_string())),
+        }
+    }
+}
+
+/// CLDR plural category of a number
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PluralCategory {
+    One,
+    Few,
+    Many,
+    Other,
+}
+
+/// Units of a duration, from the largest to the smallest
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Unit {
+    Day,
+    Hour,
+    Minute,
+    Second,
+}
+
+impl Locale {
+    /// Plural category of the integer `n`, following the cardinal rules of the CLDR
+    pub fn plural_category(self, n: i64) -> PluralCategory {
+        let n = n.abs();
+        let (last, last_two) = (n % 10, n % 100);
+        match self {
+            Locale::En | Locale::De if n == 1 => PluralCategory::One,
+            Locale::En | Locale::De => PluralCategory::Other,
+            Locale::Fr if n <= 1 => PluralCategory::One,
+            Locale::Fr if n % 1_000_000 == 0 => PluralCategory::Many,
+            Locale::Fr => PluralCategory::Other,
+            Locale::Pl if n == 1 => PluralCategory::One,
+            Locale::Ru if last == 1 && last_two != 11 => PluralCategory::One,
+            Locale::Pl | Locale::Ru
+                if (2..=4).contains(&last) && !(12..=14).contains(&last_two) =>
+            {
+                PluralCategory::Few
+            }
+            Locale::Pl | Locale::Ru => PluralCategory::Many,
+        }
+    }
+
+    /// Name of `unit` for the number `n`
+    pub fn unit(self, unit: Unit, n: i64) -> &'static str {
+        use PluralCategory::*;
+        let category = self.plural_category(n);
+        match (self, unit) {
+            (Locale::En, Unit::Day) => pick(category, "day", "days", "days"),
+            (Locale::En, Unit::Hour) => pick(category, "hour", "hours", "hours"),
+            (Locale::En, Unit::Minute) => pick(category, "minute", "minutes", "minutes"),
+            (Locale::En, Unit::Second) => pick(category, "second", "seconds", "seconds"),
+            (Locale::De, Unit::Day) => pick(category, "Tag", "Tage", "Tage"),
+            (Locale::De, Unit::Hour) => pick(category, "Stunde", "Stunden", "Stunden"),
+            (Locale::De, Unit::Minute) => pick(category, "Minute", "Minuten", "Minuten"),
+            (Locale::De, Unit::Second) => pick(category, "Sekunde", "Sekunden", "Sekunden"),
+            // French uses `de` before the unit for the millions, e.g., `1 000 000 de jours`
+            (Locale::Fr, Unit::Day) => match category {
+                One => "jour",
+                Many => "de jours",
+                _ => "jours",
+            },
+            (Locale::Fr, Unit::Hour) => match category {
+                One => "heure",
+                Many => "d’heures",
+                _ => "heures",
+            },
+            (Locale::Fr, Unit::Minute) => match category {
+                One => "minute",
+                Many => "de minutes",
+                _ => "minutes",
+            },
+            (Locale::Fr, Unit::Second) => match category {
+                One => "seconde",
+                Many => "de secondes",
+                _ => "secondes",
+            },
+            (Locale::Pl, Unit::Day) => pick(category, "dzień", "dni", "dni"),
+            (Locale::Pl, Unit::Hour) => pick(category, "godzina", "godziny", "godzin"),
+            (Locale::Pl, Unit::Minute) => pick(category, "minuta", "minuty", "minut"),
+            (Locale::Pl, Unit::Second) => pick(category, "sekunda", "sekundy", "sekund"),
+            (Locale::Ru, Unit::Day) => pick(category, "день", "дня", "дней"),
+            (Locale::Ru, Unit::Hour) => pick(category, "час", "часа", "часов"),
+            (Locale::Ru, Unit::Minute) => pick(category, "минута", "минуты", "минут"),
+            (Locale::Ru, Unit::Second) => pick(category, "секунда", "секунды", "секунд"),
+        }
+    }
+
+    /// `n` followed by the name of `unit`, e.g., `1 hour`
+    pub fn quantity(self, unit: Unit, n: i64) -> String {
+        format!("{} {}", n, self.unit(unit, n))
+    }
+}
+
+/// Choose between the forms for [`PluralCategory::One`], [`PluralCategory::Few`], and the rest
+fn pick(
+    category: PluralCategory,
+    one: &'static str,
+    few: &'static str,
+    many: &'static str,
+) -> &'static str {
+    match category {
+        PluralCategory::One => one,
+        PluralCategory::Few => few,
+        PluralCategory::Many | PluralCategory::Other => many,
+    }
+}
+
+/// Format the duration in days, hours, minutes, and seconds, e.g., `6 days 23 hours`
+///
+/// Days are only used for durations of more than 48 hours, zero units are skipped.
+pub fn format_duration(d: &Duration, locale: Locale) -> String {
+    let mut d = *d;
+    let mut tmp = Vec::with_capacity(4);
+    if d.num_hours() > 48 {
+        tmp.push(locale.quantity(Unit::Day, d.num_days()));
+        d = d + Duration::days(-d.num_days());
+    }
+    if d.num_hours() > 0 {
+        tmp.push(locale.quantity(Unit::Hour, d.num_hours()));
+        d = d + Duration::hours(-d.num_hours());
+    }
+    if d.num_minutes() > 0 {
+        tmp.push(locale.quantity(Unit::Minute, d.num_minutes()));
+        d = d + Duration::minutes(-d.num_minutes());
+    }
+    if d.num_seconds() > 0 {
+        tmp.push(locale.quantity(Unit::Second, d.num_seconds()));
+    }
+    tmp.join(" ")
+}
+
+#[test]
+fn test_plural_category() {
+    use PluralCategory::*;
+    let categories = |locale: Locale| -> Vec<PluralCategory> {
+        [0, 1, 2, 5, 11, 21, 22, 25, 112, 1_000_000]
+            .iter()
+            .map(|&n| locale.plural_category(n))
+            .collect()
+    };
+    assert_eq!(
+        categories(Locale::En),
+        [Other, One, Other, Other, Other, Other, Other, Other, Other, Other]
+    );
+    assert_eq!(
+        categories(Locale::Fr),
+        [One, One, Other, Other, Other, Other, Other, Other, Other, Many]
+    );
+    assert_eq!(
+        categories(Locale::Pl),
+        [Many, One, Few, Many, Many, Many, Few, Many, Many, Many]
+    );
+    assert_eq!(
+        categories(Locale::Ru),
+        [Many, One, Few, Many, Many, One, Few, Many, Many, Many]
+    );
+}
+
+#[test]
+fn test_format_duration() {
+    let duration = Duration::days(1) + Duration::hours(1) + Duration::minutes(21);
+    assert_eq!(
+        format_duration(&duration, Locale::En),
+        "25 hours 21 minutes"
+    );
+    assert_eq!(format_duration(&Duration::hours(1), Locale::En), "1 hour");
+    assert_eq!(
+        format_duration(&(Duration::days(7) + Duration::seconds(1)), Locale::En),
+        "7 days 1 second"
+    );
+    assert_eq!(
+        format_duration(&duration, Locale::De),
+        "25 Stunden 21 Minuten"
+    );
+    assert_eq!(format_duration(&Duration::hours(1), Locale::De), "1 Stunde");
+    assert_eq!(format_duration(&Duration::hours(0), Locale::De), "");
+    assert_eq!(
+        format_duration(&(Duration::days(3) + Duration::minutes(1)), Locale::Fr),
+        "3 jours 1 minute"
+    );
+    assert_eq!(format_duration(&duration, Locale::Pl), "25 godzin 21 minut");
+    assert_eq!(
+        format_duration(&(Duration::hours(22) + Duration::minutes(1)), Locale::Pl),
+        "22 godziny 1 minuta"
+    );
+    assert_eq!(format_duration(&duration, Locale::Ru), "25 часов 21 минута");
+    assert_eq!(
+        format_duration(&(Duration::days(3) + Duration::hours(2)), Locale::Ru),
+        "3 дня 2 часа"
+    );
+
+    assert_eq!("de-AT".parse(), Ok(Locale::De));
+    assert_eq!("pl_PL".parse(), Ok(Locale::Pl));
+    assert!("xx".parse::<Locale>().is_err());
+}
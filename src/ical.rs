@@ -0,0 +1,217 @@
+//! Export of the events as iCalendar file, such that the schedule can be imported into calendar apps
+//!
+//! The file follows [RFC 5545]: lines end with CRLF, long lines are folded, and text values are escaped.
+//! Each event gets a stable UID from its CTFtime id, such that importing an updated file replaces the old entries.
+//! Besides the one-shot export, the calendar can be served as [`Feed`], which calendar apps subscribe to.
+//!
+//! [RFC 5545]: https://datatracker.ietf.org/doc/html/rfc5545
+
+use crate::{plain_text::event_lines, CtfEvent};
+use chrono::{DateTime, Utc};
+use log::error;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Maximal length of a line in octets, without the line break
+const MAX_LINE_OCTETS: usize = 75;
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape a `TEXT` value
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Split the line into chunks of at most 75 octets, continuation lines start with a space
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded += "\r\n ";
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded += "\r\n";
+    folded
+}
+
+fn event_properties(event: &CtfEvent, now: DateTime<Utc>) -> Vec<String> {
+    let description: Vec<String> = event_lines(event)
+        .into_iter()
+        .skip(1)
+        .map(|line| line.trim().to_string())
+        .collect();
+    let mut properties = vec![
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}@ctftime.org", event.id()),
+        format!("DTSTAMP:{}", format_time(now)),
+        format!(
+            "DTSTART:{}",
+            format_time(event.start_date().with_timezone(&Utc))
+        ),
+        format!(
+            "DTEND:{}",
+            format_time(event.finish_date().with_timezone(&Utc))
+        ),
+        format!("SUMMARY:{}", escape_text(&event.display_title())),
+        format!("URL:{}", event.url().unwrap_or_else(|| event.ctftime_url())),
+        format!("DESCRIPTION:{}", escape_text(&description.join("\n"))),
+    ];
+    if event.onsite() {
+        if let Some(location) = event.location() {
+            properties.push(format!("LOCATION:{}", escape_text(location)));
+        }
+    }
+    properties.push("END:VEVENT".to_string());
+    properties
+}
+
+/// Calendar with one `VEVENT` per event, `now` is used as timestamp of the entries
+pub fn to_ics(events: &[&CtfEvent], now: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//ctftimebot//CTFtime events//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:Upcoming CTFs".to_string(),
+    ];
+    for event in events {
+        lines.extend(event_properties(event, now));
+    }
+    lines.push("END:VCALENDAR".to_string());
+    lines.iter().map(|line| fold_line(line)).collect()
+}
+
+/// Calendar which is generated on demand and cached for the refresh interval
+///
+/// If generating the calendar fails, the last calendar is served until the next refresh interval.
+pub struct Feed {
+    refresh: Duration,
+    generate: Box<dyn Fn() -> Result<String, BoxError> + Send + Sync>,
+    cached: Mutex<Option<(Instant, Arc<String>)>>,
+}
+
+impl Feed {
+    pub fn new(
+        refresh: Duration,
+        generate: impl Fn() -> Result<String, BoxError> + Send + Sync + 'static,
+    ) -> Self {
+        Feed {
+            refresh,
+            generate: Box::new(generate),
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// The current calendar, `None` if it was never generated successfully
+    ///
+    /// Concurrent requests wait for a running refresh, such that CTFtime is only queried once.
+    pub fn get(&self, now: Instant) -> Option<Arc<String>> {
+        let mut cached = self.cached.lock().unwrap_or_else(|err| err.into_inner());
+        match &*cached {
+            Some((generated, ics)) if now < *generated + self.refresh => return Some(ics.clone()),
+            _ => {}
+        }
+        match (self.generate)() {
+            Ok(ics) => {
+                let ics = Arc::new(ics);
+                *cached = Some((now, ics.clone()));
+                Some(ics)
+            }
+            Err(err) => {
+                error!("Couldn't refresh the calendar: {}", err);
+                let ics = cached.as_ref().map(|(_, ics)| ics.clone())?;
+                // Retry with the next refresh, instead of during every request
+                *cached = Some((now, ics.clone()));
+                Some(ics)
+            }
+        }
+    }
+
+    /// Seconds until the cached calendar is refreshed, for the `Cache-Control` header
+    pub fn max_age(&self) -> u64 {
+        self.refresh.as_secs()
+    }
+}
+
+#[test]
+fn test_to_ics() {
+    use crate::calendar::parse_ics;
+    use chrono::TimeZone;
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let now = Utc.ymd(2018, 12, 1).and_hms(12, 0, 0);
+
+    let ics = to_ics(&events.iter().collect::<Vec<_>>(), now);
+    assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+    assert!(ics.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+    assert!(ics.contains("\r\nUID:724@ctftime.org\r\n"));
+    assert!(ics.contains("\r\nDTSTAMP:20181201T120000Z\r\n"));
+    assert!(ics.contains("\r\nDTSTART:20181214T180000Z\r\n"));
+    assert!(ics.contains("\r\nURL:https://www.xmas-ctf.cf/\r\n"));
+    assert!(ics.contains("\r\nDESCRIPTION:Date:"));
+    assert!(ics.split("\r\n").all(|line| line.len() <= MAX_LINE_OCTETS));
+
+    let parsed = parse_ics(&ics, None);
+    assert_eq!(parsed.len(), 1);
+    assert_eq!(parsed[0].summary, "X-MAS CTF 2018");
+    assert_eq!(parsed[0].start, events[0].start_date());
+
+    assert_eq!(escape_text("a, b; c\\d\ne"), "a\\, b\\; c\\\\d\\ne");
+    let folded = fold_line(&"ä".repeat(40));
+    assert_eq!(
+        folded,
+        format!("{}\r\n {}\r\n", "ä".repeat(37), "ä".repeat(3))
+    );
+}
+
+#[test]
+fn test_feed() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+    let feed = Feed::new(Duration::from_secs(60), move || {
+        match counter.fetch_add(1, Ordering::SeqCst) {
+            1 => Err("CTFtime is down".into()),
+            n => Ok(format!("calendar {}", n)),
+        }
+    });
+    let start = Instant::now();
+
+    assert_eq!(*feed.get(start).unwrap(), "calendar 0");
+    assert_eq!(
+        *feed.get(start + Duration::from_secs(59)).unwrap(),
+        "calendar 0"
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+    // Failed refreshes keep the old calendar
+    assert_eq!(
+        *feed.get(start + Duration::from_secs(60)).unwrap(),
+        "calendar 0"
+    );
+    assert_eq!(
+        *feed.get(start + Duration::from_secs(90)).unwrap(),
+        "calendar 0"
+    );
+    assert_eq!(
+        *feed.get(start + Duration::from_secs(120)).unwrap(),
+        "calendar 2"
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    let feed = Feed::new(Duration::from_secs(60), || Err("CTFtime is down".into()));
+    assert_eq!(feed.get(start), None);
+}
@@ -0,0 +1,154 @@
+//! Run the bot as AWS Lambda function, e.g., triggered by an EventBridge schedule
+//!
+//! Requires the `lambda` feature. The binary is used as the `bootstrap` of a custom runtime.
+//! Each invocation runs the same announcement as the cron mode.
+//!
+//! * The configuration is read from the environment.
+//!   With `SSM_PARAMETER_PATH`, all parameters below the path are added to the environment first,
+//!   e.g., `/ctftimebot/webhook_url` becomes `WEBHOOK_URL`. Variables of the function take precedence.
+//! * With `STATE_BUCKET`, the state is stored in S3 under `STATE_KEY`.
+//!   It is downloaded to a temporary `STATE_FILE` before and uploaded again after each invocation.
+//!   Since the bot runs once per schedule, the invocations don't overlap.
+//!
+//! Cloudflare Workers and other WASI targets are not supported yet.
+//! All requests use the blocking client of `reqwest` and the state is a local file, neither is available there.
+//! A worker entry point requires async requests via `fetch` throughout the pipeline and a state store backed by Workers KV.
+
+use log::info;
+use rusoto_core::{Region, RusotoError};
+use rusoto_s3::{GetObjectError, GetObjectRequest, PutObjectRequest, S3Client, S3};
+use rusoto_ssm::{GetParametersByPathRequest, Ssm, SsmClient};
+use std::{env, fs, io, path::PathBuf};
+use tokio::io::AsyncReadExt;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The only writable directory of a Lambda function
+const STATE_FILE: &str = "/tmp/ctftimebot-state.json";
+
+/// Location of the state in S3
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct S3State {
+    pub bucket: String,
+    pub key: String,
+    /// Local copy of the state during the invocation
+    pub path: PathBuf,
+}
+
+/// Name of the environment variable for a parameter, the uppercase last part of the path
+pub fn env_name(parameter: &str) -> String {
+    parameter
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .replace('-', "_")
+        .to_uppercase()
+}
+
+/// Add the SSM parameters below `SSM_PARAMETER_PATH` to the environment
+async fn load_parameters(path: &str) -> Result<(), BoxError> {
+    let client = SsmClient::new(Region::default());
+    let mut next_token = None;
+    loop {
+        let output = client
+            .get_parameters_by_path(GetParametersByPathRequest {
+                path: path.to_string(),
+                recursive: Some(true),
+                with_decryption: Some(true),
+                next_token: next_token.take(),
+                ..Default::default()
+            })
+            .await?;
+        for parameter in output.parameters.unwrap_or_default() {
+            if let (Some(name), Some(value)) = (parameter.name, parameter.value) {
+                let name = env_name(&name);
+                if env::var_os(&name).is_none() {
+                    env::set_var(name, value);
+                }
+            }
+        }
+        next_token = output.next_token;
+        if next_token.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+/// Prepare the environment, this must happen before the configuration is loaded
+///
+/// Returns the location of the state in S3, if configured.
+pub async fn prepare() -> Result<Option<S3State>, BoxError> {
+    if let Ok(path) = env::var("SSM_PARAMETER_PATH") {
+        load_parameters(&path).await?;
+    }
+    let bucket = match env::var("STATE_BUCKET") {
+        Ok(bucket) => bucket,
+        Err(_) => return Ok(None),
+    };
+    env::set_var("STATE_FILE", STATE_FILE);
+    Ok(Some(S3State {
+        bucket,
+        key: env::var("STATE_KEY").unwrap_or_else(|_| "state.json".to_string()),
+        path: STATE_FILE.into(),
+    }))
+}
+
+impl S3State {
+    /// Download the state into the local file, a missing object results in an empty state
+    pub async fn download(&self) -> Result<(), BoxError> {
+        let client = S3Client::new(Region::default());
+        let request = GetObjectRequest {
+            bucket: self.bucket.clone(),
+            key: self.key.clone(),
+            ..Default::default()
+        };
+        match client.get_object(request).await {
+            Ok(output) => {
+                let mut content = Vec::new();
+                if let Some(body) = output.body {
+                    body.into_async_read().read_to_end(&mut content).await?;
+                }
+                fs::write(&self.path, content)?;
+            }
+            Err(RusotoError::Service(GetObjectError::NoSuchKey(_))) => {
+                info!(
+                    "No state in s3://{}/{}, starting empty",
+                    self.bucket, self.key
+                );
+                match fs::remove_file(&self.path) {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+                    _ => {}
+                }
+            }
+            Err(err) => return Err(err.into()),
+        }
+        Ok(())
+    }
+
+    /// Upload the local file, if the run created one
+    pub async fn upload(&self) -> Result<(), BoxError> {
+        let content = match fs::read(&self.path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        let client = S3Client::new(Region::default());
+        client
+            .put_object(PutObjectRequest {
+                bucket: self.bucket.clone(),
+                key: self.key.clone(),
+                body: Some(content.into()),
+                content_type: Some("application/json".to_string()),
+                ..Default::default()
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_env_name() {
+    assert_eq!(env_name("/ctftimebot/webhook_url"), "WEBHOOK_URL");
+    assert_eq!(env_name("/teams/ctf/bot-username"), "BOT_USERNAME");
+    assert_eq!(env_name("LOOKAHEAD"), "LOOKAHEAD");
+}
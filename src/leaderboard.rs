@@ -0,0 +1,271 @@
+//! Track the rank of the team in the CTFtime leaderboard of its country
+//!
+//! The national top 10 is fetched during every run and compared to the rank stored in the [`State`][crate::state::State].
+//! Changes are posted, with special messages when the team breaks into the top 10, top 5, top 3, or reaches the first place.
+//!
+//! Additionally, the top 10 teams, globally or of a country, can be posted once per week.
+
+use crate::{timed, trivia::ordinal, BASE_URL};
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Number of teams in the leaderboards
+const TOP: usize = 10;
+/// Ranks which are announced with a milestone message, from the highest
+const MILESTONES: [usize; 3] = [3, 5, 10];
+
+/// Entry of the national leaderboard as returned by `/api/v1/top-by-country/<country>/`
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct CountryEntry {
+    pub team_id: usize,
+    pub team_name: String,
+    /// Rank within the country
+    pub country_place: usize,
+    pub points: f64,
+}
+
+/// Entry of the global leaderboard as returned by `/api/v1/top/<year>/`
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TopEntry {
+    pub team_id: usize,
+    pub team_name: String,
+    pub points: f64,
+}
+
+impl From<CountryEntry> for TopEntry {
+    fn from(entry: CountryEntry) -> Self {
+        TopEntry {
+            team_id: entry.team_id,
+            team_name: entry.team_name,
+            points: entry.points,
+        }
+    }
+}
+
+/// Rank of the team as seen during the last run
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, PartialEq)]
+pub struct NationalRank {
+    /// Lowercase country code, e.g., `de`
+    pub country: String,
+    /// `None` if the team is not in the top 10
+    pub place: Option<usize>,
+}
+
+/// Top teams of the `country`, e.g., `de`
+pub fn fetch_country_top(
+    client: &Client,
+    country: &str,
+) -> Result<Vec<CountryEntry>, reqwest::Error> {
+    timed("Fetching the national leaderboard", || {
+        client
+            .get(&format!(
+                "{}/api/v1/top-by-country/{}/",
+                BASE_URL,
+                country.to_lowercase()
+            ))
+            .send()?
+            .error_for_status()?
+            .json()
+    })
+}
+
+/// Top teams of `year`, or of the current year
+pub fn fetch_top(client: &Client, year: Option<i32>) -> Result<Vec<TopEntry>, reqwest::Error> {
+    let url = match year {
+        Some(year) => format!("{}/api/v1/top/{}/", BASE_URL, year),
+        None => format!("{}/api/v1/top/", BASE_URL),
+    };
+    // The teams are keyed by the year, even if only one year is requested
+    let top: BTreeMap<String, Vec<TopEntry>> = timed("Fetching the leaderboard", || {
+        client.get(&url).send()?.error_for_status()?.json()
+    })?;
+    Ok(top
+        .into_iter()
+        .last()
+        .map(|(_, top)| top)
+        .unwrap_or_default())
+}
+
+/// Top 10 teams of `country` or of the world in `year`, sorted by their rank
+///
+/// The national leaderboards are only available for the current year, so `year` is ignored with a `country`.
+pub fn fetch_leaderboard(
+    client: &Client,
+    year: Option<i32>,
+    country: Option<&str>,
+) -> Result<Vec<TopEntry>, reqwest::Error> {
+    let mut top: Vec<TopEntry> = match country {
+        Some(country) => fetch_country_top(client, country)?
+            .into_iter()
+            .map(TopEntry::from)
+            .collect(),
+        None => fetch_top(client, year)?,
+    };
+    top.truncate(TOP);
+    Ok(top)
+}
+
+/// Markdown list of the `top` teams, with our team `team_id` in bold
+///
+/// `scope` describes the leaderboard in the heading, e.g., `2022` or `DE`.
+pub fn leaderboard_message(top: &[TopEntry], scope: &str, team_id: Option<usize>) -> String {
+    let mut text = format!("### 🏆 CTFtime top {} {}\n", TOP, scope);
+    for (place, entry) in top.iter().enumerate() {
+        let team = format!("[{}]({}/team/{})", entry.team_name, BASE_URL, entry.team_id);
+        let team = if Some(entry.team_id) == team_id {
+            format!("**{}**", team)
+        } else {
+            team
+        };
+        text += &format!("{}. {} with {:.2} points\n", place + 1, team, entry.points);
+    }
+    text
+}
+
+/// Whether the weekly leaderboard is due on `today`, since it was not posted after the last `weekday`
+///
+/// The leaderboard stays due after the weekday, e.g., if the bot was not running, until the next week.
+pub fn leaderboard_due(weekday: Weekday, today: NaiveDate, last: Option<NaiveDate>) -> bool {
+    let days_since =
+        (7 + today.weekday().num_days_from_monday() - weekday.num_days_from_monday()) % 7;
+    let date = today - Duration::days(i64::from(days_since));
+    last.map_or(true, |last| last < date)
+}
+
+/// National rank of the team `team_id`, `None` if it is not in the top 10
+pub fn national_place(top: &[CountryEntry], team_id: usize) -> Option<usize> {
+    top.iter()
+        .find(|entry| entry.team_id == team_id)
+        .map(|entry| entry.country_place)
+        .filter(|&place| place <= TOP)
+}
+
+/// Message about the change from rank `old` to `new`, `None` if the rank did not change
+pub fn rank_message(old: Option<usize>, new: Option<usize>, country: &str) -> Option<String> {
+    let country = country.to_uppercase();
+    if old == new {
+        return None;
+    }
+    let new = match new {
+        Some(new) => new,
+        None => {
+            return Some(format!(
+                "📉 We dropped out of the top {} in {} on CTFtime",
+                TOP, country
+            ))
+        }
+    };
+    // Outside of the top 10 counts as one below it
+    let old = old.unwrap_or(TOP + 1);
+    if new == 1 {
+        return Some(format!(
+            "🥇 We are the number 1 team in {} on CTFtime!",
+            country
+        ));
+    }
+    if new > old {
+        return Some(format!(
+            "📉 We dropped from {} to {} in {} on CTFtime",
+            ordinal(old),
+            ordinal(new),
+            country
+        ));
+    }
+    Some(
+        match MILESTONES
+            .iter()
+            .find(|&&milestone| new <= milestone && milestone < old)
+        {
+            Some(&milestone) => milestone_message(new, &country, milestone),
+            None => format!(
+                "📈 We moved up from {} to {} in {} on CTFtime",
+                ordinal(old),
+                ordinal(new),
+                country
+            ),
+        },
+    )
+}
+
+fn milestone_message(place: usize, country: &str, milestone: usize) -> String {
+    format!(
+        "🎉 We broke into the national top {}! We are now {} in {} on CTFtime",
+        milestone,
+        ordinal(place),
+        country
+    )
+}
+
+#[test]
+fn test_leaderboard() {
+    let top: BTreeMap<String, Vec<TopEntry>> = serde_json::from_str(
+        r#"{"2022": [
+            {"team_name": "A", "points": 1500.123, "team_id": 1},
+            {"team_name": "Us", "points": 900.5, "team_id": 42}
+        ]}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        leaderboard_message(&top["2022"], "2022", Some(42)),
+        "### 🏆 CTFtime top 10 2022
+1. [A](https://ctftime.org/team/1) with 1500.12 points
+2. **[Us](https://ctftime.org/team/42)** with 900.50 points
+"
+    );
+
+    let day = |d| NaiveDate::from_ymd(2022, 5, d);
+    // 2022-05-02 is a Monday
+    assert!(leaderboard_due(Weekday::Mon, day(2), None));
+    assert!(leaderboard_due(Weekday::Mon, day(2), Some(day(1))));
+    assert!(!leaderboard_due(Weekday::Mon, day(2), Some(day(2))));
+    assert!(!leaderboard_due(Weekday::Mon, day(8), Some(day(2))));
+    assert!(leaderboard_due(Weekday::Mon, day(9), Some(day(2))));
+    assert!(leaderboard_due(Weekday::Fri, day(9), Some(day(2))));
+    assert!(!leaderboard_due(Weekday::Fri, day(9), Some(day(6))));
+}
+
+#[test]
+fn test_national_rank() {
+    let top: Vec<CountryEntry> = serde_json::from_str(
+        r#"[
+            {"team_country": "DE", "country_place": 1, "team_id": 1, "place": 3, "team_name": "A", "events": 30, "points": 900.5},
+            {"team_country": "DE", "country_place": 2, "team_id": 42, "place": 25, "team_name": "Us", "events": 20, "points": 300.0}
+        ]"#,
+    )
+    .unwrap();
+    assert_eq!(national_place(&top, 42), Some(2));
+    assert_eq!(national_place(&top, 7), None);
+
+    assert_eq!(rank_message(Some(4), Some(4), "de"), None);
+    assert_eq!(rank_message(None, None, "de"), None);
+    assert_eq!(
+        rank_message(Some(2), Some(1), "de").unwrap(),
+        "🥇 We are the number 1 team in DE on CTFtime!"
+    );
+    assert_eq!(
+        rank_message(None, Some(8), "de").unwrap(),
+        "🎉 We broke into the national top 10! We are now 8th in DE on CTFtime"
+    );
+    assert_eq!(
+        rank_message(None, Some(2), "de").unwrap(),
+        "🎉 We broke into the national top 3! We are now 2nd in DE on CTFtime"
+    );
+    assert_eq!(
+        rank_message(Some(7), Some(4), "de").unwrap(),
+        "🎉 We broke into the national top 5! We are now 4th in DE on CTFtime"
+    );
+    assert_eq!(
+        rank_message(Some(9), Some(8), "de").unwrap(),
+        "📈 We moved up from 9th to 8th in DE on CTFtime"
+    );
+    assert_eq!(
+        rank_message(Some(3), Some(6), "de").unwrap(),
+        "📉 We dropped from 3rd to 6th in DE on CTFtime"
+    );
+    assert_eq!(
+        rank_message(Some(10), None, "de").unwrap(),
+        "📉 We dropped out of the top 10 in DE on CTFtime"
+    );
+}
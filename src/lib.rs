@@ -1,57 +1,159 @@
+pub mod access;
+pub mod actions;
+pub mod admin;
+pub mod alerts;
+pub mod api;
+pub mod apprise;
+pub mod board;
+pub mod broadcast;
+pub mod caldav;
+pub mod calendar;
+pub mod checklist;
+pub mod compact;
+pub mod config;
+pub mod ctftime_api;
+pub mod digest;
+pub mod discord_hook_api;
+pub mod duration;
+pub mod email;
+pub mod escalation;
+pub mod event_ref;
+pub mod export;
+pub mod filters;
+pub mod github_issues;
+pub mod gitlab_issues;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+pub mod holidays;
+pub mod html;
+pub mod i18n;
+pub mod ical;
+#[cfg(feature = "lambda")]
+pub mod lambda;
+pub mod leaderboard;
+pub mod mastodon;
+pub mod matrix;
 pub mod mattermost_hook_api;
+pub mod metrics;
+pub mod news;
+pub mod notifier;
+pub mod notion;
+pub mod ops;
+pub mod plain_text;
+pub mod preferences;
+pub mod push;
+pub mod qualifiers;
+pub mod ratelimit;
+pub mod recap;
+pub mod reformat;
+pub mod render;
+pub mod reporting;
+pub mod rocketchat_api;
+pub mod rsvp;
+pub mod scheduler;
+pub mod server;
+pub mod signal;
+pub mod signature;
+pub mod slack_api;
+pub mod spreadsheet;
+pub mod state;
+pub mod teams;
+pub mod teams_api;
+pub mod trivia;
+pub mod twilio;
+pub mod twitter;
+pub mod vote;
+pub mod warmup;
+pub mod webhook;
+pub mod xmpp;
+pub mod zulip;
 
-use crate::mattermost_hook_api::Attachment;
-use chrono::{DateTime, Duration, FixedOffset, Local, Offset, Utc};
+pub use crate::config::Config;
+use crate::{
+    digest::{organizers_text, Footnotes, TeamLinks},
+    discord_hook_api::Embed,
+    holidays::{find_blackout, holiday_note},
+    mattermost_hook_api::{Attachment, Props},
+    qualifiers::phase_label,
+};
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, Offset, SecondsFormat, TimeZone,
+    Utc,
+};
+use chrono_tz::Tz;
 use lazy_static::lazy_static;
+use log::{debug, info, warn};
 use regex::Regex;
 use serde::Deserialize;
-use serde_with::{serde_as, DefaultOnError, NoneAsEmptyString};
+use serde_json::json;
+use serde_with::{serde_as, DefaultOnError, DeserializeFromStr, NoneAsEmptyString};
+use sha2::{Digest, Sha256};
+use std::{fmt, str::FromStr};
 
-const BASE_URL: &str = "https://ctftime.org";
+pub(crate) const BASE_URL: &str = "https://ctftime.org";
+/// Prefix of the attachment footer which carries the CTFtime event id
+const EVENT_ID_FOOTER_PREFIX: &str = "CTFtime event #";
 
-#[derive(Deserialize, Debug, Eq, PartialEq)]
-pub struct Config {
-    pub webhook_url: String,
-    pub days_into_future: i64,
-    pub color_jeopardy: String,
-    pub color_attack_defense: String,
-    pub bot_icon: Option<String>,
-    pub always_show_ctfs: Vec<usize>,
-    pub mattermost_channel: Option<String>,
+lazy_static! {
+    pub static ref CONFIG: Config = Config::load().expect("Couldn't read config");
+    pub static ref RE_RATING_WEIGHT: Regex =
+        Regex::new(r"Rating weight:\s*(?P<weight>\d+)").unwrap();
+    /// Identifies all posts created during the same execution of the bot
+    pub static ref RUN_ID: String = format!("{}-{}", Utc::now().timestamp(), std::process::id());
 }
 
-#[test]
-fn test_load_config() {
-    dotenv::dotenv().expect("Failed to read .env file");
-    let config = envy::from_env::<Config>().expect("Couldn't read config");
-    let expected = Config {
-        webhook_url: "".to_string(),
-        days_into_future: 21,
-        color_jeopardy: "#0099e1".to_string(),
-        color_attack_defense: "#da5422".to_string(),
-        bot_icon: Some("https://ctftime.org/static/images/ctftime-logo-avatar.png".to_string()),
-        always_show_ctfs: vec![6, 7, 24, 117, 412],
-        mattermost_channel: None,
-    };
-    assert_eq!(config, expected)
+/// Create the HTTP client shared by all requests of a run
+///
+/// Sharing the client allows reusing connections.
+/// Responses are requested gzip compressed.
+pub fn http_client() -> reqwest::blocking::Client {
+    reqwest::blocking::Client::builder()
+        .gzip(true)
+        .user_agent(concat!("ctftimebot/", env!("CARGO_PKG_VERSION")))
+        .pool_idle_timeout(std::time::Duration::from_secs(90))
+        .build()
+        .expect("Couldn't create the HTTP client")
 }
 
-lazy_static! {
-    pub static ref CONFIG: Config = {
-        dotenv::dotenv().expect("Failed to read .env file");
-        envy::from_env::<Config>().expect("Couldn't read config")
-    };
-    pub static ref RE_RATING_WEIGHT: Regex =
-        Regex::new(r"Rating weight:\s*(?P<weight>\d+)").unwrap();
+/// Run `f` and log how long it took, e.g., to measure the latency of a request
+pub fn timed<T>(description: &str, f: impl FnOnce() -> T) -> T {
+    let start = std::time::Instant::now();
+    let res = f();
+    debug!("{} took {} ms", description, start.elapsed().as_millis());
+    res
+}
+
+/// Key in [`Props::extras`] under which the bot metadata is stored
+pub const PROPS_METADATA_KEY: &str = "ctftimebot";
+
+/// Machine-readable metadata attached to every post of the bot
+///
+/// The metadata is stored as a JSON object under the [`PROPS_METADATA_KEY`] key of the post props.
+/// Other integrations can use it to find the posts of the bot and the events contained in them.
+pub fn post_metadata(event_ids: &[usize]) -> Props {
+    let mut props = Props::default();
+    props.extras.insert(
+        PROPS_METADATA_KEY.to_string(),
+        json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "run_id": *RUN_ID,
+            "event_ids": event_ids,
+            "filter_profile": CONFIG.filter_profile,
+        }),
+    );
+    props
 }
 
 #[serde_as]
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct CtfEvent {
     /// Event title, this is specific to one event, e.g. "FAUST CTF 2017"
     title: String,
     /// Link to CTF time page of event
     ctftime_url: String,
+    /// Description provided by the organizers
+    #[serde(default)]
+    description: String,
     /// Event id
     id: usize,
     /// Start time
@@ -92,77 +194,120 @@ pub struct CtfEvent {
     participants: usize,
 }
 
+/// Format the duration in the configured locale, see [`i18n::format_duration`]
 fn format_duration(d: &Duration) -> String {
-    let mut d = *d;
-    let mut tmp = Vec::with_capacity(4);
-    if d.num_hours() > 48 {
-        tmp.push(format!("{} days", d.num_days()));
-        d = d + Duration::days(-d.num_days());
-    }
-    if d.num_hours() > 0 {
-        tmp.push(format!("{} hours", d.num_hours()));
-        d = d + Duration::hours(-d.num_hours());
+    i18n::format_duration(d, CONFIG.locale)
+}
+
+/// Format `date` in `timezone`, followed by the time in UTC, e.g., `Sat 2024-03-16 10:00 CET (09:00 UTC)`
+///
+/// Without a timezone, the local timezone of the bot is used, which is only known as a numeric offset.
+fn format_date(date: &DateTime<FixedOffset>, timezone: Option<Tz>) -> String {
+    let local = match timezone {
+        Some(Tz::UTC) => return date.with_timezone(&Utc).format("%a %F %R UTC").to_string(),
+        Some(tz) => date.with_timezone(&tz).format("%a %F %R %Z").to_string(),
+        None => date
+            .with_timezone(&Local)
+            .format("%a %F %R %:z")
+            .to_string(),
+    };
+    format!("{} ({} UTC)", local, date.with_timezone(&Utc).format("%R"))
+}
+
+/// The calendar date of `date` in `timezone`, or the local timezone if unset
+pub(crate) fn local_date(date: &DateTime<FixedOffset>, timezone: Option<Tz>) -> NaiveDate {
+    match timezone {
+        Some(tz) => date.with_timezone(&tz).date().naive_local(),
+        None => date.with_timezone(&Local).date().naive_local(),
     }
-    if d.num_minutes() > 0 {
-        tmp.push(format!("{} minutes", d.num_minutes()));
-        d = d + Duration::minutes(-d.num_minutes());
+}
+
+/// Whether the UTC offset of `timezone` differs between `start` and `finish`, e.g., due to DST
+///
+/// The duration of an event is the elapsed time, which then differs from the difference of the local start and end times.
+fn clocks_change(
+    start: &DateTime<FixedOffset>,
+    finish: &DateTime<FixedOffset>,
+    timezone: Option<Tz>,
+) -> bool {
+    match timezone {
+        Some(tz) => {
+            start.with_timezone(&tz).offset().fix() != finish.with_timezone(&tz).offset().fix()
+        }
+        None => start.with_timezone(&Local).offset() != finish.with_timezone(&Local).offset(),
     }
-    if d.num_seconds() > 0 {
-        tmp.push(format!("{} seconds", d.num_seconds()));
+}
+
+/// Value of a field, with a fallback for empty values or `None` if empty fields are hidden
+fn field_value(value: &str, hide_empty: bool) -> Option<&str> {
+    match (value.trim().is_empty(), hide_empty) {
+        (false, _) => Some(value),
+        (true, false) => Some("unknown"),
+        (true, true) => None,
     }
-    tmp.join(" ")
 }
 
+/// Note on events during which the clocks change
+const CLOCKS_CHANGE_NOTE: &str = "Note: clocks change during this event";
+
 impl CtfEvent {
     pub fn to_slack(&self) -> Attachment {
-        let duration = format_duration(&self.finish_date.signed_duration_since(self.start_date));
-        let title = format!("{} — {}", self.title, self.format.as_str());
-        let organizers = ((&self.organizers)
-            .iter()
-            .map(CtfTeam::to_markdown_link)
-            .collect::<Vec<_>>())
-        .join(", ");
-        let url = self.url.clone().unwrap_or_else(|| self.ctftime_url.clone());
+        use std::fmt::Write;
 
-        let mut text = format!(
-            r#"**Date:** {} for {}
-{}**Organizers:** {}
-[{url:}]({url:})
+        let duration = format_duration(&self.finish_date.signed_duration_since(self.start_date));
+        let title = format!("{} — {}", self.display_title(), self.format);
+        let organizers = organizers_text(
+            self.known_organizers(),
+            TeamLinks::Inline,
+            &mut Footnotes::default(),
+        );
+        let url = self.url.as_deref().unwrap_or(&self.ctftime_url);
 
-"#,
-            self.start_date.with_timezone(&Local).format("%A, %F %R"),
+        // Writing into a `String` cannot fail
+        let mut text = String::with_capacity(256 + organizers.len() + 2 * url.len());
+        let _ = writeln!(
+            text,
+            "**Date:** {} for {}",
+            format_date(&self.start_date, CONFIG.timezone),
             duration,
-            if let Some(rating) = self.rating_weight() {
-                format!("**Rating**: {}\n", rating)
-            } else {
-                "".to_string()
-            },
-            organizers,
-            url = url
         );
+        if clocks_change(&self.start_date, &self.finish_date, CONFIG.timezone) {
+            let _ = writeln!(text, "_{}_", CLOCKS_CHANGE_NOTE);
+        }
+        if let Some(blackout) = find_blackout(self, &CONFIG.blackouts, CONFIG.timezone) {
+            let _ = writeln!(text, "**{}**", blackout.tag());
+        } else if let Some(note) = holiday_note(self, &CONFIG.holidays, CONFIG.timezone) {
+            let _ = writeln!(text, "{}", note);
+        }
+        if let Some(rating) = self.rating_weight() {
+            let _ = writeln!(text, "**Rating**: {}", rating);
+        }
+        // Only shown if the post is kept current, otherwise the number is outdated quickly
+        if CONFIG.update_in_place {
+            let _ = writeln!(text, "**Teams:** {}", self.participants);
+        }
+        if let Some(organizers) = field_value(&organizers, CONFIG.hide_empty_fields) {
+            let _ = writeln!(text, "**Organizers:** {}", organizers);
+        }
+        let _ = write!(text, "[{url}]({url})\n\n", url = url);
 
         if self.onsite {
-            if let Some(ref location) = self.location {
-                text += &format!("**Location:** {}\n", location);
+            let location = self.location.as_deref().unwrap_or_default();
+            if let Some(location) = field_value(location, CONFIG.hide_empty_fields) {
+                let _ = writeln!(text, "**Location:** {}", location);
             }
         }
         if self.restrictions == CtfRestrictions::Prequalified {
             text += "Prequalified teams only\n"
         }
-
-        let fallback = format!(
-            "{}\nDate: {} for {}\n{}",
-            title,
-            self.start_date.with_timezone(&Local).naive_local(),
-            duration,
-            url
-        );
+        text.truncate(text.trim_end().len());
 
         let mut attachment = Attachment {
-            fallback,
+            fallback: plain_text::event_lines(self).join("\n"),
             title: Some(title),
-            title_link: Some(self.ctftime_url.clone()),
-            text: Some(text.trim().to_string()),
+            title_link: self.ctftime_url.parse().ok(),
+            text: Some(text),
+            // Cloning the colors is cheap, since they are reference counted
             color: Some(if self.format == CtfFormat::AttackDefense {
                 CONFIG.color_attack_defense.clone()
             } else {
@@ -171,48 +316,472 @@ impl CtfEvent {
             ..Default::default()
         };
         if let Some(ref url) = self.logo_url {
-            attachment.thumb_url = Some(url.clone());
+            attachment.thumb_url = url.parse().ok();
         }
+        attachment.footer = Some(format!("{}{}", EVENT_ID_FOOTER_PREFIX, self.id));
         attachment
     }
 
+    /// Render the event as a Discord embed with the same content as [`to_slack`][CtfEvent::to_slack]
+    pub fn to_discord(&self) -> Embed {
+        Embed::from(&self.to_slack())
+    }
+
+    /// Render the event as Slack blocks with the same content as [`to_slack`][CtfEvent::to_slack]
+    pub fn to_slack_blocks(&self) -> Vec<slack_api::Block> {
+        slack_api::attachment_blocks(&self.to_slack())
+    }
+
+    /// Render the event as a Teams card container with the same content as [`to_slack`][CtfEvent::to_slack]
+    pub fn to_teams(&self) -> teams_api::Element {
+        teams_api::attachment_container(&self.to_slack())
+    }
+
+    /// Render the event as plain text without any markup
+    ///
+    /// The organizers are listed without links, see [`to_plain_text_with`][CtfEvent::to_plain_text_with].
+    pub fn to_plain_text(&self) -> String {
+        self.to_plain_text_with(TeamLinks::Omit, &mut Footnotes::default())
+    }
+
+    /// Render the event as plain text, linking the organizers as set by `links`
+    pub fn to_plain_text_with(&self, links: TeamLinks, footnotes: &mut Footnotes) -> String {
+        let duration = format_duration(&self.finish_date.signed_duration_since(self.start_date));
+        let mut text = format!(
+            "{} — {}\nDate: {} for {}\n",
+            self.display_title(),
+            self.format,
+            format_date(&self.start_date, CONFIG.timezone),
+            duration,
+        );
+        if clocks_change(&self.start_date, &self.finish_date, CONFIG.timezone) {
+            text += CLOCKS_CHANGE_NOTE;
+            text += "\n";
+        }
+        if let Some(blackout) = find_blackout(self, &CONFIG.blackouts, CONFIG.timezone) {
+            text += &blackout.tag();
+            text += "\n";
+        } else if let Some(note) = holiday_note(self, &CONFIG.holidays, CONFIG.timezone) {
+            text += &note;
+            text += "\n";
+        }
+        if let Some(rating) = self.rating_weight() {
+            text += &format!("Rating: {}\n", rating);
+        }
+        let organizers = organizers_text(self.known_organizers(), links, footnotes);
+        if let Some(organizers) = field_value(&organizers, CONFIG.hide_empty_fields) {
+            text += &format!("Organizers: {}\n", organizers);
+        }
+        if self.onsite {
+            let location = self.location.as_deref().unwrap_or_default();
+            if let Some(location) = field_value(location, CONFIG.hide_empty_fields) {
+                text += &format!("Location: {}\n", location);
+            }
+        }
+        if self.restrictions == CtfRestrictions::Prequalified {
+            text += "Prequalified teams only\n"
+        }
+        text += self.url.as_deref().unwrap_or(&self.ctftime_url);
+        text
+    }
+
+    /// Organizers of the event, without the placeholder teams CTFtime lists for some events
+    pub fn known_organizers(&self) -> impl Iterator<Item = &CtfTeam> {
+        self.organizers
+            .iter()
+            .filter(|organizer| !organizer.is_placeholder())
+    }
+
+    /// Event id on CTFtime
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Event title, e.g. "FAUST CTF 2017"
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Title with the configured phase, if the title doesn't mention it, e.g., `FAUST CTF 2024 (Teaser)`
+    pub fn display_title(&self) -> String {
+        match phase_label(self, &CONFIG.phases) {
+            Some(phase) => format!("{} ({})", self.title, phase),
+            None => self.title.clone(),
+        }
+    }
+
+    /// Link to the CTFtime page of the event
+    pub fn ctftime_url(&self) -> &str {
+        &self.ctftime_url
+    }
+
+    /// The weight of the event
+    pub fn weight(&self) -> f32 {
+        self.weight
+    }
+
+    /// Number of teams who want to participate
+    pub fn participants(&self) -> usize {
+        self.participants
+    }
+
+    /// Start time
+    pub fn start_date(&self) -> DateTime<FixedOffset> {
+        self.start_date
+    }
+
+    /// End time
+    pub fn finish_date(&self) -> DateTime<FixedOffset> {
+        self.finish_date
+    }
+
+    /// Link to the event page
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+
+    /// Link to the live feed of the event
+    pub fn live_feed(&self) -> Option<&str> {
+        self.live_feed.as_deref()
+    }
+
+    /// Link to the logo of the event
+    pub fn logo_url(&self) -> Option<&str> {
+        self.logo_url.as_deref()
+    }
+
+    /// ID of the general event, which is the same for all years
+    pub fn ctf_id(&self) -> usize {
+        self.ctf_id
+    }
+
+    /// Teams organizing the event
+    pub fn organizers(&self) -> &[CtfTeam] {
+        &self.organizers
+    }
+
+    /// Description of the event, may contain Markdown
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Location of onsite events
+    pub fn location(&self) -> Option<&str> {
+        self.location.as_deref()
+    }
+
+    /// Whether the event takes place at a physical location
+    pub fn onsite(&self) -> bool {
+        self.onsite
+    }
+
+    /// Format style of the CTF
+    pub fn format(&self) -> CtfFormat {
+        self.format
+    }
+
+    /// Determines if the public is allowed to vote for the final weight
+    pub fn public_votable(&self) -> bool {
+        self.public_votable
+    }
+
     /// Determines if this event should be printed
     ///
-    /// Reasons to exclude it are it is too far in the future or it is not available online.
+    /// Reasons to exclude it are it is too far in the future, it is not available online, or its weight is too low.
     pub fn should_print_event(&self) -> bool {
-        if CONFIG.always_show_ctfs.contains(&self.ctf_id) {
+        self.matches_filters(&CONFIG, Utc::now())
+    }
+
+    /// Determines if this event should be printed with the filters of `config` at time `now`
+    ///
+    /// The time until the start is compared exactly with [`Config::lookahead`], not in whole days.
+    /// With `21d`, an event starting in 21 days and one hour is not shown yet.
+    pub fn matches_filters(&self, config: &Config, now: DateTime<Utc>) -> bool {
+        if config.always_show_ctfs.contains(&self.ctf_id) {
             return true;
         }
+        if let Some(min_weight) = config.min_weight {
+            if self.rating_weight().unwrap_or(0) < min_weight {
+                return false;
+            }
+        }
 
         if self.restrictions != CtfRestrictions::Open
             && self.restrictions != CtfRestrictions::Academic
         {
             return false;
         }
-        let days_into_future = (self
+        let until_start = self
             .start_date
-            .signed_duration_since(Utc::now().with_timezone(&Utc.fix())))
-        .num_days();
-        !self.onsite && days_into_future <= CONFIG.days_into_future
+            .signed_duration_since(now.with_timezone(&Utc.fix()));
+        !self.onsite && until_start <= config.lookahead
     }
 
     pub fn rating_weight(&self) -> Option<u32> {
         Some(self.weight.floor() as u32)
     }
+
+    /// Hex encoded SHA-256 of the fields shown in the announcements, changes whenever the event needs to be announced again
+    ///
+    /// Only times, title, link, format, restrictions, and location are covered, such that the weight or the number of participants don't change it.
+    /// The value is stored in the state, so it must stay the same between versions of the bot.
+    pub fn fingerprint(&self) -> String {
+        let date = |date: DateTime<FixedOffset>| {
+            date.with_timezone(&Utc)
+                .to_rfc3339_opts(SecondsFormat::Secs, true)
+        };
+        let fields = json!([
+            self.id,
+            self.title,
+            date(self.start_date),
+            date(self.finish_date),
+            self.url,
+            self.format.to_string(),
+            self.restrictions.to_string(),
+            self.location,
+            self.onsite,
+        ]);
+        Sha256::digest(fields.to_string().as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+/// Log the data quality problems of the events, which lead to missing information in the posts
+pub fn log_data_quality(events: &[CtfEvent]) {
+    for event in events {
+        if event.known_organizers().next().is_none() {
+            info!("Event {} has no known organizers", event.id);
+        }
+        if event.onsite && event.location.is_none() {
+            info!("Onsite event {} has no location", event.id);
+        }
+    }
+}
+
+/// Fields of the CTFtime API which the bot doesn't use, they don't count as unknown fields
+const UNUSED_API_FIELDS: &[&str] = &["duration", "format_id", "is_votable_now"];
+
+/// Parse the events returned by the CTFtime API
+///
+/// Fields which are neither used nor listed in [`UNUSED_API_FIELDS`] are logged, such that new fields of the API get noticed.
+/// With `strict`, they are an error instead, which is meant for tests and staging setups, not for production.
+pub fn parse_events(data: &str, strict: bool) -> serde_json::Result<Vec<CtfEvent>> {
+    parse_api(data, strict)
+}
+
+/// Parse a single event as returned by `/api/v1/events/<id>/`, see [`parse_events`]
+pub fn parse_event(data: &str, strict: bool) -> serde_json::Result<CtfEvent> {
+    parse_api(data, strict)
+}
+
+fn parse_api<T: serde::de::DeserializeOwned>(data: &str, strict: bool) -> serde_json::Result<T> {
+    let mut unknown = std::collections::BTreeSet::new();
+    let mut deserializer = serde_json::Deserializer::from_str(data);
+    let parsed: T = serde_ignored::deserialize(&mut deserializer, |path| {
+        // Paths like `3.organizers.0.country`, without the indices and `?` of options
+        let path = path
+            .to_string()
+            .split('.')
+            .filter(|segment| *segment != "?" && segment.parse::<usize>().is_err())
+            .collect::<Vec<_>>()
+            .join(".");
+        if !UNUSED_API_FIELDS.contains(&path.as_str()) {
+            unknown.insert(path);
+        }
+    })?;
+    deserializer.end()?;
+
+    if !unknown.is_empty() {
+        let unknown = unknown.into_iter().collect::<Vec<_>>().join(", ");
+        if strict {
+            return Err(serde::de::Error::custom(format!(
+                "unknown fields in the CTFtime API: {}",
+                unknown
+            )));
+        }
+        info!("Ignoring unknown fields in the CTFtime API: {}", unknown);
+    }
+    Ok(parsed)
+}
+
+/// Windows shorter than this are not split any further, see [`fetch_windowed`]
+const MIN_WINDOW_SECONDS: i64 = 3600;
+
+/// Fetch all events between `start` and `end`, even if the API returns at most `limit` events per request
+///
+/// `fetch` requests the events of a window of Unix timestamps.
+/// If it returns `limit` events, the window is probably truncated and gets split into two halves, which are fetched separately.
+/// Events are deduplicated by their id, since events overlapping both halves can be returned twice.
+pub fn fetch_windowed<E>(
+    start: i64,
+    end: i64,
+    limit: usize,
+    fetch: &mut dyn FnMut(i64, i64) -> Result<Vec<CtfEvent>, E>,
+) -> Result<Vec<CtfEvent>, E> {
+    let events = fetch(start, end)?;
+    if events.len() < limit {
+        return Ok(events);
+    }
+    if end - start < 2 * MIN_WINDOW_SECONDS {
+        warn!(
+            "CTFtime returned {} events between {} and {}, some events might be missing, increase API_LIMIT",
+            events.len(),
+            start,
+            end
+        );
+        return Ok(events);
+    }
+    warn!(
+        "CTFtime returned {} events between {} and {}, the result is probably truncated, fetching smaller windows",
+        events.len(),
+        start,
+        end
+    );
+    let middle = start + (end - start) / 2;
+    let mut events = fetch_windowed(start, middle, limit, fetch)?;
+    merge_events(&mut events, fetch_windowed(middle, end, limit, fetch)?);
+    Ok(events)
+}
+
+/// Add the events of `more` which are not yet part of `events`
+fn merge_events(events: &mut Vec<CtfEvent>, more: Vec<CtfEvent>) {
+    for event in more {
+        if !events.iter().any(|known| known.id == event.id) {
+            events.push(event);
+        }
+    }
+}
+
+/// Lookaheads longer than this many days are fetched in monthly chunks, see [`monthly_windows`]
+///
+/// This is the lookahead used by the bot, unless [`Config::lookahead`] is longer.
+pub const MAX_SINGLE_WINDOW_DAYS: i64 = 100;
+
+/// Split the time range into windows of Unix timestamps ending at the start of each month
+///
+/// Short ranges are kept as a single window.
+pub fn monthly_windows(start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<(i64, i64)> {
+    if end - start <= Duration::days(MAX_SINGLE_WINDOW_DAYS) {
+        return vec![(start.timestamp(), end.timestamp())];
+    }
+    let mut windows = Vec::new();
+    let mut window_start = start;
+    while window_start < end {
+        let (year, month) = match window_start.month() {
+            12 => (window_start.year() + 1, 1),
+            month => (window_start.year(), month + 1),
+        };
+        let window_end = Utc.ymd(year, month, 1).and_hms(0, 0, 0).min(end);
+        windows.push((window_start.timestamp(), window_end.timestamp()));
+        window_start = window_end;
+    }
+    windows
+}
+
+/// Fetch all events between `start` and `end`, one month after the other for long time ranges
+///
+/// See [`fetch_windowed`] for the arguments.
+pub fn fetch_monthly<E>(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    limit: usize,
+    fetch: &mut dyn FnMut(i64, i64) -> Result<Vec<CtfEvent>, E>,
+) -> Result<Vec<CtfEvent>, E> {
+    let mut events = Vec::new();
+    for (start, end) in monthly_windows(start, end) {
+        merge_events(&mut events, fetch_windowed(start, end, limit, fetch)?);
+    }
+    Ok(events)
+}
+
+/// Sort events by start date and use the event id as tie breaker
+///
+/// This gives a deterministic order of the attachments, even if CTFtime returns the events in a different order.
+pub fn sort_events(events: &mut [CtfEvent]) {
+    events.sort_by_key(|event| (event.start_date, event.id));
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+/// Extract the CTFtime event id from an [`Attachment`] created by [`CtfEvent::to_slack`]
+///
+/// The id is stored in the footer of the attachment.
+pub fn event_id_from_attachment(attachment: &Attachment) -> Option<usize> {
+    event_id_from_footer(attachment.footer.as_ref()?)
+}
+
+/// Extract the CTFtime event id from the footer of an attachment, see [`event_id_from_attachment`]
+pub fn event_id_from_footer(footer: &str) -> Option<usize> {
+    footer.strip_prefix(EVENT_ID_FOOTER_PREFIX)?.parse().ok()
+}
+
+/// Who may participate in a CTF
+#[derive(Clone, Copy, Debug, Eq, PartialEq, DeserializeFromStr)]
 pub enum CtfRestrictions {
     Open,
     Prequalified,
     Academic,
     Invited,
-    #[serde(rename = "High-school")]
     HighSchool,
 }
 
+/// Error while parsing [`CtfRestrictions`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidRestrictions(String);
+
+impl fmt::Display for InvalidRestrictions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid restrictions `{}`, expected one of `open`, `prequalified`, `academic`, `invited`, or `high-school`",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidRestrictions {}
+
+/// Lowercase `s` and remove separators, such that `Attack-Defense` and `attack defense` are the same
+fn normalize_name(s: &str) -> String {
+    s.chars()
+        .filter(|c| !matches!(c, '-' | '_' | ' '))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl FromStr for CtfRestrictions {
+    type Err = InvalidRestrictions;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*normalize_name(s) {
+            "open" => Ok(CtfRestrictions::Open),
+            "prequalified" | "prequal" => Ok(CtfRestrictions::Prequalified),
+            "academic" => Ok(CtfRestrictions::Academic),
+            "invited" | "invite" | "invitational" => Ok(CtfRestrictions::Invited),
+            "highschool" => Ok(CtfRestrictions::HighSchool),
+            _ => Err(InvalidRestrictions(s.to_string())),
+        }
+    }
+}
+
+/// Uses the spelling of the CTFtime API, e.g., `High-school`
+impl fmt::Display for CtfRestrictions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CtfRestrictions::Open => "Open",
+            CtfRestrictions::Prequalified => "Prequalified",
+            CtfRestrictions::Academic => "Academic",
+            CtfRestrictions::Invited => "Invited",
+            CtfRestrictions::HighSchool => "High-school",
+        })
+    }
+}
+
 /// What type of CTF, e.g. `AttackDefense`
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+///
+/// Parsing accepts the spellings of the CTFtime API, e.g., `Hack quest`, and common names like `ad`.
+/// The API uses an empty string for [`CtfFormat::Unknown`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, DeserializeFromStr)]
 pub enum CtfFormat {
     Jeopardy,
     AttackDefense,
@@ -220,48 +789,44 @@ pub enum CtfFormat {
     Unknown,
 }
 
-impl<'de> serde::de::Deserialize<'de> for CtfFormat {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        use serde::de::*;
-        struct CtfFormatVisitor;
+/// Error while parsing a [`CtfFormat`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidFormat(String);
 
-        impl<'de> serde::de::Visitor<'de> for CtfFormatVisitor {
-            type Value = CtfFormat;
+impl fmt::Display for InvalidFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid format `{}`, expected one of `jeopardy`, `attack-defense`, or `hack-quest`",
+            self.0
+        )
+    }
+}
 
-            fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                formatter.write_str("one of `Jeopardy`, `Attack-Defense`, `Hack quest`, ``")
-            }
+impl std::error::Error for InvalidFormat {}
 
-            fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
-            where
-                E: serde::de::Error,
-            {
-                use crate::CtfFormat::*;
-                match value {
-                    "Jeopardy" => Ok(Jeopardy),
-                    "Attack-Defense" => Ok(AttackDefense),
-                    "Hack quest" => Ok(HackQuest),
-                    "" => Ok(Unknown),
-                    _ => Err(Error::invalid_value(Unexpected::Str(value), &self)),
-                }
-            }
-        }
+impl FromStr for CtfFormat {
+    type Err = InvalidFormat;
 
-        deserializer.deserialize_str(CtfFormatVisitor)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*normalize_name(s) {
+            "jeopardy" => Ok(CtfFormat::Jeopardy),
+            "attackdefense" | "attackdefence" | "ad" => Ok(CtfFormat::AttackDefense),
+            "hackquest" => Ok(CtfFormat::HackQuest),
+            "" | "unknown" => Ok(CtfFormat::Unknown),
+            _ => Err(InvalidFormat(s.to_string())),
+        }
     }
 }
 
-impl CtfFormat {
-    fn as_str(&self) -> &str {
-        match *self {
+impl fmt::Display for CtfFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
             CtfFormat::Jeopardy => "Jeopardy",
             CtfFormat::AttackDefense => "Attack-Defense",
             CtfFormat::HackQuest => "Hack-Quest",
             CtfFormat::Unknown => "Unknown",
-        }
+        })
     }
 }
 
@@ -273,11 +838,72 @@ pub struct CtfTeam {
 }
 
 impl CtfTeam {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Teams without id or name, which CTFtime lists if the organizers are unknown
+    pub fn is_placeholder(&self) -> bool {
+        self.id == 0 || self.name.trim().is_empty()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Page of the team on CTFtime
+    pub fn url(&self) -> String {
+        format!("{}/team/{}", BASE_URL, self.id)
+    }
+
     pub fn to_markdown_link(&self) -> String {
-        format!("[{}]({}/team/{})", self.name, BASE_URL, self.id)
+        format!("[{}]({})", self.name, self.url())
     }
 }
 
+#[test]
+fn test_format_date() {
+    let winter = "2024-03-16T09:00:00+00:00".parse().unwrap();
+    let summer = "2024-07-06T09:00:00+00:00".parse().unwrap();
+    assert_eq!(
+        format_date(&winter, Some(chrono_tz::Europe::Berlin)),
+        "Sat 2024-03-16 10:00 CET (09:00 UTC)"
+    );
+    assert_eq!(
+        format_date(&summer, Some(chrono_tz::Europe::Berlin)),
+        "Sat 2024-07-06 11:00 CEST (09:00 UTC)"
+    );
+    assert_eq!(
+        format_date(&winter, Some(chrono_tz::America::New_York)),
+        "Sat 2024-03-16 05:00 EDT (09:00 UTC)"
+    );
+    assert_eq!(
+        format_date(&winter, Some(Tz::UTC)),
+        "Sat 2024-03-16 09:00 UTC"
+    );
+}
+
+#[test]
+fn test_clocks_change() {
+    let berlin = Some(chrono_tz::Europe::Berlin);
+    // DST starts on 2024-03-31 in Europe, but already on 2024-03-10 in the US
+    let start = "2024-03-30T12:00:00+00:00".parse().unwrap();
+    let finish = "2024-04-01T12:00:00+00:00".parse().unwrap();
+    assert!(clocks_change(&start, &finish, berlin));
+    assert!(!clocks_change(
+        &start,
+        &finish,
+        Some(chrono_tz::America::New_York)
+    ));
+    assert!(!clocks_change(&start, &finish, Some(Tz::UTC)));
+
+    // The duration is the elapsed time of 48 hours, even though the clocks in Berlin advanced by 49 hours
+    assert_eq!(
+        format_duration(&finish.signed_duration_since(start)),
+        "48 hours"
+    );
+}
+
 #[allow(clippy::float_cmp)]
 #[test]
 fn test_deserialize_ctf_event() {
@@ -350,3 +976,251 @@ fn test_deserialize_ctf_event_rating_weight() {
     assert_eq!(event.ctftime_url, "https://ctftime.org/event/724/");
     assert_eq!(event.rating_weight(), Some(24));
 }
+
+#[test]
+fn test_missing_organizers() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs.json").unwrap();
+    let res: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    assert_eq!(res[0].id(), 82);
+    assert_eq!(res[0].known_organizers().count(), 0);
+    let text = res[0].to_slack().text.unwrap();
+    assert!(text.contains("\n**Organizers:** unknown\n"), "{}", text);
+
+    let placeholder = CtfTeam {
+        id: 0,
+        name: String::new(),
+    };
+    assert!(placeholder.is_placeholder());
+
+    assert_eq!(field_value("HTsP", true), Some("HTsP"));
+    assert_eq!(field_value(" ", false), Some("unknown"));
+    assert_eq!(field_value("", true), None);
+}
+
+#[test]
+fn test_parse_format_and_restrictions() {
+    for s in &["Attack-Defense", "attack defense", "AD", "attack_defence"] {
+        assert_eq!(s.parse::<CtfFormat>(), Ok(CtfFormat::AttackDefense));
+    }
+    assert_eq!("Hack quest".parse::<CtfFormat>(), Ok(CtfFormat::HackQuest));
+    assert_eq!("".parse::<CtfFormat>(), Ok(CtfFormat::Unknown));
+    assert!("golf".parse::<CtfFormat>().is_err());
+    for format in &[
+        CtfFormat::Jeopardy,
+        CtfFormat::AttackDefense,
+        CtfFormat::HackQuest,
+        CtfFormat::Unknown,
+    ] {
+        assert_eq!(format.to_string().parse::<CtfFormat>().as_ref(), Ok(format));
+    }
+
+    assert_eq!(
+        "High-school".parse::<CtfRestrictions>(),
+        Ok(CtfRestrictions::HighSchool)
+    );
+    assert_eq!(
+        "prequal".parse::<CtfRestrictions>(),
+        Ok(CtfRestrictions::Prequalified)
+    );
+    assert_eq!(CtfRestrictions::HighSchool.to_string(), "High-school");
+    assert!(serde_json::from_str::<CtfRestrictions>(r#""Closed""#).is_err());
+}
+
+#[test]
+fn test_sort_events_and_event_id_roundtrip() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs.json").unwrap();
+
+    let mut res: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    res.reverse();
+    sort_events(&mut res);
+    for pair in res.windows(2) {
+        assert!((pair[0].start_date, pair[0].id) <= (pair[1].start_date, pair[1].id));
+    }
+
+    let event = &res[0];
+    let attachment = event.to_slack();
+    assert_eq!(event_id_from_attachment(&attachment), Some(event.id()));
+    assert_eq!(event_id_from_attachment(&Attachment::default()), None);
+}
+
+#[test]
+fn test_fingerprint() {
+    let json = std::fs::read_to_string("./tests/ctfs-1.json").unwrap();
+    let mut events = parse_events(&json, false).unwrap();
+    let event = &mut events[0];
+    // Must not change between versions, since it is stored in the state
+    assert_eq!(
+        event.fingerprint(),
+        "59fb4cac2ec4197ef019ddf6daee2c6d7ec44a02f58b90b8fca47a443cb5038e"
+    );
+
+    let old = event.fingerprint();
+    event.participants += 100;
+    event.weight = 50.;
+    event.description = "Updated description".to_string();
+    assert_eq!(event.fingerprint(), old);
+    // The same time in a different timezone
+    event.start_date = event.start_date.with_timezone(&FixedOffset::east(3600));
+    assert_eq!(event.fingerprint(), old);
+
+    event.finish_date = event.finish_date + Duration::hours(1);
+    assert_ne!(event.fingerprint(), old);
+    let old = event.fingerprint();
+    event.restrictions = CtfRestrictions::Academic;
+    assert_ne!(event.fingerprint(), old);
+    let old = event.fingerprint();
+    event.location = Some("Bucharest".to_string());
+    assert_ne!(event.fingerprint(), old);
+}
+
+#[test]
+fn test_parse_events_strictness() {
+    let data = std::fs::read_to_string("./tests/ctfs.json").unwrap();
+    assert_eq!(parse_events(&data, false).unwrap().len(), 442);
+    // All fields of the fixtures are known
+    assert_eq!(parse_events(&data, true).unwrap().len(), 442);
+
+    let mut json: serde_json::Value = serde_json::from_str(&data).unwrap();
+    json[3]["prizes"] = json!("A trophy");
+    json[5]["organizers"][0]["country"] = json!("DE");
+    let data = json.to_string();
+    assert_eq!(parse_events(&data, false).unwrap().len(), 442);
+    let err = parse_events(&data, true).unwrap_err().to_string();
+    assert!(
+        err.contains("unknown fields in the CTFtime API: organizers.country, prizes"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_parse_event() {
+    let data = std::fs::read_to_string("./tests/ctfs-1.json").unwrap();
+    let mut json: serde_json::Value = serde_json::from_str(&data).unwrap();
+    let event = parse_event(&json[0].to_string(), true).unwrap();
+    assert_eq!(event.id, 724);
+    assert_eq!(event.title, "X-MAS CTF 2018");
+
+    json[0]["prizes"] = json!("A trophy");
+    assert!(parse_event(&json[0].to_string(), false).is_ok());
+    assert!(parse_event(&json[0].to_string(), true).is_err());
+    // The endpoint of a single event returns no list
+    assert!(parse_event(&json.to_string(), false).is_err());
+}
+
+#[test]
+fn test_matches_filters_lookahead() {
+    let json = std::fs::read_to_string("./tests/ctfs.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_str(&json).unwrap();
+    let event = events
+        .iter()
+        .find(|event| !event.onsite && event.restrictions == CtfRestrictions::Open)
+        .unwrap();
+    let config: Config = toml::from_str(
+        r##"
+lookahead = "21d"
+color_jeopardy = "#0099e1"
+color_attack_defense = "danger"
+always_show_ctfs = []
+"##,
+    )
+    .unwrap();
+    let start = event.start_date().with_timezone(&Utc);
+
+    assert!(event.matches_filters(&config, start - Duration::days(21)));
+    // Whole days used to be compared, which included events up to a day after the lookahead
+    assert!(!event.matches_filters(&config, start - Duration::days(21) - Duration::hours(1)));
+}
+
+#[test]
+fn test_fetch_windowed() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let start = events
+        .iter()
+        .map(|e| e.start_date.timestamp())
+        .min()
+        .unwrap();
+    let end = events
+        .iter()
+        .map(|e| e.start_date.timestamp())
+        .max()
+        .unwrap()
+        + 1;
+
+    let mut requests = 0;
+    let mut fetch = |start: i64, end: i64| -> Result<Vec<CtfEvent>, ()> {
+        requests += 1;
+        Ok(events
+            .iter()
+            .filter(|e| (start..end).contains(&e.start_date.timestamp()))
+            .take(30)
+            .cloned()
+            .collect())
+    };
+    let fetched = fetch_windowed(start, end, 30, &mut fetch).unwrap();
+    assert!(requests > 1);
+    let mut expected: Vec<usize> = events.iter().map(|e| e.id).collect();
+    let mut fetched: Vec<usize> = fetched.iter().map(|e| e.id).collect();
+    expected.sort_unstable();
+    fetched.sort_unstable();
+    assert_eq!(fetched, expected);
+
+    // A single request suffices if the limit is not reached
+    let mut requests = 0;
+    let mut fetch = |_: i64, _: i64| -> Result<Vec<CtfEvent>, ()> {
+        requests += 1;
+        Ok(vec![])
+    };
+    assert!(fetch_windowed(start, end, 30, &mut fetch)
+        .unwrap()
+        .is_empty());
+    assert_eq!(requests, 1);
+}
+
+#[test]
+fn test_monthly_windows() {
+    let start: DateTime<Utc> = "2023-11-15T12:00:00Z".parse().unwrap();
+    let short = start + Duration::days(MAX_SINGLE_WINDOW_DAYS);
+    assert_eq!(
+        monthly_windows(start, short),
+        vec![(start.timestamp(), short.timestamp())]
+    );
+
+    let end: DateTime<Utc> = "2024-03-10T00:00:00Z".parse().unwrap();
+    let end = end + Duration::days(MAX_SINGLE_WINDOW_DAYS);
+    let windows = monthly_windows(start, end);
+    assert_eq!(windows.len(), 8);
+    assert_eq!(windows[0].0, start.timestamp());
+    assert_eq!(
+        windows[0].1,
+        "2023-12-01T00:00:00Z"
+            .parse::<DateTime<Utc>>()
+            .unwrap()
+            .timestamp()
+    );
+    assert_eq!(
+        windows[1].1,
+        "2024-01-01T00:00:00Z"
+            .parse::<DateTime<Utc>>()
+            .unwrap()
+            .timestamp()
+    );
+    assert_eq!(windows[7].1, end.timestamp());
+    assert!(windows.windows(2).all(|pair| pair[0].1 == pair[1].0));
+
+    // Events overlapping two months are only contained once
+    let json = std::fs::read_to_string("./tests/ctfs-1.json").unwrap();
+    let mut requests = 0;
+    let mut fetch = |_: i64, _: i64| -> Result<Vec<CtfEvent>, ()> {
+        requests += 1;
+        Ok(serde_json::from_str(&json).unwrap())
+    };
+    let events = fetch_monthly(start, end, 30, &mut fetch).unwrap();
+    assert_eq!(requests, 8);
+    assert_eq!(events.len(), 1);
+}
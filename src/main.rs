@@ -1,51 +1,1541 @@
-use chrono::Utc;
-use ctftimebot::{mattermost_hook_api::Message, CtfEvent, CONFIG};
-use log::{error, info};
-use std::io::Read;
+use chrono::{DateTime, Datelike, Duration, Utc};
+#[cfg(feature = "lambda")]
+use ctftimebot::lambda;
+use ctftimebot::{
+    access::{create_token, token_list, Scope},
+    alerts::{participants_alerts, weight_alerts},
+    board::sync_board,
+    caldav::sync_caldav,
+    calendar::{clash_notes, load_calendar},
+    config::Target,
+    ctftime_api::{self, ApiError},
+    digest::{freshness_footer, markdown_to_plain_text, Digest},
+    export::{export, ExportFormat},
+    fetch_monthly,
+    filters::diff_filters,
+    github_issues::sync_issues,
+    gitlab_issues::sync_gitlab_issues,
+    html::{html_context, render_html, DEFAULT_TEMPLATE},
+    http_client,
+    ical::{to_ics, Feed},
+    leaderboard::{
+        fetch_country_top, fetch_leaderboard, leaderboard_due, leaderboard_message, national_place,
+        rank_message, NationalRank,
+    },
+    log_data_quality,
+    mattermost_hook_api::Message,
+    metrics::RunMetrics,
+    news::{fetch_news, news_message, news_url, polled_events},
+    notifier::{direct_message_target, notifiers, notify_all, post, post_direct},
+    notion::sync_notion,
+    ops, post_metadata,
+    preferences::{
+        due_keyword_notifications, due_personal_reminders, keyword_notification, personal_reminder,
+    },
+    qualifiers::chain_notes,
+    recap::{fetch_ratings, local_today, recap_message},
+    reformat::{reformat_posts, sync_digest_post},
+    render::Registry,
+    reporting,
+    scheduler::{pending_jobs, Reminder},
+    server, sort_events,
+    spreadsheet::sync_spreadsheet,
+    state::{State, StateStore},
+    teams::{enrich_teams, fetch_teams},
+    timed,
+    trivia::{fetch_results, fetch_scores, pick_trivia, result_facts, rivals_summary},
+    webhook::digest_events,
+    Config, CtfEvent, CONFIG, MAX_SINGLE_WINDOW_DAYS,
+};
+use lazy_static::lazy_static;
+use log::{error, info, warn};
+use std::{path::PathBuf, sync::Arc};
+use structopt::StructOpt;
+
+lazy_static! {
+    /// Renderers for the backends of the targets, renderers of further backends are added here
+    static ref RENDERERS: Registry = Registry::new();
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(about = "Announce upcoming CTFs from ctftime.org in Mattermost")]
+struct CliArgs {
+    /// Keep running and send reminders for announced and played events
+    ///
+    /// Requires a state file.
+    /// Also starts the server for interactive buttons, if `SERVER_ADDRESS` is set.
+    #[structopt(long)]
+    daemon: bool,
+    /// Post the digest or print it to stdout as `markdown` or `json`, without posting or changing the state
+    ///
+    /// `json` prints the events of the digest, with the fields of the template webhooks and the notes.
+    #[structopt(long, default_value = "post", possible_values = &["post", "markdown", "json"])]
+    output: Output,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Show which events the filters of the new configuration add to or remove from the digest
+    DiffFilters {
+        /// Configuration file with the current filters
+        old: PathBuf,
+        /// Configuration file with the changed filters
+        new: PathBuf,
+    },
+    /// Export the events of the digest as iCalendar file, e.g., for importing them into a calendar app
+    Ical {
+        /// File to write the calendar to, stdout if missing
+        file: Option<PathBuf>,
+        /// Serve the calendar as feed on this address instead, e.g., `127.0.0.1:8080`
+        ///
+        /// Calendar apps can subscribe to `/calendar.ics`.
+        /// The events are refreshed after `REFRESH_INTERVAL`.
+        #[structopt(long, conflicts_with = "file")]
+        serve: Option<String>,
+    },
+    /// Export all fetched events with all their fields, e.g., for planning in a spreadsheet
+    Export {
+        /// File to write the export to, stdout if missing
+        file: Option<PathBuf>,
+        #[structopt(long, default_value = "csv", possible_values = &["csv"])]
+        format: ExportFormat,
+    },
+    /// Render the upcoming events as static HTML page, e.g., for publishing it on the website of the team
+    RenderHtml {
+        /// File to write the page to, stdout if missing
+        file: Option<PathBuf>,
+        /// Handlebars template of the page, instead of the built-in page
+        ///
+        /// The template receives `title`, `link`, `generated`, `formats`, and `events` with the fields of `--output json`.
+        #[structopt(long)]
+        template: Option<PathBuf>,
+    },
+    /// Manage the access tokens of the server, requires a state file
+    Token(TokenCommand),
+    /// Fetch a single event and render it like in the digest, e.g., for re-announcing it or debugging its rendering
+    ///
+    /// The state is not changed, even if the event is posted.
+    Show {
+        /// CTFtime id of the event, the number in `https://ctftime.org/event/<id>`
+        id: usize,
+        /// Post the event or print it to stdout as `markdown` or `json`
+        #[structopt(long, default_value = "markdown", possible_values = &["post", "markdown", "json"])]
+        output: Output,
+    },
+    /// Fetch a team from CTFtime and show its current rating
+    Team {
+        /// CTFtime id of the team, the number in `https://ctftime.org/team/<id>`, defaults to `TEAM_ID`
+        id: Option<usize>,
+        /// Post the team or print it to stdout as `markdown` or `json`
+        #[structopt(long, default_value = "markdown", possible_values = &["post", "markdown", "json"])]
+        output: Output,
+    },
+    /// Show the top 10 teams of CTFtime, globally or of a country
+    Leaderboard {
+        /// Year of the leaderboard, defaults to the current year
+        #[structopt(long, conflicts_with = "country")]
+        year: Option<i32>,
+        /// Country code, e.g., `de`, to show the national leaderboard, defaults to `LEADERBOARD_COUNTRY`
+        #[structopt(long)]
+        country: Option<String>,
+        /// Post the leaderboard or print it to stdout as `markdown` or `json`
+        #[structopt(long, default_value = "markdown", possible_values = &["post", "markdown", "json"])]
+        output: Output,
+    },
+    /// Edit the recent digests, such that they use the current colors and formatting
+    ///
+    /// Requires `mattermost_api` in the configuration file.
+    Reformat {
+        /// Edit the posts of this period, e.g., `30d` or `12h`
+        #[structopt(long, default_value = "30d", parse(try_from_str = parse_since))]
+        since: Duration,
+    },
+}
+
+fn parse_since(s: &str) -> Result<Duration, String> {
+    ctftimebot::duration::parse_duration(s)
+}
+
+#[derive(Debug, StructOpt)]
+enum TokenCommand {
+    /// Create a token and print its secret, which is not shown again
+    Create {
+        name: String,
+        /// Permission of the token, can be repeated
+        #[structopt(long = "scope", required = true, possible_values = &["read-events", "manage-subscriptions", "admin"])]
+        scopes: Vec<Scope>,
+        /// Use this secret instead of a random one, e.g., the token of the Mattermost slash command
+        #[structopt(long)]
+        secret: Option<String>,
+    },
+    /// List the names and scopes of all tokens
+    List,
+    /// Delete the token
+    Revoke { name: String },
+}
+
+/// Destination of the digest
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Output {
+    Post,
+    Markdown,
+    Json,
+}
+
+impl std::str::FromStr for Output {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "post" => Ok(Output::Post),
+            "markdown" => Ok(Output::Markdown),
+            "json" => Ok(Output::Json),
+            _ => Err(format!("Unknown output `{}`", s)),
+        }
+    }
+}
 
 fn main() {
-    env_logger::init();
+    let args = CliArgs::from_args();
+
+    match args.command {
+        Some(Command::DiffFilters { old, new }) => {
+            env_logger::init();
+            return run_diff_filters(old, new);
+        }
+        Some(Command::Ical {
+            serve: Some(address),
+            ..
+        }) => {
+            let _reporting = reporting::init("ical");
+            return run_ical_feed(&address);
+        }
+        Some(Command::Ical { file, serve: None }) => {
+            env_logger::init();
+            return run_ical(file);
+        }
+        Some(Command::Export { file, format }) => {
+            env_logger::init();
+            return run_export(file, format);
+        }
+        Some(Command::RenderHtml { file, template }) => {
+            env_logger::init();
+            return run_render_html(file, template);
+        }
+        Some(Command::Token(command)) => {
+            env_logger::init();
+            return run_token(command);
+        }
+        Some(Command::Show { id, output }) => {
+            let _reporting = reporting::init("show");
+            return run_show(id, output);
+        }
+        Some(Command::Team { id, output }) => {
+            let _reporting = reporting::init("team");
+            return run_team(id, output);
+        }
+        Some(Command::Leaderboard {
+            year,
+            country,
+            output,
+        }) => {
+            let _reporting = reporting::init("leaderboard");
+            return run_leaderboard(year, country, output);
+        }
+        Some(Command::Reformat { since }) => {
+            env_logger::init();
+            return run_reformat(since);
+        }
+        None => {}
+    }
+    #[cfg(feature = "lambda")]
+    {
+        if std::env::var_os("AWS_LAMBDA_RUNTIME_API").is_some() {
+            return run_lambda();
+        }
+    }
+    let _reporting = reporting::init(if args.daemon { "daemon" } else { "cron" });
+    if args.output != Output::Post {
+        return print_digest(&http_client(), args.output);
+    }
+
+    let targets = CONFIG.targets();
+    if targets.is_empty() {
+        error!("No webhook configured. Set WEBHOOK_URL or add targets to the config file.");
+        return;
+    }
+    if let Some(target) = targets
+        .iter()
+        .find(|target| RENDERERS.get(&target.backend).is_none())
+    {
+        error!(
+            "Unknown backend `{}`, available are: {}",
+            target.backend,
+            RENDERERS.names().collect::<Vec<_>>().join(", ")
+        );
+        return;
+    }
+    let client = http_client();
 
-    let today = Utc::now().timestamp();
-    let end = today + 100 * (3600 * 24);
-    let url = format!(
-        "https://ctftime.org/api/v1/events/?limit=30&start={}&finish={}",
-        today, end
+    if args.daemon {
+        run_daemon(&client, &targets)
+    } else {
+        run_once(&client, &targets)
+    }
+}
+
+/// Fetch the events from CTFtime, which start between `start` and 100 days or `LOOKAHEAD` into the future
+fn fetch_events(
+    client: &reqwest::blocking::Client,
+    start: DateTime<Utc>,
+) -> Result<Vec<CtfEvent>, ApiError> {
+    let lookahead = CONFIG.lookahead.max(Duration::days(MAX_SINGLE_WINDOW_DAYS));
+    let end = Utc::now() + lookahead;
+    let ctftime = ctftime_api::Client::new(
+        client.clone(),
+        CONFIG.ctftime_url.clone(),
+        CONFIG.strict_api,
     );
-    let mut resp = reqwest::blocking::get(&url).unwrap();
-    let mut data = String::new();
-    resp.read_to_string(&mut data).unwrap();
-    let events: Vec<CtfEvent> = serde_json::from_str(&data).unwrap();
-    let events: Vec<_> = events
-        .into_iter()
-        .filter(CtfEvent::should_print_event)
-        .map(|x| x.to_slack())
+    let mut fetch = |start: i64, end: i64| ctftime.events(start, end, CONFIG.api_limit);
+    let mut events = fetch_monthly(start, end, CONFIG.api_limit, &mut fetch)?;
+    sort_events(&mut events);
+    Ok(events)
+}
+
+/// Print the events added and removed by the filters of `new` compared to `old`
+fn run_diff_filters(old: PathBuf, new: PathBuf) {
+    let (old, new) = match (Config::from_file(old), Config::from_file(new)) {
+        (Ok(old), Ok(new)) => (old, new),
+        (Err(err), _) | (_, Err(err)) => {
+            error!("{}", err);
+            return;
+        }
+    };
+    let events = match fetch_events(&http_client(), Utc::now()) {
+        Ok(events) => events,
+        Err(err) => {
+            error!("Couldn't fetch the events: {}", err);
+            return;
+        }
+    };
+    print!(
+        "{}",
+        diff_filters(&events, &old, &new, Utc::now()).to_markdown()
+    );
+}
+
+/// Calendar of the events which would be part of the digest
+fn generate_ical(
+    client: &reqwest::blocking::Client,
+) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let now = Utc::now();
+    let events = fetch_events(client, now)?;
+    let events: Vec<&CtfEvent> = events
+        .iter()
+        .filter(|event| event.should_print_event())
         .collect();
-    if events.is_empty() {
-        info!("No CTFs in the specified time frame. Exiting...");
-        // early exit in case there is no upcoming CTF
+    Ok(to_ics(&events, now))
+}
+
+fn run_ical(file: Option<PathBuf>) {
+    let ics = match generate_ical(&http_client()) {
+        Ok(ics) => ics,
+        Err(err) => {
+            error!("Couldn't fetch the events: {}", err);
+            return;
+        }
+    };
+    match file {
+        Some(file) => {
+            if let Err(err) = std::fs::write(&file, ics) {
+                error!("Couldn't write {}: {}", file.display(), err);
+            }
+        }
+        None => print!("{}", ics),
+    }
+}
+
+fn run_ical_feed(address: &str) {
+    let client = http_client();
+    let refresh = CONFIG.refresh_interval.to_std().unwrap_or_default();
+    let feed = Feed::new(refresh, move || generate_ical(&client));
+    if let Err(err) = server::serve_feed(address, Arc::new(feed)) {
+        error!("Couldn't start the server: {}", err);
+    }
+}
+
+/// Update the cached team information, if enabled
+fn refresh_teams(client: &reqwest::blocking::Client, store: &StateStore, events: &[CtfEvent]) {
+    if !CONFIG.enrich_teams {
         return;
     }
-    info!("Found {} events in the specified time frame.", events.len());
+    if let Err(err) = enrich_teams(client, store, events, CONFIG.team_cache_ttl, Utc::now()) {
+        error!("Couldn't update the team cache: {}", err)
+    }
+}
+
+/// Run [`run_once`] for every invocation of the Lambda function, see [`lambda`]
+#[cfg(feature = "lambda")]
+fn run_lambda() {
+    let runtime = tokio::runtime::Runtime::new().expect("Couldn't start the async runtime");
+    // The logger depends on the configuration, which might only be complete after this
+    let state = match runtime.block_on(lambda::prepare()) {
+        Ok(state) => state,
+        Err(err) => panic!("Couldn't prepare the Lambda environment: {}", err),
+    };
+    let _reporting = reporting::init("lambda");
+
+    let handler = lambda_runtime::handler_fn(
+        move |_event: serde_json::Value, _context: lambda_runtime::Context| {
+            let state = state.clone();
+            async move {
+                if let Some(ref state) = state {
+                    state.download().await?;
+                }
+                tokio::task::spawn_blocking(|| run_once(&http_client(), &CONFIG.targets())).await?;
+                if let Some(ref state) = state {
+                    state.upload().await?;
+                }
+                Ok::<_, lambda_runtime::Error>(serde_json::json!({ "status": "done" }))
+            }
+        },
+    );
+    if let Err(err) = runtime.block_on(lambda_runtime::run(handler)) {
+        error!("The Lambda runtime failed: {}", err);
+    }
+}
 
-    let mut message = Message {
-        username: Some("Upcoming CTFs".to_string()),
-        text: Some("[Upcoming CTFs](https://ctftime.org/event/list/upcoming)".to_string()),
-        attachments: events,
+/// Run [`announce`] and push the metrics of the run, if configured
+fn run_once(client: &reqwest::blocking::Client, targets: &[Target]) {
+    let start = std::time::Instant::now();
+    let mut metrics = announce(client, targets);
+    metrics.duration = start.elapsed();
+    metrics.finished = Some(Utc::now());
+    if let Some(ref url) = CONFIG.pushgateway_url {
+        if let Err(err) = timed("Pushing the metrics", || metrics.push(client, url)) {
+            error!("Couldn't push the metrics: {}", err)
+        }
+    }
+    report_issues(client);
+}
+
+/// Post the warnings and errors of the run to the admin webhook, if configured
+fn report_issues(client: &reqwest::blocking::Client) {
+    let (issues, dropped) = ops::take_issues();
+    let target = match CONFIG.admin_target() {
+        Some(target) => target,
+        None => return,
+    };
+    if let Some(message) = ops::ops_message(&issues, dropped) {
+        // Logged as a warning, such that it is reported with the next run
+        if let Err(err) = post(client, &RENDERERS, &target, message) {
+            warn!("Couldn't post to the admin webhook: {}", err);
+        }
+    }
+}
+
+/// Post the digest of upcoming events and notifications about changed events
+fn announce(client: &reqwest::blocking::Client, targets: &[Target]) -> RunMetrics {
+    let fetched = Utc::now();
+    let events = match fetch_events(client, fetched) {
+        Ok(events) => events,
+        Err(err) => {
+            error!("Couldn't fetch the events: {}", err);
+            return RunMetrics::default();
+        }
+    };
+    let mut metrics = RunMetrics {
+        events_fetched: events.len(),
         ..Default::default()
     };
-    if let Some(ref c) = CONFIG.mattermost_channel {
-        message.channel = Some(c.to_string());
+    reporting::set_context("events_fetched", events.len());
+    log_data_quality(&events);
+
+    let store = CONFIG.state_file.clone().map(StateStore::new);
+    // Without a readable state, the alerts depending on it are skipped
+    let state = store.as_ref().and_then(|store| {
+        refresh_teams(client, store, &events);
+        store
+            .read()
+            .map_err(|err| error!("Couldn't read state file: {}", err))
+            .ok()
+    });
+    if let Some(ref state) = state {
+        if let Some(until) = state
+            .admin
+            .muted_until
+            .filter(|_| state.admin.is_muted(fetched))
+        {
+            info!("Posting is muted until {}", until);
+            if let Some(store) = store {
+                if let Err(err) = store.update(|state| state.record_events(&events)) {
+                    error!("Couldn't write state file: {}", err)
+                }
+            }
+            return metrics;
+        }
+    }
+    if let Some(ref state) = state {
+        let mut alerts = Vec::new();
+        if let Some(threshold) = CONFIG.min_weight {
+            alerts.extend(weight_alerts(state, &events, threshold));
+        }
+        if let Some(threshold) = CONFIG.participants_threshold {
+            alerts.extend(participants_alerts(state, &events, threshold));
+        }
+        metrics.alerts_sent = alerts.len();
+        for text in alerts {
+            metrics.failed_deliveries += send(client, targets, &Notification::text(text, &[]));
+        }
+    }
+    if let (Some(store), Some(state)) = (&store, &state) {
+        send_keyword_notifications(client, targets, store, &events, state, fetched);
+    }
+    if let Some(ref store) = store {
+        metrics.failed_deliveries += track_national_rank(client, targets, store);
+        metrics.failed_deliveries += post_season_recap(client, targets, store);
+        metrics.failed_deliveries += post_weekly_leaderboard(client, targets, store);
+    }
+
+    let digest = build_digest(client, &events, state.as_ref(), fetched);
+    let event_ids = digest.event_ids();
+    if digest.is_empty() {
+        info!("No CTFs in the specified time frame.");
+    } else {
+        info!(
+            "Found {} events in the specified time frame.",
+            digest.events.len()
+        );
+        metrics.events_announced = digest.events.len();
+        reporting::set_context("events_announced", digest.events.len());
+        metrics.failed_deliveries += notify_all(
+            client,
+            &notifiers(&CONFIG, targets, &RENDERERS),
+            &digest,
+            &CONFIG.templates,
+            CONFIG.post_limits(),
+        );
+        metrics.failed_deliveries += send_broadcasts(client, targets, &digest.events);
+        if let Some(ref push) = CONFIG.push {
+            metrics.failed_deliveries += push.notify_events(client, &digest.events, state.as_ref());
+        }
+        if let Some(ref email) = CONFIG.email {
+            if email.is_due(fetched, CONFIG.timezone) {
+                if let Err(err) = email.send(&digest, fetched, CONFIG.timezone) {
+                    error!("Couldn't send the email: {}", err);
+                    metrics.failed_deliveries += 1;
+                }
+            }
+        }
+        if let Some(ref spreadsheet) = CONFIG.spreadsheet {
+            sync_spreadsheet(spreadsheet, client, &digest.events);
+        }
+    }
+
+    if let Some(store) = store {
+        let res = store.update(|state| {
+            state.record_events(&events);
+            state.mark_announced(&event_ids);
+            state.clone()
+        });
+        match res {
+            Ok(mut synced) => {
+                // The requests run without holding the lock, afterwards only the ids are written back
+                if let Some(ref board) = CONFIG.board {
+                    sync_board(board, client, &mut synced, &events, &event_ids, Utc::now());
+                }
+                if let Some(ref caldav) = CONFIG.caldav {
+                    sync_caldav(caldav, client, &mut synced, &events, &event_ids, Utc::now());
+                }
+                if let Some(ref issues) = CONFIG.github_issues {
+                    sync_issues(issues, client, &mut synced, &events, &event_ids);
+                }
+                if let Some(ref notion) = CONFIG.notion {
+                    sync_notion(notion, client, &mut synced, &events, &event_ids, Utc::now());
+                }
+                if let (true, Some(api)) = (CONFIG.update_in_place, &CONFIG.mattermost_api) {
+                    let posted = !event_ids.is_empty();
+                    sync_digest_post(api, client, &mut synced, &events, posted, fetched);
+                }
+                if let Err(err) = store.update(|state| state.merge_synced(&synced)) {
+                    error!("Couldn't write state file: {}", err)
+                }
+            }
+            Err(err) => error!("Couldn't write state file: {}", err),
+        }
+    }
+    if let Some(ref issues) = CONFIG.gitlab_issues {
+        sync_gitlab_issues(issues, client, &events, &event_ids);
+    }
+    metrics
+}
+
+/// Keep the existing cards, calendar entries, pages, and the pinned digest up to date
+///
+/// The requests change `synced`, a copy of the state, such that the state file isn't locked meanwhile.
+fn sync_existing(client: &reqwest::blocking::Client, synced: &mut State, events: &[CtfEvent]) {
+    if let Some(ref board) = CONFIG.board {
+        sync_board(board, client, synced, events, &[], Utc::now());
+    }
+    if let Some(ref caldav) = CONFIG.caldav {
+        // Only keep the existing entries up to date, new entries are created by the digest
+        let existing: Vec<usize> = synced
+            .events
+            .iter()
+            .filter(|(_, record)| record.caldav_entry.is_some())
+            .map(|(&id, _)| id)
+            .collect();
+        sync_caldav(caldav, client, synced, events, &existing, Utc::now());
+    }
+    if let Some(ref notion) = CONFIG.notion {
+        sync_notion(notion, client, synced, events, &[], Utc::now());
+    }
+    if let (true, Some(api)) = (CONFIG.update_in_place, &CONFIG.mattermost_api) {
+        sync_digest_post(api, client, synced, events, false, Utc::now());
+    }
+}
+
+/// Print the digest to stdout instead of posting it
+///
+/// The state is only read, such that previewing doesn't change the next post.
+fn print_digest(client: &reqwest::blocking::Client, output: Output) {
+    let fetched = Utc::now();
+    let events = match fetch_events(client, fetched) {
+        Ok(events) => events,
+        Err(err) => {
+            error!("Couldn't fetch the events: {}", err);
+            return;
+        }
+    };
+    let state = read_state();
+    let digest = build_digest(client, &events, state.as_ref(), fetched);
+    match output {
+        Output::Markdown => print!("{}", digest.to_markdown()),
+        Output::Json => match serde_json::to_string_pretty(&digest_events(&digest)) {
+            Ok(json) => println!("{}", json),
+            Err(err) => error!("Couldn't serialize the events: {}", err),
+        },
+        Output::Post => unreachable!("Posting is not a preview"),
     }
-    if let Some(ref url) = CONFIG.bot_icon {
-        message.icon_url = Some(url.clone())
+}
+
+/// Read the state file, if configured, without changing it
+fn read_state() -> Option<State> {
+    CONFIG
+        .state_file
+        .clone()
+        .map(StateStore::new)
+        .and_then(|store| match store.read() {
+            Ok(state) => Some(state),
+            Err(err) => {
+                error!("Couldn't read state file: {}", err);
+                None
+            }
+        })
+}
+
+fn run_export(file: Option<PathBuf>, format: ExportFormat) {
+    let events = match fetch_events(&http_client(), Utc::now()) {
+        Ok(events) => events,
+        Err(err) => {
+            error!("Couldn't fetch the events: {}", err);
+            return;
+        }
+    };
+    let export = export(&events, format);
+    match file {
+        Some(file) => {
+            if let Err(err) = std::fs::write(&file, export) {
+                error!("Couldn't write {}: {}", file.display(), err);
+            }
+        }
+        None => print!("{}", export),
     }
+}
 
-    let res = reqwest::blocking::Client::new()
-        .post(&CONFIG.webhook_url)
-        .json(&message)
-        .send();
-    if let Err(x) = res {
-        error!("ERR: {:?}", x)
+fn run_render_html(file: Option<PathBuf>, template: Option<PathBuf>) {
+    let template = match template {
+        Some(path) => match std::fs::read_to_string(&path) {
+            Ok(template) => template,
+            Err(err) => {
+                error!("Couldn't read {}: {}", path.display(), err);
+                return;
+            }
+        },
+        None => DEFAULT_TEMPLATE.to_string(),
+    };
+    let client = http_client();
+    let fetched = Utc::now();
+    let events = match fetch_events(&client, fetched) {
+        Ok(events) => events,
+        Err(err) => {
+            error!("Couldn't fetch the events: {}", err);
+            return;
+        }
+    };
+    let state = read_state();
+    let digest = build_digest(&client, &events, state.as_ref(), fetched);
+    let html = match render_html(&template, &html_context(&digest, fetched)) {
+        Ok(html) => html,
+        Err(err) => {
+            error!("Couldn't render the template: {}", err);
+            return;
+        }
+    };
+    match file {
+        Some(file) => {
+            if let Err(err) = std::fs::write(&file, html) {
+                error!("Couldn't write {}: {}", file.display(), err);
+            }
+        }
+        None => print!("{}", html),
+    }
+}
+
+fn run_show(id: usize, output: Output) {
+    let client = http_client();
+    let ctftime = ctftime_api::Client::new(
+        client.clone(),
+        CONFIG.ctftime_url.clone(),
+        CONFIG.strict_api,
+    );
+    let event = match ctftime.event(id) {
+        Ok(event) => event,
+        Err(err) => {
+            error!("Couldn't fetch event {}: {}", id, err);
+            return;
+        }
+    };
+    let mut digest = Digest::new(vec![&event]);
+    digest.actions_url = CONFIG.actions_url();
+    match output {
+        Output::Markdown => print!("{}", digest.to_markdown()),
+        Output::Json => match serde_json::to_string_pretty(&digest_events(&digest)) {
+            Ok(json) => println!("{}", json),
+            Err(err) => error!("Couldn't serialize the event: {}", err),
+        },
+        Output::Post => {
+            let targets = CONFIG.targets();
+            let failed = notify_all(
+                &client,
+                &notifiers(&CONFIG, &targets, &RENDERERS),
+                &digest,
+                &CONFIG.templates,
+                CONFIG.post_limits(),
+            );
+            if failed > 0 {
+                error!("Couldn't post the event to {} targets", failed);
+            }
+        }
+    }
+}
+
+fn run_team(id: Option<usize>, output: Output) {
+    let id = match id.or(CONFIG.team_id) {
+        Some(id) => id,
+        None => {
+            error!("No team id given and TEAM_ID is not configured");
+            return;
+        }
+    };
+    let client = http_client();
+    let ctftime = ctftime_api::Client::new(
+        client.clone(),
+        CONFIG.ctftime_url.clone(),
+        CONFIG.strict_api,
+    );
+    let team = match ctftime.team(id) {
+        Ok(team) => team,
+        Err(err) => {
+            error!("Couldn't fetch team {}: {}", id, err);
+            return;
+        }
+    };
+    let text = team.to_markdown(Utc::now().year());
+    match output {
+        Output::Markdown => print!("{}", text),
+        Output::Json => match serde_json::to_string_pretty(&team) {
+            Ok(json) => println!("{}", json),
+            Err(err) => error!("Couldn't serialize the team: {}", err),
+        },
+        Output::Post => {
+            let failed = send(&client, &CONFIG.targets(), &Notification::text(text, &[]));
+            if failed > 0 {
+                error!("Couldn't post the team to {} targets", failed);
+            }
+        }
+    }
+}
+
+fn run_leaderboard(year: Option<i32>, country: Option<String>, output: Output) {
+    let country = country.or_else(|| {
+        CONFIG
+            .leaderboard_country
+            .clone()
+            .filter(|_| year.is_none())
+    });
+    let client = http_client();
+    let top = match fetch_leaderboard(&client, year, country.as_deref()) {
+        Ok(top) => top,
+        Err(err) => {
+            error!("Couldn't fetch the leaderboard: {}", err);
+            return;
+        }
+    };
+    let scope = leaderboard_scope(year, country.as_deref());
+    match output {
+        Output::Markdown => print!("{}", leaderboard_message(&top, &scope, CONFIG.team_id)),
+        Output::Json => match serde_json::to_string_pretty(&top) {
+            Ok(json) => println!("{}", json),
+            Err(err) => error!("Couldn't serialize the leaderboard: {}", err),
+        },
+        Output::Post => {
+            let text = leaderboard_message(&top, &scope, CONFIG.team_id);
+            let failed = send(&client, &CONFIG.targets(), &Notification::text(text, &[]));
+            if failed > 0 {
+                error!("Couldn't post the leaderboard to {} targets", failed);
+            }
+        }
+    }
+}
+
+/// Heading of the leaderboard, e.g., `in DE` or `2022`
+fn leaderboard_scope(year: Option<i32>, country: Option<&str>) -> String {
+    match country {
+        Some(country) => format!("in {}", country.to_uppercase()),
+        None => year.unwrap_or_else(|| Utc::now().year()).to_string(),
+    }
+}
+
+fn run_reformat(since: Duration) {
+    let api = match CONFIG.mattermost_api {
+        Some(ref api) => api,
+        None => {
+            error!("Reformatting requires `mattermost_api` in the configuration file");
+            return;
+        }
+    };
+    let client = http_client();
+    let since = Utc::now() - since;
+    let events = match fetch_events(&client, since) {
+        Ok(events) => events,
+        Err(err) => {
+            error!("Couldn't fetch the events: {}", err);
+            return;
+        }
+    };
+    match reformat_posts(api, &client, &events, since) {
+        Ok(edited) => info!("Edited {} posts", edited),
+        Err(err) => error!("Couldn't edit the posts: {}", err),
+    }
+}
+
+fn run_token(command: TokenCommand) {
+    let store = match CONFIG.state_file.clone() {
+        Some(path) => StateStore::new(path),
+        None => {
+            error!("Managing tokens requires STATE_FILE");
+            return;
+        }
+    };
+    let result = store.update(|state| match command {
+        TokenCommand::Create {
+            name,
+            scopes,
+            secret,
+        } => create_token(state, &name, &scopes, secret, Utc::now()),
+        TokenCommand::List => Ok(token_list(state).join("\n")),
+        TokenCommand::Revoke { name } => match state.tokens.remove(&name) {
+            Some(_) => Ok(format!("Revoked token `{}`", name)),
+            None => Err(format!("No token named `{}`", name)),
+        },
+    });
+    match result {
+        Ok(Ok(output)) => println!("{}", output),
+        Ok(Err(err)) => error!("{}", err),
+        Err(err) => error!("Couldn't update state file: {}", err),
+    }
+}
+
+/// Compose the digest of `events`, with the notes, trivia, and footer as configured
+fn build_digest<'a>(
+    client: &reqwest::blocking::Client,
+    events: &'a [CtfEvent],
+    state: Option<&State>,
+    fetched: DateTime<Utc>,
+) -> Digest<'a> {
+    let always_show = state
+        .map(|state| state.admin.always_show.clone())
+        .unwrap_or_default();
+    let mut digest = Digest::new(
+        events
+            .iter()
+            .filter(|x| x.should_print_event() || always_show.contains(&x.ctf_id()))
+            .collect(),
+    );
+    if let Some(state) = state {
+        if CONFIG.sticky_announcements {
+            digest.keep_announced(events, state);
+        }
+        if CONFIG.whats_new {
+            digest.add_new_events(events, state);
+        }
+    }
+    digest.add_notes(chain_notes(
+        events,
+        state.unwrap_or(&State::default()),
+        &CONFIG.qualifiers,
+    ));
+    if let (Some(escalation), Some(state)) = (&CONFIG.escalation, state) {
+        digest.add_notes(escalation.notes(state));
+    }
+    if let Some(ref url) = CONFIG.team_calendar {
+        match timed("Loading the team calendar", || {
+            load_calendar(client, url, CONFIG.timezone)
+        }) {
+            Ok(calendar) => digest.add_notes(clash_notes(events, &calendar, CONFIG.timezone)),
+            Err(err) => error!("Couldn't load the team calendar: {}", err),
+        }
+    }
+    digest.actions_url = CONFIG.actions_url();
+    if CONFIG.trivia_footer {
+        let facts = match CONFIG.team_id {
+            Some(team_id) => match fetch_results(client, fetched.year() - 1, team_id) {
+                Ok(last_year) => result_facts(&digest.events, &last_year),
+                Err(err) => {
+                    error!("Couldn't fetch the results: {}", err);
+                    Vec::new()
+                }
+            },
+            None => Vec::new(),
+        };
+        digest.trivia = pick_trivia(&facts, &CONFIG.trivia, fetched.ordinal() as usize);
+    }
+    if CONFIG.digest_footer {
+        digest.footer = Some(freshness_footer(
+            fetched,
+            CONFIG.digest_next_update.as_deref(),
+        ));
+        digest.footer_icon = CONFIG.digest_footer_icon.clone();
+    }
+    digest
+}
+
+/// Periodically refresh the events and send reminders once they are due
+///
+/// If configured, the server for the interactive buttons runs in the background.
+fn run_daemon(client: &reqwest::blocking::Client, targets: &[Target]) {
+    let store = match CONFIG.state_file {
+        Some(ref path) => Arc::new(StateStore::new(path.clone())),
+        None => {
+            error!("The daemon mode requires a state file. Set STATE_FILE.");
+            return;
+        }
+    };
+    if let Some(ref address) = CONFIG.server_address {
+        let address = address.clone();
+        let store = store.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = server::serve(&address, store) {
+                error!("Couldn't start the server: {}", err);
+            }
+        });
+    }
+    let refresh_interval = CONFIG.refresh_interval;
+
+    loop {
+        // Include running events, such that reminders during the event are possible
+        let events = match fetch_events(client, Utc::now() - chrono::Duration::days(14)) {
+            Ok(events) => events,
+            Err(err) => {
+                error!("Couldn't fetch the events: {}", err);
+                report_issues(client);
+                std::thread::sleep(refresh_interval.to_std().unwrap_or_default());
+                continue;
+            }
+        };
+        let state = match store.update(|state| {
+            state.record_events(&events);
+            state.clone()
+        }) {
+            Ok(mut synced) => {
+                sync_existing(client, &mut synced, &events);
+                match store.update(|state| {
+                    state.merge_synced(&synced);
+                    state.clone()
+                }) {
+                    Ok(state) => state,
+                    Err(err) => {
+                        error!("Couldn't update state file: {}", err);
+                        synced
+                    }
+                }
+            }
+            Err(err) => {
+                error!("Couldn't update state file: {}", err);
+                State::default()
+            }
+        };
+
+        refresh_teams(client, &store, &events);
+
+        let now = Utc::now();
+        let mut next_wakeup = now + refresh_interval;
+        for job in pending_jobs(&events, &state) {
+            if job.due > now {
+                next_wakeup = next_wakeup.min(job.due);
+                break;
+            }
+            let event = match events.iter().find(|event| event.id() == job.event_id) {
+                Some(event) => event,
+                None => continue,
+            };
+            if job.reminder == Reminder::Warmup {
+                // Not a post, such that muting doesn't stop the warm-up
+                if let (Some(ref warmup), true) =
+                    (&CONFIG.warmup, job.reminder.is_relevant(event, now))
+                {
+                    info!("Running the warm-up for event {}", job.event_id);
+                    if let Err(err) = timed("Running the warm-up", || warmup.trigger(client, event))
+                    {
+                        error!("Couldn't warm up for event {}: {}", job.event_id, err);
+                    }
+                }
+                if let Err(err) = store.update(|state| state.mark_sent(job.event_id, job.reminder))
+                {
+                    error!("Couldn't write state file: {}", err)
+                }
+                continue;
+            }
+            if job.reminder == Reminder::Escalation {
+                let needs_players = CONFIG.escalation.as_ref().map_or(false, |escalation| {
+                    escalation.needs_players(&state, job.event_id)
+                });
+                if !needs_players {
+                    info!("Event {} has enough players", job.event_id);
+                    if let Err(err) =
+                        store.update(|state| state.mark_sent(job.event_id, job.reminder))
+                    {
+                        error!("Couldn't write state file: {}", err)
+                    }
+                    continue;
+                }
+                escalate(client, targets, &store, event, &state, now);
+            }
+            let mut text = job.reminder.message(event, &state, now);
+            if job.reminder == Reminder::Results && job.reminder.is_relevant(event, now) {
+                match results_summary(client, event, &state) {
+                    Ok(Some(summary)) => text += &format!("\n\n{}", summary),
+                    // Retried during the next wakeup, until the reminder is no longer relevant
+                    Ok(None) => {
+                        info!("No results for event {} yet", job.event_id);
+                        continue;
+                    }
+                    Err(err) => {
+                        error!(
+                            "Couldn't fetch the results of event {}: {}",
+                            job.event_id, err
+                        );
+                        continue;
+                    }
+                }
+            }
+            if state.admin.is_muted(now) {
+                info!(
+                    "Skipping {:?} reminder for event {}, since posting is muted",
+                    job.reminder, job.event_id
+                );
+            } else if job.reminder.is_relevant(event, now) {
+                info!(
+                    "Sending {:?} reminder for event {}",
+                    job.reminder, job.event_id
+                );
+                let mut notification = Notification::text(text, &[job.event_id]);
+                notification.message.attachments = job.reminder.attachments(event);
+                send(client, targets, &notification);
+                if let Some(ref twilio) = CONFIG.twilio {
+                    if twilio.is_high_priority(job.reminder, event) {
+                        timed("Sending the Twilio messages", || {
+                            twilio.send(client, &notification.plain_text)
+                        });
+                    }
+                }
+            }
+            if let Err(err) = store.update(|state| state.mark_sent(job.event_id, job.reminder)) {
+                error!("Couldn't write state file: {}", err)
+            }
+        }
+
+        send_personal_reminders(client, targets, &store, &events, &state, now);
+        send_keyword_notifications(client, targets, &store, &events, &state, now);
+        if !state.admin.is_muted(now) {
+            post_news(client, targets, &store, &events, &state, now);
+            track_national_rank(client, targets, &store);
+            post_season_recap(client, targets, &store);
+        }
+
+        report_issues(client);
+        let sleep = (next_wakeup - Utc::now())
+            .to_std()
+            .unwrap_or_else(|_| std::time::Duration::from_secs(0));
+        std::thread::sleep(sleep);
+    }
+}
+
+/// Mark the event as needing players and send direct messages to the captains
+///
+/// The `@channel` post is sent as the [`Reminder::Escalation`].
+fn escalate(
+    client: &reqwest::blocking::Client,
+    targets: &[Target],
+    store: &StateStore,
+    event: &CtfEvent,
+    state: &State,
+    now: DateTime<Utc>,
+) {
+    let escalation = match CONFIG.escalation {
+        Some(ref escalation) => escalation,
+        None => return,
+    };
+    info!("Event {} needs players", event.id());
+    if let Err(err) = store.update(|state| {
+        state.events.entry(event.id()).or_default().needs_players = true;
+    }) {
+        error!("Couldn't write state file: {}", err)
+    }
+    if state.admin.is_muted(now) || !Reminder::Escalation.is_relevant(event, now) {
+        return;
+    }
+    let target = match direct_message_target(targets) {
+        Some(target) => target,
+        None => {
+            warn!("No Mattermost target for the direct messages to the captains");
+            return;
+        }
+    };
+    let text = escalation.captain_message(event, state);
+    for captain in &escalation.captains {
+        if let Err(err) = send_direct(client, target, captain, text.clone(), event.id()) {
+            error!("Couldn't send direct message to {}: {}", captain, err);
+        }
+    }
+}
+
+/// Our placement at `event` next to the rivals, `None` if CTFtime has no results yet
+fn results_summary(
+    client: &reqwest::blocking::Client,
+    event: &CtfEvent,
+    state: &State,
+) -> Result<Option<String>, reqwest::Error> {
+    let team_id = match CONFIG.team_id {
+        Some(team_id) => team_id,
+        None => return Ok(None),
+    };
+    let scores = match fetch_scores(client, event)? {
+        Some(scores) => scores,
+        None => return Ok(None),
+    };
+    let uncached: Vec<usize> = CONFIG
+        .rivals
+        .iter()
+        .copied()
+        .filter(|&id| state.team(id).is_none())
+        .collect();
+    let fetched = fetch_teams(client, &uncached);
+    let name = |id| {
+        state
+            .team(id)
+            .or_else(|| fetched.iter().find(|team| team.id == id))
+            .map_or_else(|| format!("Team {}", id), |team| team.name.clone())
+    };
+    Ok(Some(
+        rivals_summary(&scores, team_id, &CONFIG.rivals, name)
+            .unwrap_or_else(|| "We are not on the scoreboard".to_string()),
+    ))
+}
+
+/// Post changes of our rank in the national leaderboard, returns the number of failed deliveries
+///
+/// The first run only records the rank, such that enabling the tracking doesn't post anything.
+fn track_national_rank(
+    client: &reqwest::blocking::Client,
+    targets: &[Target],
+    store: &StateStore,
+) -> usize {
+    let (country, team_id) = match (&CONFIG.country, CONFIG.team_id) {
+        (Some(country), Some(team_id)) => (country.to_lowercase(), team_id),
+        _ => return 0,
+    };
+    let top = match fetch_country_top(client, &country) {
+        Ok(top) => top,
+        Err(err) => {
+            error!("Couldn't fetch the leaderboard of {}: {}", country, err);
+            return 0;
+        }
+    };
+    let rank = NationalRank {
+        place: national_place(&top, team_id),
+        country,
+    };
+    let previous = match store.update(|state| state.national_rank.replace(rank.clone())) {
+        Ok(previous) => previous,
+        Err(err) => {
+            error!("Couldn't write state file: {}", err);
+            return 0;
+        }
+    };
+    match previous
+        .filter(|previous| previous.country == rank.country)
+        .and_then(|previous| rank_message(previous.place, rank.place, &rank.country))
+    {
+        Some(text) => send(client, targets, &Notification::text(text, &[])),
+        None => 0,
+    }
+}
+
+/// Post the new items of the news feeds of the running events the team plays
+///
+/// The items present during the first poll are only recorded, such that old news are not posted.
+fn post_news(
+    client: &reqwest::blocking::Client,
+    targets: &[Target],
+    store: &StateStore,
+    events: &[CtfEvent],
+    state: &State,
+    now: DateTime<Utc>,
+) {
+    for event in polled_events(events, state, now) {
+        let url = news_url(event).expect("Polled events have a news URL");
+        let items = match fetch_news(client, url) {
+            Ok(items) => items,
+            Err(err) => {
+                error!("Couldn't fetch the news of event {}: {}", event.id(), err);
+                continue;
+            }
+        };
+        let seen = state
+            .events
+            .get(&event.id())
+            .and_then(|record| record.news_seen.as_ref());
+        if let Some(seen) = seen {
+            // Feeds list the newest item first
+            for item in items.iter().rev().filter(|item| !seen.contains(&item.id)) {
+                info!("Posting news item {} of event {}", item.id, event.id());
+                send(
+                    client,
+                    targets,
+                    &Notification::text(news_message(event, item), &[event.id()]),
+                );
+            }
+        }
+        if let Err(err) = store.update(|state| {
+            state
+                .events
+                .entry(event.id())
+                .or_default()
+                .news_seen
+                .get_or_insert_with(Default::default)
+                .extend(items.into_iter().map(|item| item.id));
+        }) {
+            error!("Couldn't write state file: {}", err)
+        }
+    }
+}
+
+/// Post the recap of the season once it is due, returns the number of failed deliveries
+///
+/// The recap is only marked as posted once the data was fetched, such that a failed fetch is retried.
+fn post_season_recap(
+    client: &reqwest::blocking::Client,
+    targets: &[Target],
+    store: &StateStore,
+) -> usize {
+    let team_id = match CONFIG.team_id {
+        Some(team_id) if CONFIG.season_recap => team_id,
+        _ => return 0,
+    };
+    let last_recap = match store.read() {
+        Ok(state) => state.last_recap,
+        Err(err) => {
+            error!("Couldn't read state file: {}", err);
+            return 0;
+        }
+    };
+    let today = local_today(Utc::now(), CONFIG.timezone);
+    let season = match CONFIG.recap_date.due_season(today, last_recap) {
+        Some(season) => season,
+        None => return 0,
+    };
+    let (results, ratings) = match (
+        fetch_results(client, season, team_id),
+        fetch_ratings(client, team_id),
+    ) {
+        (Ok(results), Ok(ratings)) => (results, ratings),
+        (Err(err), _) | (_, Err(err)) => {
+            error!("Couldn't fetch the data for the season recap: {}", err);
+            return 0;
+        }
+    };
+    if let Err(err) = store.update(|state| state.last_recap = Some(season)) {
+        error!("Couldn't write state file: {}", err);
+        return 0;
+    }
+    info!("Posting the recap of the season {}", season);
+    let text = recap_message(season, &results, &ratings);
+    send(client, targets, &Notification::text(text, &[]))
+}
+
+/// Post the top 10 teams once per week on `LEADERBOARD_WEEKDAY`, returns the number of failed deliveries
+fn post_weekly_leaderboard(
+    client: &reqwest::blocking::Client,
+    targets: &[Target],
+    store: &StateStore,
+) -> usize {
+    let weekday = match CONFIG.leaderboard_weekday {
+        Some(weekday) => weekday,
+        None => return 0,
+    };
+    let last = match store.read() {
+        Ok(state) => state.last_leaderboard,
+        Err(err) => {
+            error!("Couldn't read state file: {}", err);
+            return 0;
+        }
+    };
+    let today = local_today(Utc::now(), CONFIG.timezone);
+    if !leaderboard_due(weekday, today, last) {
+        return 0;
+    }
+    let country = CONFIG.leaderboard_country.as_deref();
+    let top = match fetch_leaderboard(client, None, country) {
+        Ok(top) => top,
+        Err(err) => {
+            error!("Couldn't fetch the leaderboard: {}", err);
+            return 0;
+        }
+    };
+    if let Err(err) = store.update(|state| state.last_leaderboard = Some(today)) {
+        error!("Couldn't write state file: {}", err);
+        return 0;
+    }
+    info!("Posting the weekly leaderboard");
+    let text = leaderboard_message(&top, &leaderboard_scope(None, country), CONFIG.team_id);
+    send(client, targets, &Notification::text(text, &[]))
+}
+
+/// Send the due personal reminders as direct messages, see [`direct_message_target`]
+fn send_personal_reminders(
+    client: &reqwest::blocking::Client,
+    targets: &[Target],
+    store: &StateStore,
+    events: &[CtfEvent],
+    state: &State,
+    now: DateTime<Utc>,
+) {
+    let due = due_personal_reminders(events, state, now);
+    if due.is_empty() {
+        return;
+    }
+    let target = match direct_message_target(targets) {
+        Some(target) => target,
+        None => {
+            warn!("No Mattermost target for the personal reminders");
+            return;
+        }
+    };
+    for (event_id, user_name) in due {
+        let event = match events.iter().find(|event| event.id() == event_id) {
+            Some(event) => event,
+            None => continue,
+        };
+        info!(
+            "Sending personal reminder for event {} to {}",
+            event_id, user_name
+        );
+        let text = personal_reminder(event, now);
+        if let Err(err) = send_direct(client, target, &user_name, text, event_id) {
+            error!("Couldn't send personal reminder: {}", err);
+            continue;
+        }
+        if let Err(err) = store.update(|state| {
+            state
+                .events
+                .entry(event_id)
+                .or_default()
+                .personal_reminders
+                .insert(user_name);
+        }) {
+            error!("Couldn't write state file: {}", err)
+        }
+    }
+}
+
+/// Notify users about new events matching their keyword subscriptions, see [`direct_message_target`]
+fn send_keyword_notifications(
+    client: &reqwest::blocking::Client,
+    targets: &[Target],
+    store: &StateStore,
+    events: &[CtfEvent],
+    state: &State,
+    now: DateTime<Utc>,
+) {
+    let due = due_keyword_notifications(events, state, now);
+    if due.is_empty() {
+        return;
+    }
+    let target = match direct_message_target(targets) {
+        Some(target) => target,
+        None => {
+            warn!("No Mattermost target for the keyword notifications");
+            return;
+        }
+    };
+    for (event_id, user_name, keyword) in due {
+        let event = match events.iter().find(|event| event.id() == event_id) {
+            Some(event) => event,
+            None => continue,
+        };
+        info!(
+            "Notifying {} about event {} matching “{}”",
+            user_name, event_id, keyword
+        );
+        let text = keyword_notification(event, &keyword);
+        if let Err(err) = send_direct(client, target, &user_name, text, event_id) {
+            error!("Couldn't send keyword notification: {}", err);
+            continue;
+        }
+        if let Err(err) = store.update(|state| {
+            state
+                .events
+                .entry(event_id)
+                .or_default()
+                .keyword_notified
+                .insert(user_name);
+        }) {
+            error!("Couldn't write state file: {}", err)
+        }
+    }
+}
+
+/// Post the simplified announcement of the major `events` to the broadcast targets
+///
+/// Returns the number of failed deliveries.
+fn send_broadcasts(
+    client: &reqwest::blocking::Client,
+    targets: &[Target],
+    events: &[&CtfEvent],
+) -> usize {
+    let mut failed = 0;
+    for target in targets {
+        let message = match target
+            .broadcast
+            .as_ref()
+            .and_then(|broadcast| broadcast.message(events, CONFIG.timezone))
+        {
+            Some(message) => message,
+            None => continue,
+        };
+        if let Err(err) = post(client, &RENDERERS, target, message) {
+            error!(
+                "Couldn't post to {}: {}",
+                target.webhook_url.host_str().unwrap_or_default(),
+                err
+            );
+            failed += 1;
+        }
+    }
+    failed
+}
+
+/// Send `text` about the event as a direct message to `user_name`
+fn send_direct(
+    client: &reqwest::blocking::Client,
+    target: &Target,
+    user_name: &str,
+    text: String,
+    event_id: usize,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let message = Notification::text(text, &[event_id]).message;
+    post_direct(client, &RENDERERS, target, user_name, message)
+}
+
+/// A notification rendered for all backends
+struct Notification {
+    /// Short summary, used by backends with a separate title
+    title: String,
+    message: Message,
+    markdown: String,
+    plain_text: String,
+}
+
+impl Notification {
+    /// Create a notification with only text, e.g., for alerts and reminders
+    fn text(text: String, event_ids: &[usize]) -> Self {
+        Self {
+            title: "Upcoming CTFs".to_string(),
+            message: Message {
+                username: Some("Upcoming CTFs".to_string()),
+                text: Some(text.clone()),
+                props: Some(post_metadata(event_ids)),
+                ..Default::default()
+            },
+            plain_text: markdown_to_plain_text(&text),
+            markdown: text,
+        }
+    }
+}
+
+/// Post the notification to all targets and backends
+///
+/// Broadcast targets are skipped, see [`send_broadcasts`].
+/// Returns the number of failed deliveries.
+fn send(
+    client: &reqwest::blocking::Client,
+    targets: &[Target],
+    notification: &Notification,
+) -> usize {
+    let mut failed = 0;
+    for target in targets.iter().filter(|target| target.broadcast.is_none()) {
+        if let Err(err) = post(client, &RENDERERS, target, notification.message.clone()) {
+            error!(
+                "Couldn't post to {}: {}",
+                target.webhook_url.host_str().unwrap_or_default(),
+                err
+            );
+            failed += 1;
+        }
+    }
+    if let Some(ref signal) = CONFIG.signal {
+        if let Err(err) = timed("Sending the Signal message", || {
+            signal.send(client, &notification.plain_text)
+        }) {
+            error!("Couldn't send Signal message: {}", err);
+            failed += 1;
+        }
+    }
+    if let Some(ref matrix) = CONFIG.matrix {
+        if let Err(err) = timed("Sending the Matrix message", || {
+            matrix.send(client, &notification.markdown, &notification.plain_text)
+        }) {
+            error!("Couldn't send Matrix message: {}", err);
+            failed += 1;
+        }
+    }
+    if let Some(ref apprise) = CONFIG.apprise {
+        let res = timed("Sending the Apprise notification", || {
+            apprise.send(
+                client,
+                &notification.title,
+                &notification.markdown,
+                &notification.plain_text,
+            )
+        });
+        if let Err(err) = res {
+            error!("Couldn't send Apprise notification: {}", err);
+            failed += 1;
+        }
     }
+    failed
 }
@@ -0,0 +1,144 @@
+//! Post the digest to Mastodon or other servers implementing the Mastodon API
+//!
+//! The digest becomes a thread: a short introduction, followed by one reply per event with its logo and hashtags.
+//! Only the digest is posted, alerts and reminders are meant for the team and stay private.
+
+use crate::{compact::compact_text, digest::Digest, mattermost_hook_api::Url, timed, CtfEvent};
+use log::warn;
+use reqwest::blocking::{multipart, Client};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Maximal length of a status on most servers
+const MAX_STATUS_LENGTH: usize = 500;
+
+/// Configuration of the Mastodon backend, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct MastodonConfig {
+    /// Base URL of the server, e.g., `https://infosec.exchange`
+    pub instance: Url,
+    /// Access token of the account with the `write:statuses` and `write:media` scopes
+    pub access_token: String,
+    /// Visibility of the statuses, one of `public`, `unlisted`, `private`, or `direct`
+    #[serde(default = "default_visibility")]
+    pub visibility: String,
+    /// Hashtags added to every event, without the `#`
+    #[serde(default = "default_hashtags")]
+    pub hashtags: Vec<String>,
+}
+
+fn default_visibility() -> String {
+    "public".to_string()
+}
+
+fn default_hashtags() -> Vec<String> {
+    vec!["CTF".to_string()]
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+impl MastodonConfig {
+    fn api(&self, path: &str) -> String {
+        format!(
+            "{}/api/{}",
+            self.instance.as_str().trim_end_matches('/'),
+            path
+        )
+    }
+
+    /// Post a status and return its id
+    fn post_status(
+        &self,
+        client: &Client,
+        text: &str,
+        in_reply_to: Option<&str>,
+        media_id: Option<&str>,
+    ) -> Result<String, BoxError> {
+        let mut status = json!({
+            "status": text,
+            "visibility": self.visibility,
+            "sensitive": false,
+        });
+        if let Some(id) = in_reply_to {
+            status["in_reply_to_id"] = id.into();
+        }
+        if let Some(id) = media_id {
+            status["media_ids"] = json!([id]);
+        }
+        let created: Value = client
+            .post(&self.api("v1/statuses"))
+            .bearer_auth(&self.access_token)
+            .json(&status)
+            .send()?
+            .error_for_status()?
+            .json()?;
+        Ok(created["id"].as_str().unwrap_or_default().to_string())
+    }
+
+    /// Download the logo of the event and upload it as media attachment
+    fn upload_logo(&self, client: &Client, event: &CtfEvent) -> Result<Option<String>, BoxError> {
+        let logo_url = match event.logo_url() {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+        let logo = client.get(logo_url).send()?.error_for_status()?;
+        let mime = logo
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|mime| mime.to_str().ok())
+            .unwrap_or("image/png")
+            .to_string();
+        let part = multipart::Part::bytes(logo.bytes()?.to_vec())
+            .file_name("logo")
+            .mime_str(&mime)?;
+        let form = multipart::Form::new()
+            .part("file", part)
+            .text("description", format!("Logo of {}", event.title()));
+        let media: Value = client
+            .post(&self.api("v2/media"))
+            .bearer_auth(&self.access_token)
+            .multipart(form)
+            .send()?
+            .error_for_status()?
+            .json()?;
+        Ok(media["id"].as_str().map(str::to_string))
+    }
+
+    /// Post `text` as a single status, shortened to the maximal length
+    pub fn post_text(&self, client: &Client, text: &str) -> Result<(), BoxError> {
+        let text: String = text.chars().take(MAX_STATUS_LENGTH).collect();
+        timed("Posting to Mastodon", || {
+            self.post_status(client, &text, None, None)
+        })?;
+        Ok(())
+    }
+
+    /// Post the digest as a thread with one status per event
+    pub fn post_digest(&self, client: &Client, digest: &Digest) -> Result<(), BoxError> {
+        let intro = format!(
+            "{}: {} events in the next days\n{}",
+            digest.heading(),
+            digest.events.len(),
+            digest.link
+        );
+        let mut previous = timed("Posting to Mastodon", || {
+            self.post_status(client, &intro, None, None)
+        })?;
+        for event in &digest.events {
+            // A missing logo should not prevent the status
+            let media_id = self.upload_logo(client, event).unwrap_or_else(|err| {
+                warn!("Couldn't upload the logo of event {}: {}", event.id(), err);
+                None
+            });
+            previous = timed("Posting to Mastodon", || {
+                self.post_status(
+                    client,
+                    &compact_text(event, &self.hashtags, MAX_STATUS_LENGTH),
+                    Some(&previous),
+                    media_id.as_deref(),
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
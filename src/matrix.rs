@@ -0,0 +1,129 @@
+//! Send messages to a Matrix room using the client-server API
+//!
+//! The messages are sent as `m.notice`, such that other bots do not react to them.
+//! The Markdown of the messages is converted to the HTML subset supported by Matrix clients.
+
+use crate::{mattermost_hook_api::Url, RUN_ID};
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+lazy_static! {
+    static ref RE_LINK: Regex = Regex::new(r"\[(?P<text>[^\]]*)\]\((?P<url>[^)]*)\)").unwrap();
+    static ref RE_BOLD: Regex = Regex::new(r"\*\*(?P<text>[^*]+)\*\*").unwrap();
+    static ref RE_ITALIC: Regex = Regex::new(r"\b_(?P<text>[^_]+)_\b").unwrap();
+}
+
+/// Counter for the transaction ids, which must be unique per access token
+static TRANSACTION: AtomicUsize = AtomicUsize::new(0);
+
+/// Configuration of the Matrix backend, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct MatrixConfig {
+    /// Base URL of the homeserver, e.g., `https://matrix.example.com`
+    pub homeserver: Url,
+    /// Access token of the bot account, which must have joined the room
+    pub access_token: String,
+    /// Internal id of the room, e.g., `!abcdef:example.com`
+    pub room_id: String,
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Convert the Markdown used by the bot into HTML
+///
+/// Supports links, bold and italic text, headings, and lists.
+pub fn markdown_to_html(markdown: &str) -> String {
+    let mut html = String::with_capacity(markdown.len() * 2);
+    let mut in_list = false;
+    for line in markdown.lines() {
+        let line = escape_html(line);
+        let line = RE_LINK.replace_all(&line, r#"<a href="$url">$text</a>"#);
+        let line = RE_BOLD.replace_all(&line, "<strong>$text</strong>");
+        let line = RE_ITALIC.replace_all(&line, "<em>$text</em>");
+        if let Some(item) = line.strip_prefix("- ") {
+            if !in_list {
+                html += "<ul>";
+                in_list = true;
+            }
+            html += &format!("<li>{}</li>", item);
+            continue;
+        }
+        if in_list {
+            html += "</ul>";
+            in_list = false;
+        }
+        if let Some(heading) = line.strip_prefix("### ") {
+            html += &format!("<h3>{}</h3>", heading);
+        } else if !line.is_empty() {
+            html += &line;
+            html += "<br>";
+        }
+    }
+    if in_list {
+        html += "</ul>";
+    }
+    html
+}
+
+impl MatrixConfig {
+    /// Send the message to the room, `plain_text` is shown by clients without HTML support
+    pub fn send(
+        &self,
+        client: &Client,
+        markdown: &str,
+        plain_text: &str,
+    ) -> Result<(), reqwest::Error> {
+        let mut url = self.homeserver.clone();
+        let transaction = format!(
+            "ctftimebot-{}-{}",
+            *RUN_ID,
+            TRANSACTION.fetch_add(1, Ordering::Relaxed)
+        );
+        url.path_segments_mut()
+            .expect("The homeserver URL is a base URL")
+            .pop_if_empty()
+            .extend(&[
+                "_matrix",
+                "client",
+                "v3",
+                "rooms",
+                self.room_id.as_str(),
+                "send",
+                "m.room.message",
+                transaction.as_str(),
+            ]);
+        client
+            .put(url)
+            .bearer_auth(&self.access_token)
+            .json(&json!({
+                "msgtype": "m.notice",
+                "body": plain_text,
+                "format": "org.matrix.custom.html",
+                "formatted_body": markdown_to_html(markdown),
+            }))
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_markdown_to_html() {
+    assert_eq!(
+        markdown_to_html("[Upcoming CTFs](https://ctftime.org/event/list/upcoming)\n\n### [X-MAS CTF 2018](https://ctftime.org/event/724/)\n**Rating**: 24\n_Note: clocks change during this event_"),
+        r#"<a href="https://ctftime.org/event/list/upcoming">Upcoming CTFs</a><br><h3><a href="https://ctftime.org/event/724/">X-MAS CTF 2018</a></h3><strong>Rating</strong>: 24<br><em>Note: clocks change during this event</em><br>"#
+    );
+    assert_eq!(
+        markdown_to_html("Writeups\n- [ ] web\n- [ ] <pwn>\ndone"),
+        "Writeups<br><ul><li>[ ] web</li><li>[ ] &lt;pwn&gt;</li></ul>done<br>"
+    );
+}
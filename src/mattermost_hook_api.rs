@@ -2,8 +2,9 @@
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use serde_with::serde_as;
-use std::collections::HashMap;
+use serde_with::{serde_as, DeserializeFromStr, SerializeDisplay};
+use std::{collections::HashMap, fmt, str::FromStr, sync::Arc};
+pub use url::Url;
 
 /// Incoming webhooks let you POST some data to a Mattermost endpoint to create a message in a channel.
 #[serde_with::skip_serializing_none]
@@ -38,7 +39,7 @@ pub struct Message {
     /// Must be enabled [in the configuration][Mattermost-icon].
     ///
     /// [Mattermost-icon]: https://docs.mattermost.com/administration/config-settings.html#enable-integrations-to-override-profile-picture-icons
-    pub icon_url: Option<String>,
+    pub icon_url: Option<Url>,
     /// Overrides the profile picture and icon_url parameter.
     ///
     /// Defaults to none and is not set during webhook creation.
@@ -86,10 +87,10 @@ pub struct Attachment {
     ///
     /// This is used in notifications, and in clients that don’t support formatted text (e.g. IRC).
     pub fallback: String,
-    /// A hex color code that will be used as the left border color for the attachment.
+    /// A [`Color`] that will be used as the left border color for the attachment.
     ///
     /// If not specified, it will default to match the left hand sidebar header background color.
-    pub color: Option<String>,
+    pub color: Option<Color>,
     /// An optional line of text that will be shown above the attachment.
     pub pretext: Option<String>,
     /// The text to be included in the attachment.
@@ -108,9 +109,9 @@ pub struct Attachment {
     /// If no [`author_name`] is specified, this field does nothing.
     ///
     /// [`author_name`]: Attachment::author_name
-    pub author_link: Option<String>,
+    pub author_link: Option<Url>,
     /// An optional URL used to display a 16x16 pixel icon beside the [`author_name`][Attachment::author_name].
-    pub author_icon: Option<String>,
+    pub author_icon: Option<Url>,
     /// An optional title displayed below the author information in the attachment.
     pub title: Option<String>,
     /// An optional URL used to hyperlink the [`title`].
@@ -118,29 +119,179 @@ pub struct Attachment {
     /// If no [`title`] is specified, this field does nothing.
     ///
     /// [`title`]: Attachment::title
-    pub title_link: Option<String>,
+    pub title_link: Option<Url>,
     /// Fields can be included as an optional array within `attachments`, and are used to display information in a table format inside the attachment.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub fields: Vec<Field>,
     /// An optional URL to an image file (GIF, JPEG, PNG, BMP, or SVG) that is displayed inside a message attachment.
     ///
     /// Large images are resized to a maximum width of 400px or a maximum height of 300px, while still maintaining the original aspect ratio.
-    pub image_url: Option<String>,
+    pub image_url: Option<Url>,
     /// An optional URL to an image file (GIF, JPEG, PNG, BMP, or SVG) that is displayed as a 75x75 pixel thumbnail on the right side of an attachment.
     ///
     /// We recommend using an image that is already 75x75 pixels, but larger images will be scaled down with the aspect ratio maintained.
-    pub thumb_url: Option<String>,
+    pub thumb_url: Option<Url>,
     /// An optional line of text that will be displayed at the bottom of the attachment.
     ///
     /// Footers with more than 300 characters will be truncated with an ellipsis (`…`).
     pub footer: Option<String>,
     /// An optional URL to an image file (GIF, JPEG, PNG, BMP, or SVG) that is displayed as a 16x16 pixel thumbnail before the footer text.
-    pub footer_icon: Option<String>,
+    pub footer_icon: Option<Url>,
     /// Actions make webhook messages interactive
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub actions: Vec<Action>,
 }
 
+/// A color in the hex notation, e.g. `#0099e1` or `#09e`
+///
+/// The value is validated while parsing, such that invalid colors are detected when loading the configuration.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, DeserializeFromStr, SerializeDisplay)]
+pub struct HexColor(Arc<str>);
+
+/// Error while parsing a [`HexColor`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidHexColor(String);
+
+impl fmt::Display for InvalidHexColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid color `{}`, expected a `#` followed by 3 or 6 hex digits, e.g. `#0099e1`",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidHexColor {}
+
+impl FromStr for HexColor {
+    type Err = InvalidHexColor;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s
+            .strip_prefix('#')
+            .ok_or_else(|| InvalidHexColor(s.to_string()))?;
+        if (digits.len() == 3 || digits.len() == 6) && digits.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            Ok(HexColor(s.into()))
+        } else {
+            Err(InvalidHexColor(s.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for HexColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl HexColor {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+#[test]
+fn test_parse_hex_color() {
+    assert_eq!("#0099e1".parse::<HexColor>().unwrap().as_str(), "#0099e1");
+    assert_eq!("#FFF".parse::<HexColor>().unwrap().as_str(), "#FFF");
+    assert!("0099e1".parse::<HexColor>().is_err());
+    assert!("#0099e".parse::<HexColor>().is_err());
+    assert!("#0099eg".parse::<HexColor>().is_err());
+    assert!(serde_json::from_str::<HexColor>(r#""red""#).is_err());
+}
+
+/// Color of an [`Attachment`]
+///
+/// Besides hex colors, Mattermost and Slack understand the names `good`, `warning`, and `danger`.
+/// [`Color::Theme`] is serialized as an empty string, which makes the client fall back to the theme color.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, DeserializeFromStr, SerializeDisplay)]
+pub enum Color {
+    /// Green
+    Good,
+    /// Yellow
+    Warning,
+    /// Red
+    Danger,
+    /// Use the default color of the theme, i.e., the sidebar header background color
+    Theme,
+    Hex(HexColor),
+}
+
+/// Error while parsing a [`Color`]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidColor(String);
+
+impl fmt::Display for InvalidColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid color `{}`, expected one of `good`, `warning`, `danger`, `theme`, or a hex color like `#0099e1`",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidColor {}
+
+impl FromStr for Color {
+    type Err = InvalidColor;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "good" => Ok(Color::Good),
+            "warning" => Ok(Color::Warning),
+            "danger" => Ok(Color::Danger),
+            "" | "theme" | "default" => Ok(Color::Theme),
+            _ => s
+                .parse()
+                .map(Color::Hex)
+                .map_err(|_| InvalidColor(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Color::Good => f.write_str("good"),
+            Color::Warning => f.write_str("warning"),
+            Color::Danger => f.write_str("danger"),
+            Color::Theme => Ok(()),
+            Color::Hex(color) => fmt::Display::fmt(color, f),
+        }
+    }
+}
+
+impl From<HexColor> for Color {
+    fn from(color: HexColor) -> Self {
+        Color::Hex(color)
+    }
+}
+
+#[test]
+fn test_parse_color() {
+    assert_eq!("good".parse::<Color>().unwrap(), Color::Good);
+    assert_eq!("Danger".parse::<Color>().unwrap(), Color::Danger);
+    assert_eq!("theme".parse::<Color>().unwrap(), Color::Theme);
+    assert_eq!(
+        "#0099e1".parse::<Color>().unwrap(),
+        Color::Hex("#0099e1".parse().unwrap())
+    );
+    assert!("blue".parse::<Color>().is_err());
+
+    assert_eq!(
+        serde_json::to_string(&Color::Warning).unwrap(),
+        r#""warning""#
+    );
+    assert_eq!(serde_json::to_string(&Color::Theme).unwrap(), r#""""#);
+    assert_eq!(
+        serde_json::from_str::<Color>(r##""#da5422""##).unwrap(),
+        Color::Hex("#da5422".parse().unwrap())
+    );
+}
+
 /// Fields can be included as an optional array within [`attachments`][Attachment], and are used to display information in a table format inside the attachment.
 #[serde_with::skip_serializing_none]
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -271,6 +422,74 @@ impl From<Message> for PostUpdate {
     }
 }
 
+/// Request sent by Mattermost when a user invokes a slash command
+///
+/// Mattermost sends the fields form encoded. Only the used fields are extracted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CommandRequest {
+    /// Token of the slash command, used to verify the request
+    pub token: String,
+    pub channel_id: String,
+    pub user_id: String,
+    pub user_name: String,
+    /// The text following the command
+    pub text: String,
+}
+
+impl CommandRequest {
+    /// Parse the form encoded request body
+    pub fn from_form(body: &[u8]) -> Self {
+        let mut request = Self::default();
+        for (key, value) in url::form_urlencoded::parse(body) {
+            match &*key {
+                "token" => request.token = value.into_owned(),
+                "channel_id" => request.channel_id = value.into_owned(),
+                "user_id" => request.user_id = value.into_owned(),
+                "user_name" => request.user_name = value.into_owned(),
+                "text" => request.text = value.into_owned(),
+                _ => {}
+            }
+        }
+        request
+    }
+}
+
+/// Response to a [`CommandRequest`]
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CommandResponse {
+    /// `ephemeral` shows the response only to the user, `in_channel` to everyone
+    pub response_type: Option<String>,
+    pub text: String,
+}
+
+impl CommandResponse {
+    /// A response only visible to the user invoking the command
+    pub fn ephemeral(text: String) -> Self {
+        Self {
+            response_type: Some("ephemeral".to_string()),
+            text,
+        }
+    }
+}
+
+#[test]
+fn test_parse_command_request() {
+    let request = CommandRequest::from_form(
+        b"channel_id=abc&command=%2Fctftime&team_domain=team&text=quiet+22-7&token=secret&user_id=u1&user_name=alice",
+    );
+    assert_eq!(
+        request,
+        CommandRequest {
+            token: "secret".to_string(),
+            channel_id: "abc".to_string(),
+            user_id: "u1".to_string(),
+            user_name: "alice".to_string(),
+            text: "quiet 22-7".to_string(),
+        }
+    );
+}
+
 #[test]
 fn test_convert_message_to_update() {
     let mut msg = Message {
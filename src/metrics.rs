@@ -0,0 +1,110 @@
+//! Metrics of a single run in cron mode
+//!
+//! Cron runs are too short-lived to be scraped, so the metrics are pushed to a Prometheus pushgateway at the end of the run.
+
+use crate::{mattermost_hook_api::Url, CONFIG};
+use chrono::{DateTime, Utc};
+use std::{fmt::Write, time::Duration};
+
+/// Job name used in the pushgateway
+const JOB: &str = "ctftimebot";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RunMetrics {
+    /// Number of events received from CTFtime
+    pub events_fetched: usize,
+    /// Number of events in the digest
+    pub events_announced: usize,
+    /// Number of alerts about changed events
+    pub alerts_sent: usize,
+    /// Number of posts which could not be delivered to a target or backend
+    pub failed_deliveries: usize,
+    pub duration: Duration,
+    /// End of the run
+    pub finished: Option<DateTime<Utc>>,
+}
+
+impl RunMetrics {
+    /// Render the metrics in the Prometheus text format
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let mut gauge = |name: &str, help: &str, value: f64| {
+            let _ = write!(
+                text,
+                "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}\n",
+                name = name,
+                help = help,
+                value = value
+            );
+        };
+        gauge(
+            "ctftimebot_events_fetched",
+            "Number of events received from CTFtime",
+            self.events_fetched as f64,
+        );
+        gauge(
+            "ctftimebot_events_announced",
+            "Number of events in the digest",
+            self.events_announced as f64,
+        );
+        gauge(
+            "ctftimebot_alerts_sent",
+            "Number of alerts about changed events",
+            self.alerts_sent as f64,
+        );
+        gauge(
+            "ctftimebot_failed_deliveries",
+            "Number of posts which could not be delivered",
+            self.failed_deliveries as f64,
+        );
+        gauge(
+            "ctftimebot_run_duration_seconds",
+            "Duration of the run",
+            self.duration.as_secs_f64(),
+        );
+        if let Some(finished) = self.finished {
+            gauge(
+                "ctftimebot_last_run_timestamp_seconds",
+                "End of the last run as Unix timestamp",
+                finished.timestamp() as f64,
+            );
+        }
+        text
+    }
+
+    /// Replace the metrics of this job in the pushgateway at `url`
+    ///
+    /// The metrics are grouped by the [filter profile][crate::Config::filter_profile], if any.
+    pub fn push(&self, client: &reqwest::blocking::Client, url: &Url) -> reqwest::Result<()> {
+        let mut endpoint = format!("{}/metrics/job/{}", url.as_str().trim_end_matches('/'), JOB);
+        if let Some(ref profile) = CONFIG.filter_profile {
+            endpoint += "/profile/";
+            endpoint.extend(url::form_urlencoded::byte_serialize(profile.as_bytes()));
+        }
+        client
+            .put(&endpoint)
+            .header(reqwest::header::CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(self.to_text())
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_metrics_text() {
+    let metrics = RunMetrics {
+        events_fetched: 30,
+        events_announced: 5,
+        alerts_sent: 1,
+        failed_deliveries: 0,
+        duration: Duration::from_millis(1500),
+        finished: Some("2024-03-12T09:00:00Z".parse().unwrap()),
+    };
+    let text = metrics.to_text();
+    assert!(text.contains(
+        "# HELP ctftimebot_events_fetched Number of events received from CTFtime\n# TYPE ctftimebot_events_fetched gauge\nctftimebot_events_fetched 30\n"
+    ));
+    assert!(text.contains("\nctftimebot_run_duration_seconds 1.5\n"));
+    assert!(text.ends_with("\nctftimebot_last_run_timestamp_seconds 1710234000\n"));
+}
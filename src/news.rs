@@ -0,0 +1,200 @@
+//! Organizer announcements during played events, e.g., new services, patches, or hints
+//!
+//! The daemon polls the `live_feed` of running events, or the news URL configured for the event.
+//! The format of the feed is detected from its content: RSS, Atom, JSON Feed, or a JSON list of items.
+//! Items are identified by their id or link, such that each item is only posted once.
+
+use crate::{state::State, timed, CtfEvent, BASE_URL, CONFIG};
+use chrono::{DateTime, Utc};
+use reqwest::blocking::Client;
+use serde_json::Value;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A single announcement of the feed
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NewsItem {
+    /// Identifies the item for the deduplication
+    pub id: String,
+    pub title: String,
+    pub link: Option<String>,
+}
+
+fn xml_items(xml: &str) -> Result<Vec<NewsItem>, BoxError> {
+    let document = roxmltree::Document::parse(xml)?;
+    let child_text = |node: roxmltree::Node, name: &str| {
+        node.children()
+            .find(|child| child.tag_name().name() == name)
+            .and_then(|child| child.text())
+            .map(|text| text.trim().to_string())
+    };
+    Ok(document
+        .descendants()
+        .filter(|node| matches!(node.tag_name().name(), "item" | "entry"))
+        .filter_map(|node| {
+            // Atom links are stored in the `href` attribute
+            let link = node
+                .children()
+                .find(|child| child.tag_name().name() == "link")
+                .and_then(|link| link.attribute("href").or_else(|| link.text()))
+                .map(|link| link.trim().to_string());
+            let title = child_text(node, "title")
+                .or_else(|| child_text(node, "description"))
+                .unwrap_or_default();
+            let id = child_text(node, "guid")
+                .or_else(|| child_text(node, "id"))
+                .or_else(|| link.clone())
+                .or_else(|| Some(title.clone()).filter(|title| !title.is_empty()))?;
+            Some(NewsItem { id, title, link })
+        })
+        .collect())
+}
+
+fn json_items(json: &str) -> Result<Vec<NewsItem>, BoxError> {
+    let value: Value = serde_json::from_str(json)?;
+    let items = match value {
+        // JSON Feed stores the items in `items`
+        Value::Object(mut feed) => match feed.remove("items") {
+            Some(Value::Array(items)) => items,
+            _ => return Err("JSON feed without `items`".into()),
+        },
+        Value::Array(items) => items,
+        _ => return Err("JSON feed is neither an object nor a list".into()),
+    };
+    let string = |item: &Value, keys: &[&str]| {
+        keys.iter().find_map(|key| match &item[key] {
+            Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+    };
+    Ok(items
+        .iter()
+        .filter_map(|item| {
+            let title = string(
+                item,
+                &["title", "content_text", "text", "message", "summary"],
+            )
+            .unwrap_or_default();
+            let link = string(item, &["url", "link"]);
+            let id = string(item, &["id", "guid"])
+                .or_else(|| link.clone())
+                .or_else(|| Some(title.clone()).filter(|title| !title.is_empty()))?;
+            Some(NewsItem { id, title, link })
+        })
+        .collect())
+}
+
+/// Parse the feed, detecting whether it is XML or JSON
+pub fn parse_feed(body: &str) -> Result<Vec<NewsItem>, BoxError> {
+    let body = body.trim_start_matches('\u{feff}').trim_start();
+    if body.starts_with('<') {
+        xml_items(body)
+    } else if body.starts_with('{') || body.starts_with('[') {
+        json_items(body)
+    } else {
+        Err("unknown feed format, expected RSS, Atom, or JSON".into())
+    }
+}
+
+/// URL of the news of the event, the configured news URL or the live feed
+///
+/// The live pages of CTFtime are HTML and not used.
+pub fn news_url(event: &CtfEvent) -> Option<&str> {
+    CONFIG
+        .event_settings(event.id())
+        .and_then(|settings| settings.news_url.as_ref())
+        .map(|url| url.as_str())
+        .or_else(|| event.live_feed().filter(|url| !url.starts_with(BASE_URL)))
+}
+
+/// Running events the team plays, whose news are polled
+pub fn polled_events<'a>(
+    events: &'a [CtfEvent],
+    state: &State,
+    now: DateTime<Utc>,
+) -> Vec<&'a CtfEvent> {
+    events
+        .iter()
+        .filter(|event| event.start_date() <= now && now < event.finish_date())
+        .filter(|event| {
+            CONFIG.is_playing(event.id())
+                || state
+                    .events
+                    .get(&event.id())
+                    .map_or(false, |record| !record.rsvps.is_empty())
+        })
+        .filter(|event| news_url(event).is_some())
+        .collect()
+}
+
+/// Fetch the items of the feed at `url`
+pub fn fetch_news(client: &Client, url: &str) -> Result<Vec<NewsItem>, BoxError> {
+    let body = timed("Fetching the news", || {
+        client.get(url).send()?.error_for_status()?.text()
+    })?;
+    parse_feed(&body)
+}
+
+/// Text of the post announcing the item
+pub fn news_message(event: &CtfEvent, item: &NewsItem) -> String {
+    let mut text = format!(
+        "📣 News from [{}]({}): {}",
+        event.display_title(),
+        event.ctftime_url(),
+        item.title
+    );
+    if let Some(ref link) = item.link {
+        text += &format!(" — {}", link);
+    }
+    text
+}
+
+#[test]
+fn test_parse_feed() {
+    let rss = r#"<?xml version="1.0"?>
+        <rss version="2.0"><channel><title>CTF</title>
+            <item><title>Service "bank" released</title><link>https://ctf.example.com/news/2</link><guid>news-2</guid></item>
+            <item><title>Welcome</title><link>https://ctf.example.com/news/1</link></item>
+        </channel></rss>"#;
+    assert_eq!(
+        parse_feed(rss).unwrap(),
+        vec![
+            NewsItem {
+                id: "news-2".to_string(),
+                title: r#"Service "bank" released"#.to_string(),
+                link: Some("https://ctf.example.com/news/2".to_string()),
+            },
+            NewsItem {
+                id: "https://ctf.example.com/news/1".to_string(),
+                title: "Welcome".to_string(),
+                link: Some("https://ctf.example.com/news/1".to_string()),
+            },
+        ]
+    );
+
+    let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+        <entry><id>urn:1</id><title>Hint for pwn1</title><link href="https://ctf.example.com/hints"/></entry>
+    </feed>"#;
+    assert_eq!(
+        parse_feed(atom).unwrap(),
+        vec![NewsItem {
+            id: "urn:1".to_string(),
+            title: "Hint for pwn1".to_string(),
+            link: Some("https://ctf.example.com/hints".to_string()),
+        }]
+    );
+
+    let json_feed = r#"{"version": "https://jsonfeed.org/version/1.1", "items": [{"id": "7", "content_text": "Scoreboard frozen"}]}"#;
+    let json_list = r#"[{"id": 7, "message": "Scoreboard frozen"}]"#;
+    let expected = vec![NewsItem {
+        id: "7".to_string(),
+        title: "Scoreboard frozen".to_string(),
+        link: None,
+    }];
+    assert_eq!(parse_feed(json_feed).unwrap(), expected);
+    assert_eq!(parse_feed(json_list).unwrap(), expected);
+
+    assert!(parse_feed("Scoreboard frozen").is_err());
+    assert!(parse_feed(r#"{"news": []}"#).is_err());
+}
@@ -0,0 +1,534 @@
+//! Common interface of the backends receiving the digest
+//!
+//! Every configured backend implements [`Notifier`] and receives the same digest, i.e., the same filtered list of events.
+//! A failing backend doesn't stop the others, see [`notify_all`].
+//! Large digests are split into multiple posts for all backends, see [`Digest::split`].
+//! The text of single backends can be replaced with a template, see [`Config::templates`].
+//! Targets convert the posts with a renderer of the [`Registry`] passed to [`notifiers`].
+
+use crate::{
+    apprise::AppriseConfig,
+    config::Target,
+    digest::{markdown_to_plain_text, Digest, PostLimits},
+    mastodon::MastodonConfig,
+    matrix::MatrixConfig,
+    mattermost_hook_api::Message,
+    post_metadata,
+    render::Registry,
+    signal::SignalConfig,
+    signature, timed,
+    twitter::TwitterConfig,
+    webhook::{digest_context, render_text, TemplateWebhook},
+    xmpp::XmppConfig,
+    zulip::ZulipConfig,
+    Config,
+};
+use log::error;
+use reqwest::blocking::Client;
+use std::collections::BTreeMap;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A backend which posts the digest
+pub trait Notifier {
+    /// Name of the backend in log messages, e.g., the host of the webhook
+    fn name(&self) -> String;
+
+    /// Key of the backend in [`Config::templates`], or `None` if the text cannot be replaced
+    fn template_key(&self) -> Option<&str>;
+
+    /// Post the digest, which contains the filtered events of the run
+    fn send(&self, client: &Client, digest: &Digest) -> Result<(), BoxError>;
+
+    /// Post `text`, rendered from the template of the backend, instead of the digest
+    fn send_text(&self, client: &Client, digest: &Digest, text: &str) -> Result<(), BoxError>;
+}
+
+/// All backends of `config` which receive the digest
+///
+/// Broadcast targets are skipped, since they only receive selected events.
+/// The targets use the `renderers` for their backend.
+pub fn notifiers<'a>(
+    config: &'a Config,
+    targets: &'a [Target],
+    renderers: &'a Registry,
+) -> Vec<Box<dyn Notifier + 'a>> {
+    let mut notifiers: Vec<Box<dyn Notifier + 'a>> = Vec::new();
+    for target in targets.iter().filter(|target| target.broadcast.is_none()) {
+        notifiers.push(Box::new(TargetNotifier { target, renderers }));
+    }
+    if let Some(ref signal) = config.signal {
+        notifiers.push(Box::new(signal));
+    }
+    if let Some(ref matrix) = config.matrix {
+        notifiers.push(Box::new(matrix));
+    }
+    if let Some(ref apprise) = config.apprise {
+        notifiers.push(Box::new(apprise));
+    }
+    if let Some(ref mastodon) = config.mastodon {
+        notifiers.push(Box::new(mastodon));
+    }
+    if let Some(ref twitter) = config.twitter {
+        notifiers.push(Box::new(twitter));
+    }
+    if let Some(ref zulip) = config.zulip {
+        notifiers.push(Box::new(zulip));
+    }
+    if let Some(ref xmpp) = config.xmpp {
+        notifiers.push(Box::new(xmpp));
+    }
+    for webhook in &config.webhooks {
+        notifiers.push(Box::new(webhook));
+    }
+    notifiers
+}
+
+impl<N: Notifier + ?Sized> Notifier for &N {
+    fn name(&self) -> String {
+        (**self).name()
+    }
+
+    fn template_key(&self) -> Option<&str> {
+        (**self).template_key()
+    }
+
+    fn send(&self, client: &Client, digest: &Digest) -> Result<(), BoxError> {
+        (**self).send(client, digest)
+    }
+
+    fn send_text(&self, client: &Client, digest: &Digest, text: &str) -> Result<(), BoxError> {
+        (**self).send_text(client, digest, text)
+    }
+}
+
+/// Send the digest with all notifiers, failures are logged
+///
+/// Notifiers with an entry in `templates` send the rendered template instead.
+/// Digests exceeding the `limits` are sent as multiple posts, a notifier stops at the first failed post.
+/// Returns the number of failed deliveries.
+pub fn notify_all(
+    client: &Client,
+    notifiers: &[Box<dyn Notifier + '_>],
+    digest: &Digest,
+    templates: &BTreeMap<String, String>,
+    limits: PostLimits,
+) -> usize {
+    let parts = digest.split(limits);
+    let mut failed = 0;
+    for notifier in notifiers {
+        let template = notifier.template_key().and_then(|key| templates.get(key));
+        let res = parts.iter().try_for_each(|part| match template {
+            Some(template) => render_text(template, &digest_context(part))
+                .and_then(|text| notifier.send_text(client, part, &text)),
+            None => notifier.send(client, part),
+        });
+        if let Err(err) = res {
+            error!("Couldn't post to {}: {}", notifier.name(), err);
+            failed += 1;
+        }
+    }
+    failed
+}
+
+/// Backend whose webhooks post direct messages if the channel is set to `@user`
+const DIRECT_MESSAGE_BACKEND: &str = "mattermost";
+
+/// Post `message` to the webhook of `target`, applying the overrides of the target
+///
+/// The message is converted by the renderer of the target from `renderers`.
+pub fn post(
+    client: &Client,
+    renderers: &Registry,
+    target: &Target,
+    mut message: Message,
+) -> Result<(), BoxError> {
+    target.apply(&mut message);
+    deliver(client, renderers, target, &message)
+}
+
+/// Target for direct messages, the first Mattermost target which is not a broadcast
+pub fn direct_message_target(targets: &[Target]) -> Option<&Target> {
+    targets
+        .iter()
+        .find(|target| target.broadcast.is_none() && target.backend == DIRECT_MESSAGE_BACKEND)
+}
+
+/// Post `message` as direct message to `user_name`, via a target from [`direct_message_target`]
+pub fn post_direct(
+    client: &Client,
+    renderers: &Registry,
+    target: &Target,
+    user_name: &str,
+    mut message: Message,
+) -> Result<(), BoxError> {
+    target.apply(&mut message);
+    message.channel = Some(format!("@{}", user_name));
+    deliver(client, renderers, target, &message)
+}
+
+/// Post the `message` with the renderer of `target`, without applying the overrides
+fn deliver(
+    client: &Client,
+    renderers: &Registry,
+    target: &Target,
+    message: &Message,
+) -> Result<(), BoxError> {
+    let renderer = renderers
+        .get(&target.backend)
+        .ok_or_else(|| format!("Unknown backend `{}`", target.backend))?;
+    timed(
+        &format!(
+            "Posting to {}",
+            target.webhook_url.host_str().unwrap_or_default()
+        ),
+        || {
+            for payload in renderer.render_message(message) {
+                signature::json_body(
+                    client.post(target.webhook_url.clone()),
+                    &payload,
+                    target.signing_secret.as_deref(),
+                )?
+                .send()?
+                .error_for_status()?;
+            }
+            Ok(())
+        },
+    )
+}
+
+/// A target together with the renderers for its backend
+struct TargetNotifier<'a> {
+    target: &'a Target,
+    renderers: &'a Registry,
+}
+
+impl Notifier for TargetNotifier<'_> {
+    fn name(&self) -> String {
+        self.target
+            .webhook_url
+            .host_str()
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    fn template_key(&self) -> Option<&str> {
+        Some(&self.target.backend)
+    }
+
+    fn send(&self, client: &Client, digest: &Digest) -> Result<(), BoxError> {
+        post(client, self.renderers, self.target, digest.to_mattermost())
+    }
+
+    fn send_text(&self, client: &Client, digest: &Digest, text: &str) -> Result<(), BoxError> {
+        let message = Message {
+            username: Some(digest.title.clone()),
+            text: Some(text.to_string()),
+            props: Some(post_metadata(&digest.event_ids())),
+            ..Default::default()
+        };
+        post(client, self.renderers, self.target, message)
+    }
+}
+
+impl Notifier for SignalConfig {
+    fn name(&self) -> String {
+        "Signal".to_string()
+    }
+
+    fn template_key(&self) -> Option<&str> {
+        Some("signal")
+    }
+
+    fn send(&self, client: &Client, digest: &Digest) -> Result<(), BoxError> {
+        self.send_text(client, digest, &digest.to_plain_text())
+    }
+
+    fn send_text(&self, client: &Client, _digest: &Digest, text: &str) -> Result<(), BoxError> {
+        timed("Sending the Signal message", || {
+            SignalConfig::send(self, client, text)
+        })?;
+        Ok(())
+    }
+}
+
+impl Notifier for MatrixConfig {
+    fn name(&self) -> String {
+        "Matrix".to_string()
+    }
+
+    fn template_key(&self) -> Option<&str> {
+        Some("matrix")
+    }
+
+    fn send(&self, client: &Client, digest: &Digest) -> Result<(), BoxError> {
+        timed("Sending the Matrix message", || {
+            MatrixConfig::send(self, client, &digest.to_markdown(), &digest.to_plain_text())
+        })?;
+        Ok(())
+    }
+
+    fn send_text(&self, client: &Client, _digest: &Digest, text: &str) -> Result<(), BoxError> {
+        timed("Sending the Matrix message", || {
+            MatrixConfig::send(self, client, text, &markdown_to_plain_text(text))
+        })?;
+        Ok(())
+    }
+}
+
+impl Notifier for AppriseConfig {
+    fn name(&self) -> String {
+        "Apprise".to_string()
+    }
+
+    fn template_key(&self) -> Option<&str> {
+        Some("apprise")
+    }
+
+    fn send(&self, client: &Client, digest: &Digest) -> Result<(), BoxError> {
+        timed("Sending the Apprise notification", || {
+            AppriseConfig::send(
+                self,
+                client,
+                &digest.heading(),
+                &digest.to_markdown(),
+                &digest.to_plain_text(),
+            )
+        })?;
+        Ok(())
+    }
+
+    fn send_text(&self, client: &Client, digest: &Digest, text: &str) -> Result<(), BoxError> {
+        timed("Sending the Apprise notification", || {
+            AppriseConfig::send(
+                self,
+                client,
+                &digest.heading(),
+                text,
+                &markdown_to_plain_text(text),
+            )
+        })?;
+        Ok(())
+    }
+}
+
+impl Notifier for MastodonConfig {
+    fn name(&self) -> String {
+        "Mastodon".to_string()
+    }
+
+    fn template_key(&self) -> Option<&str> {
+        Some("mastodon")
+    }
+
+    fn send(&self, client: &Client, digest: &Digest) -> Result<(), BoxError> {
+        self.post_digest(client, digest)
+    }
+
+    fn send_text(&self, client: &Client, _digest: &Digest, text: &str) -> Result<(), BoxError> {
+        self.post_text(client, text)
+    }
+}
+
+impl Notifier for TwitterConfig {
+    fn name(&self) -> String {
+        "X".to_string()
+    }
+
+    fn template_key(&self) -> Option<&str> {
+        Some("x")
+    }
+
+    fn send(&self, client: &Client, digest: &Digest) -> Result<(), BoxError> {
+        self.post_digest(client, digest)
+    }
+
+    fn send_text(&self, client: &Client, _digest: &Digest, text: &str) -> Result<(), BoxError> {
+        self.post_text(client, text)
+    }
+}
+
+impl Notifier for ZulipConfig {
+    fn name(&self) -> String {
+        "Zulip".to_string()
+    }
+
+    fn template_key(&self) -> Option<&str> {
+        Some("zulip")
+    }
+
+    fn send(&self, client: &Client, digest: &Digest) -> Result<(), BoxError> {
+        Ok(self.post_digest(client, digest)?)
+    }
+
+    fn send_text(&self, client: &Client, _digest: &Digest, text: &str) -> Result<(), BoxError> {
+        timed("Posting to Zulip", || ZulipConfig::send(self, client, text))?;
+        Ok(())
+    }
+}
+
+impl Notifier for XmppConfig {
+    fn name(&self) -> String {
+        "XMPP".to_string()
+    }
+
+    fn template_key(&self) -> Option<&str> {
+        Some("xmpp")
+    }
+
+    fn send(&self, _client: &Client, digest: &Digest) -> Result<(), BoxError> {
+        self.post_digest(digest)
+    }
+
+    fn send_text(&self, _client: &Client, _digest: &Digest, text: &str) -> Result<(), BoxError> {
+        timed("Posting to XMPP", || XmppConfig::send(self, text))
+    }
+}
+
+impl Notifier for TemplateWebhook {
+    fn name(&self) -> String {
+        self.url.host_str().unwrap_or_default().to_string()
+    }
+
+    /// The webhook has its own template
+    fn template_key(&self) -> Option<&str> {
+        None
+    }
+
+    fn send(&self, client: &Client, digest: &Digest) -> Result<(), BoxError> {
+        self.post_digest(client, digest)
+    }
+
+    fn send_text(&self, client: &Client, digest: &Digest, _text: &str) -> Result<(), BoxError> {
+        self.post_digest(client, digest)
+    }
+}
+
+#[test]
+fn test_notify_all() {
+    use crate::CtfEvent;
+    use std::{cell::RefCell, fs::File};
+
+    /// Records the event ids and texts it receives, or fails
+    struct Recorder {
+        fail: bool,
+        received: RefCell<Vec<usize>>,
+        text: RefCell<Option<String>>,
+        posts: RefCell<usize>,
+    }
+
+    impl Notifier for Recorder {
+        fn name(&self) -> String {
+            "Recorder".to_string()
+        }
+
+        fn template_key(&self) -> Option<&str> {
+            Some(if self.fail { "failing" } else { "recorder" })
+        }
+
+        fn send(&self, _client: &Client, digest: &Digest) -> Result<(), BoxError> {
+            if self.fail {
+                return Err("unavailable".into());
+            }
+            *self.received.borrow_mut() = digest.event_ids();
+            *self.posts.borrow_mut() += 1;
+            Ok(())
+        }
+
+        fn send_text(&self, client: &Client, digest: &Digest, text: &str) -> Result<(), BoxError> {
+            *self.text.borrow_mut() = Some(text.to_string());
+            self.send(client, digest)
+        }
+    }
+
+    let json = File::open("./tests/ctfs.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let digest = Digest::new(events.iter().take(5).collect());
+    let recorders: Vec<Recorder> = [false, true, false]
+        .iter()
+        .map(|&fail| Recorder {
+            fail,
+            received: RefCell::default(),
+            text: RefCell::default(),
+            posts: RefCell::default(),
+        })
+        .collect();
+    let notifiers: Vec<Box<dyn Notifier>> = recorders
+        .iter()
+        .map(|recorder| Box::new(recorder) as Box<dyn Notifier>)
+        .collect();
+
+    let client = Client::new();
+    let limits = PostLimits {
+        max_events: 20,
+        max_length: 16000,
+    };
+    assert_eq!(
+        notify_all(&client, &notifiers, &digest, &BTreeMap::new(), limits),
+        1
+    );
+    assert_eq!(*recorders[0].received.borrow(), digest.event_ids());
+    assert_eq!(*recorders[2].received.borrow(), digest.event_ids());
+    assert!(recorders[1].received.borrow().is_empty());
+    assert_eq!(*recorders[0].text.borrow(), None);
+
+    let mut templates = BTreeMap::new();
+    templates.insert("recorder".to_string(), "{{count}} CTFs".to_string());
+    assert_eq!(
+        notify_all(&client, &notifiers, &digest, &templates, limits),
+        1
+    );
+    assert_eq!(recorders[0].text.borrow().as_deref(), Some("5 CTFs"));
+    // Invalid templates are failed deliveries
+    templates.insert("recorder".to_string(), "{{unknown}}".to_string());
+    assert_eq!(
+        notify_all(&client, &notifiers, &digest, &templates, limits),
+        3
+    );
+
+    // Each part of a split digest is a post, the last one is recorded
+    let posts = *recorders[0].posts.borrow();
+    let limits = PostLimits {
+        max_events: 1,
+        max_length: 16000,
+    };
+    assert_eq!(
+        notify_all(&client, &notifiers, &digest, &BTreeMap::new(), limits),
+        1
+    );
+    assert_eq!(*recorders[0].posts.borrow(), posts + 5);
+    assert_eq!(*recorders[0].received.borrow(), vec![digest.event_ids()[4]]);
+}
+
+#[test]
+fn test_direct_message_target() {
+    use crate::broadcast::Broadcast;
+
+    let target = |backend: &str, broadcast: Option<Broadcast>| Target {
+        webhook_url: "https://chat.example.com/hooks/abc".parse().unwrap(),
+        channel: None,
+        username: None,
+        icon_url: None,
+        icon_emoji: None,
+        backend: backend.to_string(),
+        broadcast,
+        signing_secret: None,
+    };
+    let broadcast = Broadcast {
+        min_weight: 50.,
+        team_name: "Our security team".to_string(),
+    };
+
+    assert_eq!(direct_message_target(&[]), None);
+    let targets = vec![
+        target("discord", None),
+        target("mattermost", Some(broadcast.clone())),
+    ];
+    assert_eq!(direct_message_target(&targets), None);
+    let targets = vec![
+        target("discord", None),
+        target("mattermost", Some(broadcast)),
+        target("mattermost", None),
+    ];
+    assert_eq!(direct_message_target(&targets), Some(&targets[2]));
+}
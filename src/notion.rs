@@ -0,0 +1,224 @@
+//! Row in a Notion database for each announced event
+//!
+//! The database needs the properties `Name` (title), `Date` (date), `Weight` (number), `Format` (select), `URL` (URL), `Status` (select), and `CTFtime ID` (number).
+//! The rows are found by the `CTFtime ID`, such that reruns without the state update the rows instead of adding new ones.
+//! The status follows the [`Column`] of the event on a board.
+
+use crate::{board::Column, state::State, timed, CtfEvent};
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::{error, info};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const NOTION_API: &str = "https://api.notion.com/v1";
+const NOTION_VERSION: &str = "2022-02-22";
+
+/// Configuration of the database, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct NotionConfig {
+    /// Token of the internal integration, which needs access to the database
+    pub token: String,
+    pub database_id: String,
+    /// Only events with at least this rating weight get a row
+    #[serde(default)]
+    pub min_weight: f32,
+}
+
+/// The row of an event, as stored in the [`State`]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct NotionPage {
+    pub id: String,
+    pub status: Column,
+}
+
+fn status_name(status: Column) -> &'static str {
+    match status {
+        Column::Upcoming => "Upcoming",
+        Column::Playing => "Playing",
+        Column::Done => "Done",
+    }
+}
+
+fn status_property(status: Column) -> Value {
+    json!({ "select": { "name": status_name(status) } })
+}
+
+/// Properties of the row of `event`
+pub fn page_properties(event: &CtfEvent, status: Column) -> Value {
+    let date = |date: DateTime<Utc>| date.to_rfc3339_opts(SecondsFormat::Secs, true);
+    json!({
+        "Name": { "title": [{ "text": { "content": event.display_title() } }] },
+        "Date": { "date": {
+            "start": date(event.start_date().with_timezone(&Utc)),
+            "end": date(event.finish_date().with_timezone(&Utc)),
+        } },
+        "Weight": { "number": event.weight() },
+        "Format": { "select": { "name": event.format().to_string() } },
+        "URL": { "url": event.ctftime_url() },
+        "Status": status_property(status),
+        "CTFtime ID": { "number": event.id() },
+    })
+}
+
+impl NotionConfig {
+    fn request(
+        &self,
+        client: &Client,
+        method: reqwest::Method,
+        path: &str,
+        body: Value,
+    ) -> Result<Value, reqwest::Error> {
+        client
+            .request(method, &format!("{}/{}", NOTION_API, path))
+            .bearer_auth(&self.token)
+            .header("Notion-Version", NOTION_VERSION)
+            .json(&body)
+            .send()?
+            .error_for_status()?
+            .json()
+    }
+
+    /// Id of the row with the `CTFtime ID` of the event
+    fn find_page(
+        &self,
+        client: &Client,
+        event_id: usize,
+    ) -> Result<Option<String>, reqwest::Error> {
+        let found = self.request(
+            client,
+            reqwest::Method::POST,
+            &format!("databases/{}/query", self.database_id),
+            json!({ "filter": { "property": "CTFtime ID", "number": { "equals": event_id } } }),
+        )?;
+        Ok(found["results"][0]["id"].as_str().map(str::to_string))
+    }
+
+    /// Create the row of the event, or update it if it exists, and return its id
+    pub fn upsert_page(
+        &self,
+        client: &Client,
+        event: &CtfEvent,
+        known: Option<&str>,
+        status: Column,
+    ) -> Result<String, reqwest::Error> {
+        let properties = page_properties(event, status);
+        let existing = match known {
+            Some(id) => Some(id.to_string()),
+            None => self.find_page(client, event.id())?,
+        };
+        match existing {
+            Some(id) => {
+                self.request(
+                    client,
+                    reqwest::Method::PATCH,
+                    &format!("pages/{}", id),
+                    json!({ "properties": properties }),
+                )?;
+                Ok(id)
+            }
+            None => {
+                let created = self.request(
+                    client,
+                    reqwest::Method::POST,
+                    "pages",
+                    json!({
+                        "parent": { "database_id": self.database_id },
+                        "properties": properties,
+                    }),
+                )?;
+                Ok(created["id"].as_str().unwrap_or_default().to_string())
+            }
+        }
+    }
+
+    /// Change the status of an existing row
+    pub fn update_status(
+        &self,
+        client: &Client,
+        page: &NotionPage,
+        status: Column,
+    ) -> Result<(), reqwest::Error> {
+        self.request(
+            client,
+            reqwest::Method::PATCH,
+            &format!("pages/{}", page.id),
+            json!({ "properties": { "Status": status_property(status) } }),
+        )?;
+        Ok(())
+    }
+}
+
+/// Create or update the rows of the `announced` events and update the status of the existing rows
+///
+/// `events` should contain all known events, such that the status of past events is still updated.
+/// Errors are logged and retried during the next synchronization.
+pub fn sync_notion(
+    notion: &NotionConfig,
+    client: &Client,
+    state: &mut State,
+    events: &[CtfEvent],
+    announced: &[usize],
+    now: DateTime<Utc>,
+) {
+    for event in events {
+        let status = Column::of_event(event, now);
+        let record = state.events.entry(event.id()).or_default();
+        if announced.contains(&event.id()) && event.weight() >= notion.min_weight {
+            let known = record.notion_page.as_ref().map(|page| page.id.as_str());
+            match timed("Updating a Notion page", || {
+                notion.upsert_page(client, event, known, status)
+            }) {
+                Ok(id) => {
+                    if record.notion_page.is_none() {
+                        info!("Added event {} to the Notion database", event.id());
+                    }
+                    record.notion_page = Some(NotionPage { id, status });
+                }
+                Err(err) => error!(
+                    "Couldn't update the Notion page of event {}: {}",
+                    event.id(),
+                    err
+                ),
+            }
+        } else if let Some(ref mut page) = record.notion_page {
+            if page.status != status {
+                match timed("Updating a Notion page", || {
+                    notion.update_status(client, page, status)
+                }) {
+                    Ok(()) => page.status = status,
+                    Err(err) => error!(
+                        "Couldn't update the Notion status of event {}: {}",
+                        event.id(),
+                        err
+                    ),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_page_properties() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    let properties = page_properties(&events[0], Column::Upcoming);
+    assert_eq!(
+        properties["Name"]["title"][0]["text"]["content"],
+        "X-MAS CTF 2018"
+    );
+    assert_eq!(
+        properties["Date"]["date"],
+        json!({"start": "2018-12-14T18:00:00Z", "end": "2018-12-21T18:00:00Z"})
+    );
+    assert_eq!(properties["Format"]["select"]["name"], "Jeopardy");
+    assert_eq!(properties["URL"]["url"], "https://ctftime.org/event/724/");
+    assert_eq!(properties["Status"]["select"]["name"], "Upcoming");
+    assert_eq!(properties["CTFtime ID"]["number"], 724);
+    assert_eq!(
+        status_property(Column::Done),
+        json!({"select": {"name": "Done"}})
+    );
+}
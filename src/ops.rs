@@ -0,0 +1,165 @@
+//! Operational messages for the admins of the bot
+//!
+//! Warnings and errors logged by the bot, e.g., failed fetches or deliveries, are collected by the [`OpsLogger`].
+//! After each run they are posted as a single message to [`Config::admin_webhook_url`][crate::Config::admin_webhook_url],
+//! such that problems are noticed without watching the logs, while the announcement channels only receive announcements.
+
+use crate::{
+    mattermost_hook_api::{Attachment, Color, Message},
+    RUN_ID,
+};
+use lazy_static::lazy_static;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::sync::Mutex;
+
+/// Number of collected issues per run, further issues are only counted
+const MAX_ISSUES: usize = 50;
+
+lazy_static! {
+    static ref ISSUES: Mutex<Issues> = Mutex::new(Issues::default());
+}
+
+/// A warning or error logged by the bot
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Issue {
+    pub level: Level,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+struct Issues {
+    issues: Vec<Issue>,
+    dropped: usize,
+}
+
+/// Logger collecting the warnings and errors of the bot, before passing all records to the `inner` logger
+pub struct OpsLogger<L> {
+    inner: L,
+}
+
+impl<L: Log> OpsLogger<L> {
+    pub fn new(inner: L) -> Self {
+        Self { inner }
+    }
+}
+
+/// Maximal level of the log records, which includes the warnings even if the inner logger ignores them
+pub fn max_level(inner_filter: LevelFilter) -> LevelFilter {
+    inner_filter.max(LevelFilter::Warn)
+}
+
+impl<L: Log> Log for OpsLogger<L> {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= Level::Warn || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        // Only the bot itself, not the logs of dependencies
+        if record.level() <= Level::Warn && record.target().starts_with("ctftimebot") {
+            let mut issues = ISSUES.lock().unwrap_or_else(|err| err.into_inner());
+            if issues.issues.len() < MAX_ISSUES {
+                issues.issues.push(Issue {
+                    level: record.level(),
+                    message: record.args().to_string(),
+                });
+            } else {
+                issues.dropped += 1;
+            }
+        }
+        if self.inner.enabled(record.metadata()) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush()
+    }
+}
+
+/// Remove and return the collected issues and the number of issues exceeding [`MAX_ISSUES`]
+pub fn take_issues() -> (Vec<Issue>, usize) {
+    let mut issues = ISSUES.lock().unwrap_or_else(|err| err.into_inner());
+    let dropped = std::mem::take(&mut issues.dropped);
+    (std::mem::take(&mut issues.issues), dropped)
+}
+
+/// Message listing the issues, repeated messages are only shown once
+///
+/// Returns `None` if there are no issues.
+pub fn ops_message(issues: &[Issue], dropped: usize) -> Option<Message> {
+    if issues.is_empty() {
+        return None;
+    }
+    let mut lines: Vec<(&Issue, usize)> = Vec::new();
+    for issue in issues {
+        match lines.iter_mut().find(|(seen, _)| *seen == issue) {
+            Some((_, count)) => *count += 1,
+            None => lines.push((issue, 1)),
+        }
+    }
+    let mut text = String::new();
+    for (issue, count) in lines {
+        text += if issue.level == Level::Error {
+            "❌ "
+        } else {
+            "⚠ "
+        };
+        text += &issue.message;
+        if count > 1 {
+            text += &format!(" (×{})", count);
+        }
+        text += "\n";
+    }
+    if dropped > 0 {
+        text += &format!("… and {} more\n", dropped);
+    }
+    text.truncate(text.trim_end().len());
+
+    let total = issues.len() + dropped;
+    let summary = format!(
+        "Run `{}` reported {} problem{}",
+        *RUN_ID,
+        total,
+        if total == 1 { "" } else { "s" }
+    );
+    Some(Message {
+        text: Some(format!("**{}**", summary)),
+        username: Some("CTFtime Bot (ops)".to_string()),
+        attachments: vec![Attachment {
+            fallback: summary,
+            color: Some(if issues.iter().any(|issue| issue.level == Level::Error) {
+                Color::Danger
+            } else {
+                Color::Warning
+            }),
+            text: Some(text),
+            ..Default::default()
+        }],
+        ..Default::default()
+    })
+}
+
+#[test]
+fn test_ops_message() {
+    assert!(ops_message(&[], 0).is_none());
+
+    let failed = Issue {
+        level: Level::Error,
+        message: "Couldn't post to chat.example.com: timeout".to_string(),
+    };
+    let logo = Issue {
+        level: Level::Warn,
+        message: "Couldn't upload the logo of event 724: 404".to_string(),
+    };
+    let message = ops_message(&[failed.clone(), logo.clone(), failed], 2).unwrap();
+    assert!(message.text.unwrap().ends_with(" reported 5 problems**"));
+    let attachment = &message.attachments[0];
+    assert_eq!(attachment.color, Some(Color::Danger));
+    assert_eq!(
+        attachment.text.as_deref(),
+        Some("❌ Couldn't post to chat.example.com: timeout (×2)\n⚠ Couldn't upload the logo of event 724: 404\n… and 2 more")
+    );
+
+    let message = ops_message(&[logo], 0).unwrap();
+    assert_eq!(message.attachments[0].color, Some(Color::Warning));
+}
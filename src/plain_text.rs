@@ -0,0 +1,114 @@
+//! Plain text without any markup, for IRC, raw text webhooks, and the fallback texts of attachments
+//!
+//! The text only uses plain lines with aligned labels, such that it stays readable in fixed-width fonts.
+//! Links are written out, since most IRC clients make them clickable.
+
+use crate::{
+    digest::markdown_to_plain_text, format_date, format_duration, mattermost_hook_api::Message,
+    CtfEvent, CtfRestrictions, CONFIG,
+};
+
+/// Width of the labels, such that the values line up
+const LABEL_WIDTH: usize = 12;
+
+fn labeled(label: &str, value: &str) -> String {
+    format!(
+        "  {:width$}{}",
+        format!("{}:", label),
+        value,
+        width = LABEL_WIDTH
+    )
+}
+
+/// Lines of the event, starting with the title and followed by indented details
+pub fn event_lines(event: &CtfEvent) -> Vec<String> {
+    let duration = format_duration(
+        &event
+            .finish_date()
+            .signed_duration_since(event.start_date()),
+    );
+    let mut lines = vec![
+        format!("{} [{}]", event.display_title(), event.format()),
+        labeled(
+            "Date",
+            &format!(
+                "{} for {}",
+                format_date(&event.start_date(), CONFIG.timezone),
+                duration
+            ),
+        ),
+        labeled("Weight", &format!("{:.2}", event.weight())),
+    ];
+    let organizers: Vec<&str> = event.known_organizers().map(|team| team.name()).collect();
+    if !organizers.is_empty() {
+        lines.push(labeled("Organizers", &organizers.join(", ")));
+    }
+    if event.onsite() {
+        if let Some(location) = event.location() {
+            lines.push(labeled("Location", location));
+        }
+    }
+    if event.restrictions == CtfRestrictions::Prequalified {
+        lines.push(labeled("Teams", "Prequalified only"));
+    }
+    lines.push(labeled(
+        "Link",
+        event.url().unwrap_or_else(|| event.ctftime_url()),
+    ));
+    lines
+}
+
+/// Lines of a post, the text followed by the fallback texts of the attachments
+pub fn message_lines(message: &Message) -> Vec<String> {
+    let mut lines: Vec<String> = message
+        .text
+        .iter()
+        .flat_map(|text| {
+            markdown_to_plain_text(text)
+                .lines()
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    for attachment in &message.attachments {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.extend(attachment.fallback.lines().map(str::to_string));
+    }
+    lines
+}
+
+#[test]
+fn test_plain_text() {
+    use crate::mattermost_hook_api::Attachment;
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    let lines = event_lines(&events[0]);
+    assert_eq!(lines[0], "X-MAS CTF 2018 [Jeopardy]");
+    assert!(lines[1].starts_with("  Date:       "));
+    assert_eq!(lines[2], "  Weight:     24.07");
+    assert_eq!(lines[3], "  Organizers: Hecării, Țuica și Păunii");
+    assert_eq!(lines[4], "  Link:       https://www.xmas-ctf.cf/");
+    assert_eq!(events[0].to_slack().fallback, lines.join("\n"));
+
+    let message = Message {
+        text: Some("**Upcoming** [CTFs](https://ctftime.org/)".to_string()),
+        attachments: vec![Attachment {
+            fallback: "X-MAS CTF 2018\n  Weight: 24.07".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    assert_eq!(
+        message_lines(&message),
+        vec![
+            "Upcoming CTFs (https://ctftime.org/)",
+            "",
+            "X-MAS CTF 2018",
+            "  Weight: 24.07"
+        ]
+    );
+}
@@ -0,0 +1,423 @@
+//! Personal notification preferences of the users
+//!
+//! Users change their preferences with the `/ctftime` slash command, see [`handle_command`].
+//! Personal reminders are sent as direct messages before the start of an event and honor these preferences.
+
+use crate::{
+    format_date, format_duration,
+    state::{EventRecord, State},
+    CtfEvent, CtfFormat, CONFIG,
+};
+use chrono::{DateTime, Duration, Local, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Hours before the start of an event to send the personal reminder
+const DEFAULT_LEAD_TIME_HOURS: i64 = 1;
+
+/// Preferences of a single user
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Preferences {
+    /// Mattermost user id, which is also stored in the RSVPs
+    #[serde(default)]
+    pub user_id: String,
+    /// Receive personal reminders as direct messages
+    #[serde(default)]
+    pub reminders: bool,
+    /// Formats of announced events to be reminded of, e.g., `jeopardy`
+    ///
+    /// Events the user RSVP'd to are always included.
+    #[serde(default)]
+    pub formats: BTreeSet<String>,
+    /// Hours before the start of an event to send the reminder
+    #[serde(default)]
+    pub lead_time_hours: Option<i64>,
+    /// No direct messages from the first until the second hour of the day
+    #[serde(default)]
+    pub quiet_hours: Option<(u32, u32)>,
+    /// Notify about all new events matching one of these keywords, see [`matches_keyword`]
+    #[serde(default)]
+    pub keywords: BTreeSet<String>,
+}
+
+/// Name of the format as used in [`Preferences::formats`]
+fn format_key(format: CtfFormat) -> String {
+    format.to_string().to_lowercase()
+}
+
+impl Preferences {
+    pub fn lead_time(&self) -> Duration {
+        Duration::hours(self.lead_time_hours.unwrap_or(DEFAULT_LEAD_TIME_HOURS))
+    }
+
+    /// Whether `now` is within the quiet hours, taken in `timezone` or the local timezone if unset
+    pub fn is_quiet(&self, now: DateTime<Utc>, timezone: Option<Tz>) -> bool {
+        let hour = match timezone {
+            Some(tz) => now.with_timezone(&tz).hour(),
+            None => now.with_timezone(&Local).hour(),
+        };
+        match self.quiet_hours {
+            Some((start, end)) if start <= end => start <= hour && hour < end,
+            // The quiet hours span midnight
+            Some((start, end)) => start <= hour || hour < end,
+            None => false,
+        }
+    }
+
+    /// Whether the user wants to be reminded of `event`
+    fn wants(&self, event: &CtfEvent, record: &EventRecord) -> bool {
+        record.rsvps.contains(&self.user_id)
+            || (record.announced && self.formats.contains(&format_key(event.format())))
+    }
+
+    /// Summary of the preferences for the user
+    pub fn describe(&self) -> String {
+        let formats = if self.formats.is_empty() {
+            "none".to_string()
+        } else {
+            self.formats.iter().cloned().collect::<Vec<_>>().join(", ")
+        };
+        let quiet = match self.quiet_hours {
+            Some((start, end)) => format!("{}-{}", start, end),
+            None => "off".to_string(),
+        };
+        let keywords = if self.keywords.is_empty() {
+            "none".to_string()
+        } else {
+            self.keywords
+                .iter()
+                .map(|keyword| format!("“{}”", keyword))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        format!(
+            "Personal reminders: {}\nFormats: {} (and all events you RSVP'd to)\nLead time: {}\nQuiet hours: {}\nKeywords: {}",
+            if self.reminders { "on" } else { "off" },
+            formats,
+            format_duration(&self.lead_time()),
+            quiet,
+            keywords
+        )
+    }
+}
+
+const HELP: &str = "Usage:
+- `/ctftime` shows your preferences
+- `/ctftime reminders on|off` enables personal reminders as direct messages
+- `/ctftime formats jeopardy attack-defense` selects the formats of announced events you want to be reminded of, `none` for only your RSVPs
+- `/ctftime lead <hours>` sets how many hours before the start you are reminded
+- `/ctftime quiet <from>-<to>|off` sets hours without direct messages, e.g., `22-7`
+- `/ctftime subscribe <keywords>` notifies you about all new events matching the keywords, e.g., `onsite Berlin`
+- `/ctftime unsubscribe <keywords>` removes the subscription";
+
+/// Process the text of a slash command and create the reply for the user
+pub fn handle_command(state: &mut State, user_id: &str, user_name: &str, text: &str) -> String {
+    let mut words = text.split_whitespace();
+    let command = words.next().unwrap_or("show");
+    let args: Vec<&str> = words.collect();
+
+    let prefs = state.users.entry(user_name.to_string()).or_default();
+    prefs.user_id = user_id.to_string();
+    let res = match (command, &*args) {
+        ("show", []) => Ok(()),
+        ("reminders", ["on"]) => {
+            prefs.reminders = true;
+            Ok(())
+        }
+        ("reminders", ["off"]) => {
+            prefs.reminders = false;
+            Ok(())
+        }
+        ("formats", ["none"]) => {
+            prefs.formats.clear();
+            Ok(())
+        }
+        ("formats", formats) if !formats.is_empty() => {
+            let known: Vec<String> = [
+                CtfFormat::Jeopardy,
+                CtfFormat::AttackDefense,
+                CtfFormat::HackQuest,
+            ]
+            .iter()
+            .map(|&format| format_key(format))
+            .collect();
+            let parsed: Result<BTreeSet<String>, String> = formats
+                .iter()
+                .map(|format| match format.parse() {
+                    Ok(CtfFormat::Unknown) | Err(_) => Err(format!(
+                        "Unknown format `{}`, expected one of {}",
+                        format,
+                        known.join(", ")
+                    )),
+                    Ok(format) => Ok(format_key(format)),
+                })
+                .collect();
+            parsed.map(|formats| prefs.formats = formats)
+        }
+        ("lead", [hours]) => match hours.parse::<i64>() {
+            Ok(hours) if hours > 0 => {
+                prefs.lead_time_hours = Some(hours);
+                Ok(())
+            }
+            _ => Err(format!("Invalid number of hours `{}`", hours)),
+        },
+        ("subscribe", words) if !words.is_empty() => {
+            prefs.keywords.insert(words.join(" ").to_lowercase());
+            Ok(())
+        }
+        ("unsubscribe", words) if !words.is_empty() => {
+            let keyword = words.join(" ").to_lowercase();
+            if prefs.keywords.remove(&keyword) {
+                Ok(())
+            } else {
+                Err(format!("You are not subscribed to “{}”", keyword))
+            }
+        }
+        ("quiet", ["off"]) => {
+            prefs.quiet_hours = None;
+            Ok(())
+        }
+        ("quiet", [range]) => match parse_hours(range) {
+            Some(range) => {
+                prefs.quiet_hours = Some(range);
+                Ok(())
+            }
+            None => Err(format!(
+                "Invalid quiet hours `{}`, expected e.g. `22-7`",
+                range
+            )),
+        },
+        _ => Err(HELP.to_string()),
+    };
+    match res {
+        Ok(()) => prefs.describe(),
+        Err(err) => err,
+    }
+}
+
+/// Parse a range of hours like `22-7`
+fn parse_hours(range: &str) -> Option<(u32, u32)> {
+    let mut parts = range.splitn(2, '-');
+    let start: u32 = parts.next()?.parse().ok()?;
+    let end: u32 = parts.next()?.parse().ok()?;
+    if start < 24 && end < 24 {
+        Some((start, end))
+    } else {
+        None
+    }
+}
+
+/// Personal reminders due at `now`, as pairs of the event id and the user name
+///
+/// Reminders during the quiet hours of a user are postponed, until the quiet hours end or the event starts.
+pub fn due_personal_reminders(
+    events: &[CtfEvent],
+    state: &State,
+    now: DateTime<Utc>,
+) -> Vec<(usize, String)> {
+    let mut res = Vec::new();
+    for (user_name, prefs) in &state.users {
+        if !prefs.reminders || prefs.is_quiet(now, CONFIG.timezone) {
+            continue;
+        }
+        for event in events {
+            let record = match state.events.get(&event.id()) {
+                Some(record) => record,
+                None => continue,
+            };
+            let start = event.start_date().with_timezone(&Utc);
+            if start - prefs.lead_time() <= now
+                && now < start
+                && !record.personal_reminders.contains(user_name)
+                && prefs.wants(event, record)
+            {
+                res.push((event.id(), user_name.clone()));
+            }
+        }
+    }
+    res
+}
+
+/// Whether all words of `keyword` occur in the event, ignoring case
+///
+/// The title, description, location, and format are searched, as well as `onsite` or `online`.
+pub fn matches_keyword(event: &CtfEvent, keyword: &str) -> bool {
+    let haystack = format!(
+        "{}\n{}\n{}\n{}\n{}",
+        event.title(),
+        event.description(),
+        event.location().unwrap_or_default(),
+        format_key(event.format()),
+        if event.onsite() { "onsite" } else { "online" },
+    )
+    .to_lowercase();
+    keyword
+        .split_whitespace()
+        .all(|word| haystack.contains(&word.to_lowercase()))
+}
+
+/// Upcoming events matching a keyword subscription, which the user was not notified about yet
+///
+/// The result contains triples of the event id, the user name, and the matching keyword.
+/// The filters of the digest do not apply.
+pub fn due_keyword_notifications(
+    events: &[CtfEvent],
+    state: &State,
+    now: DateTime<Utc>,
+) -> Vec<(usize, String, String)> {
+    let mut res = Vec::new();
+    for (user_name, prefs) in &state.users {
+        for event in events {
+            if event.start_date() <= now
+                || state
+                    .events
+                    .get(&event.id())
+                    .map_or(false, |record| record.keyword_notified.contains(user_name))
+            {
+                continue;
+            }
+            if let Some(keyword) = prefs
+                .keywords
+                .iter()
+                .find(|keyword| matches_keyword(event, keyword))
+            {
+                res.push((event.id(), user_name.clone(), keyword.clone()));
+            }
+        }
+    }
+    res
+}
+
+/// Text of the notification about `event` matching the subscribed `keyword`
+pub fn keyword_notification(event: &CtfEvent, keyword: &str) -> String {
+    format!(
+        "🔔 [{}]({}) matches your subscription “{}” and starts {}",
+        event.title(),
+        event.ctftime_url(),
+        keyword,
+        format_date(&event.start_date(), CONFIG.timezone)
+    )
+}
+
+/// Text of the personal reminder for `event`
+pub fn personal_reminder(event: &CtfEvent, now: DateTime<Utc>) -> String {
+    format!(
+        "⏰ [{}]({}) starts in {}",
+        event.title(),
+        event.ctftime_url(),
+        format_duration(&event.start_date().signed_duration_since(now))
+    )
+}
+
+#[test]
+fn test_handle_command() {
+    let mut state = State::default();
+    assert_eq!(
+        handle_command(&mut state, "u1", "alice", ""),
+        "Personal reminders: off\nFormats: none (and all events you RSVP'd to)\nLead time: 1 hour\nQuiet hours: off\nKeywords: none"
+    );
+    handle_command(&mut state, "u1", "alice", "reminders on");
+    handle_command(&mut state, "u1", "alice", "formats Jeopardy attack-defense");
+    handle_command(&mut state, "u1", "alice", "lead 3");
+    assert_eq!(
+        handle_command(&mut state, "u1", "alice", "quiet 22-7"),
+        "Personal reminders: on\nFormats: attack-defense, jeopardy (and all events you RSVP'd to)\nLead time: 3 hours\nQuiet hours: 22-7\nKeywords: none"
+    );
+    assert!(handle_command(&mut state, "u1", "alice", "formats golf").starts_with("Unknown format"));
+    handle_command(&mut state, "u1", "alice", "formats AD hackquest");
+    assert_eq!(
+        state.users["alice"].formats.iter().collect::<Vec<_>>(),
+        vec!["attack-defense", "hack-quest"]
+    );
+    assert!(
+        handle_command(&mut state, "u1", "alice", "quiet 25-7").starts_with("Invalid quiet hours")
+    );
+    assert!(handle_command(&mut state, "u1", "alice", "help").starts_with("Usage:"));
+    assert_eq!(state.users["alice"].user_id, "u1");
+    assert_eq!(state.users["alice"].lead_time_hours, Some(3));
+}
+
+#[test]
+fn test_keyword_subscriptions() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let before = events[0].start_date().with_timezone(&Utc) - Duration::days(1);
+
+    assert!(matches_keyword(&events[0], "x-mas"));
+    assert!(matches_keyword(&events[0], "Beginners online"));
+    assert!(matches_keyword(&events[0], "jeopardy"));
+    assert!(!matches_keyword(&events[0], "onsite beginners"));
+
+    let mut state = State::default();
+    handle_command(&mut state, "u1", "alice", "subscribe Online Beginners");
+    assert!(
+        handle_command(&mut state, "u2", "bob", "subscribe hardware")
+            .ends_with("Keywords: “hardware”")
+    );
+    assert_eq!(
+        due_keyword_notifications(&events, &state, before),
+        vec![(724, "alice".to_string(), "online beginners".to_string())]
+    );
+    assert!(due_keyword_notifications(&events, &state, before + Duration::days(2)).is_empty());
+
+    state
+        .events
+        .entry(724)
+        .or_default()
+        .keyword_notified
+        .insert("alice".to_string());
+    assert!(due_keyword_notifications(&events, &state, before).is_empty());
+
+    assert!(
+        handle_command(&mut state, "u1", "alice", "unsubscribe online beginners")
+            .ends_with("Keywords: none")
+    );
+    assert!(
+        handle_command(&mut state, "u1", "alice", "unsubscribe crypto")
+            .starts_with("You are not subscribed")
+    );
+}
+
+#[test]
+fn test_due_personal_reminders() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let start = events[0].start_date().with_timezone(&Utc);
+
+    let mut state = State::default();
+    state.record_events(&events);
+    handle_command(&mut state, "u1", "alice", "reminders on");
+    let now = start - Duration::minutes(30);
+    assert!(due_personal_reminders(&events, &state, now).is_empty());
+
+    state
+        .events
+        .get_mut(&724)
+        .unwrap()
+        .rsvps
+        .insert("u1".to_string());
+    assert_eq!(
+        due_personal_reminders(&events, &state, now),
+        vec![(724, "alice".to_string())]
+    );
+    assert!(due_personal_reminders(&events, &state, start - Duration::hours(2)).is_empty());
+    assert!(due_personal_reminders(&events, &state, start).is_empty());
+
+    // X-MAS CTF 2018 starts at 18:00 UTC
+    handle_command(&mut state, "u1", "alice", "quiet 17-18");
+    let prefs = &state.users["alice"];
+    assert!(prefs.is_quiet(now, Some(Tz::UTC)));
+    assert!(!prefs.is_quiet(start, Some(Tz::UTC)));
+
+    state
+        .events
+        .get_mut(&724)
+        .unwrap()
+        .personal_reminders
+        .insert("alice".to_string());
+    handle_command(&mut state, "u1", "alice", "quiet off");
+    assert!(due_personal_reminders(&events, &state, now).is_empty());
+}
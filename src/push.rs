@@ -0,0 +1,211 @@
+//! Push notifications to phones via [ntfy] or [Gotify]
+//!
+//! Each event which is announced for the first time and reaches the minimal weight results in one notification.
+//! Events with a higher weight get a higher priority, such that the important CTFs stand out.
+//!
+//! [ntfy]: https://docs.ntfy.sh/publish/
+//! [Gotify]: https://gotify.net/docs/pushmsg
+
+use crate::{
+    format_date,
+    mattermost_hook_api::Url,
+    state::State,
+    timed,
+    webhook::{event_context, render_text},
+    CtfEvent, CONFIG,
+};
+use log::error;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::json;
+
+/// Configuration of the push backend, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct PushConfig {
+    #[serde(flatten)]
+    pub service: PushService,
+    /// Only notify about events with at least this weight
+    #[serde(default)]
+    pub min_weight: f32,
+}
+
+/// Server receiving the notifications, selected with the `kind` key
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PushService {
+    Ntfy {
+        #[serde(default = "default_ntfy_server")]
+        server: Url,
+        topic: String,
+        /// Access token for protected topics
+        token: Option<String>,
+    },
+    Gotify {
+        server: Url,
+        /// Token of the Gotify application
+        app_token: String,
+    },
+}
+
+fn default_ntfy_server() -> Url {
+    "https://ntfy.sh".parse().expect("The URL is valid")
+}
+
+/// Priority from 1 (min) to 5 (max), as used by ntfy
+pub fn priority(weight: f32) -> u8 {
+    match weight {
+        w if w >= 75. => 5,
+        w if w >= 50. => 4,
+        w if w >= 25. => 3,
+        _ => 2,
+    }
+}
+
+/// Events of the digest which were not announced before and reach the minimal weight
+pub fn qualifying_events<'a>(
+    events: &[&'a CtfEvent],
+    state: Option<&State>,
+    min_weight: f32,
+) -> Vec<&'a CtfEvent> {
+    events
+        .iter()
+        .copied()
+        .filter(|event| event.weight() >= min_weight)
+        .filter(|event| {
+            state.map_or(true, |state| {
+                !state
+                    .events
+                    .get(&event.id())
+                    .map_or(false, |record| record.announced)
+            })
+        })
+        .collect()
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Text of the notification, rendered from the `push` template if configured
+fn notification_text(event: &CtfEvent) -> Result<String, BoxError> {
+    if let Some(template) = CONFIG.templates.get("push") {
+        return render_text(template, &event_context(event));
+    }
+    Ok(format!(
+        "{} · weight {:.2}\n{}",
+        event.format(),
+        event.weight(),
+        format_date(&event.start_date(), CONFIG.timezone)
+    ))
+}
+
+impl PushService {
+    /// Send a notification about the event
+    pub fn notify(&self, client: &Client, event: &CtfEvent) -> Result<(), BoxError> {
+        let link = event.url().unwrap_or_else(|| event.ctftime_url());
+        let text = notification_text(event)?;
+        let request = match self {
+            PushService::Ntfy {
+                server,
+                topic,
+                token,
+            } => {
+                // Publishing as JSON allows non-ASCII titles, which are invalid in headers
+                let request = client.post(server.clone()).json(&json!({
+                    "topic": topic,
+                    "title": event.title(),
+                    "message": text,
+                    "priority": priority(event.weight()),
+                    "click": link,
+                    "tags": ["triangular_flag_on_post"],
+                }));
+                match token {
+                    Some(token) => request.bearer_auth(token),
+                    None => request,
+                }
+            }
+            PushService::Gotify { server, app_token } => client
+                .post(&format!(
+                    "{}/message",
+                    server.as_str().trim_end_matches('/')
+                ))
+                .header("X-Gotify-Key", app_token)
+                .json(&json!({
+                    "title": event.title(),
+                    "message": text,
+                    // Gotify uses priorities from 0 to 10
+                    "priority": priority(event.weight()) * 2,
+                    "extras": {
+                        "client::notification": { "click": { "url": link } },
+                    },
+                })),
+        };
+        request.send()?.error_for_status()?;
+        Ok(())
+    }
+}
+
+impl PushConfig {
+    /// Send one notification per qualifying event, failures are logged
+    ///
+    /// Returns the number of failed notifications.
+    pub fn notify_events(
+        &self,
+        client: &Client,
+        events: &[&CtfEvent],
+        state: Option<&State>,
+    ) -> usize {
+        let mut failed = 0;
+        for event in qualifying_events(events, state, self.min_weight) {
+            if let Err(err) = timed("Sending the push notification", || {
+                self.service.notify(client, event)
+            }) {
+                error!(
+                    "Couldn't send push notification for event {}: {}",
+                    event.id(),
+                    err
+                );
+                failed += 1;
+            }
+        }
+        failed
+    }
+}
+
+#[test]
+fn test_push() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let events: Vec<&CtfEvent> = events.iter().collect();
+
+    assert_eq!(priority(0.), 2);
+    assert_eq!(priority(24.07), 2);
+    assert_eq!(priority(25.), 3);
+    assert_eq!(priority(100.), 5);
+
+    let heavy = qualifying_events(&events, None, 50.);
+    assert!(!heavy.is_empty());
+    assert!(heavy.len() < events.len());
+    let mut state = State::default();
+    state.mark_announced(&[heavy[0].id()]);
+    assert_eq!(
+        qualifying_events(&events, Some(&state), 50.).len(),
+        heavy.len() - 1
+    );
+
+    let config: PushConfig = toml::from_str(
+        r#"
+kind = "ntfy"
+topic = "ctf-alerts"
+min_weight = 50.0
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        config.service,
+        PushService::Ntfy {
+            server: default_ntfy_server(),
+            topic: "ctf-alerts".to_string(),
+            token: None,
+        }
+    );
+}
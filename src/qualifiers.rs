@@ -0,0 +1,311 @@
+//! Phases of multi-phase events and links between qualifiers and their finals
+//!
+//! The [`Phase`] of an event, e.g., teaser or finals, is detected from its title or configured manually.
+//! Qualifiers and finals are linked by a manual mapping in the configuration or by their titles,
+//! e.g., `RHme3 - Qualifiers` and `RHme3 - Finals`.
+//! Since qualifiers usually happened long before the finals, the events from the [`State`] are considered, too.
+
+use crate::{state::State, CtfEvent, BASE_URL};
+use chrono::{DateTime, Datelike, Utc};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt};
+
+/// Manual link between a qualifier and its finals, only available in the configuration file
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+pub struct QualifierLink {
+    /// CTFtime id of the qualifier event
+    pub qualifier: usize,
+    /// CTFtime id of the finals event
+    pub finals: usize,
+}
+
+/// Manual phase of an event whose title doesn't mention it, only available in the configuration file
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+pub struct PhaseOverride {
+    /// CTFtime id of the event
+    pub event: usize,
+    pub phase: Phase,
+}
+
+/// Phase of an event which is split into multiple CTFtime events
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// A short event before the main CTF, usually with the same name
+    Teaser,
+    Qualifier,
+    Finals,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Teaser => "Teaser",
+            Phase::Qualifier => "Qualifier",
+            Phase::Finals => "Finals",
+        })
+    }
+}
+
+fn is_phase_word(word: &str, phase: Phase) -> bool {
+    match phase {
+        Phase::Teaser => word.starts_with("teaser"),
+        Phase::Qualifier => word.starts_with("qual"),
+        Phase::Finals => word.starts_with("final"),
+    }
+}
+
+/// Words of the lowercase title, without punctuation
+fn words(title: &str) -> impl Iterator<Item = String> + '_ {
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Phase of the event according to its title
+fn phase(title: &str) -> Option<Phase> {
+    words(title).find_map(|word| {
+        if is_phase_word(&word, Phase::Teaser) {
+            Some(Phase::Teaser)
+        } else if is_phase_word(&word, Phase::Qualifier) {
+            Some(Phase::Qualifier)
+        } else if is_phase_word(&word, Phase::Finals) {
+            Some(Phase::Finals)
+        } else {
+            None
+        }
+    })
+}
+
+/// Title without the stage, which is the same for a qualifier and its finals
+fn base_title(title: &str) -> String {
+    words(title)
+        .filter(|word| {
+            !is_phase_word(word, Phase::Qualifier) && !is_phase_word(word, Phase::Finals)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Phase of the event, the configured `overrides` take precedence over the title
+pub fn event_phase(event: &CtfEvent, overrides: &[PhaseOverride]) -> Option<Phase> {
+    overrides
+        .iter()
+        .find(|phase| phase.event == event.id())
+        .map(|phase| phase.phase)
+        .or_else(|| phase(event.title()))
+}
+
+/// Label of the phase, if the title doesn't already mention it
+///
+/// Events with the same name, e.g., a teaser and the main event, are distinguishable in posts and reminders this way.
+pub fn phase_label(event: &CtfEvent, overrides: &[PhaseOverride]) -> Option<Phase> {
+    event_phase(event, overrides).filter(|&event_phase| phase(event.title()) != Some(event_phase))
+}
+
+/// An event which can be linked, either fetched during this run or known from the [`State`]
+#[derive(Clone, Debug)]
+struct Known {
+    title: String,
+    start: Option<DateTime<Utc>>,
+    finish: Option<DateTime<Utc>>,
+}
+
+impl Known {
+    /// Markdown link with the dates of the event, e.g., `[DEF CON CTF Finals](…) (Aug 8–11)`
+    fn to_markdown(&self, id: usize) -> String {
+        let mut text = format!("[{}]({}/event/{}/)", self.title, BASE_URL, id);
+        if let (Some(start), Some(finish)) = (self.start, self.finish) {
+            let finish = if (start.year(), start.month()) == (finish.year(), finish.month()) {
+                finish.format("%-d")
+            } else {
+                finish.format("%b %-d")
+            };
+            text += &format!(" ({}–{})", start.format("%b %-d"), finish);
+        }
+        text
+    }
+}
+
+fn known_events(events: &[CtfEvent], state: &State) -> BTreeMap<usize, Known> {
+    let mut known: BTreeMap<usize, Known> = state
+        .events
+        .iter()
+        .filter(|(_, record)| !record.title.is_empty())
+        .map(|(&id, record)| {
+            (
+                id,
+                Known {
+                    title: record.title.clone(),
+                    start: record.start,
+                    finish: record.finish,
+                },
+            )
+        })
+        .collect();
+    for event in events {
+        known.insert(
+            event.id(),
+            Known {
+                title: event.title().to_string(),
+                start: Some(event.start_date().with_timezone(&Utc)),
+                finish: Some(event.finish_date().with_timezone(&Utc)),
+            },
+        );
+    }
+    known
+}
+
+/// Pairs of qualifier and finals ids
+///
+/// The configured `links` take precedence.
+/// Otherwise, a qualifier is linked to the first finals with the same title starting after it.
+fn linked_pairs(known: &BTreeMap<usize, Known>, links: &[QualifierLink]) -> Vec<(usize, usize)> {
+    let mut pairs: Vec<(usize, usize)> = links
+        .iter()
+        .map(|link| (link.qualifier, link.finals))
+        .collect();
+    let finals: Vec<(usize, &Known, String)> = known
+        .iter()
+        .filter(|(_, event)| phase(&event.title) == Some(Phase::Finals))
+        .map(|(&id, event)| (id, event, base_title(&event.title)))
+        .collect();
+    for (&id, qualifier) in known {
+        if phase(&qualifier.title) != Some(Phase::Qualifier)
+            || pairs.iter().any(|&(qualifier, _)| qualifier == id)
+        {
+            continue;
+        }
+        let base = base_title(&qualifier.title);
+        let matching = finals
+            .iter()
+            .filter(|(_, event, finals_base)| {
+                *finals_base == base && event.start >= qualifier.start
+            })
+            .min_by_key(|(_, event, _)| event.start);
+        if let Some(&(finals, _, _)) = matching {
+            pairs.push((id, finals));
+        }
+    }
+    pairs
+}
+
+/// Notes linking the qualifiers and finals among `events`, keyed by the event id
+///
+/// Finals also list the users who RSVPed for the qualifier.
+pub fn chain_notes(
+    events: &[CtfEvent],
+    state: &State,
+    links: &[QualifierLink],
+) -> BTreeMap<usize, String> {
+    let known = known_events(events, state);
+    let mut notes = BTreeMap::new();
+    for (qualifier, finals) in linked_pairs(&known, links) {
+        if let Some(event) = known.get(&finals) {
+            notes.insert(
+                qualifier,
+                format!("Qualifier for: {}", event.to_markdown(finals)),
+            );
+        }
+        if let Some(event) = known.get(&qualifier) {
+            let mut note = format!("Finals of: {}", event.to_markdown(qualifier));
+            let rsvps = state
+                .events
+                .get(&qualifier)
+                .map(|record| &record.rsvps)
+                .filter(|rsvps| !rsvps.is_empty());
+            if let Some(rsvps) = rsvps {
+                note += " — played by";
+                for user in rsvps {
+                    note += &format!(" @{}", user);
+                }
+            }
+            notes.insert(finals, note);
+        }
+    }
+    notes.retain(|id, _| events.iter().any(|event| event.id() == *id));
+    notes
+}
+
+#[test]
+fn test_chain_notes() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    assert_eq!(phase("RHme3 - Qualifiers"), Some(Phase::Qualifier));
+    assert_eq!(phase("CSAW CTF Final Round 2015"), Some(Phase::Finals));
+    assert_eq!(phase("X-MAS CTF 2018"), None);
+    assert_eq!(phase("Google CTF 2017 Teaser"), Some(Phase::Teaser));
+    assert_eq!(
+        base_title("CSAW CTF Qualification Round 2015"),
+        "csaw ctf round 2015"
+    );
+    assert_eq!(
+        base_title("CSAW CTF Final Round 2015"),
+        "csaw ctf round 2015"
+    );
+
+    // The qualifier was announced during an earlier run and only the finals are upcoming
+    let mut state = State::default();
+    state.record_events(&events[..240]);
+    state
+        .events
+        .get_mut(&227)
+        .unwrap()
+        .rsvps
+        .insert("alice".to_string());
+    let notes = chain_notes(&events[240..241], &state, &[]);
+    assert_eq!(
+        notes[&271],
+        "Finals of: [CSAW CTF Qualification Round 2015](https://ctftime.org/event/227/) (Sep 18–20) — played by @alice"
+    );
+
+    let notes = chain_notes(&events[223..241], &State::default(), &[]);
+    assert_eq!(
+        notes[&227],
+        "Qualifier for: [CSAW CTF Final Round 2015](https://ctftime.org/event/271/) (Nov 12–14)"
+    );
+
+    // Manual links for events with unrelated titles
+    let links = [QualifierLink {
+        qualifier: 501,
+        finals: 514,
+    }];
+    assert!(chain_notes(&events[440..], &State::default(), &[]).is_empty());
+    let notes = chain_notes(&events[440..], &State::default(), &links);
+    assert_eq!(
+        notes[&501],
+        "Qualifier for: [Hardwear.io - Hardware Security Conference and Training](https://ctftime.org/event/514/) (Aug 21–Sep 12)"
+    );
+    assert_eq!(
+        notes[&514],
+        "Finals of: [RHme3 - Qualifiers](https://ctftime.org/event/501/) (Aug 7–28)"
+    );
+}
+
+#[test]
+fn test_event_phase() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let hardwear = events.iter().find(|event| event.id() == 514).unwrap();
+    let rhme = events.iter().find(|event| event.id() == 501).unwrap();
+
+    // The title already mentions the phase
+    assert_eq!(event_phase(rhme, &[]), Some(Phase::Qualifier));
+    assert_eq!(phase_label(rhme, &[]), None);
+
+    let overrides: Vec<PhaseOverride> = toml::from_str::<BTreeMap<String, Vec<PhaseOverride>>>(
+        r#"phases = [{ event = 514, phase = "finals" }, { event = 501, phase = "teaser" }]"#,
+    )
+    .unwrap()
+    .remove("phases")
+    .unwrap();
+    assert_eq!(event_phase(hardwear, &[]), None);
+    assert_eq!(event_phase(hardwear, &overrides), Some(Phase::Finals));
+    assert_eq!(phase_label(hardwear, &overrides), Some(Phase::Finals));
+    assert_eq!(phase_label(rhme, &overrides), Some(Phase::Teaser));
+    assert_eq!(Phase::Finals.to_string(), "Finals");
+}
@@ -0,0 +1,63 @@
+//! Rate limits for the interactive server
+//!
+//! Each key, e.g., a user or channel id, may send a limited number of requests within a fixed time window.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Number of tracked keys after which expired windows are removed
+const CLEANUP_THRESHOLD: usize = 1024;
+
+/// Fixed window rate limiter, shared between threads
+#[derive(Debug)]
+pub struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    /// Start of the current window and the number of requests in it, per key
+    windows: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    /// Allow `limit` requests per key in each `window`
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Count a request for `key` and return whether it is within the limit
+    pub fn check(&self, key: &str, now: Instant) -> bool {
+        let mut windows = self.windows.lock().unwrap_or_else(|err| err.into_inner());
+        if windows.len() >= CLEANUP_THRESHOLD {
+            let window = self.window;
+            windows.retain(|_, (start, _)| now.duration_since(*start) < window);
+        }
+        let entry = windows.entry(key.to_string()).or_insert((now, 0));
+        if now.duration_since(entry.0) >= self.window {
+            *entry = (now, 0);
+        }
+        if entry.1 < self.limit {
+            entry.1 += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[test]
+fn test_rate_limiter() {
+    let limiter = RateLimiter::new(2, Duration::from_secs(60));
+    let now = Instant::now();
+    assert!(limiter.check("alice", now));
+    assert!(limiter.check("alice", now + Duration::from_secs(1)));
+    assert!(!limiter.check("alice", now + Duration::from_secs(2)));
+    assert!(limiter.check("bob", now + Duration::from_secs(2)));
+    // A new window starts after the old one expired
+    assert!(limiter.check("alice", now + Duration::from_secs(61)));
+}
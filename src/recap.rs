@@ -0,0 +1,195 @@
+//! Recap of the season, posted once per year on the configured date
+//!
+//! The recap is compiled from the results of the team on CTFtime and its rating history,
+//! e.g., how many events the team played, its best placements, and how its rank changed since last year.
+
+use crate::{
+    local_date,
+    teams::{fetch_team, YearRating},
+    timed,
+    trivia::{ordinal, PastResult},
+};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, Utc};
+use chrono_tz::Tz;
+use reqwest::blocking::Client;
+use serde_with::DeserializeFromStr;
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+/// Number of events listed as best events
+const BEST_EVENTS: usize = 3;
+
+/// Day of the year on which the recap is posted, written as `MM-DD`, e.g., `12-31`
+#[derive(Clone, Copy, Debug, Eq, PartialEq, DeserializeFromStr)]
+pub struct RecapDate {
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Default for RecapDate {
+    fn default() -> Self {
+        RecapDate { month: 12, day: 31 }
+    }
+}
+
+impl FromStr for RecapDate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid recap date `{}`, expected `MM-DD`", s);
+        let (month, day) = s.split_once('-').ok_or_else(invalid)?;
+        let date = RecapDate {
+            month: month.parse().map_err(|_| invalid())?,
+            day: day.parse().map_err(|_| invalid())?,
+        };
+        // 2020 is a leap year, such that `02-29` is valid
+        NaiveDate::from_ymd_opt(2020, date.month, date.day).ok_or_else(invalid)?;
+        Ok(date)
+    }
+}
+
+impl fmt::Display for RecapDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}-{:02}", self.month, self.day)
+    }
+}
+
+impl RecapDate {
+    /// Season whose recap is due on `today`, unless it was already posted
+    ///
+    /// The recap stays due after the date, e.g., if the bot was not running, until the end of the year.
+    /// In years without February 29, a recap configured for that day is posted on March 1.
+    pub fn due_season(self, today: NaiveDate, last_recap: Option<i32>) -> Option<i32> {
+        let year = today.year();
+        let date = NaiveDate::from_ymd_opt(year, self.month, self.day)
+            .unwrap_or_else(|| NaiveDate::from_ymd(year, 3, 1));
+        if today >= date && last_recap.map_or(true, |last| last < year) {
+            Some(year)
+        } else {
+            None
+        }
+    }
+}
+
+/// Date of `now` in `timezone`, or the local timezone if unset
+pub fn local_today(now: DateTime<Utc>, timezone: Option<Tz>) -> NaiveDate {
+    local_date(&now.with_timezone(&FixedOffset::east(0)), timezone)
+}
+
+/// Rating history of the team `team_id`, keyed by the year
+pub fn fetch_ratings(
+    client: &Client,
+    team_id: usize,
+) -> Result<BTreeMap<String, YearRating>, reqwest::Error> {
+    let team = timed("Fetching the rating history", || {
+        fetch_team(client, team_id)
+    })?;
+    Ok(team.rating)
+}
+
+/// Change between the rank of last year and this year, e.g., `120th → 85th (📈 35 places)`
+fn progression(last: Option<usize>, current: usize) -> String {
+    match last {
+        Some(last) if current < last => format!(
+            "{} → {} (📈 {} places)",
+            ordinal(last),
+            ordinal(current),
+            last - current
+        ),
+        Some(last) if current > last => format!(
+            "{} → {} (📉 {} places)",
+            ordinal(last),
+            ordinal(current),
+            current - last
+        ),
+        _ => ordinal(current),
+    }
+}
+
+/// Markdown text of the recap of `season`
+pub fn recap_message(
+    season: i32,
+    results: &[PastResult],
+    ratings: &BTreeMap<String, YearRating>,
+) -> String {
+    let mut text = format!("### 🎆 Season recap {}\n", season);
+    if results.is_empty() {
+        text += "We didn't play any rated events this season.\n";
+    } else {
+        let points: f64 = results.iter().map(|result| result.points).sum();
+        text += &format!("**Events played:** {}\n", results.len());
+        text += &format!("**Total points:** {:.2}\n", points);
+        let mut best: Vec<&PastResult> = results.iter().collect();
+        best.sort_by_key(|result| result.place);
+        let best: Vec<String> = best
+            .iter()
+            .take(BEST_EVENTS)
+            .map(|result| format!("{} at {}", ordinal(result.place), result.title))
+            .collect();
+        text += &format!("**Best events:** {}\n", best.join(", "));
+    }
+    let current = ratings.get(&season.to_string());
+    let last = ratings.get(&(season - 1).to_string());
+    if let Some(place) = current.and_then(|rating| rating.rating_place) {
+        let last = last.and_then(|rating| rating.rating_place);
+        text += &format!("**CTFtime rank:** {}", progression(last, place));
+        if let Some(points) = current.and_then(|rating| rating.rating_points) {
+            text += &format!(" with {:.2} rating points", points);
+        }
+        text += "\n";
+    }
+    if let Some(place) = current.and_then(|rating| rating.country_place) {
+        let last = last.and_then(|rating| rating.country_place);
+        text += &format!("**National rank:** {}\n", progression(last, place));
+    }
+    text
+}
+
+#[test]
+fn test_recap() {
+    let date: RecapDate = "12-31".parse().unwrap();
+    assert_eq!(date, RecapDate::default());
+    assert_eq!(date.to_string(), "12-31");
+    assert!("13-01".parse::<RecapDate>().is_err());
+    assert!("12/31".parse::<RecapDate>().is_err());
+
+    let day = |y, m, d| NaiveDate::from_ymd(y, m, d);
+    assert_eq!(date.due_season(day(2021, 12, 30), None), None);
+    assert_eq!(date.due_season(day(2021, 12, 31), None), Some(2021));
+    assert_eq!(date.due_season(day(2021, 12, 31), Some(2020)), Some(2021));
+    assert_eq!(date.due_season(day(2021, 12, 31), Some(2021)), None);
+    let leap: RecapDate = "02-29".parse().unwrap();
+    assert_eq!(leap.due_season(day(2021, 3, 1), None), Some(2021));
+
+    let ratings: BTreeMap<String, YearRating> = serde_json::from_str(
+        r#"{
+            "2020": {"rating_place": 120, "organizer_points": 0, "rating_points": 80.5, "country_place": 9},
+            "2021": {"rating_place": 85, "organizer_points": 0, "rating_points": 150.25, "country_place": 9}
+        }"#,
+    )
+    .unwrap();
+    let result = |title: &str, place, points| PastResult {
+        title: title.to_string(),
+        place,
+        points,
+    };
+    let results = vec![
+        result("A CTF", 12, 1000.),
+        result("B CTF", 3, 2500.5),
+        result("C CTF", 40, 100.),
+        result("D CTF", 7, 1800.),
+    ];
+    assert_eq!(
+        recap_message(2021, &results, &ratings),
+        "### 🎆 Season recap 2021
+**Events played:** 4
+**Total points:** 5400.50
+**Best events:** 3rd at B CTF, 7th at D CTF, 12th at A CTF
+**CTFtime rank:** 120th → 85th (📈 35 places) with 150.25 rating points
+**National rank:** 9th
+"
+    );
+    assert_eq!(
+        recap_message(2022, &[], &ratings),
+        "### 🎆 Season recap 2022\nWe didn't play any rated events this season.\n"
+    );
+}
@@ -0,0 +1,330 @@
+//! Edit the recent posts of the bot, such that changed colors and formatting also apply to the channel history
+//!
+//! Webhooks cannot edit posts, so this uses the REST API of Mattermost with the token of a bot account.
+//! The posts of the bot are found by their [`post_metadata`][crate::post_metadata], the events by the footer of their attachments.
+//! The events are rendered again with [`CtfEvent::to_slack`] and get a new RSVP button if they had one.
+//! Notes added to the events when they were posted are not kept.
+//!
+//! With `UPDATE_IN_PLACE`, the latest digest is pinned and edited on every run instead, see [`sync_digest_post`].
+
+use crate::{
+    event_id_from_footer, mattermost_hook_api::Url, rsvp::rsvp_button, state::State, timed,
+    CtfEvent, CONFIG, PROPS_METADATA_KEY, RUN_ID,
+};
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// Access to the REST API of Mattermost, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct MattermostApiConfig {
+    /// URL of the Mattermost server
+    pub url: Url,
+    /// Access token of a bot account, which needs the permission to edit the posts of others
+    pub token: String,
+    /// Id of the channel the digest is posted to
+    pub channel_id: String,
+}
+
+/// Posts as returned by the Mattermost API
+#[derive(Debug, Deserialize)]
+struct PostList {
+    order: Vec<String>,
+    posts: HashMap<String, Post>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Post {
+    id: String,
+    #[serde(default)]
+    props: Map<String, Value>,
+}
+
+/// Attachment without the empty values and the fields added by Mattermost, for comparing attachments
+fn normalized(attachment: &Value) -> Value {
+    let attachment = attachment.as_object().cloned().unwrap_or_default();
+    Value::Object(
+        attachment
+            .into_iter()
+            .filter(|(key, value)| match value {
+                _ if key == "id" || key == "actions" => false,
+                Value::Null => false,
+                Value::String(value) => !value.is_empty(),
+                Value::Array(value) => !value.is_empty(),
+                _ => true,
+            })
+            .collect(),
+    )
+}
+
+/// Attachment of `event` with the current configuration, with an RSVP button if `old` had buttons
+fn fresh_attachment(event: &CtfEvent, old: &Value) -> Value {
+    let mut attachment = event.to_slack();
+    let had_buttons = old["actions"]
+        .as_array()
+        .map_or(false, |actions| !actions.is_empty());
+    if let (true, Some(actions_url)) = (had_buttons, CONFIG.actions_url()) {
+        attachment
+            .actions
+            .push(rsvp_button(event.id(), &actions_url));
+    }
+    serde_json::to_value(attachment).expect("Serializing an attachment cannot fail")
+}
+
+/// Props of a post with the attachments of the `events` rendered again
+///
+/// Returns `None` if the post is not from the bot or nothing changed.
+/// Attachments of unknown events, e.g., the footer, are kept as they are.
+pub fn reformatted_props(
+    props: &Map<String, Value>,
+    events: &[CtfEvent],
+) -> Option<Map<String, Value>> {
+    if !props.contains_key(PROPS_METADATA_KEY) {
+        return None;
+    }
+    let mut changed = false;
+    let attachments: Vec<Value> = props
+        .get("attachments")?
+        .as_array()?
+        .iter()
+        .map(|old| {
+            let event = old["footer"]
+                .as_str()
+                .and_then(event_id_from_footer)
+                .and_then(|id| events.iter().find(|event| event.id() == id));
+            match event {
+                Some(event) => {
+                    let fresh = fresh_attachment(event, old);
+                    changed |= normalized(&fresh) != normalized(old);
+                    fresh
+                }
+                None => old.clone(),
+            }
+        })
+        .collect();
+    if !changed {
+        return None;
+    }
+    let mut props = props.clone();
+    props.insert("attachments".to_string(), Value::Array(attachments));
+    Some(props)
+}
+
+impl MattermostApiConfig {
+    fn api_url(&self, path: &str) -> String {
+        format!(
+            "{}/api/v4/{}",
+            self.url.as_str().trim_end_matches('/'),
+            path
+        )
+    }
+
+    /// Posts of the channel created or edited after `since`
+    fn recent_posts(
+        &self,
+        client: &Client,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Post>, reqwest::Error> {
+        let PostList { order, mut posts } = client
+            .get(&self.api_url(&format!("channels/{}/posts", self.channel_id)))
+            .bearer_auth(&self.token)
+            .query(&[("since", since.timestamp_millis())])
+            .send()?
+            .error_for_status()?
+            .json()?;
+        Ok(order.iter().filter_map(|id| posts.remove(id)).collect())
+    }
+
+    fn post(&self, client: &Client, post_id: &str) -> Result<Post, reqwest::Error> {
+        client
+            .get(&self.api_url(&format!("posts/{}", post_id)))
+            .bearer_auth(&self.token)
+            .send()?
+            .error_for_status()?
+            .json()
+    }
+
+    /// Pin or unpin the post to the channel
+    fn set_pinned(
+        &self,
+        client: &Client,
+        post_id: &str,
+        pinned: bool,
+    ) -> Result<(), reqwest::Error> {
+        let action = if pinned { "pin" } else { "unpin" };
+        client
+            .post(&self.api_url(&format!("posts/{}/{}", post_id, action)))
+            .bearer_auth(&self.token)
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    fn patch_props(
+        &self,
+        client: &Client,
+        post_id: &str,
+        props: Map<String, Value>,
+    ) -> Result<(), reqwest::Error> {
+        client
+            .put(&self.api_url(&format!("posts/{}/patch", post_id)))
+            .bearer_auth(&self.token)
+            .json(&json!({ "props": props }))
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Edit the posts of the bot since `since`, which show one of the `events`, and return the number of edited posts
+pub fn reformat_posts(
+    api: &MattermostApiConfig,
+    client: &Client,
+    events: &[CtfEvent],
+    since: DateTime<Utc>,
+) -> Result<usize, reqwest::Error> {
+    let posts = timed("Fetching the recent posts", || {
+        api.recent_posts(client, since)
+    })?;
+    let mut edited = 0;
+    for post in posts {
+        if let Some(props) = reformatted_props(&post.props, events) {
+            timed("Editing a post", || {
+                api.patch_props(client, &post.id, props)
+            })?;
+            info!("Edited post {}", post.id);
+            edited += 1;
+        }
+    }
+    Ok(edited)
+}
+
+/// Whether the props belong to a digest posted during the run `run_id`
+fn is_digest_of_run(props: &Map<String, Value>, run_id: &str) -> bool {
+    let metadata = match props.get(PROPS_METADATA_KEY) {
+        Some(metadata) => metadata,
+        None => return false,
+    };
+    metadata["run_id"] == run_id
+        && metadata["event_ids"]
+            .as_array()
+            .map_or(false, |ids| !ids.is_empty())
+}
+
+/// Switch the pinned post to the digest of this run, returns its id if it was found
+fn pin_new_digest(
+    api: &MattermostApiConfig,
+    client: &Client,
+    old: Option<&str>,
+    since: DateTime<Utc>,
+) -> Result<Option<String>, reqwest::Error> {
+    let posts = timed("Fetching the recent posts", || {
+        api.recent_posts(client, since)
+    })?;
+    let new = match posts
+        .into_iter()
+        .find(|post| is_digest_of_run(&post.props, &RUN_ID))
+    {
+        Some(post) => post.id,
+        None => return Ok(None),
+    };
+    timed("Pinning the digest", || api.set_pinned(client, &new, true))?;
+    if let Some(old) = old.filter(|&old| old != new) {
+        // The old digest may have been deleted in the meantime
+        if let Err(err) = api.set_pinned(client, old, false) {
+            error!("Couldn't unpin post {}: {}", old, err);
+        }
+    }
+    Ok(Some(new))
+}
+
+/// Keep the weights and numbers of participants of the pinned digest current
+///
+/// If a digest was `posted` during this run, it is pinned and updated from now on, instead of the previous one.
+/// The post is found by the run id in its metadata, so `since` must be before the digest was posted.
+/// Errors are logged and retried during the next synchronization.
+pub fn sync_digest_post(
+    api: &MattermostApiConfig,
+    client: &Client,
+    state: &mut State,
+    events: &[CtfEvent],
+    posted: bool,
+    since: DateTime<Utc>,
+) {
+    if posted {
+        match pin_new_digest(api, client, state.digest_post.as_deref(), since) {
+            Ok(Some(id)) => {
+                info!("Pinned the digest {}", id);
+                state.digest_post = Some(id);
+                // The digest was just posted with the current data
+                return;
+            }
+            Ok(None) => error!("Couldn't find the new digest in the channel"),
+            Err(err) => error!("Couldn't pin the new digest: {}", err),
+        }
+    }
+    let post_id = match state.digest_post {
+        Some(ref post_id) => post_id,
+        None => return,
+    };
+    let res = timed("Fetching the pinned digest", || api.post(client, post_id)).and_then(|post| {
+        match reformatted_props(&post.props, events) {
+            Some(props) => timed("Editing a post", || api.patch_props(client, post_id, props))
+                .map(|()| info!("Updated the pinned digest {}", post_id)),
+            None => Ok(()),
+        }
+    });
+    if let Err(err) = res {
+        error!("Couldn't update the pinned digest {}: {}", post_id, err);
+    }
+}
+
+#[test]
+fn test_reformatted_props() {
+    use crate::{mattermost_hook_api::Attachment, post_metadata};
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    let footer = Attachment {
+        fallback: "Data from CTFtime".to_string(),
+        footer: Some("Data from CTFtime".to_string()),
+        ..Default::default()
+    };
+    let mut props = post_metadata(&[724])
+        .extras
+        .into_iter()
+        .collect::<Map<_, _>>();
+    props.insert(
+        "attachments".to_string(),
+        json!([events[0].to_slack(), footer]),
+    );
+    assert_eq!(reformatted_props(&props, &events), None);
+    assert_eq!(reformatted_props(&props, &[]), None);
+
+    // Mattermost adds ids and empty values to the stored attachments
+    props["attachments"][0]["id"] = json!(1);
+    props["attachments"][0]["pretext"] = json!("");
+    assert_eq!(reformatted_props(&props, &events), None);
+
+    props["attachments"][0]["color"] = json!("#000000");
+    let reformatted = reformatted_props(&props, &events).unwrap();
+    assert_eq!(
+        reformatted["attachments"][0],
+        serde_json::to_value(events[0].to_slack()).unwrap()
+    );
+    assert_eq!(reformatted["attachments"][1], props["attachments"][1]);
+    assert_eq!(reformatted[PROPS_METADATA_KEY], props[PROPS_METADATA_KEY]);
+
+    assert!(is_digest_of_run(&props, &RUN_ID));
+    assert!(!is_digest_of_run(&props, "1-1"));
+    let empty = post_metadata(&[]).extras.into_iter().collect::<Map<_, _>>();
+    assert!(!is_digest_of_run(&empty, &RUN_ID));
+
+    props.remove(PROPS_METADATA_KEY);
+    assert_eq!(reformatted_props(&props, &events), None);
+    assert!(!is_digest_of_run(&props, &RUN_ID));
+}
@@ -0,0 +1,242 @@
+//! Renderers turn the posts of the bot into the payloads of a chat backend
+//!
+//! Each [`Target`][crate::config::Target] selects a renderer by name via its `backend` option.
+//! Posts are composed as Mattermost [`Message`]s, which the renderers convert into their format.
+//!
+//! Renderers for further backends, e.g., Telegram or XMPP, can be published as separate crates implementing [`Renderer`].
+//! A program using this crate adds them to a [`Registry`] and passes it to [`notifiers`][crate::notifier::notifiers] and [`post`][crate::notifier::post]:
+//!
+//! ```ignore
+//! let renderers = Registry::new().with(TelegramRenderer);
+//! ```
+
+use crate::{
+    discord_hook_api, mattermost_hook_api::Message, plain_text, rocketchat_api, slack_api,
+    teams_api,
+};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// Conversion of posts into the payloads of a chat backend
+pub trait Renderer: Send + Sync {
+    /// Name of the renderer, as used in the `backend` option of the targets
+    fn name(&self) -> &str;
+
+    /// Render a post into one or more payloads, which are posted to the webhook in order
+    fn render_message(&self, message: &Message) -> Vec<Value>;
+}
+
+/// Renderers keyed by their name
+pub struct Registry {
+    renderers: BTreeMap<String, Box<dyn Renderer>>,
+}
+
+impl Registry {
+    /// Registry with the built-in renderers
+    pub fn new() -> Self {
+        Self::empty()
+            .with(MattermostRenderer)
+            .with(DiscordRenderer)
+            .with(PlainTextRenderer)
+            .with(RocketChatRenderer)
+            .with(SlackRenderer)
+            .with(TeamsRenderer)
+    }
+
+    /// Registry without any renderer
+    pub fn empty() -> Self {
+        Self {
+            renderers: BTreeMap::new(),
+        }
+    }
+
+    /// Add a renderer, replacing a renderer with the same name
+    pub fn register(&mut self, renderer: Box<dyn Renderer>) {
+        self.renderers.insert(renderer.name().to_string(), renderer);
+    }
+
+    /// Builder style variant of [`Registry::register`]
+    pub fn with(mut self, renderer: impl Renderer + 'static) -> Self {
+        self.register(Box::new(renderer));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Renderer> {
+        self.renderers.get(name).map(|renderer| &**renderer)
+    }
+
+    /// Names of all registered renderers
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.renderers.keys().map(|name| name.as_str())
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Renders the posts as Mattermost (or Slack) messages with attachments
+pub struct MattermostRenderer;
+
+impl Renderer for MattermostRenderer {
+    fn name(&self) -> &str {
+        "mattermost"
+    }
+
+    fn render_message(&self, message: &Message) -> Vec<Value> {
+        vec![serde_json::to_value(message).expect("Serializing a message cannot fail")]
+    }
+}
+
+/// Renders the posts as Discord messages with embeds, see [`discord_hook_api`]
+pub struct DiscordRenderer;
+
+impl Renderer for DiscordRenderer {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    fn render_message(&self, message: &Message) -> Vec<Value> {
+        discord_hook_api::Message::from(message)
+            .split()
+            .iter()
+            .map(|message| {
+                serde_json::to_value(message).expect("Serializing a message cannot fail")
+            })
+            .collect()
+    }
+}
+
+/// Renders the posts as Rocket.Chat messages with attachments, see [`rocketchat_api`]
+pub struct RocketChatRenderer;
+
+impl Renderer for RocketChatRenderer {
+    fn name(&self) -> &str {
+        "rocketchat"
+    }
+
+    fn render_message(&self, message: &Message) -> Vec<Value> {
+        vec![serde_json::to_value(rocketchat_api::Message::from(message))
+            .expect("Serializing a message cannot fail")]
+    }
+}
+
+/// Renders the posts as Slack messages with Block Kit blocks, see [`slack_api`]
+pub struct SlackRenderer;
+
+impl Renderer for SlackRenderer {
+    fn name(&self) -> &str {
+        "slack"
+    }
+
+    fn render_message(&self, message: &Message) -> Vec<Value> {
+        slack_api::Message::from(message)
+            .split()
+            .iter()
+            .map(|message| {
+                serde_json::to_value(message).expect("Serializing a message cannot fail")
+            })
+            .collect()
+    }
+}
+
+/// Renders the posts as Microsoft Teams messages with Adaptive Cards, see [`teams_api`]
+pub struct TeamsRenderer;
+
+impl Renderer for TeamsRenderer {
+    fn name(&self) -> &str {
+        "teams"
+    }
+
+    fn render_message(&self, message: &Message) -> Vec<Value> {
+        teams_api::Message::from_mattermost(message)
+            .iter()
+            .map(|message| {
+                serde_json::to_value(message).expect("Serializing a message cannot fail")
+            })
+            .collect()
+    }
+}
+
+/// Renders the posts as plain text without markup, e.g., for IRC bridges, see [`plain_text`]
+///
+/// The payload is `{"username": …, "text": …}`, the format of most raw text webhooks, e.g., of Matterbridge.
+pub struct PlainTextRenderer;
+
+impl Renderer for PlainTextRenderer {
+    fn name(&self) -> &str {
+        "plain"
+    }
+
+    fn render_message(&self, message: &Message) -> Vec<Value> {
+        let mut payload = json!({ "text": plain_text::message_lines(message).join("\n") });
+        if let Some(ref username) = message.username {
+            payload["username"] = json!(username);
+        }
+        vec![payload]
+    }
+}
+
+#[test]
+fn test_registry() {
+    let registry = Registry::new();
+    assert_eq!(
+        registry.names().collect::<Vec<_>>(),
+        vec![
+            "discord",
+            "mattermost",
+            "plain",
+            "rocketchat",
+            "slack",
+            "teams"
+        ]
+    );
+    assert!(registry.get("irc").is_none());
+
+    /// Renderer of a third-party backend
+    struct IrcRenderer;
+
+    impl Renderer for IrcRenderer {
+        fn name(&self) -> &str {
+            "irc"
+        }
+
+        fn render_message(&self, message: &Message) -> Vec<Value> {
+            vec![json!({ "line": message.text })]
+        }
+    }
+
+    assert!(Registry::empty().names().next().is_none());
+    let registry = registry.with(IrcRenderer);
+    assert_eq!(registry.names().count(), 7);
+    assert_eq!(
+        registry.get("irc").unwrap().render_message(&Message {
+            text: Some("Hi".to_string()),
+            ..Default::default()
+        }),
+        vec![json!({"line": "Hi"})]
+    );
+
+    let message = Message {
+        text: Some("Hello".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(
+        registry.get("discord").unwrap().render_message(&message),
+        vec![json!({"content": "Hello"})]
+    );
+    assert_eq!(
+        registry.get("mattermost").unwrap().render_message(&message),
+        vec![json!({"text": "Hello"})]
+    );
+    assert_eq!(
+        registry.get("rocketchat").unwrap().render_message(&message),
+        vec![json!({"text": "Hello"})]
+    );
+    assert_eq!(
+        registry.get("plain").unwrap().render_message(&message),
+        vec![json!({"text": "Hello"})]
+    );
+}
@@ -0,0 +1,72 @@
+//! Error reporting to Sentry
+//!
+//! Requires the `sentry` feature and [`Config::sentry_dsn`][crate::Config::sentry_dsn].
+//! Errors logged with `error!` and panics are reported, together with the context of the run.
+//! Without the feature, only the logger is set up.
+//! In both cases, warnings and errors are collected for the [`ops`][crate::ops] messages.
+
+use crate::ops::{self, OpsLogger};
+#[cfg(not(feature = "sentry"))]
+use crate::CONFIG;
+#[cfg(not(feature = "sentry"))]
+use log::warn;
+
+/// Flushes the pending reports when dropped, keep it alive until the end of `main`
+pub struct ReportingGuard(#[cfg(feature = "sentry")] Option<sentry::ClientInitGuard>);
+
+/// Set up the logger and the error reporting
+///
+/// `mode` describes how the bot runs, e.g., `daemon` or `cron`, and is attached to all reports.
+#[cfg(feature = "sentry")]
+pub fn init(mode: &str) -> ReportingGuard {
+    use crate::CONFIG;
+
+    let logger = env_logger::Builder::from_default_env().build();
+    let max_level = ops::max_level(logger.filter());
+    log::set_boxed_logger(Box::new(
+        sentry::integrations::log::SentryLogger::with_dest(OpsLogger::new(logger)),
+    ))
+    .expect("The logger is only set once");
+    log::set_max_level(max_level);
+
+    let dsn = match CONFIG.sentry_dsn {
+        Some(ref dsn) => dsn.as_str(),
+        None => return ReportingGuard(None),
+    };
+    let guard = sentry::init((
+        dsn,
+        sentry::ClientOptions {
+            release: sentry::release_name!(),
+            ..Default::default()
+        },
+    ));
+    sentry::configure_scope(|scope| {
+        scope.set_tag("mode", mode);
+        if let Some(ref profile) = CONFIG.filter_profile {
+            scope.set_tag("filter_profile", profile);
+        }
+        scope.set_extra("targets", CONFIG.targets().len().into());
+    });
+    ReportingGuard(Some(guard))
+}
+
+#[cfg(not(feature = "sentry"))]
+pub fn init(_mode: &str) -> ReportingGuard {
+    let logger = env_logger::Builder::from_default_env().build();
+    let max_level = ops::max_level(logger.filter());
+    log::set_boxed_logger(Box::new(OpsLogger::new(logger))).expect("The logger is only set once");
+    log::set_max_level(max_level);
+    if CONFIG.sentry_dsn.is_some() {
+        warn!("SENTRY_DSN is set, but the bot was built without the `sentry` feature");
+    }
+    ReportingGuard()
+}
+
+/// Attach a number, e.g., the number of fetched events, to all further reports
+#[cfg(feature = "sentry")]
+pub fn set_context(key: &str, value: usize) {
+    sentry::configure_scope(|scope| scope.set_extra(key, value.into()));
+}
+
+#[cfg(not(feature = "sentry"))]
+pub fn set_context(_key: &str, _value: usize) {}
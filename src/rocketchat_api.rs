@@ -0,0 +1,162 @@
+//! Types of Rocket.Chat incoming webhooks
+//!
+//! The messages are converted from the Mattermost [`Message`][mattermost::Message]s, such that both show the same content.
+//! Rocket.Chat attachments are similar to Mattermost's, but differ in some details:
+//! the bot is overridden with `alias`, `avatar`, and `emoji`, attachments have no `pretext` and `footer`,
+//! and bold text uses single asterisks.
+
+use crate::{
+    discord_hook_api::rgb,
+    mattermost_hook_api::{self as mattermost, Url},
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+lazy_static! {
+    static ref RE_BOLD: Regex = Regex::new(r"\*\*(?P<text>[^*]+)\*\*").unwrap();
+    static ref RE_HEADING: Regex = Regex::new(r"(?m)^#+ (?P<text>.*)$").unwrap();
+}
+
+/// Message posted to a Rocket.Chat incoming webhook
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Message {
+    pub text: Option<String>,
+    /// Overrides the channel of the webhook, e.g., `#ctf` or `@alice`
+    pub channel: Option<String>,
+    /// Overrides the display name of the webhook
+    pub alias: Option<String>,
+    /// Overrides the avatar of the webhook
+    pub avatar: Option<Url>,
+    /// Overrides the avatar with an emoji, including the colons, e.g., `:triangular_flag_on_post:`
+    pub emoji: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Attachment {
+    pub title: Option<String>,
+    pub title_link: Option<Url>,
+    pub text: Option<String>,
+    /// Color of the left border as a CSS color
+    pub color: Option<String>,
+    pub thumb_url: Option<Url>,
+    pub image_url: Option<Url>,
+    pub author_name: Option<String>,
+    pub author_link: Option<Url>,
+    pub author_icon: Option<Url>,
+    /// Time shown next to the attachment in RFC 3339 format
+    pub ts: Option<String>,
+    /// Makes the [`ts`][Attachment::ts] a link
+    pub message_link: Option<Url>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<Field>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Field {
+    /// Show the field next to other short fields
+    pub short: bool,
+    pub title: String,
+    pub value: String,
+}
+
+/// Convert the Markdown used by the bot into Rocket.Chat's message format
+pub fn markdown_to_rocketchat(markdown: &str) -> String {
+    let text = RE_BOLD.replace_all(markdown, "*$text*");
+    RE_HEADING.replace_all(&text, "*$text*").into_owned()
+}
+
+impl From<&mattermost::Attachment> for Attachment {
+    fn from(attachment: &mattermost::Attachment) -> Self {
+        // Without pretext and footer, both are added to the text
+        let text: Vec<String> = attachment
+            .pretext
+            .iter()
+            .chain(&attachment.text)
+            .map(|text| markdown_to_rocketchat(text))
+            .chain(
+                attachment
+                    .footer
+                    .iter()
+                    .map(|footer| format!("_{}_", footer)),
+            )
+            .collect();
+        Attachment {
+            title: attachment.title.clone(),
+            title_link: attachment.title_link.clone(),
+            text: if text.is_empty() {
+                Some(attachment.fallback.clone())
+            } else {
+                Some(text.join("\n"))
+            },
+            color: attachment
+                .color
+                .as_ref()
+                .and_then(rgb)
+                .map(|rgb| format!("#{:06x}", rgb)),
+            thumb_url: attachment.thumb_url.clone(),
+            image_url: attachment.image_url.clone(),
+            author_name: attachment.author_name.clone(),
+            author_link: attachment.author_link.clone(),
+            author_icon: attachment.author_icon.clone(),
+            ts: None,
+            message_link: None,
+            fields: attachment
+                .fields
+                .iter()
+                .map(|field| Field {
+                    short: field.short.unwrap_or(false),
+                    title: field.title.clone().unwrap_or_default(),
+                    value: markdown_to_rocketchat(field.value.as_deref().unwrap_or_default()),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&mattermost::Message> for Message {
+    fn from(message: &mattermost::Message) -> Self {
+        Message {
+            text: message.text.as_deref().map(markdown_to_rocketchat),
+            channel: message.channel.clone(),
+            alias: message.username.clone(),
+            avatar: message.icon_url.clone(),
+            emoji: message
+                .icon_emoji
+                .as_ref()
+                .map(|emoji| format!(":{}:", emoji.trim_matches(':'))),
+            attachments: message.attachments.iter().map(Attachment::from).collect(),
+        }
+    }
+}
+
+#[test]
+fn test_rocketchat_message() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<crate::CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    let message = mattermost::Message {
+        text: Some("### Upcoming CTFs".to_string()),
+        username: Some("CTFtime".to_string()),
+        icon_emoji: Some("triangular_flag_on_post".to_string()),
+        attachments: vec![events[0].to_slack()],
+        ..Default::default()
+    };
+    let message = serde_json::to_value(Message::from(&message)).unwrap();
+    assert_eq!(message["text"], "*Upcoming CTFs*");
+    assert_eq!(message["alias"], "CTFtime");
+    assert_eq!(message["emoji"], ":triangular_flag_on_post:");
+    assert!(message.get("username").is_none());
+
+    let attachment = &message["attachments"][0];
+    assert_eq!(attachment["title"], "X-MAS CTF 2018 — Jeopardy");
+    assert!(attachment.get("footer").is_none());
+    let text = attachment["text"].as_str().unwrap();
+    assert!(text.starts_with("*Date:* "), "{}", text);
+    assert!(text.ends_with("\n_CTFtime event #724_"), "{}", text);
+}
@@ -0,0 +1,112 @@
+//! RSVP buttons on announced events
+//!
+//! Users sign up for an event by clicking a button.
+//! If the team already committed to an overlapping event, the user receives a warning.
+
+use crate::{
+    actions::ActionContext,
+    mattermost_hook_api::{Action, Integration, Url},
+    state::State,
+    CONFIG,
+};
+use serde::{Deserialize, Serialize};
+
+/// Context of the RSVP button, see [`ActionContext::Rsvp`]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct RsvpContext {
+    pub event_id: usize,
+}
+
+/// Button to sign up for the event, the click is sent to `actions_url`
+pub fn rsvp_button(event_id: usize, actions_url: &Url) -> Action {
+    Action {
+        name: "I'm in".to_string(),
+        integration: Integration {
+            url: actions_url.to_string(),
+            context: serde_json::to_value(ActionContext::Rsvp(RsvpContext { event_id }))
+                .expect("Serializing the action context cannot fail"),
+        },
+    }
+}
+
+/// Other events the team committed to, which overlap the event `event_id`
+///
+/// The team committed to an event if it is configured as playing or somebody RSVP'd.
+pub fn overlapping_commitments(state: &State, event_id: usize) -> Vec<usize> {
+    let record = match state.events.get(&event_id) {
+        Some(record) => record,
+        None => return Vec::new(),
+    };
+    let (start, finish) = match (record.start, record.finish) {
+        (Some(start), Some(finish)) => (start, finish),
+        _ => return Vec::new(),
+    };
+    state
+        .events
+        .iter()
+        .filter(|&(&id, _)| id != event_id)
+        .filter(|(&id, other)| !other.rsvps.is_empty() || CONFIG.is_playing(id))
+        .filter(|(_, other)| match (other.start, other.finish) {
+            (Some(other_start), Some(other_finish)) => other_start < finish && start < other_finish,
+            _ => false,
+        })
+        .map(|(&id, _)| id)
+        .collect()
+}
+
+/// Store the RSVP of `user_id` and create the reply for the user
+pub fn rsvp(state: &mut State, event_id: usize, user_id: String) -> String {
+    let overlapping = overlapping_commitments(state, event_id);
+    let record = state.events.entry(event_id).or_default();
+    if !record.rsvps.insert(user_id) {
+        return format!("You are already signed up for {}.", record.title);
+    }
+    let mut text = format!("You're in for {}!", record.title);
+    if !overlapping.is_empty() {
+        let titles: Vec<&str> = overlapping
+            .iter()
+            .filter_map(|id| state.events.get(id))
+            .map(|other| other.title.as_str())
+            .collect();
+        text += &format!(
+            " Note: you're already committed to {} that weekend.",
+            titles.join(", ")
+        );
+    }
+    text
+}
+
+#[test]
+fn test_rsvp_overlapping() {
+    use crate::CtfEvent;
+    let json = std::fs::read_to_string("./tests/ctfs.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_str(&json).unwrap();
+    let mut state = State::default();
+    state.record_events(&events);
+
+    // MeePwn CTF 1st 2017 and CTFZone 2017 overlap
+    let first = events.iter().find(|event| event.id() == 486).unwrap();
+    let second = events.iter().find(|event| event.id() == 476).unwrap();
+
+    assert!(overlapping_commitments(&state, second.id()).is_empty());
+    assert_eq!(
+        rsvp(&mut state, first.id(), "alice".to_string()),
+        format!("You're in for {}!", first.title())
+    );
+    assert_eq!(
+        overlapping_commitments(&state, second.id()),
+        vec![first.id()]
+    );
+    assert_eq!(
+        rsvp(&mut state, second.id(), "bob".to_string()),
+        format!(
+            "You're in for {}! Note: you're already committed to {} that weekend.",
+            second.title(),
+            first.title()
+        )
+    );
+    assert_eq!(
+        rsvp(&mut state, first.id(), "alice".to_string()),
+        format!("You are already signed up for {}.", first.title())
+    );
+}
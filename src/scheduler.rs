@@ -0,0 +1,308 @@
+//! Time based notifications for events, used in daemon mode
+//!
+//! The scheduler determines which [`Reminder`]s are due for which events.
+//! Sent reminders are stored in the [`State`], such that each reminder is only sent once.
+
+use crate::{
+    checklist::{checklist_message, permalink, DEFAULT_CHECKLIST},
+    format_duration,
+    holidays::find_blackout,
+    mattermost_hook_api::Attachment,
+    rsvp::rsvp_button,
+    state::State,
+    vote::{feedback_poll, suggest_weight},
+    warmup::wants_warmup,
+    CtfEvent, CONFIG,
+};
+use chrono::{DateTime, Utc};
+use log::error;
+use serde::{Deserialize, Serialize};
+
+/// Kinds of time based notifications
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum Reminder {
+    /// The event just started
+    Live,
+    /// The event ends in a few hours, only for events the team plays
+    EndsSoon,
+    /// The event ended, open a post collecting the writeups
+    Writeups,
+    /// Ask the participants to submit their writeups
+    WriteupPing,
+    /// Ask the players for feedback about the event, requires the server
+    FeedbackPoll,
+    /// Ask the team to vote for the weight of the event on CTFtime
+    WeightVote,
+    /// Post our placement next to the rivals, requires the team id
+    Results,
+    /// Post the checklist a few days before the event starts
+    Checklist,
+    /// Run the warm-up trigger before an Attack-Defense event, posts nothing
+    Warmup,
+    /// Escalate an announced high-weight event with too few RSVPs
+    Escalation,
+}
+
+/// A [`Reminder`] for an event, which is due at a specific time
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Job {
+    pub due: DateTime<Utc>,
+    pub event_id: usize,
+    pub reminder: Reminder,
+}
+
+/// All reminders which still need to be sent, ordered by their due time
+///
+/// Only events which were announced before receive [`Reminder::Live`].
+/// All other reminders are only sent for events the team plays, i.e., which are configured as playing or have RSVPs.
+/// Events during a blackout period receive no reminders.
+pub fn pending_jobs(events: &[CtfEvent], state: &State) -> Vec<Job> {
+    let mut jobs = Vec::new();
+    for event in events {
+        let record = match state.events.get(&event.id()) {
+            Some(record) => record,
+            None => continue,
+        };
+        if find_blackout(event, &CONFIG.blackouts, CONFIG.timezone).is_some() {
+            continue;
+        }
+        let start = event.start_date().with_timezone(&Utc);
+        let finish = event.finish_date().with_timezone(&Utc);
+        let mut candidates = Vec::with_capacity(10);
+        if record.announced {
+            candidates.push((start, Reminder::Live));
+            if let Some(ref escalation) = CONFIG.escalation {
+                if escalation.is_watched(event) {
+                    candidates.push((start - escalation.lead_time, Reminder::Escalation));
+                }
+            }
+        }
+        if CONFIG.is_playing(event.id()) || !record.rsvps.is_empty() {
+            if let Some(ref warmup) = CONFIG.warmup {
+                if wants_warmup(event) {
+                    candidates.push((start - warmup.lead_time_for(event.id()), Reminder::Warmup));
+                }
+            }
+            if CONFIG.pre_event_checklist {
+                candidates.push((start - CONFIG.checklist_lead_time, Reminder::Checklist));
+            }
+            candidates.push((finish - CONFIG.ends_soon(event.id()), Reminder::EndsSoon));
+            candidates.push((finish, Reminder::Writeups));
+            candidates.push((finish + CONFIG.writeup_ping_delay, Reminder::WriteupPing));
+            if CONFIG.server_url.is_some() {
+                candidates.push((finish, Reminder::FeedbackPoll));
+            }
+            if event.public_votable() {
+                candidates.push((finish + CONFIG.vote_prompt_delay, Reminder::WeightVote));
+            }
+            if CONFIG.team_id.is_some() {
+                candidates.push((finish + CONFIG.results_delay, Reminder::Results));
+            }
+        }
+        jobs.extend(
+            candidates
+                .into_iter()
+                .filter(|(_, reminder)| !record.sent_reminders.contains(reminder))
+                .map(|(due, reminder)| Job {
+                    due,
+                    event_id: event.id(),
+                    reminder,
+                }),
+        );
+    }
+    jobs.sort_by_key(|job| (job.due, job.event_id, job.reminder));
+    jobs
+}
+
+impl Reminder {
+    /// Whether the reminder is still relevant at time `now`
+    ///
+    /// Reminders which are overdue for too long, e.g., because the bot was not running, are skipped.
+    pub fn is_relevant(self, event: &CtfEvent, now: DateTime<Utc>) -> bool {
+        let grace_period = chrono::Duration::days(1);
+        match self {
+            Reminder::Checklist | Reminder::Warmup | Reminder::Escalation => {
+                now < event.start_date()
+            }
+            Reminder::Live | Reminder::EndsSoon => now < event.finish_date(),
+            Reminder::Writeups | Reminder::FeedbackPoll => now < event.finish_date() + grace_period,
+            Reminder::WriteupPing => {
+                now < event.finish_date() + CONFIG.writeup_ping_delay + grace_period
+            }
+            Reminder::WeightVote => {
+                now < event.finish_date() + CONFIG.vote_prompt_delay + grace_period
+            }
+            // CTFtime sometimes takes a few days until the results are final
+            Reminder::Results => {
+                now < event.finish_date() + CONFIG.results_delay + chrono::Duration::days(7)
+            }
+        }
+    }
+
+    /// Text of the notification for `event`
+    pub fn message(self, event: &CtfEvent, state: &State, now: DateTime<Utc>) -> String {
+        match self {
+            Reminder::Live => {
+                let link = event
+                    .live_feed()
+                    .or_else(|| event.url())
+                    .unwrap_or_else(|| event.ctftime_url());
+                format!(
+                    "🏁 [{}]({}) is live — scoreboard: {}, ends in {}",
+                    event.display_title(),
+                    event.ctftime_url(),
+                    link,
+                    format_duration(&event.finish_date().signed_duration_since(now))
+                )
+            }
+            Reminder::EndsSoon => format!(
+                "⏳ {} left in [{}]({}) — submit your flags and start writeups",
+                format_duration(&event.finish_date().signed_duration_since(now)),
+                event.display_title(),
+                event.ctftime_url(),
+            ),
+            Reminder::Writeups => {
+                let mut text = format!(
+                    "📝 Writeups for [{}]({})\n\nPlease add a link to your writeup for each category you solved.\n",
+                    event.display_title(),
+                    event.ctftime_url(),
+                );
+                for category in CONFIG.writeup_categories(event.id()) {
+                    text += &format!("\n- [ ] {}", category);
+                }
+                text
+            }
+            Reminder::WriteupPing => {
+                let mut text = format!(
+                    "Reminder: please submit your writeups for [{}]({})",
+                    event.display_title(),
+                    event.ctftime_url(),
+                );
+                if let Some(record) = state.events.get(&event.id()) {
+                    for user in &record.rsvps {
+                        text += &format!(" @{}", user);
+                    }
+                }
+                text
+            }
+            Reminder::FeedbackPoll => format!(
+                "How was [{}]({})? Your feedback helps with the weight vote.",
+                event.display_title(),
+                event.ctftime_url(),
+            ),
+            Reminder::WeightVote => {
+                suggest_weight(event, state.events.get(&event.id())).message(event)
+            }
+            Reminder::Checklist => {
+                let template = CONFIG
+                    .checklist_template
+                    .as_deref()
+                    .unwrap_or(DEFAULT_CHECKLIST);
+                let announcement = state
+                    .events
+                    .get(&event.id())
+                    .and_then(|record| record.announcement_post.as_deref())
+                    .and_then(|post_id| permalink(&CONFIG.targets(), post_id));
+                checklist_message(event, template, announcement.as_ref()).unwrap_or_else(|err| {
+                    error!("Couldn't render the checklist template: {}", err);
+                    format!(
+                        "📋 Checklist for [{}]({})\n\n{}",
+                        event.display_title(),
+                        event.ctftime_url(),
+                        template
+                    )
+                })
+            }
+            Reminder::Warmup => format!(
+                "🔥 Warming up the infrastructure for [{}]({})",
+                event.display_title(),
+                event.ctftime_url(),
+            ),
+            Reminder::Escalation => match CONFIG.escalation {
+                Some(ref escalation) => escalation.channel_message(event, state, now),
+                None => format!(
+                    "@channel 🙋 [{}]({}) needs players",
+                    event.display_title(),
+                    event.ctftime_url(),
+                ),
+            },
+            Reminder::Results => format!(
+                "🏆 Results of [{}]({})",
+                event.display_title(),
+                event.ctftime_url(),
+            ),
+        }
+    }
+
+    /// Additional attachments of the notification, e.g., with buttons
+    pub fn attachments(self, event: &CtfEvent) -> Vec<Attachment> {
+        match (self, CONFIG.actions_url()) {
+            (Reminder::FeedbackPoll, Some(actions_url)) => feedback_poll(event, &actions_url),
+            (Reminder::Escalation, Some(actions_url)) => {
+                let mut attachment = event.to_slack();
+                attachment
+                    .actions
+                    .push(rsvp_button(event.id(), &actions_url));
+                vec![attachment]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[test]
+fn test_pending_jobs() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    let mut state = State::default();
+    state.record_events(&events);
+    assert!(pending_jobs(&events, &state).is_empty());
+
+    state.mark_announced(&[724]);
+    let jobs = pending_jobs(&events, &state);
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].event_id, 724);
+    assert_eq!(jobs[0].reminder, Reminder::Live);
+    assert_eq!(jobs[0].due, events[0].start_date().with_timezone(&Utc));
+
+    let now = events[0].start_date().with_timezone(&Utc) + chrono::Duration::hours(1);
+    assert!(Reminder::Live.is_relevant(&events[0], now));
+    assert_eq!(
+        Reminder::Live.message(&events[0], &state, now),
+        "🏁 [X-MAS CTF 2018](https://ctftime.org/event/724/) is live — scoreboard: https://ctftime.org/live/724/, ends in 6 days 23 hours"
+    );
+
+    state.mark_sent(724, Reminder::Live);
+    assert!(pending_jobs(&events, &state).is_empty());
+
+    state
+        .events
+        .get_mut(&724)
+        .unwrap()
+        .rsvps
+        .insert("user".to_string());
+    let jobs = pending_jobs(&events, &state);
+    assert_eq!(jobs.len(), 4);
+    assert_eq!(jobs[0].reminder, Reminder::EndsSoon);
+    assert_eq!(jobs[1].reminder, Reminder::Writeups);
+    assert_eq!(jobs[2].reminder, Reminder::WeightVote);
+    assert_eq!(jobs[3].reminder, Reminder::WriteupPing);
+    assert_eq!(
+        jobs[3].due,
+        events[0].finish_date().with_timezone(&Utc) + chrono::Duration::hours(72)
+    );
+    assert_eq!(
+        jobs[0].due,
+        events[0].finish_date().with_timezone(&Utc) - chrono::Duration::hours(2)
+    );
+    assert_eq!(
+        Reminder::EndsSoon.message(&events[0], &state, jobs[0].due),
+        "⏳ 2 hours left in [X-MAS CTF 2018](https://ctftime.org/event/724/) — submit your flags and start writeups"
+    );
+    assert_eq!(
+        Reminder::WriteupPing.message(&events[0], &state, jobs[3].due),
+        "Reminder: please submit your writeups for [X-MAS CTF 2018](https://ctftime.org/event/724/) @user"
+    );
+}
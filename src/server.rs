@@ -0,0 +1,477 @@
+//! HTTP server receiving the interactive actions and slash commands from Mattermost
+//!
+//! It also serves the read-only JSON API, see [`api`][crate::api].
+//! Access to all endpoints is controlled with scoped tokens, see [`access`][crate::access].
+//! The same server setup is used to serve the iCalendar [`Feed`], see [`serve_feed`].
+
+use crate::{
+    access::{bearer_token, is_allowed, Scope},
+    actions::handle_action,
+    admin::{handle_admin, restart, AdminReply},
+    api::{api_response, API_PATH},
+    ical::Feed,
+    mattermost_hook_api::{ActionEvent, ActionResponse, CommandRequest, CommandResponse},
+    preferences::handle_command,
+    ratelimit::RateLimiter,
+    state::StateStore,
+    CONFIG,
+};
+use chrono::Utc;
+use log::{debug, error, info, warn};
+use serde::Serialize;
+use std::{
+    error::Error,
+    io::{Cursor, Read},
+    net::IpAddr,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Path which receives the [`ActionEvent`]s
+pub const ACTIONS_PATH: &str = "actions";
+/// Path which receives the `/ctftime` slash command, see [`CommandRequest`]
+pub const COMMANDS_PATH: &str = "commands";
+/// Path of the iCalendar feed
+pub const CALENDAR_PATH: &str = "calendar.ics";
+
+/// Maximal size of a request body, larger requests are rejected
+const MAX_BODY_SIZE: usize = 64 * 1024;
+/// Number of threads handling requests, such that a slow client does not block the others
+const WORKERS: usize = 4;
+/// Reply if a user or channel exceeds the rate limit
+const RATE_LIMITED: &str = "Slow down, please! You sent too many requests, try again in a minute.";
+
+/// Rate limits of the server, see [`Config::rate_limit_per_user`][crate::Config::rate_limit_per_user]
+struct Limits {
+    users: RateLimiter,
+    channels: RateLimiter,
+}
+
+impl Limits {
+    /// Count a request and return whether both the user and the channel are within their limits
+    fn check(&self, user_id: &str, channel_id: &str) -> bool {
+        let now = Instant::now();
+        // Always count both, such that a user cannot flood the channel limit
+        let user = self.users.check(user_id, now);
+        let channel = self.channels.check(channel_id, now);
+        user && channel
+    }
+}
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// Listen on `address` and handle the incoming requests
+///
+/// An address starting with `unix:` is a path to a Unix socket, e.g., for a reverse proxy.
+/// Otherwise the server uses HTTPS if a certificate is configured, and HTTP if not.
+/// Client certificates are not verified, this is left to a reverse proxy.
+///
+/// This function only returns if the server cannot be started.
+pub fn serve(address: &str, store: Arc<StateStore>) -> Result<(), BoxError> {
+    let server = Arc::new(bind(address)?);
+    info!("Listening on {}", address);
+    let minute = Duration::from_secs(60);
+    let limits = Arc::new(Limits {
+        users: RateLimiter::new(CONFIG.rate_limit_per_user, minute),
+        channels: RateLimiter::new(CONFIG.rate_limit_per_channel, minute),
+    });
+
+    let workers: Vec<_> = (0..WORKERS)
+        .map(|_| {
+            let server = server.clone();
+            let store = store.clone();
+            let limits = limits.clone();
+            std::thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    handle_request(request, &store, &limits);
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        let _ = worker.join();
+    }
+    Ok(())
+}
+
+/// Listen on `address` and serve the `feed` under [`CALENDAR_PATH`]
+///
+/// Binds like [`serve`]. This function only returns if the server cannot be started.
+pub fn serve_feed(address: &str, feed: Arc<Feed>) -> Result<(), BoxError> {
+    let server = Arc::new(bind(address)?);
+    info!("Serving the calendar on {}/{}", address, CALENDAR_PATH);
+    let workers: Vec<_> = (0..WORKERS)
+        .map(|_| {
+            let server = server.clone();
+            let feed = feed.clone();
+            std::thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    let path = request.url().trim_start_matches('/');
+                    let response = match (request.method(), path) {
+                        (Method::Get, CALENDAR_PATH) | (Method::Head, CALENDAR_PATH) => {
+                            calendar_response(&feed)
+                        }
+                        _ => Response::from_string("Not found").with_status_code(404),
+                    };
+                    if let Err(err) = request.respond(response) {
+                        error!("Couldn't send response: {}", err);
+                    }
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        let _ = worker.join();
+    }
+    Ok(())
+}
+
+fn calendar_response(feed: &Feed) -> Response<Cursor<Vec<u8>>> {
+    let ics = match feed.get(Instant::now()) {
+        Some(ics) => ics,
+        None => {
+            return Response::from_string("Calendar not available, try again later")
+                .with_status_code(503)
+        }
+    };
+    Response::from_data(ics.as_bytes().to_vec())
+        .with_header(
+            Header::from_bytes(&b"Content-Type"[..], &b"text/calendar; charset=utf-8"[..])
+                .expect("The header is valid"),
+        )
+        .with_header(
+            Header::from_bytes(
+                &b"Cache-Control"[..],
+                format!("public, max-age={}", feed.max_age()).as_bytes(),
+            )
+            .expect("The header is valid"),
+        )
+}
+
+fn bind(address: &str) -> Result<Server, BoxError> {
+    if let Some(path) = address.strip_prefix("unix:") {
+        return bind_unix(Path::new(path));
+    }
+    match (&CONFIG.server_tls_cert, &CONFIG.server_tls_key) {
+        (Some(cert), Some(key)) => bind_https(address, cert, key),
+        (None, None) => Server::http(address),
+        _ => Err("Both SERVER_TLS_CERT and SERVER_TLS_KEY are required for HTTPS".into()),
+    }
+}
+
+#[cfg(unix)]
+fn bind_unix(path: &Path) -> Result<Server, BoxError> {
+    Server::http_unix(path)
+}
+
+#[cfg(not(unix))]
+fn bind_unix(_path: &Path) -> Result<Server, BoxError> {
+    Err("Unix sockets are only supported on Unix".into())
+}
+
+#[cfg(feature = "server-tls")]
+fn bind_https(address: &str, cert: &Path, key: &Path) -> Result<Server, BoxError> {
+    Server::https(
+        address,
+        tiny_http::SslConfig {
+            certificate: std::fs::read(cert)?,
+            private_key: std::fs::read(key)?,
+        },
+    )
+}
+
+#[cfg(not(feature = "server-tls"))]
+fn bind_https(_address: &str, _cert: &Path, _key: &Path) -> Result<Server, BoxError> {
+    Err("HTTPS requires the `server-tls` feature".into())
+}
+
+/// Address of the client
+///
+/// For requests from a trusted proxy, or via a Unix socket, the `X-Forwarded-For` header is used.
+/// The header is read from the right, skipping further trusted proxies.
+fn client_address(
+    peer: Option<IpAddr>,
+    forwarded_for: Option<&str>,
+    trusted: &[IpAddr],
+) -> Option<IpAddr> {
+    if let Some(peer) = peer {
+        if !trusted.contains(&peer) {
+            return Some(peer);
+        }
+    }
+    let forwarded: Vec<IpAddr> = forwarded_for
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|addr| addr.trim().parse().ok())
+        .collect();
+    forwarded
+        .iter()
+        .rev()
+        .find(|addr| !trusted.contains(addr))
+        .or_else(|| forwarded.first())
+        .copied()
+        .or(peer)
+}
+
+/// Read the request body, unless it is larger than [`MAX_BODY_SIZE`]
+fn read_body(request: &mut Request) -> Option<Vec<u8>> {
+    if request
+        .body_length()
+        .map_or(false, |len| len > MAX_BODY_SIZE)
+    {
+        return None;
+    }
+    let mut body = Vec::new();
+    match request
+        .as_reader()
+        .take(MAX_BODY_SIZE as u64 + 1)
+        .read_to_end(&mut body)
+    {
+        Ok(len) if len <= MAX_BODY_SIZE => Some(body),
+        Ok(_) => None,
+        Err(err) => {
+            warn!("Couldn't read request: {}", err);
+            None
+        }
+    }
+}
+
+fn handle_request(mut request: Request, store: &StateStore, limits: &Limits) {
+    let method = request.method().clone();
+    let path = request.url().trim_start_matches('/').to_string();
+    let forwarded_for = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("X-Forwarded-For"))
+        .map(|header| header.value.as_str().to_string());
+    let client = client_address(
+        request.remote_addr().map(|addr| addr.ip()),
+        forwarded_for.as_deref(),
+        &CONFIG.trusted_proxies,
+    );
+    let (path, query) = match path.split_once('?') {
+        Some((path, query)) => (path.to_string(), query.to_string()),
+        None => (path.clone(), String::new()),
+    };
+    debug!("{} /{} from {:?}", method, path, client);
+
+    // Actions cannot send headers, they pass the token in the query of their URL
+    let presented = authorization(&request)
+        .and_then(bearer_token)
+        .map(str::to_string)
+        .or_else(|| {
+            url::form_urlencoded::parse(query.as_bytes())
+                .find(|(key, _)| key == "token")
+                .map(|(_, value)| value.into_owned())
+        });
+    let access_state = store.read();
+    let allowed = |presented: Option<&str>, scope: Scope, legacy: Option<&str>| match access_state {
+        Ok(ref state) => is_allowed(state, presented, scope, legacy),
+        Err(ref err) => {
+            error!("Couldn't read state file: {}", err);
+            false
+        }
+    };
+    let has_tokens = access_state
+        .as_ref()
+        .map_or(false, |state| !state.tokens.is_empty());
+    let unauthorized = |endpoint: &str| {
+        warn!("{} request from {:?} with invalid token", endpoint, client);
+        Response::from_string("Invalid token").with_status_code(401)
+    };
+
+    let mut reload = false;
+    let response = match (method, &*path) {
+        (Method::Post, ACTIONS_PATH) | (Method::Post, COMMANDS_PATH) => {
+            match read_body(&mut request) {
+                Some(_)
+                    if path == ACTIONS_PATH
+                        && !allowed(presented.as_deref(), Scope::ManageSubscriptions, None) =>
+                {
+                    unauthorized("Action")
+                }
+                Some(body) if path == ACTIONS_PATH => {
+                    match serde_json::from_slice::<ActionEvent>(&body) {
+                        Ok(event) if !limits.check(&event.user_id, &event.channel_id) => {
+                            json_response(&ActionResponse {
+                                ephemeral_text: Some(RATE_LIMITED.to_string()),
+                                ..Default::default()
+                            })
+                        }
+                        Ok(event) => json_response(&handle_action(store, event)),
+                        Err(err) => {
+                            warn!("Invalid action request from {:?}: {}", client, err);
+                            Response::from_string("Invalid request").with_status_code(400)
+                        }
+                    }
+                }
+                Some(body) => {
+                    let command = CommandRequest::from_form(&body);
+                    let scope = match command.text.split_whitespace().next() {
+                        Some("admin") => Scope::Admin,
+                        _ => Scope::ManageSubscriptions,
+                    };
+                    let token = presented.as_deref().or(Some(&*command.token));
+                    if !allowed(token, scope, CONFIG.command_token.as_deref()) {
+                        unauthorized("Command")
+                    } else if limits.check(&command.user_id, &command.channel_id) {
+                        let (response, reply_reload) = handle_command_request(command, store);
+                        reload = reply_reload;
+                        response
+                    } else {
+                        json_response(&CommandResponse::ephemeral(RATE_LIMITED.to_string()))
+                    }
+                }
+                None => Response::from_string("Request too large").with_status_code(413),
+            }
+        }
+        (Method::Get, _)
+            if path.starts_with(API_PATH) && (CONFIG.api_token.is_some() || has_tokens) =>
+        {
+            if allowed(
+                presented.as_deref(),
+                Scope::ReadEvents,
+                CONFIG.api_token.as_deref(),
+            ) {
+                handle_api_request(&path[API_PATH.len()..], store)
+            } else {
+                unauthorized("API")
+            }
+        }
+        #[cfg(feature = "graphql")]
+        (Method::Post, crate::graphql::GRAPHQL_PATH)
+            if CONFIG.api_token.is_some() || has_tokens =>
+        {
+            if !allowed(
+                presented.as_deref(),
+                Scope::ReadEvents,
+                CONFIG.api_token.as_deref(),
+            ) {
+                unauthorized("GraphQL")
+            } else {
+                match read_body(&mut request) {
+                    Some(body) => handle_graphql_request(&body, store),
+                    None => Response::from_string("Request too large").with_status_code(413),
+                }
+            }
+        }
+        _ => Response::from_string("Not found").with_status_code(404),
+    };
+
+    if let Err(err) = request.respond(response) {
+        error!("Couldn't send response: {}", err);
+    }
+    if reload {
+        info!("Restarting to reload the configuration");
+        error!("Couldn't restart: {}", restart());
+    }
+}
+
+/// Handle the slash command, the second value requests a restart after the response was sent
+fn handle_command_request(
+    command: CommandRequest,
+    store: &StateStore,
+) -> (Response<Cursor<Vec<u8>>>, bool) {
+    let words: Vec<&str> = command.text.split_whitespace().collect();
+    let res = store.update(|state| match words.split_first() {
+        Some((&"admin", args)) => handle_admin(state, &command.user_name, args, Utc::now()),
+        _ => handle_command(state, &command.user_id, &command.user_name, &command.text).into(),
+    });
+    let reply = match res {
+        Ok(reply) => reply,
+        Err(err) => {
+            error!("Couldn't write state file: {}", err);
+            AdminReply::from("Sorry, your changes could not be saved.".to_string())
+        }
+    };
+    (
+        json_response(&CommandResponse::ephemeral(reply.text)),
+        reply.reload,
+    )
+}
+
+/// Value of the `Authorization` header
+fn authorization(request: &Request) -> Option<&str> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .map(|header| header.value.as_str())
+}
+
+/// Answer the GraphQL request, see [`graphql`][crate::graphql]
+#[cfg(feature = "graphql")]
+fn handle_graphql_request(body: &[u8], store: &StateStore) -> Response<Cursor<Vec<u8>>> {
+    let state = match store.read() {
+        Ok(state) => state,
+        Err(err) => {
+            error!("Couldn't read state file: {}", err);
+            return Response::from_string("Internal error").with_status_code(500);
+        }
+    };
+    match crate::graphql::execute(body, state, reqwest::blocking::Client::new()) {
+        Ok(value) => json_response(&value),
+        Err(err) => {
+            warn!("Invalid GraphQL request: {}", err);
+            Response::from_string("Invalid request").with_status_code(400)
+        }
+    }
+}
+
+/// Answer the API request for `path`, without the [`API_PATH`] prefix
+fn handle_api_request(path: &str, store: &StateStore) -> Response<Cursor<Vec<u8>>> {
+    let state = match store.read() {
+        Ok(state) => state,
+        Err(err) => {
+            error!("Couldn't read state file: {}", err);
+            return Response::from_string("Internal error").with_status_code(500);
+        }
+    };
+    match api_response(path, &state, Utc::now()) {
+        Some(value) => json_response(&value),
+        None => Response::from_string("Not found").with_status_code(404),
+    }
+}
+
+fn json_response(value: &impl Serialize) -> Response<Cursor<Vec<u8>>> {
+    let body = serde_json::to_vec(value).expect("Serializing the response cannot fail");
+    Response::from_data(body).with_header(
+        Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+            .expect("The header is valid"),
+    )
+}
+
+#[test]
+fn test_client_address() {
+    let ip = |s: &str| -> IpAddr { s.parse().unwrap() };
+    let proxy = ip("127.0.0.1");
+    let trusted = [proxy, ip("10.0.0.1")];
+
+    // Direct connections ignore the header
+    assert_eq!(
+        client_address(Some(ip("192.0.2.1")), Some("198.51.100.7"), &trusted),
+        Some(ip("192.0.2.1"))
+    );
+    assert_eq!(
+        client_address(Some(proxy), Some("198.51.100.7"), &trusted),
+        Some(ip("198.51.100.7"))
+    );
+    // Spoofed entries left of the last untrusted address are ignored
+    assert_eq!(
+        client_address(
+            Some(proxy),
+            Some("203.0.113.9, 198.51.100.7, 10.0.0.1"),
+            &trusted
+        ),
+        Some(ip("198.51.100.7"))
+    );
+    assert_eq!(client_address(Some(proxy), None, &trusted), Some(proxy));
+    // Unix sockets
+    assert_eq!(
+        client_address(None, Some("198.51.100.7"), &trusted),
+        Some(ip("198.51.100.7"))
+    );
+    assert_eq!(client_address(None, None, &trusted), None);
+}
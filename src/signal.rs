@@ -0,0 +1,57 @@
+//! Send messages to Signal using the JSON-RPC interface of [signal-cli]
+//!
+//! signal-cli needs to run in daemon mode with the HTTP interface enabled, e.g., `signal-cli -a +4912345 daemon --http 127.0.0.1:8081`.
+//!
+//! [signal-cli]: https://github.com/AsamK/signal-cli
+
+use crate::mattermost_hook_api::Url;
+use log::error;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Configuration of the Signal backend, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct SignalConfig {
+    /// JSON-RPC endpoint of signal-cli, e.g., `http://127.0.0.1:8081/api/v1/rpc`
+    pub url: Url,
+    /// Phone number of the account, only required if signal-cli manages multiple accounts
+    pub account: Option<String>,
+    /// Base64 encoded id of the group to send the messages to
+    pub group_id: Option<String>,
+    /// Phone numbers of individual recipients
+    #[serde(default)]
+    pub recipients: Vec<String>,
+}
+
+impl SignalConfig {
+    /// Send a plain text message to the configured group and recipients
+    pub fn send(&self, client: &Client, text: &str) -> Result<(), reqwest::Error> {
+        let mut params = json!({ "message": text });
+        if let Some(ref account) = self.account {
+            params["account"] = json!(account);
+        }
+        if let Some(ref group_id) = self.group_id {
+            params["groupId"] = json!(group_id);
+        }
+        if !self.recipients.is_empty() {
+            params["recipient"] = json!(self.recipients);
+        }
+
+        let response: Value = client
+            .post(self.url.clone())
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "method": "send",
+                "params": params,
+                "id": 1,
+            }))
+            .send()?
+            .error_for_status()?
+            .json()?;
+        if let Some(err) = response.get("error") {
+            error!("signal-cli failed to send the message: {}", err);
+        }
+        Ok(())
+    }
+}
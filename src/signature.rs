@@ -0,0 +1,68 @@
+//! Signatures of the outgoing webhook requests
+//!
+//! Targets with a `signing_secret` add two headers to each request:
+//! [`TIMESTAMP_HEADER`] with the current Unix time and [`SIGNATURE_HEADER`] with `sha256=<hex>`,
+//! the HMAC-SHA256 of `<timestamp>.<body>` keyed with the secret.
+//! Receivers verify the signature and reject old timestamps to prevent replays.
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::{blocking::RequestBuilder, header::CONTENT_TYPE};
+use serde::Serialize;
+use sha2::Sha256;
+
+pub const SIGNATURE_HEADER: &str = "X-Ctftimebot-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Ctftimebot-Timestamp";
+
+fn hmac_sha256_hex(secret: &[u8], data: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Value of the [`SIGNATURE_HEADER`] for `body` sent at `timestamp`
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut data = format!("{}.", timestamp).into_bytes();
+    data.extend_from_slice(body);
+    format!("sha256={}", hmac_sha256_hex(secret.as_bytes(), &data))
+}
+
+/// Set `payload` as JSON body of the request, signed with `secret` if there is one
+///
+/// The body is serialized once, such that the signature covers exactly the bytes sent.
+pub fn json_body<T: Serialize + ?Sized>(
+    request: RequestBuilder,
+    payload: &T,
+    secret: Option<&str>,
+) -> serde_json::Result<RequestBuilder> {
+    let body = serde_json::to_vec(payload)?;
+    let mut request = request.header(CONTENT_TYPE, "application/json");
+    if let Some(secret) = secret {
+        let timestamp = Utc::now().timestamp();
+        request = request
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, sign(secret, timestamp, &body));
+    }
+    Ok(request.body(body))
+}
+
+#[test]
+fn test_sign() {
+    // Test case 2 of RFC 4231
+    assert_eq!(
+        hmac_sha256_hex(b"Jefe", b"what do ya want for nothing?"),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+    assert_eq!(
+        sign("Jefe", 1600000000, b"{}"),
+        format!("sha256={}", hmac_sha256_hex(b"Jefe", b"1600000000.{}"))
+    );
+    assert_ne!(
+        sign("Jefe", 1600000000, b"{}"),
+        sign("Jefe", 1600000001, b"{}")
+    );
+}
@@ -0,0 +1,244 @@
+//! Types of Slack incoming webhooks using [Block Kit]
+//!
+//! The messages are converted from the Mattermost [`Message`][mattermost::Message]s, such that both show the same content.
+//! Each attachment becomes a section with the thumbnail as accessory, a context line with the footer, and a divider.
+//!
+//! [Block Kit]: https://api.slack.com/block-kit
+
+use crate::mattermost_hook_api::{self as mattermost, Url};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Maximal number of blocks per message
+pub const MAX_BLOCKS: usize = 50;
+/// Maximal length of the text of a section
+const MAX_SECTION_TEXT: usize = 3000;
+
+lazy_static! {
+    static ref RE_LINK: Regex = Regex::new(r"\[(?P<text>[^\]]*)\]\((?P<url>[^)]*)\)").unwrap();
+    static ref RE_BOLD: Regex = Regex::new(r"\*\*(?P<text>[^*]+)\*\*").unwrap();
+    static ref RE_HEADING: Regex = Regex::new(r"(?m)^#+ (?P<text>.*)$").unwrap();
+}
+
+/// Slack incoming webhooks accept a fallback text and the blocks of the message
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Message {
+    /// Shown in notifications and by clients without Block Kit support
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocks: Vec<Block>,
+}
+
+/// Layout blocks of a [`Message`]
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Header {
+        /// Must be [`Element::PlainText`]
+        text: Element,
+    },
+    Section {
+        text: Element,
+        /// Element shown on the right side, e.g., an image
+        accessory: Option<Element>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        fields: Vec<Element>,
+    },
+    /// Small text and images, e.g., for footers
+    Context {
+        elements: Vec<Element>,
+    },
+    Image {
+        image_url: Url,
+        alt_text: String,
+    },
+    Divider {},
+}
+
+/// Text objects and image elements
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Element {
+    /// Text using Slack's [mrkdwn](https://api.slack.com/reference/surfaces/formatting)
+    Mrkdwn {
+        text: String,
+    },
+    PlainText {
+        text: String,
+    },
+    Image {
+        image_url: Url,
+        alt_text: String,
+    },
+}
+
+impl Element {
+    pub fn mrkdwn(text: impl Into<String>) -> Self {
+        Element::Mrkdwn { text: text.into() }
+    }
+}
+
+/// Convert the Markdown used by the bot into Slack's mrkdwn
+///
+/// Links are written as `<url|text>`, bold text uses single asterisks, and headings become bold lines.
+pub fn markdown_to_mrkdwn(markdown: &str) -> String {
+    let escaped = markdown
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    let text = RE_LINK.replace_all(&escaped, "<$url|$text>");
+    let text = RE_BOLD.replace_all(&text, "*$text*");
+    RE_HEADING.replace_all(&text, "*$text*").into_owned()
+}
+
+/// Shorten `text` to at most `max` characters
+fn truncate(text: String, max: usize) -> String {
+    if text.chars().count() <= max {
+        text
+    } else {
+        let mut text: String = text.chars().take(max - 1).collect();
+        text.push('…');
+        text
+    }
+}
+
+/// Blocks showing the same content as the attachment
+pub fn attachment_blocks(attachment: &mattermost::Attachment) -> Vec<Block> {
+    let mut blocks = Vec::with_capacity(3);
+    let mut text = String::new();
+    if let Some(ref pretext) = attachment.pretext {
+        text += &markdown_to_mrkdwn(pretext);
+        text += "\n";
+    }
+    match (&attachment.title, &attachment.title_link) {
+        (Some(title), Some(link)) => text += &format!("*<{}|{}>*\n", link, title),
+        (Some(title), None) => text += &format!("*{}*\n", title),
+        _ => {}
+    }
+    if let Some(ref body) = attachment.text {
+        text += &markdown_to_mrkdwn(body);
+    }
+    if text.trim().is_empty() {
+        text = attachment.fallback.clone();
+    }
+    blocks.push(Block::Section {
+        text: Element::mrkdwn(truncate(text, MAX_SECTION_TEXT)),
+        accessory: attachment
+            .thumb_url
+            .clone()
+            .map(|image_url| Element::Image {
+                image_url,
+                alt_text: attachment.title.clone().unwrap_or_default(),
+            }),
+        fields: attachment
+            .fields
+            .iter()
+            .map(|field| {
+                Element::mrkdwn(format!(
+                    "*{}*\n{}",
+                    field.title.as_deref().unwrap_or_default(),
+                    markdown_to_mrkdwn(field.value.as_deref().unwrap_or_default())
+                ))
+            })
+            .collect(),
+    });
+    if let Some(ref image_url) = attachment.image_url {
+        blocks.push(Block::Image {
+            image_url: image_url.clone(),
+            alt_text: attachment.fallback.clone(),
+        });
+    }
+    if let Some(ref footer) = attachment.footer {
+        let mut elements = Vec::with_capacity(2);
+        if let Some(ref icon) = attachment.footer_icon {
+            elements.push(Element::Image {
+                image_url: icon.clone(),
+                alt_text: String::new(),
+            });
+        }
+        elements.push(Element::mrkdwn(markdown_to_mrkdwn(footer)));
+        blocks.push(Block::Context { elements });
+    }
+    blocks
+}
+
+impl From<&mattermost::Message> for Message {
+    fn from(message: &mattermost::Message) -> Self {
+        let mut blocks = Vec::new();
+        if let Some(ref text) = message.text {
+            blocks.push(Block::Section {
+                text: Element::mrkdwn(truncate(markdown_to_mrkdwn(text), MAX_SECTION_TEXT)),
+                accessory: None,
+                fields: Vec::new(),
+            });
+        }
+        for attachment in &message.attachments {
+            blocks.push(Block::Divider {});
+            blocks.extend(attachment_blocks(attachment));
+        }
+        let text = message.text.clone().unwrap_or_else(|| {
+            message
+                .attachments
+                .iter()
+                .map(|attachment| attachment.fallback.as_str())
+                .collect::<Vec<_>>()
+                .join("\n")
+        });
+        Message { text, blocks }
+    }
+}
+
+impl Message {
+    /// Split the message into messages with at most [`MAX_BLOCKS`] blocks
+    ///
+    /// Each message keeps the fallback text.
+    pub fn split(self) -> Vec<Message> {
+        if self.blocks.len() <= MAX_BLOCKS {
+            return vec![self];
+        }
+        let text = self.text;
+        self.blocks
+            .chunks(MAX_BLOCKS)
+            .map(|blocks| Message {
+                text: text.clone(),
+                blocks: blocks.to_vec(),
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_slack_blocks() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<crate::CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    assert_eq!(
+        markdown_to_mrkdwn(
+            "### [X-MAS CTF 2018](https://ctftime.org/event/724/)\n**Rating**: 24 <3"
+        ),
+        "*<https://ctftime.org/event/724/|X-MAS CTF 2018>*\n*Rating*: 24 &lt;3"
+    );
+
+    let blocks = serde_json::to_value(events[0].to_slack_blocks()).unwrap();
+    assert_eq!(blocks[0]["type"], "section");
+    assert_eq!(blocks[0]["text"]["type"], "mrkdwn");
+    assert!(blocks[0]["text"]["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("*<https://ctftime.org/event/724/|X-MAS CTF 2018 — Jeopardy>*\n*Date:* "));
+    assert_eq!(blocks[0]["accessory"]["type"], "image");
+    assert_eq!(blocks[1]["type"], "context");
+
+    let message = mattermost::Message {
+        text: Some("[Upcoming CTFs](https://ctftime.org/event/list/upcoming)".to_string()),
+        attachments: vec![events[0].to_slack(); 20],
+        ..Default::default()
+    };
+    let messages = Message::from(&message).split();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].blocks.len(), MAX_BLOCKS);
+    assert_eq!(messages[1].blocks.len(), 1 + 20 * 3 - MAX_BLOCKS);
+}
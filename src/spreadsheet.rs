@@ -0,0 +1,260 @@
+//! Season plan in a Google Sheet or a CSV file in a GitHub repository
+//!
+//! Each announced event is appended as one row.
+//! The first column contains the CTFtime id, which is used to skip events already in the plan.
+
+use crate::{timed, CtfEvent};
+use chrono::{SecondsFormat, Utc};
+use log::{error, info};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+
+/// Columns of a row, see [`spreadsheet_row`]
+const HEADER: [&str; 7] = ["id", "title", "start", "finish", "format", "weight", "url"];
+
+/// Configuration of the spreadsheet, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SpreadsheetConfig {
+    /// Sheet of a Google Spreadsheet
+    GoogleSheets {
+        spreadsheet_id: String,
+        /// Name of the sheet within the spreadsheet
+        #[serde(default = "default_sheet")]
+        sheet: String,
+        /// OAuth access token with the `spreadsheets` scope
+        access_token: String,
+    },
+    /// CSV file in a GitHub repository, each change is a commit
+    Github {
+        /// Repository in the form `owner/name`
+        repository: String,
+        /// Path of the CSV file in the repository, created if missing
+        path: String,
+        /// Branch receiving the commits, the default branch if unset
+        #[serde(default)]
+        branch: Option<String>,
+        /// Personal access token with write access to the repository
+        token: String,
+    },
+}
+
+fn default_sheet() -> String {
+    "Sheet1".to_string()
+}
+
+/// Values of the row of `event`, matching [`HEADER`]
+pub fn spreadsheet_row(event: &CtfEvent) -> Vec<String> {
+    vec![
+        event.id().to_string(),
+        event.title().to_string(),
+        event
+            .start_date()
+            .with_timezone(&Utc)
+            .to_rfc3339_opts(SecondsFormat::Secs, true),
+        event
+            .finish_date()
+            .with_timezone(&Utc)
+            .to_rfc3339_opts(SecondsFormat::Secs, true),
+        event.format().to_string(),
+        event
+            .rating_weight()
+            .map(|weight| weight.to_string())
+            .unwrap_or_default(),
+        event.ctftime_url().to_string(),
+    ]
+}
+
+/// Quote the fields of a CSV line if necessary
+pub(crate) fn csv_line<S: AsRef<str>>(fields: &[S]) -> String {
+    fields
+        .iter()
+        .map(|field| {
+            let field = field.as_ref();
+            if field.contains(&[',', '"', '\n', '\r'][..]) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Ids in the first column, the header and other rows are ignored
+fn known_ids<'a>(first_column: impl Iterator<Item = &'a str>) -> BTreeSet<usize> {
+    first_column
+        .filter_map(|id| id.trim().trim_matches('"').parse().ok())
+        .collect()
+}
+
+/// Append the rows of the missing `events` to the CSV file `csv`
+///
+/// Returns the new content and the number of added rows, or `None` if all events are already listed.
+pub fn append_csv(csv: &str, events: &[&CtfEvent]) -> Option<(String, usize)> {
+    let known = known_ids(
+        csv.lines()
+            .map(|line| line.split(',').next().unwrap_or_default()),
+    );
+    let missing: Vec<_> = events
+        .iter()
+        .filter(|event| !known.contains(&event.id()))
+        .collect();
+    if missing.is_empty() {
+        return None;
+    }
+    let mut csv = if csv.trim().is_empty() {
+        csv_line(&HEADER) + "\n"
+    } else {
+        csv.to_string()
+    };
+    if !csv.ends_with('\n') {
+        csv += "\n";
+    }
+    for event in &missing {
+        csv += &csv_line(&spreadsheet_row(event));
+        csv += "\n";
+    }
+    Some((csv, missing.len()))
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+impl SpreadsheetConfig {
+    /// Append the events which are not yet in the spreadsheet and return their number
+    pub fn append(&self, client: &Client, events: &[&CtfEvent]) -> Result<usize, BoxError> {
+        match self {
+            SpreadsheetConfig::GoogleSheets {
+                spreadsheet_id,
+                sheet,
+                access_token,
+            } => {
+                let base = format!(
+                    "https://sheets.googleapis.com/v4/spreadsheets/{}/values",
+                    spreadsheet_id
+                );
+                let column: Value = client
+                    .get(&format!("{}/{}!A:A", base, sheet))
+                    .bearer_auth(access_token)
+                    .send()?
+                    .error_for_status()?
+                    .json()?;
+                let rows = column["values"].as_array().cloned().unwrap_or_default();
+                let known = known_ids(rows.iter().filter_map(|row| row[0].as_str()));
+                let mut values: Vec<Vec<String>> = events
+                    .iter()
+                    .filter(|event| !known.contains(&event.id()))
+                    .map(|event| spreadsheet_row(event))
+                    .collect();
+                let appended = values.len();
+                if appended == 0 {
+                    return Ok(0);
+                }
+                if rows.is_empty() {
+                    values.insert(0, HEADER.iter().map(|s| s.to_string()).collect());
+                }
+                client
+                    .post(&format!("{}/{}!A1:append", base, sheet))
+                    .bearer_auth(access_token)
+                    .query(&[("valueInputOption", "RAW")])
+                    .json(&json!({ "values": values }))
+                    .send()?
+                    .error_for_status()?;
+                Ok(appended)
+            }
+            SpreadsheetConfig::Github {
+                repository,
+                path,
+                branch,
+                token,
+            } => {
+                let url = format!(
+                    "https://api.github.com/repos/{}/contents/{}",
+                    repository, path
+                );
+                let mut request = client
+                    .get(&url)
+                    .bearer_auth(token)
+                    .header("Accept", "application/vnd.github.v3+json")
+                    .header("User-Agent", "ctftimebot");
+                if let Some(branch) = branch {
+                    request = request.query(&[("ref", branch)]);
+                }
+                let response = request.send()?;
+                let (csv, sha) = if response.status() == reqwest::StatusCode::NOT_FOUND {
+                    (String::new(), None)
+                } else {
+                    let file: Value = response.error_for_status()?.json()?;
+                    let content: String = file["content"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .split_whitespace()
+                        .collect();
+                    (
+                        String::from_utf8(base64::decode(content)?)?,
+                        file["sha"].as_str().map(str::to_string),
+                    )
+                };
+                let (updated, appended) = match append_csv(&csv, events) {
+                    Some(updated) => updated,
+                    None => return Ok(0),
+                };
+                let mut body = json!({
+                    "message": format!("Add {} CTFs to the season plan", appended),
+                    "content": base64::encode(updated),
+                });
+                if let Some(sha) = sha {
+                    body["sha"] = sha.into();
+                }
+                if let Some(branch) = branch {
+                    body["branch"] = branch.clone().into();
+                }
+                client
+                    .put(&url)
+                    .bearer_auth(token)
+                    .header("Accept", "application/vnd.github.v3+json")
+                    .header("User-Agent", "ctftimebot")
+                    .json(&body)
+                    .send()?
+                    .error_for_status()?;
+                Ok(appended)
+            }
+        }
+    }
+}
+
+/// Append the announced `events` to the spreadsheet, errors are logged and retried during the next run
+pub fn sync_spreadsheet(spreadsheet: &SpreadsheetConfig, client: &Client, events: &[&CtfEvent]) {
+    match timed("Updating the spreadsheet", || {
+        spreadsheet.append(client, events)
+    }) {
+        Ok(0) => {}
+        Ok(appended) => info!("Added {} events to the spreadsheet", appended),
+        Err(err) => error!("Couldn't update the spreadsheet: {}", err),
+    }
+}
+
+#[test]
+fn test_append_csv() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let events: Vec<&CtfEvent> = events.iter().collect();
+
+    let (csv, appended) = append_csv("", &events).unwrap();
+    assert_eq!(appended, 1);
+    assert_eq!(
+        csv,
+        "id,title,start,finish,format,weight,url\n724,X-MAS CTF 2018,2018-12-14T18:00:00Z,2018-12-21T18:00:00Z,Jeopardy,24,https://ctftime.org/event/724/\n"
+    );
+    // Events are only added once
+    assert_eq!(append_csv(&csv, &events), None);
+
+    // Existing rows are kept as they are
+    let (csv, _) = append_csv("id,notes\n1,\"a, b\"", &events).unwrap();
+    assert!(csv.starts_with("id,notes\n1,\"a, b\"\n724,"));
+
+    assert_eq!(csv_line(&["a", "b,c", "d\"e"]), "a,\"b,c\",\"d\"\"e\"");
+}
@@ -0,0 +1,319 @@
+//! Persistent state of the bot between runs
+//!
+//! The state is stored as JSON in the file configured with `STATE_FILE`.
+//! It allows comparing the current CTFtime data with the data seen in previous runs.
+
+use crate::{
+    access::AccessToken,
+    admin::AdminState,
+    board::Card,
+    leaderboard::NationalRank,
+    notion::NotionPage,
+    preferences::Preferences,
+    scheduler::Reminder,
+    teams::{CachedTeam, TeamInfo},
+    CtfEvent,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Number of participant samples kept per event, older samples are dropped
+const MAX_PARTICIPANTS_SAMPLES: usize = 50;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    /// Last seen data for each event, keyed by the CTFtime event id
+    #[serde(default)]
+    pub events: BTreeMap<usize, EventRecord>,
+    /// Cached information about the organizing teams, keyed by the CTFtime team id
+    #[serde(default)]
+    pub teams: BTreeMap<usize, CachedTeam>,
+    /// Notification preferences, keyed by the Mattermost user name
+    #[serde(default)]
+    pub users: BTreeMap<String, Preferences>,
+    /// Settings changed with admin commands
+    #[serde(default)]
+    pub admin: AdminState,
+    /// Rank of the team in the national leaderboard, see [`leaderboard`][crate::leaderboard]
+    #[serde(default)]
+    pub national_rank: Option<NationalRank>,
+    /// Season of the last recap, see [`recap`][crate::recap]
+    #[serde(default)]
+    pub last_recap: Option<i32>,
+    /// Day of the last weekly leaderboard, see [`leaderboard`][crate::leaderboard]
+    #[serde(default)]
+    pub last_leaderboard: Option<NaiveDate>,
+    /// Access tokens of the server keyed by their name, see [`access`][crate::access]
+    #[serde(default)]
+    pub tokens: BTreeMap<String, AccessToken>,
+    /// Recently processed button clicks and when they were received, see [`actions`][crate::actions]
+    #[serde(default)]
+    pub recent_clicks: BTreeMap<String, DateTime<Utc>>,
+    /// Id of the pinned digest, which is kept current, see [`sync_digest_post`][crate::reformat::sync_digest_post]
+    #[serde(default)]
+    pub digest_post: Option<String>,
+}
+
+/// Data of a [`CtfEvent`] as seen during the last run
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct EventRecord {
+    /// The weight of the event
+    pub weight: f32,
+    /// The title of the event
+    #[serde(default)]
+    pub title: String,
+    /// Start of the event
+    #[serde(default)]
+    pub start: Option<DateTime<Utc>>,
+    /// End of the event
+    #[serde(default)]
+    pub finish: Option<DateTime<Utc>>,
+    /// Number of participating teams, a new entry is added whenever the number changes
+    #[serde(default)]
+    pub participants: Vec<ParticipantsSample>,
+    /// The event was part of a digest
+    #[serde(default)]
+    pub announced: bool,
+    /// Reminders which were already sent for this event
+    #[serde(default)]
+    pub sent_reminders: BTreeSet<Reminder>,
+    /// Users who want to play the event
+    #[serde(default)]
+    pub rsvps: BTreeSet<String>,
+    /// Feedback of the players after the event, keyed by user id
+    #[serde(default)]
+    pub feedback: BTreeMap<String, Feedback>,
+    /// Card of the event on the configured board
+    #[serde(default)]
+    pub card: Option<Card>,
+    /// Users who received their personal reminder for this event
+    #[serde(default)]
+    pub personal_reminders: BTreeSet<String>,
+    /// Users who were notified about this event because of a keyword subscription
+    #[serde(default)]
+    pub keyword_notified: BTreeSet<String>,
+    /// Mattermost post on which the last RSVP was made, linked from the checklist
+    #[serde(default)]
+    pub announcement_post: Option<String>,
+    /// Version of the entry in the CalDAV calendar, see [`CtfEvent::fingerprint`][crate::CtfEvent::fingerprint]
+    #[serde(default)]
+    pub caldav_entry: Option<String>,
+    /// Ids of the news items seen during the event, `None` before the first poll
+    #[serde(default)]
+    pub news_seen: Option<BTreeSet<String>>,
+    /// Too few players RSVP'd to the event, see [`EscalationConfig`][crate::escalation::EscalationConfig]
+    #[serde(default)]
+    pub needs_players: bool,
+    /// Number of the GitHub issue of the event, see [`github_issues`][crate::github_issues]
+    #[serde(default)]
+    pub github_issue: Option<u64>,
+    /// Row of the event in the Notion database, see [`notion`][crate::notion]
+    #[serde(default)]
+    pub notion_page: Option<NotionPage>,
+}
+
+/// Feedback of a single player about an event
+///
+/// All scores are between `0` (bad) and `1` (good).
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct Feedback {
+    /// Stability of the infrastructure
+    pub infra: Option<f64>,
+    /// Quality of the challenges
+    pub quality: Option<f64>,
+}
+
+/// Number of participating teams of an event at a point in time
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ParticipantsSample {
+    pub time: DateTime<Utc>,
+    pub participants: usize,
+}
+
+impl EventRecord {
+    /// Most recently seen number of participants
+    pub fn participants(&self) -> Option<usize> {
+        self.participants.last().map(|sample| sample.participants)
+    }
+}
+
+/// Shared access to the state file
+///
+/// Every modification loads the current state from disk and writes it back immediately.
+/// This way the cron mode, the daemon, and the server do not overwrite each other's changes.
+#[derive(Debug)]
+pub struct StateStore {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl StateStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Load the current state
+    pub fn read(&self) -> io::Result<State> {
+        let _guard = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+        State::load(&self.path)
+    }
+
+    /// Modify the state and store the result
+    pub fn update<R>(&self, f: impl FnOnce(&mut State) -> R) -> io::Result<R> {
+        let _guard = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+        let mut state = State::load(&self.path)?;
+        let res = f(&mut state);
+        state.save(&self.path)?;
+        Ok(res)
+    }
+}
+
+impl State {
+    /// Load the state from `path`
+    ///
+    /// A missing file results in an empty state.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Store the state in `path`
+    ///
+    /// The state is first written to a temporary file, which then replaces `path`.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(tmp, path)
+    }
+
+    /// Cached information about a team
+    pub fn team(&self, id: usize) -> Option<&TeamInfo> {
+        self.teams.get(&id).map(|cached| &cached.info)
+    }
+
+    /// Remember that the events were part of a digest
+    pub fn mark_announced(&mut self, event_ids: &[usize]) {
+        for id in event_ids {
+            self.events.entry(*id).or_default().announced = true;
+        }
+    }
+
+    /// Remember that `reminder` was sent for the event
+    pub fn mark_sent(&mut self, event_id: usize, reminder: Reminder) {
+        self.events
+            .entry(event_id)
+            .or_default()
+            .sent_reminders
+            .insert(reminder);
+    }
+
+    /// Update the records with the current data of `events`
+    ///
+    /// The participant history of every event is capped at [`MAX_PARTICIPANTS_SAMPLES`].
+    pub fn record_events(&mut self, events: &[CtfEvent]) {
+        for event in events {
+            let record = self.events.entry(event.id()).or_default();
+            record.weight = event.weight();
+            record.title = event.title().to_string();
+            record.start = Some(event.start_date().with_timezone(&Utc));
+            record.finish = Some(event.finish_date().with_timezone(&Utc));
+            if record.participants() != Some(event.participants()) {
+                record.participants.push(ParticipantsSample {
+                    time: Utc::now(),
+                    participants: event.participants(),
+                });
+                let excess = record
+                    .participants
+                    .len()
+                    .saturating_sub(MAX_PARTICIPANTS_SAMPLES);
+                record.participants.drain(..excess);
+            }
+        }
+    }
+
+    /// Take the ids of the synchronized items from `synced`
+    ///
+    /// The synchronization runs on a copy of the state, such that the lock isn't held during the requests.
+    /// Everything else changed meanwhile, e.g., RSVPs, is kept.
+    pub fn merge_synced(&mut self, synced: &State) {
+        for (id, synced) in &synced.events {
+            let record = self.events.entry(*id).or_default();
+            record.card = synced.card.clone();
+            record.caldav_entry = synced.caldav_entry.clone();
+            record.github_issue = synced.github_issue;
+            record.notion_page = synced.notion_page.clone();
+        }
+        self.digest_post = synced.digest_post.clone();
+    }
+}
+
+#[test]
+fn test_record_events_prunes_participants() {
+    let json = std::fs::read_to_string("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_str(&json).unwrap();
+    let samples = |count: usize| -> Vec<ParticipantsSample> {
+        (0..count)
+            .map(|participants| ParticipantsSample {
+                time: Utc::now(),
+                participants,
+            })
+            .collect()
+    };
+
+    let mut state = State::default();
+    state.events.insert(
+        724,
+        EventRecord {
+            participants: samples(MAX_PARTICIPANTS_SAMPLES),
+            ..Default::default()
+        },
+    );
+    state.record_events(&events);
+    let participants = &state.events[&724].participants;
+    assert_eq!(participants.len(), MAX_PARTICIPANTS_SAMPLES);
+    assert_eq!(participants[0].participants, 1);
+    assert_eq!(
+        state.events[&724].participants(),
+        Some(events[0].participants())
+    );
+    // Unchanged numbers don't add samples
+    state.record_events(&events);
+    assert_eq!(state.events[&724].participants[0].participants, 1);
+}
+
+#[test]
+fn test_merge_synced_keeps_other_changes() {
+    let mut state = State::default();
+    let mut synced = state.clone();
+    let card = Card {
+        id: "card".to_string(),
+        column: crate::board::Column::Upcoming,
+    };
+    synced.events.entry(724).or_default().card = Some(card.clone());
+    synced.digest_post = Some("post".to_string());
+    // Changed by the server while the synchronization was running
+    state
+        .events
+        .entry(724)
+        .or_default()
+        .rsvps
+        .insert("alice".to_string());
+
+    state.merge_synced(&synced);
+    assert_eq!(state.events[&724].card, Some(card));
+    assert!(state.events[&724].rsvps.contains("alice"));
+    assert_eq!(state.digest_post.as_deref(), Some("post"));
+}
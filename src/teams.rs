@@ -0,0 +1,264 @@
+//! Information about the teams organizing the events
+//!
+//! CTFtime only offers a lookup of a single team per request.
+//! Lookups are coalesced, such that each team is requested once even if it organizes many events,
+//! and cached in the state file, such that a team is requested again only after the TTL expired.
+
+use crate::{
+    state::{State, StateStore},
+    timed,
+    trivia::ordinal,
+    CtfEvent, BASE_URL,
+};
+use chrono::{DateTime, Duration, Utc};
+use log::{error, info};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+};
+
+/// Number of team lookups running in parallel
+const BATCH_SIZE: usize = 8;
+
+/// Team data as returned by `/api/v1/teams/<id>/`
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TeamInfo {
+    pub id: usize,
+    pub name: String,
+    /// Country code of the team, empty if unknown
+    #[serde(default)]
+    pub country: String,
+    #[serde(default)]
+    pub academic: bool,
+    /// URL of the logo, empty if there is none
+    #[serde(default)]
+    pub logo: String,
+    /// Rating of the team keyed by the year
+    #[serde(default, deserialize_with = "deserialize_ratings")]
+    pub rating: BTreeMap<String, YearRating>,
+}
+
+/// Rating of the team in one year, as part of `/api/v1/teams/<id>/`
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct YearRating {
+    #[serde(default)]
+    pub rating_place: Option<usize>,
+    #[serde(default)]
+    pub rating_points: Option<f64>,
+    #[serde(default)]
+    pub country_place: Option<usize>,
+}
+
+/// CTFtime sends an empty list instead of an empty object for teams without a rating
+fn deserialize_ratings<'de, D>(deserializer: D) -> Result<BTreeMap<String, YearRating>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Ratings {
+        Years(BTreeMap<String, YearRating>),
+        Empty([(); 0]),
+    }
+
+    Ok(match Ratings::deserialize(deserializer)? {
+        Ratings::Years(years) => years,
+        Ratings::Empty(_) => BTreeMap::new(),
+    })
+}
+
+impl TeamInfo {
+    /// Link to the team page on CTFtime
+    pub fn ctftime_url(&self) -> String {
+        format!("{}/team/{}", BASE_URL, self.id)
+    }
+
+    /// Markdown summary of the team with its rating in `year`, e.g., to post the current rating of our team
+    pub fn to_markdown(&self, year: i32) -> String {
+        let mut text = format!("### [{}]({})\n", self.name, self.ctftime_url());
+        if !self.country.is_empty() {
+            text += &format!("**Country:** {}\n", self.country);
+        }
+        if self.academic {
+            text += "**Academic team**\n";
+        }
+        let rating = self.rating.get(&year.to_string());
+        match rating.and_then(|rating| rating.rating_place) {
+            Some(place) => {
+                text += &format!("**CTFtime rank {}:** {}", year, ordinal(place));
+                if let Some(points) = rating.and_then(|rating| rating.rating_points) {
+                    text += &format!(" with {:.2} rating points", points);
+                }
+                text += "\n";
+            }
+            None => text += &format!("Not rated in {} yet.\n", year),
+        }
+        if let Some(place) = rating.and_then(|rating| rating.country_place) {
+            text += &format!("**National rank:** {}\n", ordinal(place));
+        }
+        text
+    }
+}
+
+/// A [`TeamInfo`] together with the time it was fetched
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct CachedTeam {
+    pub fetched: DateTime<Utc>,
+    pub info: TeamInfo,
+}
+
+/// Ids of the organizers of `events`, which have no cache entry younger than `ttl`
+///
+/// Every id is only returned once, even if the team organizes multiple events.
+pub fn stale_team_ids(
+    state: &State,
+    events: &[CtfEvent],
+    ttl: Duration,
+    now: DateTime<Utc>,
+) -> Vec<usize> {
+    events
+        .iter()
+        .flat_map(|event| event.known_organizers())
+        .map(|team| team.id())
+        .filter(|id| match state.teams.get(id) {
+            Some(cached) => cached.fetched + ttl <= now,
+            None => true,
+        })
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// Fetch a single team from CTFtime
+pub fn fetch_team(client: &reqwest::blocking::Client, id: usize) -> reqwest::Result<TeamInfo> {
+    client
+        .get(&format!("{}/api/v1/teams/{}/", BASE_URL, id))
+        .send()?
+        .error_for_status()?
+        .json()
+}
+
+/// Fetch the teams, running up to [`BATCH_SIZE`] lookups in parallel
+///
+/// Failed lookups are logged and skipped, such that they are retried during the next run.
+pub fn fetch_teams(client: &reqwest::blocking::Client, ids: &[usize]) -> Vec<TeamInfo> {
+    let mut teams = Vec::with_capacity(ids.len());
+    for batch in ids.chunks(BATCH_SIZE) {
+        let handles: Vec<_> = batch
+            .iter()
+            .map(|&id| {
+                let client = client.clone();
+                std::thread::spawn(move || (id, fetch_team(&client, id)))
+            })
+            .collect();
+        for handle in handles {
+            match handle.join() {
+                Ok((_, Ok(team))) => teams.push(team),
+                Ok((id, Err(err))) => error!("Couldn't fetch team {}: {}", id, err),
+                Err(_) => error!("Team lookup panicked"),
+            }
+        }
+    }
+    teams
+}
+
+/// Refresh the cached information about all organizers of `events`
+///
+/// The lookups run without holding the lock of the state file.
+pub fn enrich_teams(
+    client: &reqwest::blocking::Client,
+    store: &StateStore,
+    events: &[CtfEvent],
+    ttl: Duration,
+    now: DateTime<Utc>,
+) -> io::Result<()> {
+    let ids = stale_team_ids(&store.read()?, events, ttl, now);
+    if ids.is_empty() {
+        return Ok(());
+    }
+    info!("Fetching {} teams", ids.len());
+    let teams = timed("Fetching the teams", || fetch_teams(client, &ids));
+    store.update(|state| {
+        for info in teams {
+            state
+                .teams
+                .insert(info.id, CachedTeam { fetched: now, info });
+        }
+    })
+}
+
+#[test]
+fn test_stale_team_ids() {
+    let json = std::fs::read_to_string("./tests/ctfs.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_str(&json).unwrap();
+    let now = Utc::now();
+    let ttl = Duration::hours(24);
+    let mut state = State::default();
+
+    let ids = stale_team_ids(&state, &events, ttl, now);
+    assert_eq!(ids.len(), 155);
+
+    let team = |id| TeamInfo {
+        id,
+        name: "Team".to_string(),
+        country: String::new(),
+        academic: false,
+        logo: String::new(),
+        rating: BTreeMap::new(),
+    };
+    state.teams.insert(
+        ids[0],
+        CachedTeam {
+            fetched: now - Duration::hours(1),
+            info: team(ids[0]),
+        },
+    );
+    state.teams.insert(
+        ids[1],
+        CachedTeam {
+            fetched: now - Duration::hours(25),
+            info: team(ids[1]),
+        },
+    );
+    let stale = stale_team_ids(&state, &events, ttl, now);
+    assert_eq!(stale.len(), 154);
+    assert!(!stale.contains(&ids[0]));
+    assert!(stale.contains(&ids[1]));
+}
+
+#[test]
+fn test_deserialize_team_info() {
+    let json = r#"{"academic": false, "primary_alias": "Hecării", "name": "Hecării, Țuica și Păunii", "rating": [], "logo": "", "country": "RO", "id": 58218, "aliases": []}"#;
+    let team: TeamInfo = serde_json::from_str(json).unwrap();
+    assert_eq!(team.id, 58218);
+    assert_eq!(team.country, "RO");
+    assert_eq!(team.logo, "");
+    assert!(team.rating.is_empty());
+}
+
+#[test]
+fn test_team_markdown() {
+    let json = r#"{"academic": false, "primary_alias": "Us", "name": "Us", "logo": "", "country": "DE", "id": 42, "aliases": [], "rating": {
+        "2020": {"rating_place": 120, "organizer_points": 0, "rating_points": 80.5, "country_place": 9},
+        "2021": {"rating_place": 85, "organizer_points": 0, "rating_points": 150.25, "country_place": 8}
+    }}"#;
+    let team: TeamInfo = serde_json::from_str(json).unwrap();
+    assert_eq!(team.rating["2021"].rating_place, Some(85));
+    assert_eq!(
+        team.to_markdown(2021),
+        "### [Us](https://ctftime.org/team/42)
+**Country:** DE
+**CTFtime rank 2021:** 85th with 150.25 rating points
+**National rank:** 8th
+"
+    );
+    assert_eq!(
+        team.to_markdown(2022),
+        "### [Us](https://ctftime.org/team/42)\n**Country:** DE\nNot rated in 2022 yet.\n"
+    );
+
+    // The rating survives the round trip through the state file
+    let cached: TeamInfo = serde_json::from_str(&serde_json::to_string(&team).unwrap()).unwrap();
+    assert_eq!(cached, team);
+}
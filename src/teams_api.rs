@@ -0,0 +1,303 @@
+//! Types of Microsoft Teams incoming webhooks using [Adaptive Cards]
+//!
+//! The messages are converted from the Mattermost [`Message`][mattermost::Message]s, such that both show the same content.
+//! Each attachment becomes a container in the card.
+//! Lines of the form `**Label:** value` become facts, which Teams shows as a table.
+//! Only a subset of Markdown is supported in text blocks, headings are shown as bold text.
+//!
+//! [Adaptive Cards]: https://adaptivecards.io/explorer/
+
+use crate::mattermost_hook_api::{self as mattermost, Url};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Maximal number of attachments per card, such that the card stays below the size limit of 28 KB
+pub const MAX_CONTAINERS: usize = 10;
+const CONTENT_TYPE: &str = "application/vnd.microsoft.card.adaptive";
+const SCHEMA: &str = "http://adaptivecards.io/schemas/adaptive-card.json";
+
+lazy_static! {
+    static ref RE_FACT: Regex =
+        Regex::new(r"^\*\*(?P<title>[^*]+?):?\*\*:? (?P<value>.+)$").unwrap();
+    static ref RE_HEADING: Regex = Regex::new(r"(?m)^#+ (?P<text>.*)$").unwrap();
+}
+
+/// Teams incoming webhooks accept a message with card attachments
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Message {
+    /// Always `message`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub attachments: Vec<CardAttachment>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardAttachment {
+    /// Always [`CONTENT_TYPE`]
+    pub content_type: String,
+    pub content: AdaptiveCard,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AdaptiveCard {
+    #[serde(rename = "$schema")]
+    pub schema: String,
+    /// Always `AdaptiveCard`
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub version: String,
+    pub body: Vec<Element>,
+    /// Teams specific options, used to show the card in the full width of the channel
+    pub msteams: serde_json::Value,
+}
+
+/// Elements of the [`AdaptiveCard::body`]
+#[serde_with::skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Element {
+    #[serde(rename_all = "camelCase")]
+    TextBlock {
+        text: String,
+        wrap: bool,
+        /// `Bolder` for titles
+        weight: Option<String>,
+        /// `Medium` for titles
+        size: Option<String>,
+        is_subtle: Option<bool>,
+    },
+    #[serde(rename_all = "camelCase")]
+    Image {
+        url: Url,
+        alt_text: String,
+        /// `Small`, `Medium`, or `Large`
+        size: Option<String>,
+    },
+    FactSet {
+        facts: Vec<Fact>,
+    },
+    Container {
+        items: Vec<Element>,
+        /// Draw a line above the container
+        separator: Option<bool>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fact {
+    pub title: String,
+    pub value: String,
+}
+
+impl Element {
+    pub fn text(text: impl Into<String>) -> Self {
+        Element::TextBlock {
+            text: text.into(),
+            wrap: true,
+            weight: None,
+            size: None,
+            is_subtle: None,
+        }
+    }
+}
+
+/// Convert the Markdown used by the bot into the subset supported by Adaptive Cards
+fn markdown_to_teams(markdown: &str) -> String {
+    RE_HEADING.replace_all(markdown, "**$text**").into_owned()
+}
+
+/// Split the text into facts and the remaining lines
+///
+/// The date of events is shown as `<date> for <duration>`, which becomes separate facts.
+fn facts(text: &str) -> (Vec<Fact>, String) {
+    let mut facts = Vec::new();
+    let mut rest = String::with_capacity(text.len());
+    for line in text.lines() {
+        match RE_FACT.captures(line) {
+            Some(caps) => {
+                let title = caps["title"].to_string();
+                let value = &caps["value"];
+                match value.split_once(" for ") {
+                    Some((date, duration)) if title == "Date" => {
+                        facts.push(Fact {
+                            title,
+                            value: date.to_string(),
+                        });
+                        facts.push(Fact {
+                            title: "Duration".to_string(),
+                            value: duration.to_string(),
+                        });
+                    }
+                    _ => facts.push(Fact {
+                        title,
+                        value: value.to_string(),
+                    }),
+                }
+            }
+            None => {
+                rest += line;
+                rest += "\n";
+            }
+        }
+    }
+    rest.truncate(rest.trim_end().len());
+    (facts, rest)
+}
+
+/// Container showing the same content as the attachment
+pub fn attachment_container(attachment: &mattermost::Attachment) -> Element {
+    let mut items = Vec::with_capacity(5);
+    if let Some(ref pretext) = attachment.pretext {
+        items.push(Element::text(markdown_to_teams(pretext)));
+    }
+    if let Some(ref logo) = attachment.thumb_url {
+        items.push(Element::Image {
+            url: logo.clone(),
+            alt_text: attachment.title.clone().unwrap_or_default(),
+            size: Some("Small".to_string()),
+        });
+    }
+    let title = match (&attachment.title, &attachment.title_link) {
+        (Some(title), Some(link)) => Some(format!("[{}]({})", title, link)),
+        (Some(title), None) => Some(title.clone()),
+        _ => None,
+    };
+    if let Some(text) = title {
+        items.push(Element::TextBlock {
+            text,
+            wrap: true,
+            weight: Some("Bolder".to_string()),
+            size: Some("Medium".to_string()),
+            is_subtle: None,
+        });
+    }
+    if let Some(ref text) = attachment.text {
+        let (mut facts, rest) = facts(&markdown_to_teams(text));
+        facts.extend(attachment.fields.iter().map(|field| Fact {
+            title: field.title.clone().unwrap_or_default(),
+            value: field.value.clone().unwrap_or_default(),
+        }));
+        if !facts.is_empty() {
+            items.push(Element::FactSet { facts });
+        }
+        if !rest.is_empty() {
+            items.push(Element::text(rest));
+        }
+    }
+    if let Some(ref image_url) = attachment.image_url {
+        items.push(Element::Image {
+            url: image_url.clone(),
+            alt_text: attachment.fallback.clone(),
+            size: None,
+        });
+    }
+    if let Some(ref footer) = attachment.footer {
+        items.push(Element::TextBlock {
+            text: footer.clone(),
+            wrap: true,
+            weight: None,
+            size: Some("Small".to_string()),
+            is_subtle: Some(true),
+        });
+    }
+    if items.is_empty() {
+        items.push(Element::text(attachment.fallback.clone()));
+    }
+    Element::Container {
+        items,
+        separator: Some(true),
+    }
+}
+
+impl Message {
+    /// Message with a single card showing `body`
+    pub fn card(body: Vec<Element>) -> Self {
+        Message {
+            kind: "message".to_string(),
+            attachments: vec![CardAttachment {
+                content_type: CONTENT_TYPE.to_string(),
+                content: AdaptiveCard {
+                    schema: SCHEMA.to_string(),
+                    kind: "AdaptiveCard".to_string(),
+                    version: "1.4".to_string(),
+                    body,
+                    msteams: serde_json::json!({"width": "Full"}),
+                },
+            }],
+        }
+    }
+
+    /// Convert the message into cards with at most [`MAX_CONTAINERS`] attachments each
+    ///
+    /// The text of the message is only shown in the first card.
+    pub fn from_mattermost(message: &mattermost::Message) -> Vec<Message> {
+        let mut heading = message
+            .text
+            .as_ref()
+            .map(|text| Element::text(markdown_to_teams(text)));
+        if message.attachments.is_empty() {
+            return vec![Message::card(heading.into_iter().collect())];
+        }
+        message
+            .attachments
+            .chunks(MAX_CONTAINERS)
+            .map(|attachments| {
+                let mut body: Vec<Element> = heading.take().into_iter().collect();
+                body.extend(attachments.iter().map(attachment_container));
+                Message::card(body)
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn test_teams_cards() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<crate::CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    let (facts, rest) = facts("**Date:** Fri 2018-12-14 18:00 UTC for 7 days\n**Rating**: 24\n**Organizers:** [HTsP](https://ctftime.org/team/59758)\n[https://www.xmas-ctf.cf/](https://www.xmas-ctf.cf/)");
+    assert_eq!(
+        facts
+            .iter()
+            .map(|fact| fact.title.as_str())
+            .collect::<Vec<_>>(),
+        vec!["Date", "Duration", "Rating", "Organizers"]
+    );
+    assert_eq!(facts[1].value, "7 days");
+    assert_eq!(rest, "[https://www.xmas-ctf.cf/](https://www.xmas-ctf.cf/)");
+
+    let container = serde_json::to_value(events[0].to_teams()).unwrap();
+    assert_eq!(container["type"], "Container");
+    assert_eq!(container["items"][0]["type"], "Image");
+    assert_eq!(
+        container["items"][1]["text"],
+        "[X-MAS CTF 2018 — Jeopardy](https://ctftime.org/event/724/)"
+    );
+    assert_eq!(container["items"][2]["type"], "FactSet");
+    assert_eq!(container["items"][2]["facts"][1]["title"], "Duration");
+
+    let message = mattermost::Message {
+        text: Some("### [Upcoming CTFs](https://ctftime.org/event/list/upcoming)".to_string()),
+        attachments: vec![events[0].to_slack(); 15],
+        ..Default::default()
+    };
+    let messages = serde_json::to_value(Message::from_mattermost(&message)).unwrap();
+    assert_eq!(messages.as_array().unwrap().len(), 2);
+    let card = &messages[0]["attachments"][0];
+    assert_eq!(card["contentType"], CONTENT_TYPE);
+    assert_eq!(
+        card["content"]["body"][0]["text"],
+        "**[Upcoming CTFs](https://ctftime.org/event/list/upcoming)**"
+    );
+    assert_eq!(card["content"]["body"].as_array().unwrap().len(), 1 + 10);
+    assert_eq!(
+        messages[1]["attachments"][0]["content"]["body"]
+            .as_array()
+            .unwrap()
+            .len(),
+        5
+    );
+}
@@ -0,0 +1,250 @@
+//! Fun facts shown at the end of the digest
+//!
+//! Facts are either taken from a configured pool or derived from the past results of the team on CTFtime,
+//! e.g., `Last year we placed 14th at X-MAS CTF`.
+//! The same results are used for the summary after a played event, comparing us to the rival teams.
+
+use crate::{timed, CtfEvent, BASE_URL};
+use chrono::Datelike;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr, PickFirst};
+use std::collections::BTreeMap;
+
+/// Placement of the team at a past event
+#[derive(Clone, Debug, PartialEq)]
+pub struct PastResult {
+    pub title: String,
+    pub place: usize,
+    pub points: f64,
+}
+
+#[derive(Deserialize)]
+struct EventResults {
+    title: String,
+    scores: Vec<Score>,
+}
+
+/// Placement of a team on the scoreboard of an event
+#[serde_as]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub struct Score {
+    pub team_id: usize,
+    pub place: usize,
+    /// Points on the scoreboard of the event, CTFtime sends them as string
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    #[serde(default)]
+    pub points: f64,
+}
+
+/// Results of all events of `year`, keyed by the event id
+fn fetch_year(
+    client: &Client,
+    year: i32,
+) -> Result<BTreeMap<String, EventResults>, reqwest::Error> {
+    timed("Fetching the results", || {
+        client
+            .get(&format!("{}/api/v1/results/{}/", BASE_URL, year))
+            .send()?
+            .error_for_status()?
+            .json()
+    })
+}
+
+/// Scoreboard of the event, `None` if CTFtime has no results for it yet
+pub fn fetch_scores(
+    client: &Client,
+    event: &CtfEvent,
+) -> Result<Option<Vec<Score>>, reqwest::Error> {
+    let mut results = fetch_year(client, event.finish_date().year())?;
+    Ok(results
+        .remove(&event.id().to_string())
+        .map(|results| results.scores))
+}
+
+/// Placements of the team `team_id` at the events of `year`
+pub fn fetch_results(
+    client: &Client,
+    year: i32,
+    team_id: usize,
+) -> Result<Vec<PastResult>, reqwest::Error> {
+    let results = fetch_year(client, year)?;
+    Ok(results
+        .into_values()
+        .filter_map(|event| {
+            let score = event.scores.iter().find(|score| score.team_id == team_id)?;
+            Some(PastResult {
+                place: score.place,
+                points: score.points,
+                title: event.title,
+            })
+        })
+        .collect())
+}
+
+/// Lowercase title without the year, which is the same for all editions of a CTF
+fn series_title(title: &str) -> String {
+    title
+        .split_whitespace()
+        .filter(|word| {
+            let digits = word.trim_matches(|c: char| !c.is_ascii_digit());
+            !(digits.len() == 4 && (digits.starts_with("19") || digits.starts_with("20")))
+        })
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `1st`, `2nd`, `3rd`, `4th`, …
+pub(crate) fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+/// Facts about the `events` based on the results of last year
+pub fn result_facts(events: &[&CtfEvent], last_year: &[PastResult]) -> Vec<String> {
+    events
+        .iter()
+        .filter_map(|event| {
+            let series = series_title(event.title());
+            let result = last_year
+                .iter()
+                .find(|result| series_title(&result.title) == series)?;
+            Some(format!(
+                "Last year we placed {} at {}",
+                ordinal(result.place),
+                result.title
+            ))
+        })
+        .collect()
+}
+
+/// Our placement next to the placements of the `rivals`, e.g., `We: 12th, RivalTeam: 15th 🎉`
+///
+/// Rivals without a placement are left out. The summary ends with 🎉 if we placed ahead of all of them.
+/// Returns `None` if we have no placement.
+pub fn rivals_summary(
+    scores: &[Score],
+    team_id: usize,
+    rivals: &[usize],
+    name: impl Fn(usize) -> String,
+) -> Option<String> {
+    let place = |id| {
+        scores
+            .iter()
+            .find(|score| score.team_id == id)
+            .map(|score| score.place)
+    };
+    let ours = place(team_id)?;
+    let mut parts = vec![format!("We: {}", ordinal(ours))];
+    let mut ahead = true;
+    for &rival in rivals {
+        if let Some(theirs) = place(rival) {
+            parts.push(format!("{}: {}", name(rival), ordinal(theirs)));
+            ahead &= ours < theirs;
+        }
+    }
+    let mut summary = parts.join(", ");
+    if ahead && parts.len() > 1 {
+        summary += " 🎉";
+    }
+    Some(summary)
+}
+
+/// Pick one fact, preferring the facts about past results over the `pool`
+///
+/// `seed` rotates through the facts, e.g., the day of the year, such that consecutive digests differ.
+pub fn pick_trivia(facts: &[String], pool: &[String], seed: usize) -> Option<String> {
+    let candidates = if facts.is_empty() { pool } else { facts };
+    if candidates.is_empty() {
+        return None;
+    }
+    Some(candidates[seed % candidates.len()].clone())
+}
+
+#[test]
+fn test_trivia() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let events: Vec<&CtfEvent> = events.iter().collect();
+
+    assert_eq!(series_title("X-MAS CTF 2018"), "x-mas ctf");
+    assert_eq!(series_title("X-MAS CTF (2017)"), "x-mas ctf");
+    assert_eq!(series_title("0CTF 2017 Quals"), "0ctf quals");
+    assert_eq!(
+        [1, 2, 3, 4, 11, 12, 13, 14, 21, 22, 101, 111]
+            .iter()
+            .map(|&n| ordinal(n))
+            .collect::<Vec<_>>(),
+        vec![
+            "1st", "2nd", "3rd", "4th", "11th", "12th", "13th", "14th", "21st", "22nd", "101st",
+            "111th"
+        ]
+    );
+
+    let last_year = vec![
+        PastResult {
+            title: "SECCON 2017 Online CTF".to_string(),
+            place: 3,
+            points: 3500.,
+        },
+        PastResult {
+            title: "X-MAS CTF 2017".to_string(),
+            place: 14,
+            points: 1234.5,
+        },
+    ];
+    let facts = result_facts(&events, &last_year);
+    assert_eq!(facts, vec!["Last year we placed 14th at X-MAS CTF 2017"]);
+
+    let pool = vec!["a".to_string(), "b".to_string()];
+    assert_eq!(pick_trivia(&facts, &pool, 5), Some(facts[0].clone()));
+    assert_eq!(pick_trivia(&[], &pool, 5), Some("b".to_string()));
+    assert_eq!(pick_trivia(&[], &[], 5), None);
+}
+
+#[test]
+fn test_rivals_summary() {
+    let scores = vec![
+        Score {
+            team_id: 1,
+            place: 12,
+            points: 1200.,
+        },
+        Score {
+            team_id: 2,
+            place: 15,
+            points: 950.,
+        },
+        Score {
+            team_id: 3,
+            place: 3,
+            points: 4000.,
+        },
+    ];
+    let name = |id| format!("Team{}", id);
+    let score: Score =
+        serde_json::from_str(r#"{"team_id": 1, "points": "1200.0000", "place": 12}"#).unwrap();
+    assert_eq!(score, scores[0]);
+
+    assert_eq!(
+        rivals_summary(&scores, 1, &[2], name),
+        Some("We: 12th, Team2: 15th 🎉".to_string())
+    );
+    assert_eq!(
+        rivals_summary(&scores, 1, &[2, 3, 4], name),
+        Some("We: 12th, Team2: 15th, Team3: 3rd".to_string())
+    );
+    assert_eq!(
+        rivals_summary(&scores, 1, &[], name),
+        Some("We: 12th".to_string())
+    );
+    assert_eq!(rivals_summary(&scores, 4, &[1], name), None);
+}
@@ -0,0 +1,67 @@
+//! Send high-priority reminders as SMS or WhatsApp messages using [Twilio]
+//!
+//! Phone notifications are intrusive, so only the configured [`Reminder`]s are sent this way.
+//! For WhatsApp, prefix the phone numbers with `whatsapp:`, e.g., `whatsapp:+4912345`.
+//!
+//! [Twilio]: https://www.twilio.com/docs/sms/api/message-resource
+
+use crate::{scheduler::Reminder, CtfEvent};
+use log::error;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+/// Configuration of the Twilio backend, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct TwilioConfig {
+    pub account_sid: String,
+    pub auth_token: String,
+    /// Sender phone number
+    pub from: String,
+    /// Recipient phone numbers
+    pub to: Vec<String>,
+    /// Reminders which are sent to the phones
+    #[serde(default = "default_reminders")]
+    pub reminders: Vec<Reminder>,
+    /// Only send reminders for events with at least this rating weight
+    #[serde(default)]
+    pub min_weight: Option<u32>,
+}
+
+fn default_reminders() -> Vec<Reminder> {
+    vec![Reminder::Live]
+}
+
+impl TwilioConfig {
+    /// Whether the reminder for the event is important enough to be sent to the phones
+    pub fn is_high_priority(&self, reminder: Reminder, event: &CtfEvent) -> bool {
+        self.reminders.contains(&reminder)
+            && self.min_weight.map_or(true, |min_weight| {
+                event.rating_weight().unwrap_or(0) >= min_weight
+            })
+    }
+
+    /// Send the text to all recipients
+    ///
+    /// Failures for individual recipients are logged and do not stop the other messages.
+    pub fn send(&self, client: &Client, text: &str) {
+        let url = format!(
+            "https://api.twilio.com/2010-04-01/Accounts/{}/Messages.json",
+            self.account_sid
+        );
+        for to in &self.to {
+            let res = client
+                .post(&url)
+                .basic_auth(&self.account_sid, Some(&self.auth_token))
+                .form(&[
+                    ("To", to),
+                    ("From", &self.from),
+                    ("Body", &text.to_string()),
+                ])
+                .send()
+                .and_then(|resp| resp.error_for_status());
+            if let Err(err) = res {
+                error!("Couldn't send Twilio message to {}: {}", to, err);
+            }
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Post the digest to X (formerly Twitter) using the API v2
+//!
+//! Like for [Mastodon][crate::mastodon], the digest becomes a thread: a short introduction, followed by one reply per event.
+//! The posts use the same compact text, shortened to the lower character limit of X.
+//! Requests hitting the rate limit are retried once after the limit resets, if that is soon enough.
+
+use crate::{compact::compact_text, digest::Digest, timed, CtfEvent};
+use log::{info, warn};
+use reqwest::{
+    blocking::{multipart, Client, RequestBuilder},
+    header::HeaderMap,
+    StatusCode,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const API_URL: &str = "https://api.x.com/2";
+/// Maximal length of a post for accounts without subscription
+const MAX_POST_LENGTH: usize = 280;
+/// Longest time to wait for a rate limit to reset before giving up
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(15 * 60);
+
+/// Configuration of the X backend, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct TwitterConfig {
+    /// OAuth 2.0 user access token with the `tweet.write`, `users.read`, and `media.write` scopes
+    pub access_token: String,
+    /// Hashtags added to every event, without the `#`
+    #[serde(default = "default_hashtags")]
+    pub hashtags: Vec<String>,
+}
+
+fn default_hashtags() -> Vec<String> {
+    vec!["CTF".to_string()]
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Time until the rate limit resets, based on the `x-rate-limit-reset` header
+fn rate_limit_reset(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let reset: u64 = headers
+        .get("x-rate-limit-reset")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+    let now = now.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(Duration::from_secs(reset.saturating_sub(now) + 1))
+}
+
+impl TwitterConfig {
+    /// Send the request, retrying once if the rate limit resets soon
+    fn send(&self, request: impl Fn() -> RequestBuilder) -> Result<Value, BoxError> {
+        let response = request().bearer_auth(&self.access_token).send()?;
+        let response = if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            match rate_limit_reset(response.headers(), SystemTime::now()) {
+                Some(wait) if wait <= MAX_RATE_LIMIT_WAIT => {
+                    info!("Rate limited by X, waiting {}s", wait.as_secs());
+                    thread::sleep(wait);
+                    request().bearer_auth(&self.access_token).send()?
+                }
+                _ => response,
+            }
+        } else {
+            response
+        };
+        Ok(response.error_for_status()?.json()?)
+    }
+
+    /// Create a post and return its id
+    fn post(
+        &self,
+        client: &Client,
+        text: &str,
+        in_reply_to: Option<&str>,
+        media_id: Option<&str>,
+    ) -> Result<String, BoxError> {
+        let mut post = json!({ "text": text });
+        if let Some(id) = in_reply_to {
+            post["reply"] = json!({ "in_reply_to_tweet_id": id });
+        }
+        if let Some(id) = media_id {
+            post["media"] = json!({ "media_ids": [id] });
+        }
+        let created = self.send(|| client.post(&format!("{}/tweets", API_URL)).json(&post))?;
+        Ok(created["data"]["id"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    /// Download the logo of the event and upload it as media
+    fn upload_logo(&self, client: &Client, event: &CtfEvent) -> Result<Option<String>, BoxError> {
+        let logo_url = match event.logo_url() {
+            Some(url) => url,
+            None => return Ok(None),
+        };
+        let logo = client.get(logo_url).send()?.error_for_status()?;
+        let mime = logo
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|mime| mime.to_str().ok())
+            .unwrap_or("image/png")
+            .to_string();
+        let logo = logo.bytes()?.to_vec();
+        let media = self.send(|| {
+            // A multipart form cannot be cloned, so it is built again for a retry
+            let part = multipart::Part::bytes(logo.clone())
+                .file_name("logo")
+                .mime_str(&mime)
+                .expect("The MIME type comes from a valid header");
+            let form = multipart::Form::new()
+                .part("media", part)
+                .text("media_category", "tweet_image");
+            client
+                .post(&format!("{}/media/upload", API_URL))
+                .multipart(form)
+        })?;
+        Ok(media["data"]["id"].as_str().map(str::to_string))
+    }
+
+    /// Post `text` as a single post, shortened to the maximal length
+    pub fn post_text(&self, client: &Client, text: &str) -> Result<(), BoxError> {
+        let text: String = text.chars().take(MAX_POST_LENGTH).collect();
+        timed("Posting to X", || self.post(client, &text, None, None))?;
+        Ok(())
+    }
+
+    /// Post the digest as a thread with one post per event
+    pub fn post_digest(&self, client: &Client, digest: &Digest) -> Result<(), BoxError> {
+        let intro = format!(
+            "{}: {} events in the next days\n{}",
+            digest.title,
+            digest.events.len(),
+            digest.link
+        );
+        let mut previous = timed("Posting to X", || self.post(client, &intro, None, None))?;
+        for event in &digest.events {
+            // A missing logo should not prevent the post
+            let media_id = self.upload_logo(client, event).unwrap_or_else(|err| {
+                warn!("Couldn't upload the logo of event {}: {}", event.id(), err);
+                None
+            });
+            previous = timed("Posting to X", || {
+                self.post(
+                    client,
+                    &compact_text(event, &self.hashtags, MAX_POST_LENGTH),
+                    Some(&previous),
+                    media_id.as_deref(),
+                )
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_rate_limit_reset() {
+    let now = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    let mut headers = HeaderMap::new();
+    assert_eq!(rate_limit_reset(&headers, now), None);
+    headers.insert("x-rate-limit-reset", "1600000059".parse().unwrap());
+    assert_eq!(
+        rate_limit_reset(&headers, now),
+        Some(Duration::from_secs(60))
+    );
+    // A reset in the past still waits a moment
+    headers.insert("x-rate-limit-reset", "1599999000".parse().unwrap());
+    assert_eq!(
+        rate_limit_reset(&headers, now),
+        Some(Duration::from_secs(1))
+    );
+}
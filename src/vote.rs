@@ -0,0 +1,188 @@
+//! Suggestion for the CTFtime weight vote after a played event
+//!
+//! The players rate the stability of the infrastructure and the quality of the challenges using buttons.
+//! Together with the previous weight of the event, this results in a suggested weight, such that the team votes consistently.
+
+use crate::{
+    actions::ActionContext,
+    mattermost_hook_api::{Action, Attachment, Color, Integration, Url},
+    state::EventRecord,
+    CtfEvent, CONFIG,
+};
+use serde::{Deserialize, Serialize};
+
+/// Answers for the stability of the infrastructure and their scores
+const INFRA_SCORES: [(&str, f64); 3] = [("Stable", 1.), ("Minor issues", 0.5), ("Unusable", 0.)];
+/// Answers for the quality of the challenges and their scores
+const QUALITY_SCORES: [(&str, f64); 3] = [("Great", 1.), ("OK", 0.5), ("Poor", 0.)];
+
+/// Aspect of an event the players give feedback about
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedbackKind {
+    Infra,
+    Quality,
+}
+
+/// Context of a feedback button, see [`ActionContext::Feedback`]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct FeedbackContext {
+    pub event_id: usize,
+    pub kind: FeedbackKind,
+    /// Score between `0` (bad) and `1` (good)
+    pub score: f64,
+}
+
+/// Attachments with buttons asking the players for feedback about the event
+///
+/// The button clicks are sent to `actions_url`.
+pub fn feedback_poll(event: &CtfEvent, actions_url: &Url) -> Vec<Attachment> {
+    let buttons = |kind: FeedbackKind, answers: &[(&str, f64)]| -> Vec<Action> {
+        answers
+            .iter()
+            .map(|&(name, score)| Action {
+                name: name.to_string(),
+                integration: Integration {
+                    url: actions_url.to_string(),
+                    context: serde_json::to_value(ActionContext::Feedback(FeedbackContext {
+                        event_id: event.id(),
+                        kind,
+                        score,
+                    }))
+                    .expect("Serializing the action context cannot fail"),
+                },
+            })
+            .collect()
+    };
+
+    vec![
+        Attachment {
+            fallback: format!("How stable was the infrastructure of {}?", event.title()),
+            text: Some("How stable was the infrastructure?".to_string()),
+            color: Some(Color::Theme),
+            actions: buttons(FeedbackKind::Infra, &INFRA_SCORES[..]),
+            ..Default::default()
+        },
+        Attachment {
+            fallback: format!("How good were the challenges of {}?", event.title()),
+            text: Some("How good were the challenges?".to_string()),
+            color: Some(Color::Theme),
+            actions: buttons(FeedbackKind::Quality, &QUALITY_SCORES[..]),
+            ..Default::default()
+        },
+    ]
+}
+
+/// Average score of all players for one aspect of the event
+fn average_score(record: &EventRecord, kind: FeedbackKind) -> Option<f64> {
+    let scores: Vec<f64> = record
+        .feedback
+        .values()
+        .filter_map(|feedback| match kind {
+            FeedbackKind::Infra => feedback.infra,
+            FeedbackKind::Quality => feedback.quality,
+        })
+        .collect();
+    if scores.is_empty() {
+        None
+    } else {
+        Some(scores.iter().sum::<f64>() / scores.len() as f64)
+    }
+}
+
+/// Suggested weight for the CTFtime vote and the data it is based on
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub weight: u32,
+    /// Weight of the event before the vote, or the configured default weight
+    pub previous_weight: f64,
+    /// Average infrastructure score of the players
+    pub infra: Option<f64>,
+    /// Average challenge quality score of the players
+    pub quality: Option<f64>,
+}
+
+/// Compute the suggested weight for the event
+///
+/// The previous weight is reduced for unstable infrastructure by up to `VOTE_INFRA_FACTOR`.
+/// The challenge quality changes the weight by up to `VOTE_QUALITY_FACTOR` in both directions.
+/// Missing feedback does not change the weight.
+pub fn suggest_weight(event: &CtfEvent, record: Option<&EventRecord>) -> Suggestion {
+    let previous_weight = if event.weight() > 0. {
+        f64::from(event.weight())
+    } else {
+        CONFIG.vote_default_weight
+    };
+    let infra = record.and_then(|record| average_score(record, FeedbackKind::Infra));
+    let quality = record.and_then(|record| average_score(record, FeedbackKind::Quality));
+
+    let infra_multiplier = 1. - CONFIG.vote_infra_factor * (1. - infra.unwrap_or(1.));
+    let quality_multiplier = 1. + CONFIG.vote_quality_factor * (quality.unwrap_or(0.5) - 0.5) * 2.;
+    let weight = (previous_weight * infra_multiplier * quality_multiplier).clamp(0., 100.);
+
+    Suggestion {
+        weight: weight.round() as u32,
+        previous_weight,
+        infra,
+        quality,
+    }
+}
+
+impl Suggestion {
+    /// Prompt asking the team to vote for the weight of the event
+    pub fn message(&self, event: &CtfEvent) -> String {
+        let mut text = format!(
+            "🗳 Time to vote for the weight of [{}]({}). Suggested weight: **{}**\n\nPrevious weight: {:.2}",
+            event.display_title(),
+            event.ctftime_url(),
+            self.weight,
+            self.previous_weight,
+        );
+        if let Some(infra) = self.infra {
+            text += &format!(", infrastructure: {:.0}%", infra * 100.);
+        }
+        if let Some(quality) = self.quality {
+            text += &format!(", challenge quality: {:.0}%", quality * 100.);
+        }
+        text
+    }
+}
+
+#[allow(clippy::float_cmp)]
+#[test]
+fn test_suggest_weight() {
+    use crate::state::Feedback;
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let event = &events[0];
+
+    // Without feedback the previous weight is kept
+    let suggestion = suggest_weight(event, None);
+    assert_eq!(suggestion.weight, 24);
+
+    let mut record = EventRecord::default();
+    record.feedback.insert(
+        "a".to_string(),
+        Feedback {
+            infra: Some(0.),
+            quality: Some(1.),
+        },
+    );
+    record.feedback.insert(
+        "b".to_string(),
+        Feedback {
+            infra: Some(1.),
+            quality: None,
+        },
+    );
+    let suggestion = suggest_weight(event, Some(&record));
+    assert_eq!(suggestion.infra, Some(0.5));
+    assert_eq!(suggestion.quality, Some(1.));
+    // 24.07 * 0.75 * 1.5
+    assert_eq!(suggestion.weight, 27);
+    assert_eq!(
+        suggestion.message(event),
+        "🗳 Time to vote for the weight of [X-MAS CTF 2018](https://ctftime.org/event/724/). Suggested weight: **27**\n\nPrevious weight: 24.07, infrastructure: 50%, challenge quality: 100%"
+    );
+}
@@ -0,0 +1,146 @@
+//! Warm up the infrastructure of the team before an Attack-Defense event, e.g., start the VM analyzing the vulnbox
+//!
+//! The trigger is either a webhook, which receives the event as JSON, or a local command.
+//! It runs once per event, driven by the [`Reminder::Warmup`][crate::scheduler::Reminder::Warmup] of the scheduler.
+
+use crate::{
+    duration::Humantime, mattermost_hook_api::Url, signature, webhook::event_context, CtfEvent,
+    CtfFormat, CONFIG,
+};
+use chrono::Duration;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_with::serde_as;
+use std::process::Command;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Configuration of the warm-up, only available in the configuration file
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct WarmupConfig {
+    /// Time before the start of the event to run the trigger, e.g., `90m`
+    #[serde_as(as = "Humantime")]
+    #[serde(default = "default_warmup_lead_time")]
+    pub lead_time: Duration,
+    #[serde(flatten)]
+    pub trigger: WarmupTrigger,
+}
+
+fn default_warmup_lead_time() -> Duration {
+    Duration::hours(12)
+}
+
+/// What runs to warm up the infrastructure
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WarmupTrigger {
+    /// POST the values of the event, like the template webhooks, as JSON to the URL
+    Webhook {
+        url: Url,
+        /// Sign the requests with this secret, see [`signature`]
+        #[serde(default)]
+        signing_secret: Option<String>,
+    },
+    /// Run the program with the arguments, the event is passed in `CTF_*` environment variables
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+/// Whether the event needs a warm-up, Attack-Defense events unless overwritten per event
+pub fn wants_warmup(event: &CtfEvent) -> bool {
+    CONFIG
+        .event_settings(event.id())
+        .and_then(|settings| settings.warmup)
+        .unwrap_or_else(|| event.format() == CtfFormat::AttackDefense)
+}
+
+/// Environment variables describing the event for the warm-up command
+pub fn command_env(event: &CtfEvent) -> Vec<(&'static str, String)> {
+    vec![
+        ("CTF_EVENT_ID", event.id().to_string()),
+        ("CTF_TITLE", event.title().to_string()),
+        ("CTF_START", event.start_date().to_rfc3339()),
+        ("CTF_FINISH", event.finish_date().to_rfc3339()),
+        (
+            "CTF_URL",
+            event
+                .url()
+                .unwrap_or_else(|| event.ctftime_url())
+                .to_string(),
+        ),
+    ]
+}
+
+impl WarmupConfig {
+    /// Time before the start of the event to run the trigger, can be overwritten per event
+    pub fn lead_time_for(&self, event_id: usize) -> Duration {
+        CONFIG
+            .event_settings(event_id)
+            .and_then(|settings| settings.warmup_lead_time)
+            .unwrap_or(self.lead_time)
+    }
+
+    /// Run the trigger for the event and wait until it finished
+    pub fn trigger(&self, client: &Client, event: &CtfEvent) -> Result<(), BoxError> {
+        match &self.trigger {
+            WarmupTrigger::Webhook {
+                url,
+                signing_secret,
+            } => {
+                signature::json_body(
+                    client.post(url.clone()),
+                    &event_context(event),
+                    signing_secret.as_deref(),
+                )?
+                .send()?
+                .error_for_status()?;
+            }
+            WarmupTrigger::Command { program, args } => {
+                let status = Command::new(program)
+                    .args(args)
+                    .envs(command_env(event))
+                    .status()?;
+                if !status.success() {
+                    return Err(format!("`{}` failed with {}", program, status).into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_warmup() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let mut events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    let config: WarmupConfig = serde_json::from_str(
+        r#"{"kind": "command", "program": "./warmup.sh", "args": ["--fast"]}"#,
+    )
+    .unwrap();
+    assert_eq!(config.lead_time, Duration::hours(12));
+    assert_eq!(
+        config.trigger,
+        WarmupTrigger::Command {
+            program: "./warmup.sh".to_string(),
+            args: vec!["--fast".to_string()],
+        }
+    );
+    let config: WarmupConfig = serde_json::from_str(
+        r#"{"kind": "webhook", "url": "https://vm.example.com/start", "lead_time": "6h"}"#,
+    )
+    .unwrap();
+    assert_eq!(config.lead_time_for(724), Duration::hours(6));
+
+    assert!(!wants_warmup(&events[0]));
+    events[0].format = CtfFormat::AttackDefense;
+    assert!(wants_warmup(&events[0]));
+    let env = command_env(&events[0]);
+    assert_eq!(env[0], ("CTF_EVENT_ID", "724".to_string()));
+    assert_eq!(env[4], ("CTF_URL", "https://www.xmas-ctf.cf/".to_string()));
+}
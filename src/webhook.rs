@@ -0,0 +1,199 @@
+//! Post user-defined JSON to arbitrary webhooks, rendered from [Handlebars] templates
+//!
+//! The template is rendered once per event or once per digest, see [`TemplateScope`].
+//! Values are escaped for JSON strings, such that `"title": "{{title}}"` stays valid for any title.
+//! The available values are documented at [`event_context`] and [`digest_context`].
+//!
+//! [Handlebars]: https://handlebarsjs.com/guide/
+
+use crate::{
+    digest::Digest, format_duration, mattermost_hook_api::Url, signature, timed, CtfEvent,
+};
+use handlebars::Handlebars;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A webhook receiving JSON rendered from a template, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct TemplateWebhook {
+    pub url: Url,
+    /// Handlebars template of the JSON body
+    pub template: String,
+    #[serde(default)]
+    pub scope: TemplateScope,
+    /// Sign the requests, see [`signature`]
+    pub signing_secret: Option<String>,
+}
+
+/// How often the template is rendered and posted
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateScope {
+    /// Once per digest
+    #[default]
+    Digest,
+    /// Once for each event of the digest
+    Event,
+}
+
+/// Values of an event available in the templates
+///
+/// `id`, `title`, `format`, `weight`, `url` (the event website or the CTFtime page), `ctftime_url`, `logo_url`,
+/// `start` and `finish` in RFC 3339, `duration`, `organizers` (list of names), `onsite`, and `location`.
+pub fn event_context(event: &CtfEvent) -> Value {
+    json!({
+        "id": event.id(),
+        "title": event.title(),
+        "format": event.format().to_string(),
+        // Rounded, since the weight as `f32` has many digits as `f64`
+        "weight": (f64::from(event.weight()) * 100.).round() / 100.,
+        "url": event.url().unwrap_or_else(|| event.ctftime_url()),
+        "ctftime_url": event.ctftime_url(),
+        "logo_url": event.logo_url(),
+        "start": event.start_date().to_rfc3339(),
+        "finish": event.finish_date().to_rfc3339(),
+        "duration": format_duration(&(event.finish_date() - event.start_date())),
+        "organizers": event.known_organizers().map(|team| team.name()).collect::<Vec<_>>(),
+        "onsite": event.onsite(),
+        "location": event.location(),
+    })
+}
+
+/// Events of the digest as printed by `--output json`
+///
+/// Each [`event_context`] additionally contains the `notes` of the digest and whether the event is `sticky`.
+pub fn digest_events(digest: &Digest) -> Value {
+    Value::Array(
+        digest
+            .events
+            .iter()
+            .map(|event| {
+                let mut context = event_context(event);
+                context["notes"] =
+                    json!(digest.notes.get(&event.id()).cloned().unwrap_or_default());
+                context["sticky"] = json!(digest.sticky.contains(&event.id()));
+                context
+            })
+            .collect(),
+    )
+}
+
+/// Values of the digest available in the templates
+///
+/// `title`, `heading`, i.e., the title with the part of a split digest, `link`, `count`, and `events`, a list of [`event_context`]s.
+pub fn digest_context(digest: &Digest) -> Value {
+    json!({
+        "title": digest.title,
+        "heading": digest.heading(),
+        "link": digest.link,
+        "count": digest.events.len(),
+        "events": digest.events.iter().map(|event| event_context(event)).collect::<Vec<_>>(),
+    })
+}
+
+/// Escape a value for use within a JSON string
+fn escape_json(value: &str) -> String {
+    let quoted = serde_json::to_string(value).expect("Serializing a string cannot fail");
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+/// Render the template and parse the result as JSON
+pub fn render(template: &str, context: &Value) -> Result<Value, BoxError> {
+    let mut handlebars = Handlebars::new();
+    handlebars.set_strict_mode(true);
+    handlebars.register_escape_fn(escape_json);
+    let body = handlebars.render_template(template, context)?;
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Render the template as text without escaping, e.g., for the message templates of the backends
+pub fn render_text(template: &str, context: &Value) -> Result<String, BoxError> {
+    let mut handlebars = Handlebars::new();
+    handlebars.set_strict_mode(true);
+    handlebars.register_escape_fn(handlebars::no_escape);
+    Ok(handlebars.render_template(template, context)?)
+}
+
+impl TemplateWebhook {
+    fn post(&self, client: &Client, context: &Value) -> Result<(), BoxError> {
+        let body = render(&self.template, context)?;
+        signature::json_body(
+            client.post(self.url.clone()),
+            &body,
+            self.signing_secret.as_deref(),
+        )?
+        .send()?
+        .error_for_status()?;
+        Ok(())
+    }
+
+    /// Post the digest or each of its events, depending on the [`TemplateScope`]
+    pub fn post_digest(&self, client: &Client, digest: &Digest) -> Result<(), BoxError> {
+        let description = format!("Posting to {}", self.url.host_str().unwrap_or_default());
+        match self.scope {
+            TemplateScope::Digest => {
+                timed(&description, || self.post(client, &digest_context(digest)))
+            }
+            TemplateScope::Event => {
+                for event in &digest.events {
+                    timed(&description, || self.post(client, &event_context(event)))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[test]
+fn test_render_template() {
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let mut events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    events[0].title = r#"X-MAS "CTF" 2018"#.to_string();
+    let digest = Digest::new(events.iter().collect());
+
+    let body = render(
+        r#"{"text": "{{title}} starts {{start}}", "weight": {{weight}}, "by": "{{organizers.[0]}}"}"#,
+        &event_context(&events[0]),
+    )
+    .unwrap();
+    assert_eq!(
+        body,
+        json!({
+            "text": "X-MAS \"CTF\" 2018 starts 2018-12-14T18:00:00+00:00",
+            "weight": 24.07,
+            "by": "Hecării, Țuica și Păunii",
+        })
+    );
+
+    let body = render(
+        r#"{"summary": "{{count}} CTFs", "titles": [{{#each events}}"{{title}}"{{#unless @last}},{{/unless}}{{/each}}]}"#,
+        &digest_context(&digest),
+    )
+    .unwrap();
+    assert_eq!(
+        body,
+        json!({"summary": "1 CTFs", "titles": ["X-MAS \"CTF\" 2018"]})
+    );
+
+    // Unknown values and invalid JSON are errors
+    assert!(render(r#"{"text": "{{name}}"}"#, &event_context(&events[0])).is_err());
+    assert!(render(r#"{"text": {{title}}}"#, &event_context(&events[0])).is_err());
+
+    assert_eq!(
+        render_text("{{title}} ({{format}})", &event_context(&events[0])).unwrap(),
+        r#"X-MAS "CTF" 2018 (Jeopardy)"#
+    );
+
+    let mut digest = digest;
+    digest
+        .notes
+        .insert(724, vec!["Qualifier for X".to_string()]);
+    let events = digest_events(&digest);
+    assert_eq!(events[0]["id"], 724);
+    assert_eq!(events[0]["notes"], json!(["Qualifier for X"]));
+    assert_eq!(events[0]["sticky"], false);
+}
@@ -0,0 +1,116 @@
+//! Post the digest to an XMPP multi-user chat (MUC)
+//!
+//! The bot logs in with its own account, joins the room, and sends the digest as a single message.
+//! The message contains a plain text body and an [XHTML-IM] body with links and emphasis.
+//! Sending requires the `xmpp` feature, without it the configuration is accepted but sending fails.
+//!
+//! [XHTML-IM]: https://xmpp.org/extensions/xep-0071.html
+
+use crate::{
+    digest::{markdown_to_plain_text, Digest},
+    matrix::{escape_html, markdown_to_html},
+    timed,
+};
+use serde::Deserialize;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Configuration of the XMPP backend, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct XmppConfig {
+    /// Account of the bot, e.g., `ctftimebot@example.com`
+    pub jid: String,
+    pub password: String,
+    /// Address of the room, e.g., `ctf@conference.example.com`
+    pub room: String,
+    /// Nickname of the bot in the room
+    #[serde(default = "default_nick")]
+    pub nick: String,
+}
+
+fn default_nick() -> String {
+    "ctftimebot".to_string()
+}
+
+/// Convert the Markdown into the XHTML subset of XHTML-IM, which has no headings
+pub fn markdown_to_xhtml(markdown: &str) -> String {
+    markdown_to_html(markdown)
+        .replace("<br>", "<br/>")
+        .replace("<h3>", "<p><strong>")
+        .replace("</h3>", "</strong></p>")
+}
+
+/// Presence stanza to join the room, without receiving the history of the room
+pub fn join_xml(room: &str, nick: &str) -> String {
+    format!(
+        r#"<presence xmlns="jabber:client" to="{}/{}"><x xmlns="http://jabber.org/protocol/muc"><history maxstanzas="0"/></x></presence>"#,
+        escape_html(room),
+        escape_html(nick)
+    )
+}
+
+/// Message stanza to the room with a plain text and an XHTML-IM body
+pub fn message_xml(room: &str, markdown: &str) -> String {
+    format!(
+        r#"<message xmlns="jabber:client" to="{}" type="groupchat"><body>{}</body><html xmlns="http://jabber.org/protocol/xhtml-im"><body xmlns="http://www.w3.org/1999/xhtml">{}</body></html></message>"#,
+        escape_html(room),
+        escape_html(&markdown_to_plain_text(markdown)),
+        markdown_to_xhtml(markdown)
+    )
+}
+
+impl XmppConfig {
+    /// Join the room and send the Markdown text as a single message
+    #[cfg(feature = "xmpp")]
+    pub fn send(&self, markdown: &str) -> Result<(), BoxError> {
+        use tokio_xmpp::SimpleClient;
+        use xmpp_parsers::Element;
+
+        let join: Element = join_xml(&self.room, &self.nick).parse()?;
+        let message: Element = message_xml(&self.room, markdown).parse()?;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        runtime.block_on(async {
+            let mut client = SimpleClient::new(&self.jid, self.password.clone()).await?;
+            client.send_stanza(join).await?;
+            client.send_stanza(message).await?;
+            client.end().await?;
+            Ok::<_, BoxError>(())
+        })
+    }
+
+    #[cfg(not(feature = "xmpp"))]
+    pub fn send(&self, _markdown: &str) -> Result<(), BoxError> {
+        Err("the bot was built without the `xmpp` feature".into())
+    }
+
+    /// Post the digest as a single message
+    pub fn post_digest(&self, digest: &Digest) -> Result<(), BoxError> {
+        timed("Posting to XMPP", || self.send(&digest.to_markdown()))
+    }
+}
+
+#[test]
+fn test_xmpp_stanzas() {
+    use crate::CtfEvent;
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+    let digest = Digest::new(events.iter().collect());
+
+    assert_eq!(
+        markdown_to_xhtml("### [X-MAS CTF](https://ctftime.org/event/724/)\n**Date:** Fri"),
+        r#"<p><strong><a href="https://ctftime.org/event/724/">X-MAS CTF</a></strong></p><strong>Date:</strong> Fri<br/>"#
+    );
+    assert_eq!(
+        join_xml("ctf@conference.example.com", "bot"),
+        r#"<presence xmlns="jabber:client" to="ctf@conference.example.com/bot"><x xmlns="http://jabber.org/protocol/muc"><history maxstanzas="0"/></x></presence>"#
+    );
+    let message = message_xml("ctf@conference.example.com", &digest.to_markdown());
+    assert!(message.starts_with(
+        r#"<message xmlns="jabber:client" to="ctf@conference.example.com" type="groupchat"><body>"#
+    ));
+    assert!(!message.contains("<br>"));
+    assert!(!message.contains("<h3>"));
+}
@@ -0,0 +1,107 @@
+//! Post the digest to a Zulip stream using the REST API
+//!
+//! Each digest is posted to a single topic of the stream: a short introduction, followed by one message per event.
+//! The bot authenticates with the email and API key of a Zulip bot user, which must be subscribed to the stream.
+
+use crate::{
+    digest::{Digest, NEW_EVENTS_TITLE},
+    mattermost_hook_api::Url,
+    timed,
+};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+
+/// Configuration of the Zulip backend, only available in the configuration file
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+pub struct ZulipConfig {
+    /// Base URL of the Zulip organization, e.g., `https://ctf.zulipchat.com`
+    pub site: Url,
+    /// Email address of the bot user
+    pub email: String,
+    pub api_key: String,
+    /// Name of the stream to post to
+    pub stream: String,
+    /// Topic of the messages within the stream
+    #[serde(default = "default_topic")]
+    pub topic: String,
+}
+
+fn default_topic() -> String {
+    "Upcoming CTFs".to_string()
+}
+
+/// Messages of the digest, in the order they are posted
+pub fn digest_messages(digest: &Digest) -> Vec<String> {
+    let mut intro = format!(
+        "**[{}]({})**: {} events in the next days",
+        digest.heading(),
+        digest.link,
+        digest.events.len()
+    );
+    if let Some(ref trivia) = digest.trivia {
+        intro += &format!("\n💡 {}", trivia);
+    }
+    let mut messages = vec![intro];
+    messages.extend(
+        digest
+            .events
+            .iter()
+            .map(|event| digest.event_markdown(event).trim_end().to_string()),
+    );
+    if !digest.new_events.is_empty() {
+        messages.push(format!(
+            "### {}\n{}",
+            NEW_EVENTS_TITLE,
+            digest.new_events_lines().join("\n")
+        ));
+    }
+    messages
+}
+
+impl ZulipConfig {
+    /// Post a message to the configured stream and topic
+    pub fn send(&self, client: &Client, content: &str) -> Result<(), reqwest::Error> {
+        client
+            .post(&format!(
+                "{}/api/v1/messages",
+                self.site.as_str().trim_end_matches('/')
+            ))
+            .basic_auth(&self.email, Some(&self.api_key))
+            .form(&[
+                ("type", "stream"),
+                ("to", self.stream.as_str()),
+                ("topic", self.topic.as_str()),
+                ("content", content),
+            ])
+            .send()?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Post the digest with one message per event
+    pub fn post_digest(&self, client: &Client, digest: &Digest) -> Result<(), reqwest::Error> {
+        for content in digest_messages(digest) {
+            timed("Posting to Zulip", || self.send(client, &content))?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_digest_messages() {
+    use crate::CtfEvent;
+    use std::fs::File;
+    let json = File::open("./tests/ctfs-1.json").unwrap();
+    let events: Vec<CtfEvent> = serde_json::from_reader(json).unwrap();
+
+    let digest = Digest::new(events.iter().collect());
+    let messages = digest_messages(&digest);
+    assert_eq!(messages.len(), 2);
+    assert_eq!(
+        messages[0],
+        "**[Upcoming CTFs](https://ctftime.org/event/list/upcoming)**: 1 events in the next days"
+    );
+    assert!(messages[1].starts_with(
+        "### [X-MAS CTF 2018 — Jeopardy](https://ctftime.org/event/724/)\n**Date:** "
+    ));
+}